@@ -30,7 +30,7 @@ mod tests {
         },
         compiler::{compiler::Compiler, vm::Vm},
         parser::parser::Parser,
-        repl::Engine,
+        engine::Engine,
     };
 
     use std::io::Read;
@@ -51,14 +51,7 @@ mod tests {
 
         if errors.is_empty() { return Ok(()); };
 
-        let mut msg = format!("The Parser had {} errors:\n", errors.len());
-
-        for e in errors {
-            msg.push_str(&e);
-            msg.push('\n');
-        }
-
-        Err(Error::new(msg))
+        Err(Error::Parse(errors))
     }
 
     #[bench]
@@ -0,0 +1,169 @@
+use num_bigint::BigInt;
+use num_traits::{Signed, ToPrimitive, Zero};
+
+use crate::{
+    error::RuntimeErrorKind,
+    object::*,
+};
+
+// Numeric operators shared by the evaluator and the VM. Each returns `None` for an operator the
+// operands don't support, so the engines can report it in their own way.
+
+// Operands for float arithmetic, an integer mixed with a float is promoted.
+pub fn float_operands(left: &MObject, right: &MObject) -> Option<(f64, f64)> {
+    match (left, right) {
+        (MObject::Float(_), MObject::Int(_) | MObject::BigInt(_) | MObject::Float(_)) |
+            (MObject::Int(_) | MObject::BigInt(_), MObject::Float(_)) => Some((left.to_f64()?, right.to_f64()?)),
+        _ => None,
+    }
+}
+
+// Operands for big integer arithmetic, when at least one side is already too big for an i128.
+pub fn bigint_operands(left: &MObject, right: &MObject) -> Option<(BigInt, BigInt)> {
+    match (left, right) {
+        (MObject::BigInt(_), MObject::Int(_) | MObject::BigInt(_)) |
+            (MObject::Int(_), MObject::BigInt(_)) => Some((left.to_bigint()?, right.to_bigint()?)),
+        _ => None,
+    }
+}
+
+// Equality of a literal match pattern, numbers compare like `==` so `1` matches `1.0`, other values
+// compare structurally.
+pub fn values_equal(left: &MObject, right: &MObject) -> bool {
+    match float_operands(left, right) {
+        Some((left, right)) => left == right,
+        None => left == right,
+    }
+}
+
+// The fast path for integer operators. Results that don't fit in an i128 are redone with big
+// integers.
+pub fn integer_infix_op(left: i128, op: &str, right: i128) -> Option<Result<MObject, RuntimeErrorKind>> {
+    let value = match op {
+        "+" => left.checked_add(right),
+        "-" => left.checked_sub(right),
+        "*" => left.checked_mul(right),
+        "/" | "%" if right == 0 => return Some(Err(RuntimeErrorKind::DivisionByZero)),
+        "/" => left.checked_div(right),
+        "%" => left.checked_rem(right),
+        "**" if right < 0 => return float_infix_op(left as f64, op, right as f64).map(Ok),
+        "**" => u32::try_from(right).ok().and_then(|r| left.checked_pow(r)),
+        "&" => Some(left & right),
+        "|" => Some(left | right),
+        "^" => Some(left ^ right),
+        "<<" | ">>" if right < 0 => return Some(Err(invalid_operand(op, "negative shift amount"))),
+        "<<" => checked_shl(left, right),
+        ">>" => Some(left >> right.min(127)),
+        "<" => return Some(Ok(bool_to_object(left < right))),
+        ">" => return Some(Ok(bool_to_object(left > right))),
+        "<=" => return Some(Ok(bool_to_object(left <= right))),
+        ">=" => return Some(Ok(bool_to_object(left >= right))),
+        "==" => return Some(Ok(bool_to_object(left == right))),
+        "!=" => return Some(Ok(bool_to_object(left != right))),
+        _ => return None,
+    };
+
+    match value {
+        Some(value) => Some(Ok(MObject::Int(Integer { value }))),
+        None => bigint_infix_op(BigInt::from(left), op, BigInt::from(right)),
+    }
+}
+
+// The slow path for integer operators, taken when an i128 operation overflows or an operand is
+// already a `BigInteger`.
+pub fn bigint_infix_op(left: BigInt, op: &str, right: BigInt) -> Option<Result<MObject, RuntimeErrorKind>> {
+    let value = match op {
+        "+" => left + right,
+        "-" => left - right,
+        "*" => left * right,
+        "/" | "%" if right.is_zero() => return Some(Err(RuntimeErrorKind::DivisionByZero)),
+        "/" => left / right,
+        "%" => left % right,
+        "**" if right.is_negative() => {
+            return float_infix_op(left.to_f64()?, op, right.to_f64()?).map(Ok)
+        },
+        "**" => match right.to_u32() {
+            Some(r) => left.pow(r),
+            None => return Some(Err(invalid_operand(op, "exponent too large"))),
+        },
+        "&" => left & right,
+        "|" => left | right,
+        "^" => left ^ right,
+        "<<" | ">>" if right.is_negative() => return Some(Err(invalid_operand(op, "negative shift amount"))),
+        "<<" => match right.to_u32() {
+            Some(r) => left << r,
+            None => return Some(Err(invalid_operand(op, "shift amount too large"))),
+        },
+        // Shifting right by more than the bit length leaves only the sign.
+        ">>" => match right.to_u32() {
+            Some(r) => left >> r,
+            None if left.is_negative() => BigInt::from(-1),
+            None => BigInt::zero(),
+        },
+        "<" => return Some(Ok(bool_to_object(left < right))),
+        ">" => return Some(Ok(bool_to_object(left > right))),
+        "<=" => return Some(Ok(bool_to_object(left <= right))),
+        ">=" => return Some(Ok(bool_to_object(left >= right))),
+        "==" => return Some(Ok(bool_to_object(left == right))),
+        "!=" => return Some(Ok(bool_to_object(left != right))),
+        _ => return None,
+    };
+
+    Some(Ok(MObject::from_bigint(value)))
+}
+
+pub fn float_infix_op(left: f64, op: &str, right: f64) -> Option<MObject> {
+    let value = match op {
+        "+" => left + right,
+        "-" => left - right,
+        "*" => left * right,
+        "/" => left / right,
+        "%" => left % right,
+        "**" => left.powf(right),
+        "<" => return Some(bool_to_object(left < right)),
+        ">" => return Some(bool_to_object(left > right)),
+        "<=" => return Some(bool_to_object(left <= right)),
+        ">=" => return Some(bool_to_object(left >= right)),
+        "==" => return Some(bool_to_object(left == right)),
+        "!=" => return Some(bool_to_object(left != right)),
+        _ => return None,
+    };
+
+    Some(MObject::Float(Float { value }))
+}
+
+pub fn integer_prefix_op(op: &str, right: &MObject) -> Option<MObject> {
+    let result = match (op, right) {
+        ("-", MObject::Int(x)) => match x.value.checked_neg() {
+            Some(value) => MObject::Int(Integer { value }),
+            None => MObject::from_bigint(-BigInt::from(x.value)),
+        },
+        ("-", MObject::BigInt(x)) => MObject::from_bigint(-x.value.clone()),
+        ("-", MObject::Float(x)) => MObject::Float(Float { value: -x.value }),
+        ("~", MObject::Int(x)) => MObject::Int(Integer { value: !x.value }),
+        ("~", MObject::BigInt(x)) => MObject::from_bigint(!x.value.clone()),
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+// `None` when bits would be shifted out, the caller then redoes the shift with big integers.
+fn checked_shl(left: i128, right: i128) -> Option<i128> {
+    if left == 0 { return Some(0); };
+
+    let shifted = left.checked_shl(u32::try_from(right).ok()?)?;
+    if shifted >> right == left {
+        Some(shifted)
+    } else {
+        None
+    }
+}
+
+fn invalid_operand(op: &str, reason: &'static str) -> RuntimeErrorKind {
+    RuntimeErrorKind::InvalidOperand { operator: op.to_string(), reason }
+}
+
+fn bool_to_object(value: bool) -> MObject {
+    if value { TRUE } else { FALSE }
+}
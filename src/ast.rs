@@ -1,9 +1,12 @@
 use std::{fmt, collections::HashMap, hash::{Hash, Hasher}, cell::RefCell, rc::Rc};
 
-use crate::{lexer::token::Token, interpreter::environment::Environment};
+use num_bigint::BigInt;
+
+use crate::{lexer::{token::Token, span::{Span, Position}}, interpreter::environment::Environment};
 
 pub trait Node: fmt::Display {
     fn token_literal(&self) -> String;
+    fn span(&self) -> Span;
 }
 
 #[derive(PartialEq, Eq, Clone, Debug, Hash)]
@@ -26,6 +29,13 @@ impl Node for Program {
             None => String::new(),
         }
     }
+
+    fn span(&self) -> Span {
+        match (self.stmts.first(), self.stmts.last()) {
+            (Some(first), Some(last)) => first.span().to(last.span()),
+            _ => Span::default(),
+        }
+    }
 }
 
 impl fmt::Display for Program {
@@ -44,6 +54,24 @@ pub enum MNode {
     Expr(Expr),
 }
 
+impl Node for MNode {
+    fn token_literal(&self) -> String {
+        match self {
+            MNode::Prog(x) => x.token_literal(),
+            MNode::Stmt(x) => x.token_literal(),
+            MNode::Expr(x) => x.token_literal(),
+        }
+    }
+
+    fn span(&self) -> Span {
+        match self {
+            MNode::Prog(x) => x.span(),
+            MNode::Stmt(x) => x.span(),
+            MNode::Expr(x) => x.span(),
+        }
+    }
+}
+
 impl fmt::Display for MNode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -73,6 +101,10 @@ impl Node for LetStatement {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
+
+    fn span(&self) -> Span {
+        self.token.span.to(self.value.span())
+    }
 }
 
 impl Statement for LetStatement {
@@ -86,6 +118,96 @@ impl fmt::Display for LetStatement {
     }
 }
 
+// `let [a, b, ...rest] = arr;` or `let {"name": n} = h;`, binds each name in the pattern.
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
+pub struct DestructureStatement {
+    pub token: Token,
+    pub pattern: Box<Pattern>,
+    pub value: Expr,
+}
+
+impl Node for DestructureStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn span(&self) -> Span {
+        self.token.span.to(self.value.span())
+    }
+}
+
+impl Statement for DestructureStatement {
+    fn stmt_node(&self) {
+    }
+}
+
+impl fmt::Display for DestructureStatement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} = {};", self.token.literal, self.pattern, self.value)
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
+pub enum Pattern {
+    Array(ArrayPattern),
+    Hash(HashPattern),
+}
+
+impl Pattern {
+    // The names bound by the pattern, in the order their values are produced.
+    pub fn names(&self) -> Vec<&Identifier> {
+        match self {
+            Pattern::Array(x) => x.names.iter().chain(x.rest.iter()).collect(),
+            Pattern::Hash(x) => x.pairs.iter().map(|(_, name)| name).collect(),
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pattern::Array(x) => write!(f, "{}", x),
+            Pattern::Hash(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
+pub struct ArrayPattern {
+    pub token: Token,
+    pub names: Vec<Identifier>,
+    pub rest: Option<Identifier>,
+}
+
+impl fmt::Display for ArrayPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut names = self.names.iter().map(ToString::to_string).collect::<Vec<String>>();
+        if let Some(rest) = &self.rest {
+            names.push(format!("...{}", rest));
+        };
+
+        write!(f, "[{}]", names.join(", "))
+    }
+}
+
+// The pairs keep their source order, unlike a hash literal's, so the names are bound in order.
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
+pub struct HashPattern {
+    pub token: Token,
+    pub pairs: Vec<(Expr, Identifier)>,
+}
+
+impl fmt::Display for HashPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pairs = self.pairs
+            .iter()
+            .map(|(key, name)| format!("{}: {}", key, name))
+            .collect::<Vec<String>>();
+
+        write!(f, "{{{}}}", pairs.join(", "))
+    }
+}
+
 #[derive(PartialEq, Eq, Clone, Debug, Hash)]
 pub struct ReturnStatement {
     pub token: Token,
@@ -96,6 +218,10 @@ impl Node for ReturnStatement {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
+
+    fn span(&self) -> Span {
+        self.token.span.to(self.retval.span())
+    }
 }
 
 impl Statement for ReturnStatement {
@@ -109,16 +235,75 @@ impl fmt::Display for ReturnStatement {
     }
 }
 
+// `break` and `continue` only parse inside the body of a loop, and not in a function defined there.
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
+pub struct BreakStatement {
+    pub token: Token,
+}
+
+impl Node for BreakStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn span(&self) -> Span {
+        self.token.span
+    }
+}
+
+impl Statement for BreakStatement {
+    fn stmt_node(&self) {
+    }
+}
+
+impl fmt::Display for BreakStatement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{};", self.token.literal)
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
+pub struct ContinueStatement {
+    pub token: Token,
+}
+
+impl Node for ContinueStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn span(&self) -> Span {
+        self.token.span
+    }
+}
+
+impl Statement for ContinueStatement {
+    fn stmt_node(&self) {
+    }
+}
+
+impl fmt::Display for ContinueStatement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{};", self.token.literal)
+    }
+}
+
 #[derive(PartialEq, Eq, Clone, Debug, Hash)]
 pub struct BlockStatement {
     pub token: Token,
     pub stmts: Vec<Stmt>,
+    // The end of the closing `}`, so that the block's span covers it.
+    pub close: Position,
 }
 
 impl Node for BlockStatement {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
+
+    fn span(&self) -> Span {
+        Span::new(self.token.span.start, self.close)
+    }
 }
 
 impl Statement for BlockStatement {
@@ -126,6 +311,13 @@ impl Statement for BlockStatement {
     }
 }
 
+impl BlockStatement {
+    // Whether any statement in the block, including nested functions, assigns to `name`.
+    pub fn assigns_to(&self, name: &str) -> bool {
+        self.stmts.iter().any(|stmt| stmt.assigns_to(name))
+    }
+}
+
 impl fmt::Display for BlockStatement {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for stmt in &self.stmts {
@@ -145,6 +337,10 @@ impl Node for ExpressionStatement {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
+
+    fn span(&self) -> Span {
+        self.expr.span()
+    }
 }
 
 impl Statement for ExpressionStatement {
@@ -161,7 +357,10 @@ impl fmt::Display for ExpressionStatement {
 #[derive(PartialEq, Eq, Clone, Debug, Hash)]
 pub enum Stmt {
     Let(LetStatement),
+    Destructure(DestructureStatement),
     Return(ReturnStatement),
+    Break(BreakStatement),
+    Continue(ContinueStatement),
     Block(BlockStatement),
     Expression(ExpressionStatement),
 }
@@ -170,29 +369,63 @@ impl Node for Stmt {
     fn token_literal(&self) -> String {
         match self {
             Stmt::Let(x) => x.token_literal(),
+            Stmt::Destructure(x) => x.token_literal(),
             Stmt::Return(x) => x.token_literal(),
+            Stmt::Break(x) => x.token_literal(),
+            Stmt::Continue(x) => x.token_literal(),
             Stmt::Block(x) => x.token_literal(),
             Stmt::Expression(x) => x.token_literal(),
         }
     }
+
+    fn span(&self) -> Span {
+        match self {
+            Stmt::Let(x) => x.span(),
+            Stmt::Destructure(x) => x.span(),
+            Stmt::Return(x) => x.span(),
+            Stmt::Break(x) => x.span(),
+            Stmt::Continue(x) => x.span(),
+            Stmt::Block(x) => x.span(),
+            Stmt::Expression(x) => x.span(),
+        }
+    }
 }
 
 impl Statement for Stmt {
     fn stmt_node(&self) {
         match self {
             Stmt::Let(x) => x.stmt_node(),
+            Stmt::Destructure(x) => x.stmt_node(),
             Stmt::Return(x) => x.stmt_node(),
+            Stmt::Break(x) => x.stmt_node(),
+            Stmt::Continue(x) => x.stmt_node(),
             Stmt::Block(x) => x.stmt_node(),
             Stmt::Expression(x) => x.stmt_node(),
         }
     }
 }
 
+impl Stmt {
+    pub fn assigns_to(&self, name: &str) -> bool {
+        match self {
+            Stmt::Let(x) => x.value.assigns_to(name),
+            Stmt::Destructure(x) => x.value.assigns_to(name),
+            Stmt::Return(x) => x.retval.assigns_to(name),
+            Stmt::Break(_) | Stmt::Continue(_) => false,
+            Stmt::Block(x) => x.assigns_to(name),
+            Stmt::Expression(x) => x.expr.assigns_to(name),
+        }
+    }
+}
+
 impl fmt::Display for Stmt {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Stmt::Let(x) => write!(f, "{}", x),
+            Stmt::Destructure(x) => write!(f, "{}", x),
             Stmt::Return(x) => write!(f, "{}", x),
+            Stmt::Break(x) => write!(f, "{}", x),
+            Stmt::Continue(x) => write!(f, "{}", x),
             Stmt::Block(x) => write!(f, "{}", x),
             Stmt::Expression(x) => write!(f, "{}", x),
         }
@@ -217,6 +450,10 @@ impl Node for Identifier {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
+
+    fn span(&self) -> Span {
+        self.token.span
+    }
 }
 
 impl Expression for Identifier {
@@ -240,6 +477,10 @@ impl Node for IntegerLiteral {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
+
+    fn span(&self) -> Span {
+        self.token.span
+    }
 }
 
 impl Expression for IntegerLiteral {
@@ -253,6 +494,71 @@ impl fmt::Display for IntegerLiteral {
     }
 }
 
+// An integer literal too big for an i128.
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
+pub struct BigIntegerLiteral {
+    pub token: Token,
+    pub value: BigInt,
+}
+
+impl Node for BigIntegerLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn span(&self) -> Span {
+        self.token.span
+    }
+}
+
+impl Expression for BigIntegerLiteral {
+    fn expr_node(&self) {
+    }
+}
+
+impl fmt::Display for BigIntegerLiteral {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct FloatLiteral {
+    pub token: Token,
+    pub value: f64,
+}
+
+// Literals are never NaN, so equality on the parsed value is reflexive.
+impl Eq for FloatLiteral {}
+
+impl Hash for FloatLiteral {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.token.hash(state);
+        self.value.to_bits().hash(state);
+    }
+}
+
+impl Node for FloatLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn span(&self) -> Span {
+        self.token.span
+    }
+}
+
+impl Expression for FloatLiteral {
+    fn expr_node(&self) {
+    }
+}
+
+impl fmt::Display for FloatLiteral {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.value)
+    }
+}
+
 #[derive(PartialEq, Eq, Clone, Debug, Hash)]
 pub struct BooleanLiteral {
     pub token: Token,
@@ -263,6 +569,10 @@ impl Node for BooleanLiteral {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
+
+    fn span(&self) -> Span {
+        self.token.span
+    }
 }
 
 impl Expression for BooleanLiteral {
@@ -286,6 +596,10 @@ impl Node for StringLiteral {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
+
+    fn span(&self) -> Span {
+        self.token.span
+    }
 }
 
 impl Expression for StringLiteral {
@@ -303,12 +617,17 @@ impl fmt::Display for StringLiteral {
 pub struct ArrayLiteral {
     pub token: Token,
     pub elements: Vec<Expr>,
+    pub close: Position,
 }
 
 impl Node for ArrayLiteral {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
+
+    fn span(&self) -> Span {
+        Span::new(self.token.span.start, self.close)
+    }
 }
 
 impl Expression for ArrayLiteral {
@@ -335,6 +654,7 @@ impl fmt::Display for ArrayLiteral {
 pub struct HashLiteral {
     pub token: Token,
     pub pairs: HashMap<Expr, Expr>,
+    pub close: Position,
 }
 
 impl Hash for HashLiteral {
@@ -346,6 +666,10 @@ impl Node for HashLiteral {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
+
+    fn span(&self) -> Span {
+        Span::new(self.token.span.start, self.close)
+    }
 }
 
 impl Expression for HashLiteral {
@@ -380,6 +704,13 @@ impl Node for IfExpression {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
+
+    fn span(&self) -> Span {
+        match &self.alternative {
+            Some(alternative) => self.token.span.to(alternative.span()),
+            None => self.token.span.to(self.consequence.span()),
+        }
+    }
 }
 
 impl Expression for IfExpression {
@@ -397,6 +728,178 @@ impl fmt::Display for IfExpression {
     }
 }
 
+// Loops evaluate to null, `break` leaves the loop early and `continue` skips to the next iteration.
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
+pub struct WhileLoop {
+    pub token: Token,
+    pub condition: Box<Expr>,
+    pub body: BlockStatement,
+}
+
+impl Node for WhileLoop {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn span(&self) -> Span {
+        self.token.span.to(self.body.span())
+    }
+}
+
+impl Expression for WhileLoop {
+    fn expr_node(&self) {
+    }
+}
+
+impl fmt::Display for WhileLoop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "while {} {{ {} }}", self.condition, self.body)
+    }
+}
+
+// `for (x in iterable) { }` binds each element of an array, each key of a hash or each character of
+// a string to the variable in turn.
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
+pub struct ForLoop {
+    pub token: Token,
+    pub variable: Identifier,
+    pub iterable: Box<Expr>,
+    pub body: BlockStatement,
+}
+
+impl Node for ForLoop {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn span(&self) -> Span {
+        self.token.span.to(self.body.span())
+    }
+}
+
+impl Expression for ForLoop {
+    fn expr_node(&self) {
+    }
+}
+
+impl fmt::Display for ForLoop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "for {} in {} {{ {} }}", self.variable, self.iterable, self.body)
+    }
+}
+
+// Tries each arm in order and evaluates the body of the first one whose pattern matches the subject
+// and whose guard, if any, is truthy. The names a pattern binds are set in the enclosing scope
+// before the guard runs. Evaluates to null when no arm matches.
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
+pub struct MatchExpression {
+    pub token: Token,
+    pub subject: Box<Expr>,
+    pub arms: Vec<MatchArm>,
+}
+
+impl Node for MatchExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn span(&self) -> Span {
+        match self.arms.last() {
+            Some(arm) => self.token.span.to(arm.body.span()),
+            None => self.token.span.to(self.subject.span()),
+        }
+    }
+}
+
+impl Expression for MatchExpression {
+    fn expr_node(&self) {
+    }
+}
+
+impl fmt::Display for MatchExpression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let arms = self.arms.iter().map(ToString::to_string).collect::<Vec<String>>();
+        write!(f, "match {} {{ {} }}", self.subject, arms.join(", "))
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
+pub struct MatchArm {
+    pub pattern: MatchPattern,
+    pub guard: Option<Expr>,
+    pub body: BlockStatement,
+}
+
+impl fmt::Display for MatchArm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.pattern)?;
+        if let Some(guard) = &self.guard {
+            write!(f, " if {}", guard)?;
+        };
+        write!(f, " => {{ {} }}", self.body)
+    }
+}
+
+// `_` matches anything, a name matches anything and binds it, a literal matches an equal value of
+// the same type. Array patterns need the same length, or at least as many elements with a `...rest`
+// pattern, hash patterns need every key and ignore the others.
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
+pub enum MatchPattern {
+    Wildcard(Token),
+    Binding(Identifier),
+    Literal(Expr),
+    Array(ArrayMatchPattern),
+    Hash(HashMatchPattern),
+}
+
+impl fmt::Display for MatchPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MatchPattern::Wildcard(x) => write!(f, "{}", x.literal),
+            MatchPattern::Binding(x) => write!(f, "{}", x),
+            MatchPattern::Literal(x) => write!(f, "{}", x),
+            MatchPattern::Array(x) => write!(f, "{}", x),
+            MatchPattern::Hash(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
+pub struct ArrayMatchPattern {
+    pub token: Token,
+    pub elements: Vec<MatchPattern>,
+    // `...rest` binds the remaining elements, `..._` ignores them.
+    pub rest: Option<Box<MatchPattern>>,
+}
+
+impl fmt::Display for ArrayMatchPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut elements = self.elements.iter().map(ToString::to_string).collect::<Vec<String>>();
+        if let Some(rest) = &self.rest {
+            elements.push(format!("...{}", rest));
+        };
+
+        write!(f, "[{}]", elements.join(", "))
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
+pub struct HashMatchPattern {
+    pub token: Token,
+    pub pairs: Vec<(Expr, MatchPattern)>,
+}
+
+impl fmt::Display for HashMatchPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pairs = self.pairs
+            .iter()
+            .map(|(key, pattern)| format!("{}: {}", key, pattern))
+            .collect::<Vec<String>>();
+
+        write!(f, "{{{}}}", pairs.join(", "))
+    }
+}
+
 #[derive(PartialEq, Eq, Clone, Debug, Hash)]
 pub struct Prefix {
     pub token: Token,
@@ -408,6 +911,10 @@ impl Node for Prefix {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
+
+    fn span(&self) -> Span {
+        self.token.span.to(self.right.span())
+    }
 }
 
 impl Expression for Prefix {
@@ -433,6 +940,10 @@ impl Node for Infix {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
+
+    fn span(&self) -> Span {
+        self.left.span().to(self.right.span())
+    }
 }
 
 impl Expression for Infix {
@@ -446,18 +957,61 @@ impl fmt::Display for Infix {
     }
 }
 
+// `start..end` leaves out the end, `start..=end` includes it.
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
+pub struct RangeExpression {
+    pub token: Token,
+    pub start: Box<Expr>,
+    pub end: Box<Expr>,
+    pub inclusive: bool,
+}
+
+impl Node for RangeExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn span(&self) -> Span {
+        self.start.span().to(self.end.span())
+    }
+}
+
+impl Expression for RangeExpression {
+    fn expr_node(&self) {
+    }
+}
+
+impl fmt::Display for RangeExpression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}{}{})", self.start, self.token.literal, self.end)
+    }
+}
+
 #[derive(PartialEq, Eq, Clone, Debug, Hash)]
 pub struct FnLiteral {
     pub token: Token,
     pub name: Option<String>,
-    pub params: Vec<Identifier>,
+    pub params: Vec<Parameter>,
+    // `...rest` collects the arguments after the other parameters into an array.
+    pub rest: Option<Box<Identifier>>,
     pub body: BlockStatement,
 }
 
+impl FnLiteral {
+    // The number of parameters without a default value, which always come first.
+    pub fn required_params(&self) -> usize {
+        self.params.iter().take_while(|p| p.default.is_none()).count()
+    }
+}
+
 impl Node for FnLiteral {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
+
+    fn span(&self) -> Span {
+        self.token.span.to(self.body.span())
+    }
 }
 
 impl Expression for FnLiteral {
@@ -471,26 +1025,63 @@ impl fmt::Display for FnLiteral {
         if self.name.is_some() {
             write!(f, "<{}>", self.name.as_ref().unwrap())?;
         };
-        write!(
-            f,
-            "{}",
-            self.params.iter().map(|p| format!("{}", p)).collect::<Vec<String>>().join(", ")
-        )?;
+        let mut params = self.params.iter().map(|p| format!("{}", p)).collect::<Vec<String>>();
+        if let Some(rest) = &self.rest {
+            params.push(format!("...{}", rest));
+        };
+        write!(f, "{}", params.join(", "))?;
         write!(f, ") {{ {} }}", self.body)
     }
 }
 
+// A default value is evaluated on every call that leaves the parameter out, after the parameters
+// before it are bound.
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
+pub struct Parameter {
+    pub name: Identifier,
+    pub default: Option<Expr>,
+}
+
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.default {
+            Some(default) => write!(f, "{} = {}", self.name, default),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+// A `name: value` argument is bound to the parameter with that name. Named arguments follow the
+// positional ones.
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
+pub struct NamedArgument {
+    pub name: Identifier,
+    pub value: Expr,
+}
+
+impl fmt::Display for NamedArgument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.value)
+    }
+}
+
 #[derive(PartialEq, Eq, Clone, Debug, Hash)]
 pub struct FnCall {
     pub token: Token,
     pub function: Box<Expr>,
     pub args: Vec<Expr>,
+    pub named: Vec<NamedArgument>,
+    pub close: Position,
 }
 
 impl Node for FnCall {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
+
+    fn span(&self) -> Span {
+        Span::new(self.function.span().start, self.close)
+    }
 }
 
 impl Expression for FnCall {
@@ -504,7 +1095,12 @@ impl fmt::Display for FnCall {
         write!(
             f,
             "{})",
-            self.args.iter().map(|p| format!("{}", p)).collect::<Vec<String>>().join(", ")
+            self.args
+                .iter()
+                .map(|p| format!("{}", p))
+                .chain(self.named.iter().map(|n| format!("{}", n)))
+                .collect::<Vec<String>>()
+                .join(", ")
         )
     }
 }
@@ -514,12 +1110,17 @@ pub struct IndexOperation {
     pub token: Token,
     pub left: Box<Expr>,
     pub index: Box<Expr>,
+    pub close: Position,
 }
 
 impl Node for IndexOperation {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
+
+    fn span(&self) -> Span {
+        Span::new(self.left.span().start, self.close)
+    }
 }
 
 impl Expression for IndexOperation {
@@ -533,6 +1134,80 @@ impl fmt::Display for IndexOperation {
     }
 }
 
+// `left[start:end]` copies part of an array or string. Either bound can be left out, negative bounds
+// count back from the end.
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
+pub struct SliceOperation {
+    pub token: Token,
+    pub left: Box<Expr>,
+    pub start: Option<Box<Expr>>,
+    pub end: Option<Box<Expr>>,
+    pub close: Position,
+}
+
+impl Node for SliceOperation {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn span(&self) -> Span {
+        Span::new(self.left.span().start, self.close)
+    }
+}
+
+impl Expression for SliceOperation {
+    fn expr_node(&self) {
+    }
+}
+
+impl fmt::Display for SliceOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}[", self.left)?;
+        if let Some(start) = &self.start { write!(f, "{}", start)?; };
+        write!(f, ":")?;
+        if let Some(end) = &self.end { write!(f, "{}", end)?; };
+        write!(f, "])")
+    }
+}
+
+// `target = value`, or a compound assignment like `target += value`. The target is a variable or an
+// element of an array or hash held in one, e.g. `x`, `arr[0]` or `h["k"][1]`.
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
+pub struct Assignment {
+    pub token: Token,
+    pub target: Box<Expr>,
+    pub operator: String,
+    pub value: Box<Expr>,
+}
+
+impl Assignment {
+    // The infix operator a compound assignment applies, e.g. `+` for `+=`.
+    pub fn infix_operator(&self) -> Option<&str> {
+        self.operator.strip_suffix('=').filter(|op| !op.is_empty())
+    }
+}
+
+impl Node for Assignment {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn span(&self) -> Span {
+        self.target.span().to(self.value.span())
+    }
+}
+
+impl Expression for Assignment {
+    fn expr_node(&self) {
+    }
+}
+
+impl fmt::Display for Assignment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({} {} {})", self.target, self.operator, self.value)
+    }
+}
+
 #[derive(PartialEq, Eq, Clone, Debug, Hash)]
 pub struct MacroLiteral {
     pub token: Token,
@@ -544,6 +1219,10 @@ impl Node for MacroLiteral {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
+
+    fn span(&self) -> Span {
+        self.token.span.to(self.body.span())
+    }
 }
 
 impl Expression for MacroLiteral {
@@ -570,6 +1249,8 @@ impl fmt::Display for MacroLiteral {
 pub enum Expr {
     Ident(Identifier),
     Int(IntegerLiteral),
+    BigInt(BigIntegerLiteral),
+    Float(FloatLiteral),
     Bool(BooleanLiteral),
     Str(StringLiteral),
     Array(ArrayLiteral),
@@ -577,10 +1258,16 @@ pub enum Expr {
     Macro(MacroLiteral),
     Pre(Prefix),
     In(Infix),
+    Range(RangeExpression),
     If(IfExpression),
+    While(WhileLoop),
+    For(ForLoop),
+    Match(MatchExpression),
     Fn(FnLiteral),
     Call(FnCall),
     Index(IndexOperation),
+    Slice(SliceOperation),
+    Assign(Assignment),
 }
 
 impl Node for Expr {
@@ -588,6 +1275,8 @@ impl Node for Expr {
         match self {
             Expr::Ident(x) => x.token_literal(),
             Expr::Int(x) => x.token_literal(),
+            Expr::BigInt(x) => x.token_literal(),
+            Expr::Float(x) => x.token_literal(),
             Expr::Bool(x) => x.token_literal(),
             Expr::Str(x) => x.token_literal(),
             Expr::Array(x) => x.token_literal(),
@@ -595,10 +1284,42 @@ impl Node for Expr {
             Expr::Macro(x) => x.token_literal(),
             Expr::Pre(x) => x.token_literal(),
             Expr::In(x) => x.token_literal(),
+            Expr::Range(x) => x.token_literal(),
             Expr::If(x) => x.token_literal(),
+            Expr::While(x) => x.token_literal(),
+            Expr::For(x) => x.token_literal(),
+            Expr::Match(x) => x.token_literal(),
             Expr::Fn(x) => x.token_literal(),
             Expr::Call(x) => x.token_literal(),
             Expr::Index(x) => x.token_literal(),
+            Expr::Slice(x) => x.token_literal(),
+            Expr::Assign(x) => x.token_literal(),
+        }
+    }
+
+    fn span(&self) -> Span {
+        match self {
+            Expr::Ident(x) => x.span(),
+            Expr::Int(x) => x.span(),
+            Expr::BigInt(x) => x.span(),
+            Expr::Float(x) => x.span(),
+            Expr::Bool(x) => x.span(),
+            Expr::Str(x) => x.span(),
+            Expr::Array(x) => x.span(),
+            Expr::Hash(x) => x.span(),
+            Expr::Macro(x) => x.span(),
+            Expr::Pre(x) => x.span(),
+            Expr::In(x) => x.span(),
+            Expr::Range(x) => x.span(),
+            Expr::If(x) => x.span(),
+            Expr::While(x) => x.span(),
+            Expr::For(x) => x.span(),
+            Expr::Match(x) => x.span(),
+            Expr::Fn(x) => x.span(),
+            Expr::Call(x) => x.span(),
+            Expr::Index(x) => x.span(),
+            Expr::Slice(x) => x.span(),
+            Expr::Assign(x) => x.span(),
         }
     }
 }
@@ -608,6 +1329,8 @@ impl Expression for Expr {
         match self {
             Expr::Ident(x) => x.expr_node(),
             Expr::Int(x) => x.expr_node(),
+            Expr::BigInt(x) => x.expr_node(),
+            Expr::Float(x) => x.expr_node(),
             Expr::Bool(x) => x.expr_node(),
             Expr::Str(x) => x.expr_node(),
             Expr::Array(x) => x.expr_node(),
@@ -615,10 +1338,77 @@ impl Expression for Expr {
             Expr::Macro(x) => x.expr_node(),
             Expr::Pre(x) => x.expr_node(),
             Expr::In(x) => x.expr_node(),
+            Expr::Range(x) => x.expr_node(),
             Expr::If(x) => x.expr_node(),
+            Expr::While(x) => x.expr_node(),
+            Expr::For(x) => x.expr_node(),
+            Expr::Match(x) => x.expr_node(),
             Expr::Fn(x) => x.expr_node(),
             Expr::Call(x) => x.expr_node(),
             Expr::Index(x) => x.expr_node(),
+            Expr::Slice(x) => x.expr_node(),
+            Expr::Assign(x) => x.expr_node(),
+        }
+    }
+}
+
+impl Expr {
+    // Splits an assignment target like `a[i][j]` into the variable `a` and the indexes `[i, j]`,
+    // outermost first. Returns None when the expression can't be assigned to.
+    pub fn assignment_path(&self) -> Option<(&Identifier, Vec<&Expr>)> {
+        match self {
+            Expr::Ident(x) => Some((x, Vec::new())),
+            Expr::Index(x) => {
+                let (name, mut indexes) = x.left.assignment_path()?;
+                indexes.push(&x.index);
+                Some((name, indexes))
+            },
+            _ => None,
+        }
+    }
+
+    pub fn assigns_to(&self, name: &str) -> bool {
+        match self {
+            Expr::Ident(_) | Expr::Int(_) | Expr::BigInt(_) | Expr::Float(_) | Expr::Bool(_) | Expr::Str(_) => false,
+            Expr::Macro(_) => false,
+            Expr::Array(x) => x.elements.iter().any(|e| e.assigns_to(name)),
+            Expr::Hash(x) => x.pairs.iter().any(|(k, v)| k.assigns_to(name) || v.assigns_to(name)),
+            Expr::Pre(x) => x.right.assigns_to(name),
+            Expr::In(x) => x.left.assigns_to(name) || x.right.assigns_to(name),
+            Expr::Range(x) => x.start.assigns_to(name) || x.end.assigns_to(name),
+            Expr::If(x) => {
+                x.condition.assigns_to(name)
+                    || x.consequence.assigns_to(name)
+                    || x.alternative.as_ref().is_some_and(|b| b.assigns_to(name))
+            },
+            Expr::While(x) => x.condition.assigns_to(name) || x.body.assigns_to(name),
+            Expr::For(x) => x.iterable.assigns_to(name) || x.body.assigns_to(name),
+            Expr::Match(x) => {
+                x.subject.assigns_to(name)
+                    || x.arms.iter().any(|arm| {
+                        arm.guard.as_ref().is_some_and(|g| g.assigns_to(name)) || arm.body.assigns_to(name)
+                    })
+            },
+            Expr::Fn(x) => {
+                x.params.iter().any(|p| p.default.as_ref().is_some_and(|d| d.assigns_to(name)))
+                    || x.body.assigns_to(name)
+            },
+            Expr::Call(x) => {
+                x.function.assigns_to(name)
+                    || x.args.iter().any(|a| a.assigns_to(name))
+                    || x.named.iter().any(|n| n.value.assigns_to(name))
+            },
+            Expr::Index(x) => x.left.assigns_to(name) || x.index.assigns_to(name),
+            Expr::Slice(x) => {
+                x.left.assigns_to(name)
+                    || x.start.as_ref().is_some_and(|e| e.assigns_to(name))
+                    || x.end.as_ref().is_some_and(|e| e.assigns_to(name))
+            },
+            Expr::Assign(x) => {
+                x.target.assignment_path().is_some_and(|(ident, _)| ident.value == name)
+                    || x.target.assigns_to(name)
+                    || x.value.assigns_to(name)
+            },
         }
     }
 }
@@ -628,6 +1418,8 @@ impl fmt::Display for Expr {
         match self {
             Expr::Ident(x) => write!(f, "{}", x),
             Expr::Int(x) => write!(f, "{}", x),
+            Expr::BigInt(x) => write!(f, "{}", x),
+            Expr::Float(x) => write!(f, "{}", x),
             Expr::Bool(x) => write!(f, "{}", x),
             Expr::Str(x) => write!(f, "{}", x),
             Expr::Array(x) => write!(f, "{}", x),
@@ -635,10 +1427,16 @@ impl fmt::Display for Expr {
             Expr::Macro(x) => write!(f, "{}", x),
             Expr::Pre(x) => write!(f, "{}", x),
             Expr::In(x) => write!(f, "{}", x),
+            Expr::Range(x) => write!(f, "{}", x),
             Expr::If(x) => write!(f, "{}", x),
+            Expr::While(x) => write!(f, "{}", x),
+            Expr::For(x) => write!(f, "{}", x),
+            Expr::Match(x) => write!(f, "{}", x),
             Expr::Fn(x) => write!(f, "{}", x),
             Expr::Call(x) => write!(f, "{}", x),
             Expr::Index(x) => write!(f, "{}", x),
+            Expr::Slice(x) => write!(f, "{}", x),
+            Expr::Assign(x) => write!(f, "{}", x),
         }
     }
 }
@@ -706,60 +1504,67 @@ pub fn modify(node: MNode, env: Rc<RefCell<Environment>>, modifier: fn(MNode, Rc
 
                     MNode::Stmt(Stmt::Let(let_stmt))
                 },
+                Stmt::Destructure(d) => {
+                    let mut destructure = d.clone();
+                    let value = destructure.value;
+
+                    destructure.value = match modify(MNode::Expr(value.clone()), env, modifier) {
+                        MNode::Expr(x) => x,
+                        _ => value,
+                    };
+
+                    MNode::Stmt(Stmt::Destructure(destructure))
+                },
+                Stmt::Break(_) | Stmt::Continue(_) => MNode::Stmt(s),
             }
         },
         MNode::Expr(ref e) => {
             match e {
                 Expr::In(i) => {
                     let mut infix = i.clone();
-                    let left = *infix.left;
-                    infix.left = match modify(MNode::Expr(left.clone()), env.clone(), modifier) {
-                        MNode::Expr(x) => Box::new(x),
-                        _ => Box::new(left),
-                    };
-
-                    let right = *infix.right;
-                    infix.right = match modify(MNode::Expr(right.clone()), env, modifier) {
-                        MNode::Expr(x) => Box::new(x),
-                        _ => Box::new(right),
-                    };
+                    infix.left = modify_expr(infix.left, env.clone(), modifier);
+                    infix.right = modify_expr(infix.right, env, modifier);
 
                     MNode::Expr(Expr::In(infix))
                 },
                 Expr::Pre(p) => {
                     let mut prefix = p.clone();
-
-                    let right = *prefix.right;
-                    prefix.right = match modify(MNode::Expr(right.clone()), env, modifier) {
-                        MNode::Expr(x) => Box::new(x),
-                        _ => Box::new(right),
-                    };
+                    prefix.right = modify_expr(prefix.right, env, modifier);
 
                     MNode::Expr(Expr::Pre(prefix))
                 },
                 Expr::Index(i) => {
                     let mut index_expr = i.clone();
-                    let left = *index_expr.left;
-                    index_expr.left = match modify(MNode::Expr(left.clone()), env.clone(), modifier) {
-                        MNode::Expr(x) => Box::new(x),
-                        _ => Box::new(left),
-                    };
-
-                    let index = *index_expr.index;
-                    index_expr.index = match modify(MNode::Expr(index.clone()), env, modifier) {
-                        MNode::Expr(x) => Box::new(x),
-                        _ => Box::new(index),
-                    };
+                    index_expr.left = modify_expr(index_expr.left, env.clone(), modifier);
+                    index_expr.index = modify_expr(index_expr.index, env, modifier);
 
                     MNode::Expr(Expr::Index(index_expr))
                 },
+                Expr::Range(r) => {
+                    let mut range = r.clone();
+                    range.start = modify_expr(range.start, env.clone(), modifier);
+                    range.end = modify_expr(range.end, env, modifier);
+
+                    MNode::Expr(Expr::Range(range))
+                },
+                Expr::Slice(s) => {
+                    let mut slice = s.clone();
+                    slice.left = modify_expr(slice.left, env.clone(), modifier);
+                    slice.start = slice.start.map(|start| modify_expr(start, env.clone(), modifier));
+                    slice.end = slice.end.map(|end| modify_expr(end, env.clone(), modifier));
+
+                    MNode::Expr(Expr::Slice(slice))
+                },
+                Expr::Assign(a) => {
+                    let mut assign = a.clone();
+                    assign.target = modify_expr(assign.target, env.clone(), modifier);
+                    assign.value = modify_expr(assign.value, env, modifier);
+
+                    MNode::Expr(Expr::Assign(assign))
+                },
                 Expr::If(i) => {
                     let mut if_expr = i.clone();
-                    let condition = *if_expr.condition;
-                    if_expr.condition = match modify(MNode::Expr(condition.clone()), env.clone(), modifier) {
-                        MNode::Expr(x) => Box::new(x),
-                        _ => Box::new(condition),
-                    };
+                    if_expr.condition = modify_expr(if_expr.condition, env.clone(), modifier);
 
                     let consequence = if_expr.consequence;
                     if_expr.consequence = match modify(MNode::Stmt(Stmt::Block(consequence.clone())), env.clone(), modifier) {
@@ -786,36 +1591,32 @@ pub fn modify(node: MNode, env: Rc<RefCell<Environment>>, modifier: fn(MNode, Rc
 
                     MNode::Expr(Expr::If(if_expr))
                 },
-                Expr::Fn(f) => {
-                    let mut func = f.clone();
-                    func.params = func.params
-                        .iter()
-                        .map(|ident| {
-                            match modify(MNode::Expr(Expr::Ident(ident.clone())), env.clone(), modifier) {
-                                MNode::Expr(e) => {
-                                    match e {
-                                        Expr::Ident(i) => i,
-                                        _ => ident.clone(),
-                                    }
-                                },
-                                _ => ident.clone(),
-                            }
-                        })
-                    .collect::<Vec<Identifier>>();
+                Expr::While(w) => {
+                    let mut while_loop = w.clone();
+                    while_loop.condition = modify_expr(while_loop.condition, env.clone(), modifier);
+
+                    let body = while_loop.body;
+                    while_loop.body = match modify(MNode::Stmt(Stmt::Block(body.clone())), env, modifier) {
+                        MNode::Stmt(Stmt::Block(b)) => b,
+                        _ => body,
+                    };
 
-                    let block = func.body;
-                    func.body = match modify(MNode::Stmt(Stmt::Block(block.clone())), env, modifier) {
-                        MNode::Stmt(x) => {
-                            match x {
-                                Stmt::Block(b) => b,
-                                _ => block,
-                            }
-                        },
-                        _ => block,
+                    MNode::Expr(Expr::While(while_loop))
+                },
+                Expr::For(l) => {
+                    let mut for_loop = l.clone();
+                    for_loop.iterable = modify_expr(for_loop.iterable, env.clone(), modifier);
+
+                    let body = for_loop.body;
+                    for_loop.body = match modify(MNode::Stmt(Stmt::Block(body.clone())), env, modifier) {
+                        MNode::Stmt(Stmt::Block(b)) => b,
+                        _ => body,
                     };
 
-                    MNode::Expr(Expr::Fn(func))
+                    MNode::Expr(Expr::For(for_loop))
                 },
+                Expr::Match(m) => MNode::Expr(Expr::Match(modify_match(m.clone(), env, modifier))),
+                Expr::Fn(f) => MNode::Expr(Expr::Fn(modify_fn(f.clone(), env, modifier))),
                 Expr::Array(a) => {
                     let mut array = a.clone();
                     array.elements = array.elements
@@ -858,6 +1659,56 @@ pub fn modify(node: MNode, env: Rc<RefCell<Environment>>, modifier: fn(MNode, Rc
     }
 }
 
+fn modify_fn(mut func: FnLiteral, env: Rc<RefCell<Environment>>, modifier: fn(MNode, Rc<RefCell<Environment>>) -> MNode) -> FnLiteral {
+    let modify_ident = |ident: &Identifier| {
+        match modify(MNode::Expr(Expr::Ident(ident.clone())), env.clone(), modifier) {
+            MNode::Expr(Expr::Ident(i)) => i,
+            _ => ident.clone(),
+        }
+    };
+
+    for param in func.params.iter_mut() {
+        param.name = modify_ident(&param.name);
+        if let Some(default) = param.default.take() {
+            param.default = Some(*modify_expr(Box::new(default), env.clone(), modifier));
+        };
+    };
+    func.rest = func.rest.as_deref().map(|rest| Box::new(modify_ident(rest)));
+
+    let block = func.body;
+    func.body = match modify(MNode::Stmt(Stmt::Block(block.clone())), env, modifier) {
+        MNode::Stmt(Stmt::Block(b)) => b,
+        _ => block,
+    };
+
+    func
+}
+
+fn modify_match(mut match_expr: MatchExpression, env: Rc<RefCell<Environment>>, modifier: fn(MNode, Rc<RefCell<Environment>>) -> MNode) -> MatchExpression {
+    match_expr.subject = modify_expr(match_expr.subject, env.clone(), modifier);
+
+    for arm in match_expr.arms.iter_mut() {
+        if let Some(guard) = arm.guard.take() {
+            arm.guard = Some(*modify_expr(Box::new(guard), env.clone(), modifier));
+        };
+
+        arm.body = match modify(MNode::Stmt(Stmt::Block(arm.body.clone())), env.clone(), modifier) {
+            MNode::Stmt(Stmt::Block(b)) => b,
+            _ => arm.body.clone(),
+        };
+    };
+
+    match_expr
+}
+
+// Modifies a boxed child expression, keeping the original if the modifier returns something else.
+fn modify_expr(expr: Box<Expr>, env: Rc<RefCell<Environment>>, modifier: fn(MNode, Rc<RefCell<Environment>>) -> MNode) -> Box<Expr> {
+    match modify(MNode::Expr((*expr).clone()), env, modifier) {
+        MNode::Expr(x) => Box::new(x),
+        _ => expr,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -872,13 +1723,13 @@ mod tests {
         let mut program = Program::new();
 
         program.stmts.push(Stmt::Let(LetStatement {
-            token: Token { token_type: TokenType::LET, literal: "let".to_string() },
+            token: Token::new(TokenType::LET, "let".to_string()),
             name: Identifier {
-                token: Token { token_type: TokenType::IDENT, literal: "myVar".to_string() },
+                token: Token::new(TokenType::IDENT, "myVar".to_string()),
                 value: "myVar".to_string(),
             },
             value: Expr::Ident(Identifier {
-                token: Token { token_type: TokenType::IDENT, literal: "anotherVar".to_string() },
+                token: Token::new(TokenType::IDENT, "anotherVar".to_string()),
                 value: "anotherVar".to_string(),
             }),
         }));
@@ -888,8 +1739,8 @@ mod tests {
 
     #[test]
     fn test_modify() -> Result<()> {
-        let one = || { Expr::Int(IntegerLiteral { token: Token { token_type: TokenType::INT, literal: "1".to_string() }, value: 1 }) };
-        let two = || { Expr::Int(IntegerLiteral { token: Token { token_type: TokenType::INT, literal: "1".to_string() }, value: 2 }) };
+        let one = || { Expr::Int(IntegerLiteral { token: Token::new(TokenType::INT, "1".to_string()), value: 1 }) };
+        let two = || { Expr::Int(IntegerLiteral { token: Token::new(TokenType::INT, "1".to_string()), value: 2 }) };
 
         let tests = vec![
             (MNode::Expr(one()), MNode::Expr(two())),
@@ -899,7 +1750,7 @@ mod tests {
                         stmts: vec![
                             Stmt::Expression(
                                 ExpressionStatement {
-                                    token: Token { token_type: TokenType::INT, literal: "1".to_string() },
+                                    token: Token::new(TokenType::INT, "1".to_string()),
                                     expr: one(),
                                 }
                             ),
@@ -911,7 +1762,7 @@ mod tests {
                         stmts: vec![
                             Stmt::Expression(
                                 ExpressionStatement {
-                                    token: Token { token_type: TokenType::INT, literal: "1".to_string() },
+                                    token: Token::new(TokenType::INT, "1".to_string()),
                                     expr: two(),
                                 }
                             ),
@@ -923,7 +1774,7 @@ mod tests {
                 MNode::Expr(
                     Expr::In(
                         Infix {
-                            token: Token { token_type: TokenType::PLUS, literal: "+".to_string() },
+                            token: Token::new(TokenType::PLUS, "+".to_string()),
                             left: Box::new(one()),
                             operator: "+".to_string(),
                             right: Box::new(two()),
@@ -933,7 +1784,7 @@ mod tests {
                 MNode::Expr(
                     Expr::In(
                         Infix {
-                            token: Token { token_type: TokenType::PLUS, literal: "+".to_string() },
+                            token: Token::new(TokenType::PLUS, "+".to_string()),
                             left: Box::new(two()),
                             operator: "+".to_string(),
                             right: Box::new(two()),
@@ -945,7 +1796,7 @@ mod tests {
                 MNode::Expr(
                     Expr::In(
                         Infix {
-                            token: Token { token_type: TokenType::PLUS, literal: "+".to_string() },
+                            token: Token::new(TokenType::PLUS, "+".to_string()),
                             left: Box::new(two()),
                             operator: "+".to_string(),
                             right: Box::new(one()),
@@ -955,7 +1806,7 @@ mod tests {
                 MNode::Expr(
                     Expr::In(
                         Infix {
-                            token: Token { token_type: TokenType::PLUS, literal: "+".to_string() },
+                            token: Token::new(TokenType::PLUS, "+".to_string()),
                             left: Box::new(two()),
                             operator: "+".to_string(),
                             right: Box::new(two()),
@@ -967,7 +1818,7 @@ mod tests {
                 MNode::Expr(
                     Expr::Pre(
                         Prefix {
-                            token: Token { token_type: TokenType::MINUS, literal: "-".to_string() },
+                            token: Token::new(TokenType::MINUS, "-".to_string()),
                             operator: "-".to_string(),
                             right: Box::new(one()),
                         },
@@ -976,7 +1827,7 @@ mod tests {
                 MNode::Expr(
                     Expr::Pre(
                         Prefix {
-                            token: Token { token_type: TokenType::MINUS, literal: "-".to_string() },
+                            token: Token::new(TokenType::MINUS, "-".to_string()),
                             operator: "-".to_string(),
                             right: Box::new(two()),
                         },
@@ -987,7 +1838,8 @@ mod tests {
                 MNode::Expr(
                     Expr::Index(
                         IndexOperation {
-                            token: Token { token_type: TokenType::LBRACKET, literal: "[".to_string() },
+                            token: Token::new(TokenType::LBRACKET, "[".to_string()),
+                            close: Position::default(),
                             left: Box::new(one()),
                             index: Box::new(one()),
                         },
@@ -996,7 +1848,8 @@ mod tests {
                 MNode::Expr(
                     Expr::Index(
                         IndexOperation {
-                            token: Token { token_type: TokenType::LBRACKET, literal: "[".to_string() },
+                            token: Token::new(TokenType::LBRACKET, "[".to_string()),
+                            close: Position::default(),
                             left: Box::new(two()),
                             index: Box::new(two()),
                         },
@@ -1007,14 +1860,15 @@ mod tests {
                 MNode::Expr(
                     Expr::If(
                         IfExpression {
-                            token: Token { token_type: TokenType::IF, literal: "if".to_string() },
+                            token: Token::new(TokenType::IF, "if".to_string()),
                             condition: Box::new(one()),
                             consequence: BlockStatement {
-                                token: Token { token_type: TokenType::LBRACE, literal: "{".to_string() },
+                                token: Token::new(TokenType::LBRACE, "{".to_string()),
+                                close: Position::default(),
                                 stmts: vec![
                                     Stmt::Expression(
                                         ExpressionStatement {
-                                            token: Token { token_type: TokenType::INT, literal: "1".to_string() },
+                                            token: Token::new(TokenType::INT, "1".to_string()),
                                             expr: one(),
                                         },
                                     ),
@@ -1022,11 +1876,12 @@ mod tests {
                             },
                             alternative: Some(
                                 BlockStatement {
-                                    token: Token { token_type: TokenType::LBRACE, literal: "{".to_string() },
+                                    token: Token::new(TokenType::LBRACE, "{".to_string()),
+                                    close: Position::default(),
                                     stmts: vec![
                                         Stmt::Expression(
                                             ExpressionStatement {
-                                                token: Token { token_type: TokenType::INT, literal: "1".to_string() },
+                                                token: Token::new(TokenType::INT, "1".to_string()),
                                                 expr: one(),
                                             },
                                         ),
@@ -1039,14 +1894,15 @@ mod tests {
                 MNode::Expr(
                     Expr::If(
                         IfExpression {
-                            token: Token { token_type: TokenType::IF, literal: "if".to_string() },
+                            token: Token::new(TokenType::IF, "if".to_string()),
                             condition: Box::new(two()),
                             consequence: BlockStatement {
-                                token: Token { token_type: TokenType::LBRACE, literal: "{".to_string() },
+                                token: Token::new(TokenType::LBRACE, "{".to_string()),
+                                close: Position::default(),
                                 stmts: vec![
                                     Stmt::Expression(
                                         ExpressionStatement {
-                                            token: Token { token_type: TokenType::INT, literal: "1".to_string() },
+                                            token: Token::new(TokenType::INT, "1".to_string()),
                                             expr: two(),
                                         },
                                     ),
@@ -1054,11 +1910,12 @@ mod tests {
                             },
                             alternative: Some(
                                 BlockStatement {
-                                    token: Token { token_type: TokenType::LBRACE, literal: "{".to_string() },
+                                    token: Token::new(TokenType::LBRACE, "{".to_string()),
+                                    close: Position::default(),
                                     stmts: vec![
                                         Stmt::Expression(
                                             ExpressionStatement {
-                                                token: Token { token_type: TokenType::INT, literal: "1".to_string() },
+                                                token: Token::new(TokenType::INT, "1".to_string()),
                                                 expr: two(),
                                             },
                                         ),
@@ -1073,7 +1930,7 @@ mod tests {
                 MNode::Stmt(
                     Stmt::Return(
                         ReturnStatement {
-                            token: Token { token_type: TokenType::RBRACKET, literal: "return".to_string() },
+                            token: Token::new(TokenType::RBRACKET, "return".to_string()),
                             retval: one(),
                         },
                     ),
@@ -1081,7 +1938,7 @@ mod tests {
                 MNode::Stmt(
                     Stmt::Return(
                         ReturnStatement {
-                            token: Token { token_type: TokenType::RBRACKET, literal: "return".to_string() },
+                            token: Token::new(TokenType::RBRACKET, "return".to_string()),
                             retval: two(),
                         },
                     ),
@@ -1091,9 +1948,9 @@ mod tests {
                 MNode::Stmt(
                     Stmt::Let(
                         LetStatement {
-                            token: Token { token_type: TokenType::LET, literal: "let".to_string() },
+                            token: Token::new(TokenType::LET, "let".to_string()),
                             name: Identifier {
-                                token: Token { token_type: TokenType::IDENT, literal: "a".to_string() },
+                                token: Token::new(TokenType::IDENT, "a".to_string()),
                                 value: "a".to_string()
                             },
                             value: one(),
@@ -1103,9 +1960,9 @@ mod tests {
                 MNode::Stmt(
                     Stmt::Let(
                         LetStatement {
-                            token: Token { token_type: TokenType::LET, literal: "let".to_string() },
+                            token: Token::new(TokenType::LET, "let".to_string()),
                             name: Identifier {
-                                token: Token { token_type: TokenType::IDENT, literal: "a".to_string() },
+                                token: Token::new(TokenType::IDENT, "a".to_string()),
                                 value: "a".to_string()
                             },
                             value: two(),
@@ -1117,15 +1974,17 @@ mod tests {
                 MNode::Expr(
                     Expr::Fn(
                         FnLiteral {
-                            token: Token { token_type: TokenType::LET, literal: "let".to_string() },
+                            token: Token::new(TokenType::LET, "let".to_string()),
                             name: None,
                             params: vec![],
+                            rest: None,
                             body: BlockStatement {
-                                token: Token { token_type: TokenType::LBRACE, literal: "{".to_string() },
+                                token: Token::new(TokenType::LBRACE, "{".to_string()),
+                                close: Position::default(),
                                 stmts: vec![
                                     Stmt::Expression(
                                         ExpressionStatement {
-                                            token: Token { token_type: TokenType::INT, literal: "1".to_string() },
+                                            token: Token::new(TokenType::INT, "1".to_string()),
                                             expr: one(),
                                         },
                                     ),
@@ -1137,15 +1996,17 @@ mod tests {
                 MNode::Expr(
                     Expr::Fn(
                         FnLiteral {
-                            token: Token { token_type: TokenType::LET, literal: "let".to_string() },
+                            token: Token::new(TokenType::LET, "let".to_string()),
                             name: None,
                             params: vec![],
+                            rest: None,
                             body: BlockStatement {
-                                token: Token { token_type: TokenType::LBRACE, literal: "{".to_string() },
+                                token: Token::new(TokenType::LBRACE, "{".to_string()),
+                                close: Position::default(),
                                 stmts: vec![
                                     Stmt::Expression(
                                         ExpressionStatement {
-                                            token: Token { token_type: TokenType::INT, literal: "1".to_string() },
+                                            token: Token::new(TokenType::INT, "1".to_string()),
                                             expr: two(),
                                         },
                                     ),
@@ -1159,7 +2020,8 @@ mod tests {
                 MNode::Expr(
                     Expr::Array(
                         ArrayLiteral {
-                            token: Token { token_type: TokenType::LBRACKET, literal: "[".to_string() },
+                            token: Token::new(TokenType::LBRACKET, "[".to_string()),
+                            close: Position::default(),
                             elements: vec![one()],
                         },
                     ),
@@ -1167,7 +2029,8 @@ mod tests {
                 MNode::Expr(
                     Expr::Array(
                         ArrayLiteral {
-                            token: Token { token_type: TokenType::LBRACKET, literal: "[".to_string() },
+                            token: Token::new(TokenType::LBRACKET, "[".to_string()),
+                            close: Position::default(),
                             elements: vec![two()],
                         },
                     ),
@@ -1177,7 +2040,8 @@ mod tests {
                 MNode::Expr(
                     Expr::Hash(
                         HashLiteral {
-                            token: Token { token_type: TokenType::LBRACE, literal: "{".to_string() },
+                            token: Token::new(TokenType::LBRACE, "{".to_string()),
+                            close: Position::default(),
                             pairs: HashMap::from([
                                 (one(), one()),
                             ]),
@@ -1187,7 +2051,8 @@ mod tests {
                 MNode::Expr(
                     Expr::Hash(
                         HashLiteral {
-                            token: Token { token_type: TokenType::LBRACE, literal: "{".to_string() },
+                            token: Token::new(TokenType::LBRACE, "{".to_string()),
+                            close: Position::default(),
                             pairs: HashMap::from([
                                 (two(), two()),
                             ]),
@@ -12,7 +12,7 @@ use monkey::{
         compiler::Compiler,
     },
     parser::parser::Parser,
-    repl::Engine,
+    engine::Engine,
 };
 
 use std::{
@@ -103,12 +103,5 @@ fn check_parser_errors<I: Iterator<Item = Result<Token>>>(p: Parser<I>) -> Resul
 
     if errors.is_empty() { return Ok(()); };
 
-    let mut msg = format!("The Parser had {} errors:\n", errors.len());
-
-    for e in errors {
-        msg.push_str(&e);
-        msg.push('\n');
-    }
-
-    Err(Error::new(msg))
+    Err(Error::Parse(errors))
 }
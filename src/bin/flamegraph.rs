@@ -1,5 +1,5 @@
 use monkey::{
-    repl::Engine,
+    engine::Engine,
     error::{Result, Error},
     ast::{
         MNode,
@@ -68,12 +68,5 @@ fn check_parser_errors<I: Iterator<Item = Result<Token>>>(p: Parser<I>) -> Resul
 
     if errors.is_empty() { return Ok(()); };
 
-    let mut msg = format!("The Parser had {} errors:\n", errors.len());
-
-    for e in errors {
-        msg.push_str(&e);
-        msg.push('\n');
-    }
-
-    Err(Error::new(msg))
+    Err(Error::Parse(errors))
 }
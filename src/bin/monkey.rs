@@ -0,0 +1,51 @@
+use std::{io::{self, IsTerminal}, env, fs, process};
+
+use monkey::{
+    cli::{Options, USAGE},
+    diagnostic,
+    repl::{start, start_interactive},
+    runner,
+};
+
+fn main() {
+    let mut options = match Options::parse(env::args().skip(1)) {
+        Some(x) => x,
+        None => usage(),
+    };
+    let engine = &mut options.engine;
+
+    let path = match options.script {
+        Some(x) => x,
+        None => {
+            let result = if io::stdin().is_terminal() {
+                start_interactive(engine)
+            } else {
+                start(io::stdin(), &mut io::stdout(), engine)
+            };
+
+            if let Err(e) = result {
+                eprintln!("error: {}", e);
+                process::exit(1);
+            };
+            return;
+        },
+    };
+
+    let source = match fs::read_to_string(&path) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("error: could not read {}: {}", path, e);
+            process::exit(1);
+        },
+    };
+
+    if let Err(e) = runner::run(&source, &options.args, engine) {
+        eprint!("{}", diagnostic::render(&e, &path, &source));
+        process::exit(1);
+    };
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
@@ -1,17 +1,33 @@
-use std::{io, env};
+use std::{io::{self, IsTerminal}, env, process};
 
-use monkey::repl::{start, Engine};
+use monkey::{
+    cli::Options,
+    repl::{start, start_interactive},
+};
+
+const USAGE: &str = "usage: repl [--engine=vm|eval] [--strict]";
 
 fn main() {
-    let args = env::args();
+    // Scripts are run by the `monkey` binary, this one only starts the REPL.
+    let mut options = match Options::parse(env::args().skip(1)) {
+        Some(x) if x.script.is_none() => x,
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        },
+    };
+
     let input = io::stdin();
     let mut output = io::stdout();
 
-    let mut engine = if args.into_iter().any(|arg| arg == "--engine=vm") {
-        Engine::vm()
+    let result = if input.is_terminal() {
+        start_interactive(&mut options.engine)
     } else {
-        Engine::eval()
+        start(input, &mut output, &mut options.engine)
     };
 
-    start(input, &mut output, &mut engine).unwrap();
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    };
 }
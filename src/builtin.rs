@@ -2,6 +2,7 @@ use std::{fmt, io::{self, Write}};
 
 use crate::{
     error::Result,
+    lexer::span::Span,
     object::*,
 };
 
@@ -87,6 +88,22 @@ impl PartialEq for Builtin {
 
 impl Eq for Builtin {}
 
+impl Builtin {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Builtin::Len(_) => "len",
+            Builtin::First(_) => "first",
+            Builtin::Last(_) => "last",
+            Builtin::Rest(_) => "rest",
+            Builtin::Push(_) => "push",
+            Builtin::Puts(_) => "puts",
+        }
+    }
+}
+
+// Ordered by their index in `get_builtin_by_index`.
+pub const NAMES: [&str; 6] = ["len", "first", "last", "rest", "push", "puts"];
+
 pub const LEN: MObject = MObject::Builtin(
     Builtin::Len(self::len)
 );
@@ -129,6 +146,8 @@ fn len(args: &mut Vec<MObject>) -> Result<MObject> {
             MObject::Err(
                 MError {
                     value: format!("wrong number of arguments, got: {}, want: 1", args.len()),
+                    span: Span::default(),
+                    note: None,
                 }
             )
         )
@@ -137,7 +156,7 @@ fn len(args: &mut Vec<MObject>) -> Result<MObject> {
     let arg = args.pop().unwrap();
 
     if let MObject::Str(s) = arg {
-        Ok(MObject::Int(Integer { value: s.value.len() as i128 }))
+        Ok(MObject::Int(Integer { value: s.value.chars().count() as i128 }))
     } else if let MObject::Array(arr) = arg {
         Ok(MObject::Int(Integer { value: arr.elements.len() as i128 }))
     } else {
@@ -145,6 +164,8 @@ fn len(args: &mut Vec<MObject>) -> Result<MObject> {
             MObject::Err(
                 MError {
                     value: format!("argument to 'len' not supported, got: {}", arg),
+                    span: Span::default(),
+                    note: None,
                 }
             )
         )
@@ -157,6 +178,8 @@ fn first(args: &mut Vec<MObject>) -> Result<MObject> {
             MObject::Err(
                 MError {
                     value: format!("wrong number of arguments, got: {}, want: 1", args.len()),
+                    span: Span::default(),
+                    note: None,
                 }
             )
         )
@@ -175,6 +198,8 @@ fn first(args: &mut Vec<MObject>) -> Result<MObject> {
             MObject::Err(
                 MError {
                     value: format!("argument to 'first' not supported, got: {}", arg),
+                    span: Span::default(),
+                    note: None,
                 }
             )
         )
@@ -187,6 +212,8 @@ fn last(args: &mut Vec<MObject>) -> Result<MObject> {
             MObject::Err(
                 MError {
                     value: format!("wrong number of arguments, got: {}, want: 1", args.len()),
+                    span: Span::default(),
+                    note: None,
                 }
             )
         )
@@ -205,6 +232,8 @@ fn last(args: &mut Vec<MObject>) -> Result<MObject> {
             MObject::Err(
                 MError {
                     value: format!("argument to 'last' not supported, got: {}", arg),
+                    span: Span::default(),
+                    note: None,
                 }
             )
         )
@@ -217,6 +246,8 @@ fn rest(args: &mut Vec<MObject>) -> Result<MObject> {
             MObject::Err(
                 MError {
                     value: format!("wrong number of arguments, got: {}, want: 1", args.len()),
+                    span: Span::default(),
+                    note: None,
                 }
             )
         )
@@ -236,6 +267,8 @@ fn rest(args: &mut Vec<MObject>) -> Result<MObject> {
             MObject::Err(
                 MError {
                     value: format!("argument to 'rest' not supported, got: {}", arg),
+                    span: Span::default(),
+                    note: None,
                 }
             )
         )
@@ -248,6 +281,8 @@ fn push(args: &mut Vec<MObject>) -> Result<MObject> {
             MObject::Err(
                 MError {
                     value: format!("wrong number of arguments, got: {}, want: 2", args.len()),
+                    span: Span::default(),
+                    note: None,
                 }
             )
         )
@@ -266,6 +301,8 @@ fn push(args: &mut Vec<MObject>) -> Result<MObject> {
             MObject::Err(
                 MError {
                     value: format!("first argument to 'push' not supported, got: {}", array),
+                    span: Span::default(),
+                    note: None,
                 }
             )
         )
@@ -0,0 +1,61 @@
+use crate::engine::Engine;
+
+// The command line shared by the `monkey` and `repl` binaries.
+pub const USAGE: &str = "usage: monkey [--engine=vm|eval] [--strict] [run <file> [args...]]";
+
+pub struct Options {
+    pub engine: Engine,
+    // The script to run and its arguments, without one the REPL is started.
+    pub script: Option<String>,
+    pub args: Vec<String>,
+}
+
+impl Options {
+    // None for an unknown flag or `run` without a file, the caller prints the usage.
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Option<Options> {
+        let mut engine = Engine::eval();
+        let mut strict = false;
+        let mut script = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--engine=vm" => engine = Engine::vm(),
+                "--engine=eval" => engine = Engine::eval(),
+                "--strict" => strict = true,
+                "run" => {
+                    script = Some(args.next()?);
+                    break;
+                },
+                _ => return None,
+            };
+        }
+
+        engine.set_strict(strict);
+
+        Some(Options { engine, script, args: args.collect() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Option<Options> {
+        Options::parse(args.iter().map(|x| x.to_string()))
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let options = parse(&[]).unwrap();
+        assert_eq!("eval", options.engine.name());
+        assert_eq!(None, options.script);
+
+        let options = parse(&["--strict", "--engine=vm", "run", "a.monkey", "--engine=eval", "x"]).unwrap();
+        assert_eq!("vm", options.engine.name());
+        assert_eq!(Some("a.monkey".to_string()), options.script);
+        assert_eq!(vec!["--engine=eval".to_string(), "x".to_string()], options.args);
+
+        assert!(parse(&["--engine=jit"]).is_none());
+        assert!(parse(&["run"]).is_none());
+    }
+}
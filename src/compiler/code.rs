@@ -1,16 +1,40 @@
 use std::{
     convert::From,
     collections::HashMap,
+    rc::Rc,
 };
 
 use byteorder::{ByteOrder, BigEndian, WriteBytesExt};
 
-use crate::error::{Result, Error};
+use crate::{
+    error::{Result, Error, CompileError, CompileErrorKind},
+    lexer::span::Span,
+};
 
 pub type Instructions = Vec<u8>;
 pub type Operand = Vec<isize>;
 pub type Opcode = u8;
 
+// The span of the expression each instruction was compiled from, by offset, so that the VM can
+// point a runtime error at the source. It is shared by all the closures made from a function.
+#[derive(Clone, Debug, Default)]
+pub struct SpanTable(Rc<[(usize, Span)]>);
+
+impl SpanTable {
+    pub fn new(spans: Vec<(usize, Span)>) -> Self {
+        Self(spans.into())
+    }
+
+    // The entries are in offset order. An instruction emitted where a removed one was has a later
+    // entry for the same offset, which wins.
+    pub fn lookup(&self, ip: usize) -> Span {
+        match self.0.partition_point(|(offset, _)| *offset <= ip) {
+            0 => Span::default(),
+            i => self.0[i - 1].1,
+        }
+    }
+}
+
 pub const OP_CONSTANT: u8    = 0;
 pub const OP_POP: u8         = 1;
 pub const OP_ADD: u8         = 2;
@@ -41,6 +65,38 @@ pub const OP_GET_BUILTIN: u8        = 26;
 pub const OP_CLOSURE: u8            = 27;
 pub const OP_GET_FREE: u8           = 28;
 pub const OP_CURRENT_CLOSURE: u8    = 29;
+pub const OP_MOD: u8                = 30;
+pub const OP_POW: u8                = 31;
+pub const OP_BIT_AND: u8            = 32;
+pub const OP_BIT_OR: u8             = 33;
+pub const OP_BIT_XOR: u8            = 34;
+pub const OP_SHL: u8                = 35;
+pub const OP_SHR: u8                = 36;
+pub const OP_BIT_NOT: u8            = 37;
+pub const OP_LESS_THAN: u8          = 38;
+pub const OP_LESS_EQUAL: u8         = 39;
+pub const OP_GREATER_EQUAL: u8      = 40;
+pub const OP_JUMP_TRUE: u8          = 41;
+pub const OP_SET_FREE: u8           = 42;
+pub const OP_CAPTURE_LOCAL: u8      = 43;
+pub const OP_CAPTURE_FREE: u8       = 44;
+pub const OP_SET_INDEX: u8          = 45;
+pub const OP_PEEK_INDEX: u8         = 46;
+pub const OP_ITER: u8               = 47;
+pub const OP_ITER_NEXT: u8          = 48;
+pub const OP_RANGE: u8              = 49;
+pub const OP_SLICE: u8              = 50;
+pub const OP_DESTRUCTURE_ARRAY: u8  = 51;
+pub const OP_DESTRUCTURE_HASH: u8   = 52;
+pub const OP_DUP: u8                = 53;
+pub const OP_MATCH_VALUE: u8        = 54;
+pub const OP_MATCH_ARRAY: u8        = 55;
+pub const OP_MATCH_HASH: u8         = 56;
+pub const OP_LOOP: u8               = 57;
+pub const OP_LOOP_END: u8           = 58;
+pub const OP_UNWIND: u8             = 59;
+pub const OP_CALL_NAMED: u8         = 60;
+pub const OP_JUMP_PASSED: u8        = 61;
 
 #[derive(Clone)]
 pub struct Definition {
@@ -85,6 +141,38 @@ impl MCode {
             (OP_CLOSURE, Definition { name: "OpClosure".to_string(), operand_widths: vec![2, 1] }),
             (OP_GET_FREE, Definition { name: "OpGetFree".to_string(), operand_widths: vec![1] }),
             (OP_CURRENT_CLOSURE, Definition { name: "OpCurrentClosure".to_string(), operand_widths: vec![] }),
+            (OP_MOD, Definition { name: "OpMod".to_string(), operand_widths: vec![] }),
+            (OP_POW, Definition { name: "OpPow".to_string(), operand_widths: vec![] }),
+            (OP_BIT_AND, Definition { name: "OpBitAnd".to_string(), operand_widths: vec![] }),
+            (OP_BIT_OR, Definition { name: "OpBitOr".to_string(), operand_widths: vec![] }),
+            (OP_BIT_XOR, Definition { name: "OpBitXor".to_string(), operand_widths: vec![] }),
+            (OP_SHL, Definition { name: "OpShl".to_string(), operand_widths: vec![] }),
+            (OP_SHR, Definition { name: "OpShr".to_string(), operand_widths: vec![] }),
+            (OP_BIT_NOT, Definition { name: "OpBitNot".to_string(), operand_widths: vec![] }),
+            (OP_LESS_THAN, Definition { name: "OpLessThan".to_string(), operand_widths: vec![] }),
+            (OP_LESS_EQUAL, Definition { name: "OpLessEqual".to_string(), operand_widths: vec![] }),
+            (OP_GREATER_EQUAL, Definition { name: "OpGreaterEqual".to_string(), operand_widths: vec![] }),
+            (OP_JUMP_TRUE, Definition { name: "OpJumpTrue".to_string(), operand_widths: vec![2] }),
+            (OP_SET_FREE, Definition { name: "OpSetFree".to_string(), operand_widths: vec![1] }),
+            (OP_CAPTURE_LOCAL, Definition { name: "OpCaptureLocal".to_string(), operand_widths: vec![1] }),
+            (OP_CAPTURE_FREE, Definition { name: "OpCaptureFree".to_string(), operand_widths: vec![1] }),
+            (OP_SET_INDEX, Definition { name: "OpSetIndex".to_string(), operand_widths: vec![1] }),
+            (OP_PEEK_INDEX, Definition { name: "OpPeekIndex".to_string(), operand_widths: vec![1] }),
+            (OP_ITER, Definition { name: "OpIter".to_string(), operand_widths: vec![] }),
+            (OP_ITER_NEXT, Definition { name: "OpIterNext".to_string(), operand_widths: vec![2] }),
+            (OP_RANGE, Definition { name: "OpRange".to_string(), operand_widths: vec![1] }),
+            (OP_SLICE, Definition { name: "OpSlice".to_string(), operand_widths: vec![] }),
+            (OP_DESTRUCTURE_ARRAY, Definition { name: "OpDestructureArray".to_string(), operand_widths: vec![2, 1] }),
+            (OP_DESTRUCTURE_HASH, Definition { name: "OpDestructureHash".to_string(), operand_widths: vec![2] }),
+            (OP_DUP, Definition { name: "OpDup".to_string(), operand_widths: vec![] }),
+            (OP_MATCH_VALUE, Definition { name: "OpMatchValue".to_string(), operand_widths: vec![] }),
+            (OP_MATCH_ARRAY, Definition { name: "OpMatchArray".to_string(), operand_widths: vec![2, 1] }),
+            (OP_MATCH_HASH, Definition { name: "OpMatchHash".to_string(), operand_widths: vec![2] }),
+            (OP_LOOP, Definition { name: "OpLoop".to_string(), operand_widths: vec![] }),
+            (OP_LOOP_END, Definition { name: "OpLoopEnd".to_string(), operand_widths: vec![] }),
+            (OP_UNWIND, Definition { name: "OpUnwind".to_string(), operand_widths: vec![] }),
+            (OP_CALL_NAMED, Definition { name: "OpCallNamed".to_string(), operand_widths: vec![1, 2] }),
+            (OP_JUMP_PASSED, Definition { name: "OpJumpPassed".to_string(), operand_widths: vec![1, 2] }),
         ]);
 
         Self {
@@ -95,7 +183,7 @@ impl MCode {
     pub fn lookup(&self, op: &u8) -> Result<Definition> {
         match self.definitions.get(op) {
             Some(x) => Ok(x.clone()),
-            None => Err(Error::new(format!("opcode {:?} undefined", op))),
+            None => Err(Error::Compile(CompileError::new(CompileErrorKind::UndefinedOpcode(*op), Span::default()))),
         }
     }
 
@@ -185,7 +273,7 @@ impl MCode {
             match width {
                 1 => operands.insert(i, ins[offset] as isize),
                 2 => operands.insert(i, BigEndian::read_u16(&ins[offset..]) as isize),
-                _  => return Err(Error::new(format!("No support for operands of width={}", width))),
+                _  => return Err(Error::Compile(CompileError::new(CompileErrorKind::UnsupportedOperandWidth(*width), Span::default()))),
             }
             offset += *width as usize;
         };
@@ -1,4 +1,4 @@
-use std::fmt;
+use std::{fmt, rc::Rc};
 
 use crate::{
     object::*,
@@ -7,13 +7,16 @@ use crate::{
         symbol_table::{SymbolTable, Symbol, Scope},
     },
     ast::*,
-    error::{Result, Error},
+    lexer::span::Span,
+    error::{Result, Error, CompileError, CompileErrorKind},
+    diagnostic,
 };
 
 #[derive(Clone)]
 pub struct Bytecode {
     pub instructions: Instructions,
     pub contstants: Vec<MObject>,
+    pub spans: SpanTable,
 }
 
 #[derive(Debug)]
@@ -36,26 +39,46 @@ impl fmt::Display for EmittedInstruction {
     }
 }
 
+// The jump targets of a loop being compiled. `continue` jumps to the start of the next iteration, the
+// jumps emitted for `break` are rewritten once the end of the loop is known.
+struct LoopContext {
+    continue_loc: usize,
+    break_jumps: Vec<usize>,
+}
+
+// One step from the subject of a match to the element a nested pattern is matched against.
+enum PatternStep<'a> {
+    Index(usize),
+    Key(&'a Expr),
+}
+
 struct CompilationScope {
     instructions: Instructions,
+    spans: Vec<(usize, Span)>,
 
     last_emitted_instruction: Option<EmittedInstruction>,
     prev_emitted_instruction: Option<EmittedInstruction>,
+
+    loops: Vec<LoopContext>,
 }
 
 impl CompilationScope {
     fn new() -> Self {
         Self {
             instructions: Vec::new(),
+            spans: Vec::new(),
 
             last_emitted_instruction: None,
             prev_emitted_instruction: None,
+
+            loops: Vec::new(),
         }
     }
 
-    fn emit(&mut self, code: &MCode, opcode: Opcode, operands: Operand) {
+    fn emit(&mut self, code: &MCode, opcode: Opcode, operands: Operand, span: Span) {
         let mut ins = code.make(&opcode, &operands);
         self.set_last_instruction(opcode, self.instructions.len());
+        self.spans.push((self.instructions.len(), span));
         self.instructions.append(&mut ins);
     }
 
@@ -101,6 +124,8 @@ pub struct Compiler  {
     constants: Vec<MObject>,
     symbols: SymbolTable,
     scopes: Vec<CompilationScope>,
+    // The expression being compiled, recorded with every instruction emitted for it.
+    span: Span,
 
     code: MCode,
 }
@@ -120,6 +145,7 @@ impl Compiler {
             constants: Vec::new(),
             symbols,
             scopes: vec![CompilationScope::new()],
+            span: Span::default(),
             code: MCode::new(),
         }
     }
@@ -129,6 +155,7 @@ impl Compiler {
             constants,
             symbols,
             scopes: vec![CompilationScope::new()],
+            span: Span::default(),
             code: MCode::new(),
         }
     }
@@ -138,14 +165,12 @@ impl Compiler {
     }
 
     pub fn bytecode(&self) -> Bytecode {
-        let instructions = self
-            .current_scope()
-            .instructions
-            .clone();
+        let scope = self.current_scope();
 
         Bytecode {
-            instructions,
+            instructions: scope.instructions.clone(),
             contstants: self.constants.clone(),
+            spans: SpanTable::new(scope.spans.clone()),
         }
     }
 
@@ -179,41 +204,57 @@ impl Compiler {
                         };
                         self.emit(opcode, vec![index as isize]);
                     },
+                    Stmt::Destructure(stmt) => self.compile_destructure(stmt)?,
                     Stmt::Return(ret_stmt) => {
                         self.compile(MNode::Expr(ret_stmt.retval))?;
                         self.emit(OP_RETURN_VAL, vec![]);
                     },
+                    // OpUnwind drops the operands that the expressions around the `break` or
+                    // `continue` left on the stack, e.g. the left side of `1 + if (x) { break; }`.
+                    Stmt::Break(stmt) => {
+                        if self.current_scope().loops.is_empty() {
+                            return Err(compile_error(CompileErrorKind::OutsideLoop(stmt.token_literal()), stmt.span()));
+                        };
+                        self.emit(OP_UNWIND, vec![]);
+
+                        // Emit a Jump Opcode with a placeholder offset, rewritten at the end of the loop.
+                        let jump_loc = self.current_instructions().len();
+                        if let Some(l) = self.scopes.last_mut().and_then(|s| s.loops.last_mut()) {
+                            l.break_jumps.push(jump_loc);
+                        };
+                        self.emit(OP_JUMP, vec![0]);
+                    },
+                    Stmt::Continue(stmt) => {
+                        let continue_loc = match self.current_scope().loops.last() {
+                            Some(l) => l.continue_loc,
+                            None => return Err(compile_error(CompileErrorKind::OutsideLoop(stmt.token_literal()), stmt.span())),
+                        };
+                        self.emit(OP_UNWIND, vec![]);
+                        self.emit(OP_JUMP, vec![continue_loc as isize]);
+                    },
                 };
             },
             MNode::Expr(e) => {
+                let outer = std::mem::replace(&mut self.span, e.span());
                 match e {
+                    Expr::In(infix) if infix.operator == "&&" || infix.operator == "||" => self.compile_logical(infix)?,
                     Expr::In(infix) => {
-                        if infix.operator.as_str() == "<" {
-                            self.compile(MNode::Expr(*infix.right))?;
-                            self.compile(MNode::Expr(*infix.left))?;
-                            self.emit(OP_GREATER_THAN, vec![]);
-                            return Ok(());
-                        }
                         self.compile(MNode::Expr(*infix.left))?;
                         self.compile(MNode::Expr(*infix.right))?;
-                        match infix.operator.as_str() {
-                            "+" => self.emit(OP_ADD, vec![]),
-                            "-" => self.emit(OP_SUB, vec![]),
-                            "*" => self.emit(OP_MUL, vec![]),
-                            "/" => self.emit(OP_DIV, vec![]),
-                            "==" => self.emit(OP_EQUAL, vec![]),
-                            "!=" => self.emit(OP_NOT_EQUAL, vec![]),
-                            ">" => self.emit(OP_GREATER_THAN, vec![]),
-                            _ => return Err(Error::new(format!("unknown operator: {}", infix.operator))),
+                        match infix_opcode(&infix.operator) {
+                            Some(op) => self.emit(op, vec![]),
+                            None => return Err(compile_error(CompileErrorKind::UnknownOperator(infix.operator), infix.token.span)),
                         };
                     },
+                    Expr::Assign(assign) => self.compile_assignment(assign)?,
                     Expr::Pre(prefix) => {
                         self.compile(MNode::Expr(*prefix.right))?;
 
                         match prefix.operator.as_str() {
                             "!" => self.emit(OP_BANG, vec![]),
                             "-" => self.emit(OP_MINUS, vec![]),
-                            _ => return Err(Error::new(format!("unknown operator: {}", prefix.operator))),
+                            "~" => self.emit(OP_BIT_NOT, vec![]),
+                            _ => return Err(compile_error(CompileErrorKind::UnknownOperator(prefix.operator), prefix.token.span)),
                         };
                     },
                     Expr::Index(op) => {
@@ -221,11 +262,23 @@ impl Compiler {
                         self.compile(MNode::Expr(*op.index))?;
                         self.emit(OP_INDEX, vec![]);
                     },
+                    Expr::Slice(slice) => self.compile_slice(slice)?,
+                    Expr::Range(range) => self.compile_range(range)?,
                     Expr::Int(int) => {
                         let literal = Integer { value: int.value };
                         self.constants.push(MObject::Int(literal));
                         self.emit(OP_CONSTANT, vec![(self.constants.len() - 1) as isize]);
                     },
+                    Expr::BigInt(x) => {
+                        let literal = BigInteger { value: x.value.clone() };
+                        self.constants.push(MObject::BigInt(literal));
+                        self.emit(OP_CONSTANT, vec![(self.constants.len() - 1) as isize]);
+                    },
+                    Expr::Float(x) => {
+                        let literal = Float { value: x.value };
+                        self.constants.push(MObject::Float(literal));
+                        self.emit(OP_CONSTANT, vec![(self.constants.len() - 1) as isize]);
+                    },
                     Expr::Bool(x) => {
                         if x.value {
                             self.emit(OP_TRUE, vec![]);
@@ -267,10 +320,13 @@ impl Compiler {
                         let after_alternative_loc = self.current_instructions().len();
                         self.change_operand(jump_loc, &vec![after_alternative_loc as isize]);
                     },
+                    Expr::While(while_loop) => self.compile_while(while_loop)?,
+                    Expr::For(for_loop) => self.compile_for(for_loop)?,
+                    Expr::Match(match_expr) => self.compile_match(match_expr)?,
                     Expr::Ident(ident) => {
                         let symbol = match self.symbols.resolve(&ident.value) {
                             Some(x) => x,
-                            None => return Err(Error::new(format!("Identifier not found: {}", ident))),
+                            None => return Err(self.undefined_identifier(&ident)),
                         };
                         self.load_symbol(&symbol);
                     },
@@ -296,51 +352,513 @@ impl Compiler {
                         };
                         self.emit(OP_HASH, vec![len]);
                     },
-                    Expr::Fn(function) => {
-                        self.enter_scope(CompilationScope::new());
+                    Expr::Fn(function) => self.compile_function(function)?,
+                    // The values of named arguments follow the positional ones, OpCallNamed refers to
+                    // an array constant holding their names.
+                    Expr::Call(fn_call) => {
+                        let len = (fn_call.args.len() + fn_call.named.len()) as isize;
+                        for arg in fn_call.args {
+                            self.compile(MNode::Expr(arg))?;
+                        };
+
+                        let mut names = Vec::new();
+                        for arg in fn_call.named {
+                            self.compile(MNode::Expr(arg.value))?;
+                            names.push(MObject::Str(MString { value: arg.name.value }));
+                        };
+
+                        self.compile(MNode::Expr(*fn_call.function))?;
+
+                        if names.is_empty() {
+                            self.emit(OP_CALL, vec![len]);
+                        } else {
+                            self.constants.push(MObject::Array(MArray { elements: names }));
+                            self.emit(OP_CALL_NAMED, vec![len, (self.constants.len() - 1) as isize]);
+                        };
+                    },
+                    _ => return Err(compile_error(CompileErrorKind::Unsupported(e.to_string()), e.span())),
+                };
+                self.span = outer;
+            },
+        };
 
-                        if let Some(name) = function.name { self.symbols.define_function_name(name); };
+        Ok(())
+    }
 
-                        let num_params = function.params.len() as u8;
-                        for param in function.params { self.symbols.define(param.value); };
+    // The default values are set before the body, behind a jump taken by calls that pass every
+    // parameter. A call that leaves some out starts where the first missing one is set. A call with
+    // named arguments can pass parameters after that one, the local following the parameters then
+    // holds which ones were passed and OpJumpPassed skips their default values.
+    fn compile_function(&mut self, function: FnLiteral) -> Result<()> {
+        self.enter_scope(CompilationScope::new());
+
+        // A function that assigns to its own name refers to the binding it was defined with, as
+        // in the evaluator, instead of to itself.
+        if let Some(name) = function.name.as_ref().filter(|name| !function.body.assigns_to(name)) {
+            self.symbols.define_function_name(name.clone());
+        };
 
-                        self.compile(MNode::Stmt(Stmt::Block(function.body)))?;
+        let num_params = function.params.len() as u8;
+        let param_names = function.params.iter().map(|p| p.name.value.clone()).collect::<Rc<[String]>>();
+        let mut params = Vec::new();
+        for param in function.params {
+            let symbol = self.symbols.define(param.name.value);
+            params.push((symbol, param.default));
+        };
+        if let Some(rest) = &function.rest { self.symbols.define(rest.value.clone()); };
 
-                        if self.last_instruction_is(OP_POP) { self.replace_last_pop_with_return(); };
-                        if !self.last_instruction_is(OP_RETURN_VAL) { self.emit(OP_RETURN, vec![]); };
+        let mut defaults = Vec::new();
+        let has_defaults = params.iter().any(|(_, default)| default.is_some());
+        if has_defaults {
+            self.symbols.reserve();
+            self.emit(OP_JUMP, vec![0]);
+        };
 
-                        let free_symbols = self.symbols.free_symbols();
-                        let num_locals = self.symbols.len();
-                        let scope = self.leave_scope();
+        for (symbol, default) in params {
+            if let Some(default) = default {
+                let guard = self.current_instructions().len();
+                defaults.push(guard);
+                self.emit(OP_JUMP_PASSED, vec![symbol.index as isize, 0]);
+                self.compile(MNode::Expr(default))?;
+                self.store_symbol(&symbol);
+
+                let after = self.current_instructions().len();
+                self.change_operand(guard, &vec![symbol.index as isize, after as isize]);
+            };
+        };
 
-                        for symbol in &free_symbols { self.load_symbol(&symbol); };
+        if has_defaults {
+            let body_loc = self.current_instructions().len();
+            self.change_operand(0, &vec![body_loc as isize]);
+        };
 
-                        let compiled_fn = CompiledFunction { num_locals, num_params, instructions: scope.instructions };
+        self.compile(MNode::Stmt(Stmt::Block(function.body)))?;
 
-                        self.constants.push(MObject::CompiledFn(compiled_fn));
-                        self.emit(OP_CLOSURE, vec![(self.constants.len() - 1) as isize, free_symbols.len() as isize]);
-                    },
-                    Expr::Call(fn_call) => {
-                        let len = fn_call.args.len() as isize;
-                        for arg in fn_call.args {
-                            self.compile(MNode::Expr(arg))?;
-                        };
+        if self.last_instruction_is(OP_POP) { self.replace_last_pop_with_return(); };
+        if !self.last_instruction_is(OP_RETURN_VAL) { self.emit(OP_RETURN, vec![]); };
 
-                        self.compile(MNode::Expr(*fn_call.function))?;
+        let free_symbols = self.symbols.free_symbols();
+        let num_locals = self.symbols.len();
+        let scope = self.leave_scope();
 
-                        self.emit(OP_CALL, vec![len]);
-                    },
-                    _ => return Err(Error::new(format!("Compilation not implemented for expression: {}", e))),
+        for symbol in &free_symbols { self.capture_symbol(symbol); };
+
+        let compiled_fn = CompiledFunction {
+            num_locals,
+            num_params,
+            instructions: scope.instructions,
+            spans: SpanTable::new(scope.spans),
+            name: function.name,
+            params: param_names,
+            defaults,
+            rest: function.rest.is_some(),
+        };
+
+        self.constants.push(MObject::CompiledFn(compiled_fn));
+        self.emit(OP_CLOSURE, vec![(self.constants.len() - 1) as isize, free_symbols.len() as isize]);
+
+        Ok(())
+    }
+
+    // `&&` and `||` jump past the right operand when the left one decides the result, both always
+    // produce a boolean.
+    fn compile_logical(&mut self, infix: Infix) -> Result<()> {
+        let (jump, decided, undecided) = match infix.operator.as_str() {
+            "&&" => (OP_JUMP_NOT_TRUE, OP_FALSE, OP_TRUE),
+            _ => (OP_JUMP_TRUE, OP_TRUE, OP_FALSE),
+        };
+
+        // Emit both conditional jumps with a placeholder offset to rewrite later.
+        self.compile(MNode::Expr(*infix.left))?;
+        let left_jump_loc = self.current_instructions().len();
+        self.emit(jump, vec![0]);
+
+        self.compile(MNode::Expr(*infix.right))?;
+        let right_jump_loc = self.current_instructions().len();
+        self.emit(jump, vec![0]);
+
+        self.emit(undecided, vec![]);
+        let jump_loc = self.current_instructions().len();
+        self.emit(OP_JUMP, vec![0]);
+
+        let decided_loc = self.current_instructions().len();
+        self.change_operand(left_jump_loc, &vec![decided_loc as isize]);
+        self.change_operand(right_jump_loc, &vec![decided_loc as isize]);
+        self.emit(decided, vec![]);
+
+        let after_loc = self.current_instructions().len();
+        self.change_operand(jump_loc, &vec![after_loc as isize]);
+
+        Ok(())
+    }
+
+    // The destructure opcodes replace the value with the values for each name, the first one on top,
+    // so they are stored in the order the names appear. The names are defined after the value is
+    // compiled, `let [a, b] = [b, a];` swaps two variables.
+    fn compile_destructure(&mut self, stmt: DestructureStatement) -> Result<()> {
+        let outer = std::mem::replace(&mut self.span, stmt.span());
+        self.compile(MNode::Expr(stmt.value))?;
+
+        match stmt.pattern.as_ref() {
+            Pattern::Array(pattern) => {
+                self.emit(OP_DESTRUCTURE_ARRAY, vec![pattern.names.len() as isize, pattern.rest.is_some() as isize]);
+            },
+            Pattern::Hash(pattern) => {
+                for (key, _) in &pattern.pairs {
+                    self.compile(MNode::Expr(key.clone()))?;
+                };
+                self.emit(OP_DESTRUCTURE_HASH, vec![pattern.pairs.len() as isize]);
+            },
+        };
+
+        for name in stmt.pattern.names() {
+            let symbol = self.symbols.define(name.value.clone());
+            let opcode = if symbol.scope == Scope::Global {
+                OP_SET_GLOBAL
+            } else {
+                OP_SET_LOCAL
+            };
+            self.emit(opcode, vec![symbol.index as isize]);
+        };
+        self.span = outer;
+
+        Ok(())
+    }
+
+    fn compile_range(&mut self, range: RangeExpression) -> Result<()> {
+        self.compile(MNode::Expr(*range.start))?;
+        self.compile(MNode::Expr(*range.end))?;
+        self.emit(OP_RANGE, vec![range.inclusive as isize]);
+
+        Ok(())
+    }
+
+    // A bound that was left out is compiled as null, so OpSlice always finds three operands.
+    fn compile_slice(&mut self, slice: SliceOperation) -> Result<()> {
+        self.compile(MNode::Expr(*slice.left))?;
+
+        for bound in [slice.start, slice.end] {
+            match bound {
+                Some(x) => self.compile(MNode::Expr(*x))?,
+                None => self.emit(OP_NULL, vec![]),
+            };
+        };
+        self.emit(OP_SLICE, vec![]);
+
+        Ok(())
+    }
+
+    // The condition is checked before every iteration, the loop leaves null on the stack like an `if`
+    // without an alternative. OpLoop records the height of the stack for OpUnwind, OpLoopEnd forgets it.
+    fn compile_while(&mut self, while_loop: WhileLoop) -> Result<()> {
+        self.emit(OP_LOOP, vec![]);
+        let start_loc = self.current_instructions().len();
+        self.compile(MNode::Expr(*while_loop.condition))?;
+
+        // Emit a JumpNotTrue Opcode with a placeholder offset to rewrite later.
+        let jump_not_true_loc = self.current_instructions().len();
+        self.emit(OP_JUMP_NOT_TRUE, vec![0]);
+
+        let break_jumps = self.compile_loop_body(while_loop.body, start_loc)?;
+        self.emit(OP_JUMP, vec![start_loc as isize]);
+
+        let after_body_loc = self.current_instructions().len();
+        self.change_operand(jump_not_true_loc, &vec![after_body_loc as isize]);
+        for jump_loc in break_jumps {
+            self.change_operand(jump_loc, &vec![after_body_loc as isize]);
+        };
+
+        self.emit(OP_LOOP_END, vec![]);
+        self.emit(OP_NULL, vec![]);
+
+        Ok(())
+    }
+
+    // OpIter turns the iterable into an iterator that stays on the stack for the whole loop. OpIterNext
+    // pushes its next element, or jumps past the body once it is exhausted, where the iterator is popped.
+    // The loop variable gets a slot of its own that is only visible in the body.
+    fn compile_for(&mut self, for_loop: ForLoop) -> Result<()> {
+        self.compile(MNode::Expr(*for_loop.iterable))?;
+        self.emit(OP_ITER, vec![]);
+        self.emit(OP_LOOP, vec![]);
+
+        // Emit an IterNext Opcode with a placeholder offset to rewrite later.
+        let iter_next_loc = self.current_instructions().len();
+        self.emit(OP_ITER_NEXT, vec![0]);
+
+        let name = for_loop.variable.value;
+        let (symbol, shadowed) = self.symbols.define_shadow(name.clone());
+        self.store_symbol(&symbol);
+
+        let break_jumps = self.compile_loop_body(for_loop.body, iter_next_loc);
+        self.symbols.end_shadow(name, shadowed);
+        let break_jumps = break_jumps?;
+        self.emit(OP_JUMP, vec![iter_next_loc as isize]);
+
+        let after_body_loc = self.current_instructions().len();
+        self.change_operand(iter_next_loc, &vec![after_body_loc as isize]);
+        for jump_loc in break_jumps {
+            self.change_operand(jump_loc, &vec![after_body_loc as isize]);
+        };
+
+        self.emit(OP_LOOP_END, vec![]);
+        self.emit(OP_POP, vec![]);
+        self.emit(OP_NULL, vec![]);
+
+        Ok(())
+    }
+
+    // The subject stays on the stack while the arms are tried. Each arm tests a copy of the subject,
+    // or of one of its elements, jumping to the next arm on the first test that fails, then binds its
+    // names and checks the guard. The subject is popped before the body runs, or after the last arm.
+    fn compile_match(&mut self, match_expr: MatchExpression) -> Result<()> {
+        self.compile(MNode::Expr(*match_expr.subject))?;
+
+        let mut end_jumps = Vec::new();
+        for arm in match_expr.arms {
+            let mut fail_jumps = Vec::new();
+            let mut shadowed = Vec::new();
+            let compiled = self.compile_match_arm(arm, &mut fail_jumps, &mut shadowed);
+            for (name, symbol) in shadowed.into_iter().rev() {
+                self.symbols.end_shadow(name, symbol);
+            };
+            compiled?;
+
+            // Emit a Jump Opcode with a placeholder offset to rewrite later.
+            end_jumps.push(self.current_instructions().len());
+            self.emit(OP_JUMP, vec![0]);
+
+            let next_arm_loc = self.current_instructions().len();
+            for jump_loc in fail_jumps {
+                self.change_operand(jump_loc, &vec![next_arm_loc as isize]);
+            };
+        };
+
+        self.emit(OP_POP, vec![]);
+        self.emit(OP_NULL, vec![]);
+
+        let after_match_loc = self.current_instructions().len();
+        for jump_loc in end_jumps {
+            self.change_operand(jump_loc, &vec![after_match_loc as isize]);
+        };
+
+        Ok(())
+    }
+
+    // The names bound by the pattern get slots of their own that are only visible in the guard and the
+    // body, the symbols they shadow are pushed to `shadowed`.
+    fn compile_match_arm(&mut self, arm: MatchArm, fail_jumps: &mut Vec<usize>, shadowed: &mut Vec<(String, Option<Rc<Symbol>>)>) -> Result<()> {
+        self.compile_pattern_test(&arm.pattern, &mut Vec::new(), fail_jumps)?;
+        self.compile_pattern_bindings(&arm.pattern, &mut Vec::new(), shadowed)?;
+
+        if let Some(guard) = arm.guard {
+            self.compile(MNode::Expr(guard))?;
+            fail_jumps.push(self.current_instructions().len());
+            self.emit(OP_JUMP_NOT_TRUE, vec![0]);
+        };
+
+        self.emit(OP_POP, vec![]);
+        self.compile(MNode::Stmt(Stmt::Block(arm.body)))?;
+        if self.last_instruction_is(OP_POP) {
+            self.remove_last_pop();
+        } else {
+            self.emit(OP_NULL, vec![]);
+        };
+
+        Ok(())
+    }
+
+    // Emits the tests for a pattern matched against the element of the subject at `path`, pushing the
+    // locations of the jumps taken when a test fails.
+    fn compile_pattern_test<'a>(&mut self, pattern: &'a MatchPattern, path: &mut Vec<PatternStep<'a>>, fail_jumps: &mut Vec<usize>) -> Result<()> {
+        match pattern {
+            MatchPattern::Wildcard(_) | MatchPattern::Binding(_) => return Ok(()),
+            MatchPattern::Literal(literal) => {
+                self.compile_pattern_value(path)?;
+                self.compile(MNode::Expr(literal.clone()))?;
+                self.emit(OP_MATCH_VALUE, vec![]);
+            },
+            MatchPattern::Array(array) => {
+                self.compile_pattern_value(path)?;
+                self.emit(OP_MATCH_ARRAY, vec![array.elements.len() as isize, array.rest.is_some() as isize]);
+            },
+            MatchPattern::Hash(hash) => {
+                self.compile_pattern_value(path)?;
+                for (key, _) in &hash.pairs {
+                    self.compile(MNode::Expr(key.clone()))?;
+                };
+                self.emit(OP_MATCH_HASH, vec![hash.pairs.len() as isize]);
+            },
+        };
+
+        fail_jumps.push(self.current_instructions().len());
+        self.emit(OP_JUMP_NOT_TRUE, vec![0]);
+
+        match pattern {
+            MatchPattern::Array(array) => {
+                for (i, element) in array.elements.iter().enumerate() {
+                    path.push(PatternStep::Index(i));
+                    self.compile_pattern_test(element, path, fail_jumps)?;
+                    path.pop();
+                };
+            },
+            MatchPattern::Hash(hash) => {
+                for (key, value) in &hash.pairs {
+                    path.push(PatternStep::Key(key));
+                    self.compile_pattern_test(value, path, fail_jumps)?;
+                    path.pop();
+                };
+            },
+            _ => (),
+        };
+
+        Ok(())
+    }
+
+    // Stores the names bound by a pattern that is known to match, in the order they appear.
+    fn compile_pattern_bindings<'a>(&mut self, pattern: &'a MatchPattern, path: &mut Vec<PatternStep<'a>>, shadowed: &mut Vec<(String, Option<Rc<Symbol>>)>) -> Result<()> {
+        match pattern {
+            MatchPattern::Binding(name) => {
+                self.compile_pattern_value(path)?;
+                let (symbol, outer) = self.symbols.define_shadow(name.value.clone());
+                shadowed.push((name.value.clone(), outer));
+                self.store_symbol(&symbol);
+            },
+            MatchPattern::Array(array) => {
+                for (i, element) in array.elements.iter().enumerate() {
+                    path.push(PatternStep::Index(i));
+                    self.compile_pattern_bindings(element, path, shadowed)?;
+                    path.pop();
+                };
+
+                if let Some(MatchPattern::Binding(name)) = array.rest.as_deref() {
+                    self.compile_pattern_value(path)?;
+                    self.emit_integer(array.elements.len() as i128);
+                    self.emit(OP_NULL, vec![]);
+                    self.emit(OP_SLICE, vec![]);
+                    let (symbol, outer) = self.symbols.define_shadow(name.value.clone());
+                    shadowed.push((name.value.clone(), outer));
+                    self.store_symbol(&symbol);
+                };
+            },
+            MatchPattern::Hash(hash) => {
+                for (key, value) in &hash.pairs {
+                    path.push(PatternStep::Key(key));
+                    self.compile_pattern_bindings(value, path, shadowed)?;
+                    path.pop();
                 };
             },
+            MatchPattern::Wildcard(_) | MatchPattern::Literal(_) => (),
+        };
+
+        Ok(())
+    }
+
+    // Pushes a copy of the subject indexed by each step of `path`.
+    fn compile_pattern_value(&mut self, path: &[PatternStep]) -> Result<()> {
+        self.emit(OP_DUP, vec![]);
+
+        for step in path {
+            match step {
+                PatternStep::Index(i) => self.emit_integer(*i as i128),
+                PatternStep::Key(key) => self.compile(MNode::Expr((*key).clone()))?,
+            };
+            self.emit(OP_INDEX, vec![]);
+        };
+
+        Ok(())
+    }
+
+    // Compiles the body of a loop whose next iteration starts at `continue_loc`, returning the
+    // locations of the jumps emitted for `break`.
+    fn compile_loop_body(&mut self, body: BlockStatement, continue_loc: usize) -> Result<Vec<usize>> {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.loops.push(LoopContext { continue_loc, break_jumps: Vec::new() });
+        };
+
+        let compiled = self.compile(MNode::Stmt(Stmt::Block(body)));
+
+        let context = self.scopes.last_mut().and_then(|s| s.loops.pop());
+        compiled?;
+
+        Ok(context.map(|l| l.break_jumps).unwrap_or_default())
+    }
+
+    // Suggests the closest name in scope, like the evaluator does at runtime.
+    fn undefined_identifier(&self, ident: &Identifier) -> Error {
+        let mut err = CompileError::new(CompileErrorKind::UndefinedIdentifier(ident.value.clone()), ident.token.span);
+        err.note = diagnostic::suggest(&ident.value, &self.symbols.visible_names()).map(|x| format!("did you mean `{}`?", x));
+        Error::Compile(err)
+    }
+
+    // Assigning to a variable stores the value and loads it back as the result. Assigning to an
+    // element loads the variable and the indexes, then OpSetIndex leaves the value under an updated
+    // copy of the array or hash, which is stored back in the variable. Compound assignments to an
+    // element read the old value with OpPeekIndex so the indexes are only evaluated once.
+    fn compile_assignment(&mut self, assign: Assignment) -> Result<()> {
+        let op = match assign.infix_operator() {
+            Some(x) => match infix_opcode(x) {
+                Some(op) => Some(op),
+                None => return Err(compile_error(CompileErrorKind::UnknownOperator(assign.operator), assign.token.span)),
+            },
+            None => None,
         };
 
+        let (name, indexes) = match assign.target.assignment_path() {
+            Some(x) => x,
+            None => return Err(compile_error(CompileErrorKind::Unsupported(assign.target.to_string()), assign.target.span())),
+        };
+
+        let symbol = match self.symbols.resolve_variable(&name.value) {
+            Some(x) if x.scope == Scope::Builtin => {
+                return Err(compile_error(CompileErrorKind::AssignToBuiltin(name.value.clone()), name.token.span));
+            },
+            Some(x) => x,
+            None => return Err(self.undefined_identifier(name)),
+        };
+
+        if indexes.is_empty() {
+            if let Some(op) = op {
+                self.load_symbol(&symbol);
+                self.compile(MNode::Expr(*assign.value))?;
+                self.emit(op, vec![]);
+            } else {
+                self.compile(MNode::Expr(*assign.value))?;
+            };
+            self.store_symbol(&symbol);
+            self.load_symbol(&symbol);
+
+            return Ok(());
+        };
+
+        let depth = indexes.len() as isize;
+        self.load_symbol(&symbol);
+        for index in indexes {
+            self.compile(MNode::Expr(index.clone()))?;
+        };
+
+        if let Some(op) = op {
+            self.emit(OP_PEEK_INDEX, vec![depth]);
+            self.compile(MNode::Expr(*assign.value))?;
+            self.emit(op, vec![]);
+        } else {
+            self.compile(MNode::Expr(*assign.value))?;
+        };
+
+        self.emit(OP_SET_INDEX, vec![depth]);
+        self.store_symbol(&symbol);
+
         Ok(())
     }
 
+    fn emit_integer(&mut self, value: i128) {
+        self.constants.push(MObject::Int(Integer { value }));
+        self.emit(OP_CONSTANT, vec![(self.constants.len() - 1) as isize]);
+    }
+
     fn emit(&mut self, op: Opcode, operands: Operand) {
         if let Some(scope) = self.scopes.last_mut() {
-            scope.emit(&self.code, op, operands);
+            scope.emit(&self.code, op, operands, self.span);
         };
     }
 
@@ -358,6 +876,27 @@ impl Compiler {
         self.emit(opcode, vec![index as isize]);
     }
 
+    fn store_symbol(&mut self, symbol: &Symbol) {
+        let opcode = match symbol.scope {
+            Scope::Global => OP_SET_GLOBAL,
+            Scope::Local => OP_SET_LOCAL,
+            Scope::Free => OP_SET_FREE,
+            Scope::Builtin | Scope::Function => unreachable!(),
+        };
+
+        self.emit(opcode, vec![symbol.index as isize]);
+    }
+
+    // Locals and free variables are captured by reference, so assignments made after the closure is
+    // created are seen on both sides.
+    fn capture_symbol(&mut self, symbol: &Symbol) {
+        match symbol.scope {
+            Scope::Local => self.emit(OP_CAPTURE_LOCAL, vec![symbol.index as isize]),
+            Scope::Free => self.emit(OP_CAPTURE_FREE, vec![symbol.index as isize]),
+            _ => self.load_symbol(symbol),
+        };
+    }
+
     fn last_instruction_is(&self, opcode: Opcode) -> bool {
         self.scopes.last().unwrap().last_instruction_is(opcode)
     }
@@ -399,6 +938,35 @@ impl Compiler {
     }
 }
 
+fn infix_opcode(operator: &str) -> Option<Opcode> {
+    let op = match operator {
+        "+" => OP_ADD,
+        "-" => OP_SUB,
+        "*" => OP_MUL,
+        "/" => OP_DIV,
+        "%" => OP_MOD,
+        "**" => OP_POW,
+        "&" => OP_BIT_AND,
+        "|" => OP_BIT_OR,
+        "^" => OP_BIT_XOR,
+        "<<" => OP_SHL,
+        ">>" => OP_SHR,
+        "==" => OP_EQUAL,
+        "!=" => OP_NOT_EQUAL,
+        ">" => OP_GREATER_THAN,
+        "<" => OP_LESS_THAN,
+        "<=" => OP_LESS_EQUAL,
+        ">=" => OP_GREATER_EQUAL,
+        _ => return None,
+    };
+
+    Some(op)
+}
+
+fn compile_error(kind: CompileErrorKind, span: Span) -> Error {
+    Error::Compile(CompileError::new(kind, span))
+}
+
 impl fmt::Display for Compiler {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let scope = self.current_scope();
@@ -524,6 +1092,47 @@ mod tests {
                     code.make(&OP_POP, &vec![]),
                 ],
             },
+            TestCase {
+                input: "1 % 2 ** 3".to_string(),
+                expected_constants: vec![1, 2, 3].iter().map(|i| i_to_o(*i) ).collect(),
+                expected_instructions: vec![
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    code.make(&OP_CONSTANT, &vec![1]),
+                    code.make(&OP_CONSTANT, &vec![2]),
+                    code.make(&OP_POW, &vec![]),
+                    code.make(&OP_MOD, &vec![]),
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+            TestCase {
+                input: "~1 & 2 | 3 ^ 4 << 5 >> 6".to_string(),
+                expected_constants: vec![1, 2, 3, 4, 5, 6].iter().map(|i| i_to_o(*i) ).collect(),
+                expected_instructions: vec![
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    code.make(&OP_BIT_NOT, &vec![]),
+                    code.make(&OP_CONSTANT, &vec![1]),
+                    code.make(&OP_BIT_AND, &vec![]),
+                    code.make(&OP_CONSTANT, &vec![2]),
+                    code.make(&OP_CONSTANT, &vec![3]),
+                    code.make(&OP_CONSTANT, &vec![4]),
+                    code.make(&OP_SHL, &vec![]),
+                    code.make(&OP_CONSTANT, &vec![5]),
+                    code.make(&OP_SHR, &vec![]),
+                    code.make(&OP_BIT_XOR, &vec![]),
+                    code.make(&OP_BIT_OR, &vec![]),
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+            TestCase {
+                input: "1.5 * 2".to_string(),
+                expected_constants: vec![f_to_o(1.5), i_to_o(2)],
+                expected_instructions: vec![
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    code.make(&OP_CONSTANT, &vec![1]),
+                    code.make(&OP_MUL, &vec![]),
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
             TestCase {
                 input: "-1 - -2".to_string(),
                 expected_constants: vec![1, 2].iter().map(|i| i_to_o(*i) ).collect(),
@@ -572,11 +1181,31 @@ mod tests {
             },
             TestCase {
                 input: "1 < 2".to_string(),
-                expected_constants: vec![2, 1].iter().map(|i| i_to_o(*i) ).collect(),
+                expected_constants: [1, 2].iter().map(|i| i_to_o(*i) ).collect(),
                 expected_instructions: vec![
                     code.make(&OP_CONSTANT, &vec![0]),
                     code.make(&OP_CONSTANT, &vec![1]),
-                    code.make(&OP_GREATER_THAN, &vec![]),
+                    code.make(&OP_LESS_THAN, &vec![]),
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+            TestCase {
+                input: "1 <= 2".to_string(),
+                expected_constants: [1, 2].iter().map(|i| i_to_o(*i) ).collect(),
+                expected_instructions: vec![
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    code.make(&OP_CONSTANT, &vec![1]),
+                    code.make(&OP_LESS_EQUAL, &vec![]),
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+            TestCase {
+                input: "1 >= 2".to_string(),
+                expected_constants: [1, 2].iter().map(|i| i_to_o(*i) ).collect(),
+                expected_instructions: vec![
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    code.make(&OP_CONSTANT, &vec![1]),
+                    code.make(&OP_GREATER_EQUAL, &vec![]),
                     code.make(&OP_POP, &vec![]),
                 ],
             },
@@ -689,15 +1318,628 @@ mod tests {
     }
 
     #[test]
-    fn test_let_statements() -> Result<()> {
+    fn test_logical_operators() -> Result<()> {
         let code = MCode::new();
         let tests = vec![
             TestCase {
-                input: r#"
-                    let one = 1;
-                    let two = 2;
-                "#.to_string(),
-                expected_constants: vec![1, 2].iter().map(|i| i_to_o(*i) ).collect(),
+                input: "true && false".to_string(),
+                expected_constants: vec![],
+                expected_instructions: vec![
+                    // 0000
+                    code.make(&OP_TRUE, &vec![]),
+                    // 0001
+                    code.make(&OP_JUMP_NOT_TRUE, &vec![12]),
+                    // 0004
+                    code.make(&OP_FALSE, &vec![]),
+                    // 0005
+                    code.make(&OP_JUMP_NOT_TRUE, &vec![12]),
+                    // 0008
+                    code.make(&OP_TRUE, &vec![]),
+                    // 0009
+                    code.make(&OP_JUMP, &vec![13]),
+                    // 0012
+                    code.make(&OP_FALSE, &vec![]),
+                    // 0013
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+            TestCase {
+                input: "1 || 2".to_string(),
+                expected_constants: [1, 2].iter().map(|i| i_to_o(*i) ).collect(),
+                expected_instructions: vec![
+                    // 0000
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    // 0003
+                    code.make(&OP_JUMP_TRUE, &vec![16]),
+                    // 0006
+                    code.make(&OP_CONSTANT, &vec![1]),
+                    // 0009
+                    code.make(&OP_JUMP_TRUE, &vec![16]),
+                    // 0012
+                    code.make(&OP_FALSE, &vec![]),
+                    // 0013
+                    code.make(&OP_JUMP, &vec![17]),
+                    // 0016
+                    code.make(&OP_TRUE, &vec![]),
+                    // 0017
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+        ];
+
+        run_compiler_tests(tests)
+    }
+
+    #[test]
+    fn test_let_statements() -> Result<()> {
+        let code = MCode::new();
+        let tests = vec![
+            TestCase {
+                input: r#"
+                    let one = 1;
+                    let two = 2;
+                "#.to_string(),
+                expected_constants: vec![1, 2].iter().map(|i| i_to_o(*i) ).collect(),
+                expected_instructions: vec![
+                    // 0000
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    // 0003
+                    code.make(&OP_SET_GLOBAL, &vec![0]),
+                    // 0006
+                    code.make(&OP_CONSTANT, &vec![1]),
+                    // 0009
+                    code.make(&OP_SET_GLOBAL, &vec![1]),
+                ],
+            },
+            TestCase {
+                input: "let one = 1; one;".to_string(),
+                expected_constants: vec![1].iter().map(|i| i_to_o(*i) ).collect(),
+                expected_instructions: vec![
+                    // 0000
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    // 0003
+                    code.make(&OP_SET_GLOBAL, &vec![0]),
+                    // 0006
+                    code.make(&OP_GET_GLOBAL, &vec![0]),
+                    // 0009
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+        ];
+
+        run_compiler_tests(tests)
+    }
+
+    #[test]
+    fn test_loops() -> Result<()> {
+        let code = MCode::new();
+        let tests = vec![
+            TestCase {
+                input: "while (true) { 10; }".to_string(),
+                expected_constants: [10].iter().map(|i| i_to_o(*i) ).collect(),
+                expected_instructions: vec![
+                    // 0000
+                    code.make(&OP_LOOP, &vec![]),
+                    // 0001
+                    code.make(&OP_TRUE, &vec![]),
+                    // 0002
+                    code.make(&OP_JUMP_NOT_TRUE, &vec![12]),
+                    // 0005
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    // 0008
+                    code.make(&OP_POP, &vec![]),
+                    // 0009
+                    code.make(&OP_JUMP, &vec![1]),
+                    // 0012
+                    code.make(&OP_LOOP_END, &vec![]),
+                    // 0013
+                    code.make(&OP_NULL, &vec![]),
+                    // 0014
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+            TestCase {
+                input: "while (true) { break; continue; }".to_string(),
+                expected_constants: vec![],
+                expected_instructions: vec![
+                    // 0000
+                    code.make(&OP_LOOP, &vec![]),
+                    // 0001
+                    code.make(&OP_TRUE, &vec![]),
+                    // 0002
+                    code.make(&OP_JUMP_NOT_TRUE, &vec![16]),
+                    // 0005
+                    code.make(&OP_UNWIND, &vec![]),
+                    // 0006
+                    code.make(&OP_JUMP, &vec![16]),
+                    // 0009
+                    code.make(&OP_UNWIND, &vec![]),
+                    // 0010
+                    code.make(&OP_JUMP, &vec![1]),
+                    // 0013
+                    code.make(&OP_JUMP, &vec![1]),
+                    // 0016
+                    code.make(&OP_LOOP_END, &vec![]),
+                    // 0017
+                    code.make(&OP_NULL, &vec![]),
+                    // 0018
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+            TestCase {
+                input: "for (x in [1]) { x; }".to_string(),
+                expected_constants: [1].iter().map(|i| i_to_o(*i) ).collect(),
+                expected_instructions: vec![
+                    // 0000
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    // 0003
+                    code.make(&OP_ARRAY, &vec![1]),
+                    // 0006
+                    code.make(&OP_ITER, &vec![]),
+                    // 0007
+                    code.make(&OP_LOOP, &vec![]),
+                    // 0008
+                    code.make(&OP_ITER_NEXT, &vec![21]),
+                    // 0011
+                    code.make(&OP_SET_GLOBAL, &vec![0]),
+                    // 0014
+                    code.make(&OP_GET_GLOBAL, &vec![0]),
+                    // 0017
+                    code.make(&OP_POP, &vec![]),
+                    // 0018
+                    code.make(&OP_JUMP, &vec![8]),
+                    // 0021
+                    code.make(&OP_LOOP_END, &vec![]),
+                    // 0022
+                    code.make(&OP_POP, &vec![]),
+                    // 0023
+                    code.make(&OP_NULL, &vec![]),
+                    // 0024
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+            TestCase {
+                input: "let x = 1; for (x in [2]) { }; x".to_string(),
+                expected_constants: [1, 2].iter().map(|i| i_to_o(*i) ).collect(),
+                expected_instructions: vec![
+                    // 0000
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    // 0003
+                    code.make(&OP_SET_GLOBAL, &vec![0]),
+                    // 0006
+                    code.make(&OP_CONSTANT, &vec![1]),
+                    // 0009
+                    code.make(&OP_ARRAY, &vec![1]),
+                    // 0012
+                    code.make(&OP_ITER, &vec![]),
+                    // 0013
+                    code.make(&OP_LOOP, &vec![]),
+                    // 0014
+                    code.make(&OP_ITER_NEXT, &vec![23]),
+                    // 0017
+                    code.make(&OP_SET_GLOBAL, &vec![1]),
+                    // 0020
+                    code.make(&OP_JUMP, &vec![14]),
+                    // 0023
+                    code.make(&OP_LOOP_END, &vec![]),
+                    // 0024
+                    code.make(&OP_POP, &vec![]),
+                    // 0025
+                    code.make(&OP_NULL, &vec![]),
+                    // 0026
+                    code.make(&OP_POP, &vec![]),
+                    // 0027
+                    code.make(&OP_GET_GLOBAL, &vec![0]),
+                    // 0030
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+            TestCase {
+                input: "fn() { for (x in []) { continue; } }".to_string(),
+                expected_constants: vec![
+                    MObject::CompiledFn(
+                        CompiledFunction {
+                            num_locals: 1,
+                            num_params: 0,
+                            instructions: vec![
+                                // 0000
+                                code.make(&OP_ARRAY, &vec![0]),
+                                // 0003
+                                code.make(&OP_ITER, &vec![]),
+                                // 0004
+                                code.make(&OP_LOOP, &vec![]),
+                                // 0005
+                                code.make(&OP_ITER_NEXT, &vec![17]),
+                                // 0008
+                                code.make(&OP_SET_LOCAL, &vec![0]),
+                                // 0010
+                                code.make(&OP_UNWIND, &vec![]),
+                                // 0011
+                                code.make(&OP_JUMP, &vec![5]),
+                                // 0014
+                                code.make(&OP_JUMP, &vec![5]),
+                                // 0017
+                                code.make(&OP_LOOP_END, &vec![]),
+                                // 0018
+                                code.make(&OP_POP, &vec![]),
+                                // 0019
+                                code.make(&OP_NULL, &vec![]),
+                                // 0020
+                                code.make(&OP_RETURN_VAL, &vec![]),
+                            ].into_iter().flatten().collect(),
+                            ..CompiledFunction::default()
+                        }
+                    ),
+                ],
+                expected_instructions: vec![
+                    code.make(&OP_CLOSURE, &vec![0, 0]),
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+        ];
+
+        run_compiler_tests(tests)
+    }
+
+    #[test]
+    fn test_assignments() -> Result<()> {
+        let code = MCode::new();
+        let tests = vec![
+            TestCase {
+                input: "let x = 1; x = 2;".to_string(),
+                expected_constants: [1, 2].iter().map(|i| i_to_o(*i) ).collect(),
+                expected_instructions: vec![
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    code.make(&OP_SET_GLOBAL, &vec![0]),
+                    code.make(&OP_CONSTANT, &vec![1]),
+                    code.make(&OP_SET_GLOBAL, &vec![0]),
+                    code.make(&OP_GET_GLOBAL, &vec![0]),
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+            TestCase {
+                input: "let x = 1; x += 2;".to_string(),
+                expected_constants: [1, 2].iter().map(|i| i_to_o(*i) ).collect(),
+                expected_instructions: vec![
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    code.make(&OP_SET_GLOBAL, &vec![0]),
+                    code.make(&OP_GET_GLOBAL, &vec![0]),
+                    code.make(&OP_CONSTANT, &vec![1]),
+                    code.make(&OP_ADD, &vec![]),
+                    code.make(&OP_SET_GLOBAL, &vec![0]),
+                    code.make(&OP_GET_GLOBAL, &vec![0]),
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+            TestCase {
+                input: "let x = 1; let x = 2;".to_string(),
+                expected_constants: [1, 2].iter().map(|i| i_to_o(*i) ).collect(),
+                expected_instructions: vec![
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    code.make(&OP_SET_GLOBAL, &vec![0]),
+                    code.make(&OP_CONSTANT, &vec![1]),
+                    code.make(&OP_SET_GLOBAL, &vec![0]),
+                ],
+            },
+            TestCase {
+                input: "let a = [1]; a[0] = 2;".to_string(),
+                expected_constants: [1, 0, 2].iter().map(|i| i_to_o(*i) ).collect(),
+                expected_instructions: vec![
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    code.make(&OP_ARRAY, &vec![1]),
+                    code.make(&OP_SET_GLOBAL, &vec![0]),
+                    code.make(&OP_GET_GLOBAL, &vec![0]),
+                    code.make(&OP_CONSTANT, &vec![1]),
+                    code.make(&OP_CONSTANT, &vec![2]),
+                    code.make(&OP_SET_INDEX, &vec![1]),
+                    code.make(&OP_SET_GLOBAL, &vec![0]),
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+            TestCase {
+                input: "let a = [[1]]; a[0][0] -= 2;".to_string(),
+                expected_constants: [1, 0, 0, 2].iter().map(|i| i_to_o(*i) ).collect(),
+                expected_instructions: vec![
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    code.make(&OP_ARRAY, &vec![1]),
+                    code.make(&OP_ARRAY, &vec![1]),
+                    code.make(&OP_SET_GLOBAL, &vec![0]),
+                    code.make(&OP_GET_GLOBAL, &vec![0]),
+                    code.make(&OP_CONSTANT, &vec![1]),
+                    code.make(&OP_CONSTANT, &vec![2]),
+                    code.make(&OP_PEEK_INDEX, &vec![2]),
+                    code.make(&OP_CONSTANT, &vec![3]),
+                    code.make(&OP_SUB, &vec![]),
+                    code.make(&OP_SET_INDEX, &vec![2]),
+                    code.make(&OP_SET_GLOBAL, &vec![0]),
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+            TestCase {
+                input: "fn() { let c = 0; fn() { c = 1; }; }".to_string(),
+                expected_constants: vec![
+                    i_to_o(0),
+                    i_to_o(1),
+                    MObject::CompiledFn(
+                        CompiledFunction {
+                            num_locals: 0,
+                            num_params: 0,
+                            instructions: vec![
+                                code.make(&OP_CONSTANT, &vec![1]),
+                                code.make(&OP_SET_FREE, &vec![0]),
+                                code.make(&OP_GET_FREE, &vec![0]),
+                                code.make(&OP_RETURN_VAL, &vec![]),
+                            ].into_iter().flatten().collect(),
+                            ..CompiledFunction::default()
+                        }
+                    ),
+                    MObject::CompiledFn(
+                        CompiledFunction {
+                            num_locals: 1,
+                            num_params: 0,
+                            instructions: vec![
+                                code.make(&OP_CONSTANT, &vec![0]),
+                                code.make(&OP_SET_LOCAL, &vec![0]),
+                                code.make(&OP_CAPTURE_LOCAL, &vec![0]),
+                                code.make(&OP_CLOSURE, &vec![2, 1]),
+                                code.make(&OP_RETURN_VAL, &vec![]),
+                            ].into_iter().flatten().collect(),
+                            ..CompiledFunction::default()
+                        }
+                    ),
+                ],
+                expected_instructions: vec![
+                    code.make(&OP_CLOSURE, &vec![3, 0]),
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+        ];
+
+        run_compiler_tests(tests)
+    }
+
+    #[test]
+    fn test_assignment_errors() -> Result<()> {
+        let tests = vec![
+            ("x = 1;", CompileErrorKind::UndefinedIdentifier("x".to_string())),
+            ("len = 1;", CompileErrorKind::AssignToBuiltin("len".to_string())),
+            ("let x = [1]; x[0] %= y;", CompileErrorKind::UndefinedIdentifier("y".to_string())),
+            ("for (x in [1]) { y = x; }", CompileErrorKind::UndefinedIdentifier("y".to_string())),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input.to_string())?;
+            match Compiler::new().compile(MNode::Prog(program)) {
+                Err(Error::Compile(e)) => assert_eq!(expected, e.kind),
+                x => panic!("Expected a compile error for {}, got: {:?}", input, x.map(|_| ())),
+            };
+        };
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_expressions() -> Result<()> {
+        let code = MCode::new();
+        let tests = vec![
+            TestCase {
+                input: r#"
+                    "monkey"
+                "#.to_string(),
+                expected_constants: vec!["monkey"].iter().map(|&x| s_to_o(x) ).collect(),
+                expected_instructions: vec![
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+            TestCase {
+                input: r#"
+                    "mon" + "key"
+                "#.to_string(),
+                expected_constants: vec!["mon", "key"].iter().map(|&x| s_to_o(x) ).collect(),
+                expected_instructions: vec![
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    code.make(&OP_CONSTANT, &vec![1]),
+                    code.make(&OP_ADD, &vec![]),
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+        ];
+
+        run_compiler_tests(tests)
+    }
+
+    #[test]
+    fn test_array_expressions() -> Result<()> {
+        let code = MCode::new();
+        let tests = vec![
+            TestCase {
+                input: r#"
+                    []
+                "#.to_string(),
+                expected_constants: vec![],
+                expected_instructions: vec![
+                    code.make(&OP_ARRAY, &vec![0]),
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+            TestCase {
+                input: r#"
+                    ["mon", "key", "go!"]
+                "#.to_string(),
+                expected_constants: vec!["mon", "key", "go!"].iter().map(|&x| s_to_o(x) ).collect(),
+                expected_instructions: vec![
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    code.make(&OP_CONSTANT, &vec![1]),
+                    code.make(&OP_CONSTANT, &vec![2]),
+                    code.make(&OP_ARRAY, &vec![3]),
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+            TestCase {
+                input: r#"
+                    [1 + 2, 3 - 4, 5 * 6]
+                "#.to_string(),
+                expected_constants: vec![1, 2, 3, 4, 5, 6].iter().map(|x| i_to_o(*x) ).collect(),
+                expected_instructions: vec![
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    code.make(&OP_CONSTANT, &vec![1]),
+                    code.make(&OP_ADD, &vec![]),
+                    code.make(&OP_CONSTANT, &vec![2]),
+                    code.make(&OP_CONSTANT, &vec![3]),
+                    code.make(&OP_SUB, &vec![]),
+                    code.make(&OP_CONSTANT, &vec![4]),
+                    code.make(&OP_CONSTANT, &vec![5]),
+                    code.make(&OP_MUL, &vec![]),
+                    code.make(&OP_ARRAY, &vec![3]),
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+        ];
+
+        run_compiler_tests(tests)
+    }
+
+    #[test]
+    fn test_hash_expressions() -> Result<()> {
+        let code = MCode::new();
+        let tests = vec![
+            TestCase {
+                input: r#"
+                    {}
+                "#.to_string(),
+                expected_constants: vec![],
+                expected_instructions: vec![
+                    code.make(&OP_HASH, &vec![0]),
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+            TestCase {
+                input: r#"
+                    {1: 2, 3: 4, 5: 6}
+                "#.to_string(),
+                expected_constants: vec![1, 2, 3, 4, 5, 6].iter().map(|x| i_to_o(*x) ).collect(),
+                expected_instructions: vec![
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    code.make(&OP_CONSTANT, &vec![1]),
+                    code.make(&OP_CONSTANT, &vec![2]),
+                    code.make(&OP_CONSTANT, &vec![3]),
+                    code.make(&OP_CONSTANT, &vec![4]),
+                    code.make(&OP_CONSTANT, &vec![5]),
+                    code.make(&OP_HASH, &vec![3]),
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+            TestCase {
+                input: r#"
+                    {1: 2 + 3, 4: 5 * 6}
+                "#.to_string(),
+                expected_constants: vec![1, 2, 3, 4, 5, 6].iter().map(|x| i_to_o(*x) ).collect(),
+                expected_instructions: vec![
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    code.make(&OP_CONSTANT, &vec![1]),
+                    code.make(&OP_CONSTANT, &vec![2]),
+                    code.make(&OP_ADD, &vec![]),
+                    code.make(&OP_CONSTANT, &vec![3]),
+                    code.make(&OP_CONSTANT, &vec![4]),
+                    code.make(&OP_CONSTANT, &vec![5]),
+                    code.make(&OP_MUL, &vec![]),
+                    code.make(&OP_HASH, &vec![2]),
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+        ];
+
+        run_compiler_tests(tests)
+    }
+
+    #[test]
+    fn test_index_operator() -> Result<()> {
+        let code = MCode::new();
+        let tests = vec![
+            TestCase {
+                input: r#"
+                    [1, 2, 3][1 + 1]
+                "#.to_string(),
+                expected_constants: vec![1, 2, 3, 1, 1].iter().map(|x| i_to_o(*x) ).collect(),
+                expected_instructions: vec![
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    code.make(&OP_CONSTANT, &vec![1]),
+                    code.make(&OP_CONSTANT, &vec![2]),
+                    code.make(&OP_ARRAY, &vec![3]),
+                    code.make(&OP_CONSTANT, &vec![3]),
+                    code.make(&OP_CONSTANT, &vec![4]),
+                    code.make(&OP_ADD, &vec![]),
+                    code.make(&OP_INDEX, &vec![]),
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+            TestCase {
+                input: r#"
+                    {1: 2}[2 - 1]
+                "#.to_string(),
+                expected_constants: vec![1, 2, 2, 1].iter().map(|x| i_to_o(*x) ).collect(),
+                expected_instructions: vec![
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    code.make(&OP_CONSTANT, &vec![1]),
+                    code.make(&OP_HASH, &vec![1]),
+                    code.make(&OP_CONSTANT, &vec![2]),
+                    code.make(&OP_CONSTANT, &vec![3]),
+                    code.make(&OP_SUB, &vec![]),
+                    code.make(&OP_INDEX, &vec![]),
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+        ];
+
+        run_compiler_tests(tests)
+    }
+
+    #[test]
+    fn test_match_expressions() -> Result<()> {
+        let code = MCode::new();
+        let tests = vec![
+            TestCase {
+                input: "match (1) { 2 => 3, n => n }".to_string(),
+                expected_constants: [1, 2, 3].iter().map(|x| i_to_o(*x) ).collect(),
+                expected_instructions: vec![
+                    // 0000
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    // 0003
+                    code.make(&OP_DUP, &vec![]),
+                    // 0004
+                    code.make(&OP_CONSTANT, &vec![1]),
+                    // 0007
+                    code.make(&OP_MATCH_VALUE, &vec![]),
+                    // 0008
+                    code.make(&OP_JUMP_NOT_TRUE, &vec![18]),
+                    // 0011
+                    code.make(&OP_POP, &vec![]),
+                    // 0012
+                    code.make(&OP_CONSTANT, &vec![2]),
+                    // 0015
+                    code.make(&OP_JUMP, &vec![31]),
+                    // 0018
+                    code.make(&OP_DUP, &vec![]),
+                    // 0019
+                    code.make(&OP_SET_GLOBAL, &vec![0]),
+                    // 0022
+                    code.make(&OP_POP, &vec![]),
+                    // 0023
+                    code.make(&OP_GET_GLOBAL, &vec![0]),
+                    // 0026
+                    code.make(&OP_JUMP, &vec![31]),
+                    // 0029
+                    code.make(&OP_POP, &vec![]),
+                    // 0030
+                    code.make(&OP_NULL, &vec![]),
+                    // 0031
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+            TestCase {
+                input: "let n = 1; match (2) { n => n }; n".to_string(),
+                expected_constants: [1, 2].iter().map(|x| i_to_o(*x) ).collect(),
                 expected_instructions: vec![
                     // 0000
                     code.make(&OP_CONSTANT, &vec![0]),
@@ -706,51 +1948,100 @@ mod tests {
                     // 0006
                     code.make(&OP_CONSTANT, &vec![1]),
                     // 0009
+                    code.make(&OP_DUP, &vec![]),
+                    // 0010
                     code.make(&OP_SET_GLOBAL, &vec![1]),
+                    // 0013
+                    code.make(&OP_POP, &vec![]),
+                    // 0014
+                    code.make(&OP_GET_GLOBAL, &vec![1]),
+                    // 0017
+                    code.make(&OP_JUMP, &vec![22]),
+                    // 0020
+                    code.make(&OP_POP, &vec![]),
+                    // 0021
+                    code.make(&OP_NULL, &vec![]),
+                    // 0022
+                    code.make(&OP_POP, &vec![]),
+                    // 0023
+                    code.make(&OP_GET_GLOBAL, &vec![0]),
+                    // 0026
+                    code.make(&OP_POP, &vec![]),
                 ],
             },
             TestCase {
-                input: "let one = 1; one;".to_string(),
-                expected_constants: vec![1].iter().map(|i| i_to_o(*i) ).collect(),
+                input: "match ([1]) { [x] => x }".to_string(),
+                expected_constants: [1, 0].iter().map(|x| i_to_o(*x) ).collect(),
                 expected_instructions: vec![
                     // 0000
                     code.make(&OP_CONSTANT, &vec![0]),
                     // 0003
-                    code.make(&OP_SET_GLOBAL, &vec![0]),
+                    code.make(&OP_ARRAY, &vec![1]),
                     // 0006
+                    code.make(&OP_DUP, &vec![]),
+                    // 0007
+                    code.make(&OP_MATCH_ARRAY, &vec![1, 0]),
+                    // 0011
+                    code.make(&OP_JUMP_NOT_TRUE, &vec![29]),
+                    // 0014
+                    code.make(&OP_DUP, &vec![]),
+                    // 0015
+                    code.make(&OP_CONSTANT, &vec![1]),
+                    // 0018
+                    code.make(&OP_INDEX, &vec![]),
+                    // 0019
+                    code.make(&OP_SET_GLOBAL, &vec![0]),
+                    // 0022
+                    code.make(&OP_POP, &vec![]),
+                    // 0023
                     code.make(&OP_GET_GLOBAL, &vec![0]),
-                    // 0009
+                    // 0026
+                    code.make(&OP_JUMP, &vec![31]),
+                    // 0029
                     code.make(&OP_POP, &vec![]),
-                ],
-            },
-        ];
-
-        run_compiler_tests(tests)
-    }
-
-    #[test]
-    fn test_string_expressions() -> Result<()> {
-        let code = MCode::new();
-        let tests = vec![
-            TestCase {
-                input: r#"
-                    "monkey"
-                "#.to_string(),
-                expected_constants: vec!["monkey"].iter().map(|&x| s_to_o(x) ).collect(),
-                expected_instructions: vec![
-                    code.make(&OP_CONSTANT, &vec![0]),
+                    // 0030
+                    code.make(&OP_NULL, &vec![]),
+                    // 0031
                     code.make(&OP_POP, &vec![]),
                 ],
             },
             TestCase {
-                input: r#"
-                    "mon" + "key"
-                "#.to_string(),
-                expected_constants: vec!["mon", "key"].iter().map(|&x| s_to_o(x) ).collect(),
+                input: "match ({1: 2}) { {1: v} => v }".to_string(),
+                expected_constants: [1, 2, 1, 1].iter().map(|x| i_to_o(*x) ).collect(),
                 expected_instructions: vec![
+                    // 0000
                     code.make(&OP_CONSTANT, &vec![0]),
+                    // 0003
                     code.make(&OP_CONSTANT, &vec![1]),
-                    code.make(&OP_ADD, &vec![]),
+                    // 0006
+                    code.make(&OP_HASH, &vec![1]),
+                    // 0009
+                    code.make(&OP_DUP, &vec![]),
+                    // 0010
+                    code.make(&OP_CONSTANT, &vec![2]),
+                    // 0013
+                    code.make(&OP_MATCH_HASH, &vec![1]),
+                    // 0016
+                    code.make(&OP_JUMP_NOT_TRUE, &vec![34]),
+                    // 0019
+                    code.make(&OP_DUP, &vec![]),
+                    // 0020
+                    code.make(&OP_CONSTANT, &vec![3]),
+                    // 0023
+                    code.make(&OP_INDEX, &vec![]),
+                    // 0024
+                    code.make(&OP_SET_GLOBAL, &vec![0]),
+                    // 0027
+                    code.make(&OP_POP, &vec![]),
+                    // 0028
+                    code.make(&OP_GET_GLOBAL, &vec![0]),
+                    // 0031
+                    code.make(&OP_JUMP, &vec![36]),
+                    // 0034
+                    code.make(&OP_POP, &vec![]),
+                    // 0035
+                    code.make(&OP_NULL, &vec![]),
+                    // 0036
                     code.make(&OP_POP, &vec![]),
                 ],
             },
@@ -760,48 +2051,58 @@ mod tests {
     }
 
     #[test]
-    fn test_array_expressions() -> Result<()> {
+    fn test_destructuring_let() -> Result<()> {
         let code = MCode::new();
         let tests = vec![
             TestCase {
-                input: r#"
-                    []
-                "#.to_string(),
-                expected_constants: vec![],
+                input: "let [a, ...b] = [1, 2];".to_string(),
+                expected_constants: [1, 2].iter().map(|x| i_to_o(*x) ).collect(),
                 expected_instructions: vec![
-                    code.make(&OP_ARRAY, &vec![0]),
-                    code.make(&OP_POP, &vec![]),
+                    code.make(&OP_CONSTANT, &vec![0]),
+                    code.make(&OP_CONSTANT, &vec![1]),
+                    code.make(&OP_ARRAY, &vec![2]),
+                    code.make(&OP_DESTRUCTURE_ARRAY, &vec![1, 1]),
+                    code.make(&OP_SET_GLOBAL, &vec![0]),
+                    code.make(&OP_SET_GLOBAL, &vec![1]),
                 ],
             },
             TestCase {
-                input: r#"
-                    ["mon", "key", "go!"]
-                "#.to_string(),
-                expected_constants: vec!["mon", "key", "go!"].iter().map(|&x| s_to_o(x) ).collect(),
+                input: "let x = 1; let [x, y] = [2, x];".to_string(),
+                expected_constants: [1, 2].iter().map(|x| i_to_o(*x) ).collect(),
                 expected_instructions: vec![
                     code.make(&OP_CONSTANT, &vec![0]),
+                    code.make(&OP_SET_GLOBAL, &vec![0]),
                     code.make(&OP_CONSTANT, &vec![1]),
-                    code.make(&OP_CONSTANT, &vec![2]),
-                    code.make(&OP_ARRAY, &vec![3]),
-                    code.make(&OP_POP, &vec![]),
+                    code.make(&OP_GET_GLOBAL, &vec![0]),
+                    code.make(&OP_ARRAY, &vec![2]),
+                    code.make(&OP_DESTRUCTURE_ARRAY, &vec![2, 0]),
+                    code.make(&OP_SET_GLOBAL, &vec![0]),
+                    code.make(&OP_SET_GLOBAL, &vec![1]),
                 ],
             },
             TestCase {
-                input: r#"
-                    [1 + 2, 3 - 4, 5 * 6]
-                "#.to_string(),
-                expected_constants: vec![1, 2, 3, 4, 5, 6].iter().map(|x| i_to_o(*x) ).collect(),
+                input: r#"fn(h) { let {"k": v} = h; v }"#.to_string(),
+                expected_constants: vec![
+                    s_to_o("k"),
+                    MObject::CompiledFn(
+                        CompiledFunction {
+                            num_locals: 2,
+                            num_params: 1,
+                            instructions: vec![
+                                code.make(&OP_GET_LOCAL, &vec![0]),
+                                code.make(&OP_CONSTANT, &vec![0]),
+                                code.make(&OP_DESTRUCTURE_HASH, &vec![1]),
+                                code.make(&OP_SET_LOCAL, &vec![1]),
+                                code.make(&OP_GET_LOCAL, &vec![1]),
+                                code.make(&OP_RETURN_VAL, &vec![]),
+                            ].into_iter().flatten().collect(),
+                            params: vec!["h".to_string()].into(),
+                            ..CompiledFunction::default()
+                        }
+                    ),
+                ],
                 expected_instructions: vec![
-                    code.make(&OP_CONSTANT, &vec![0]),
-                    code.make(&OP_CONSTANT, &vec![1]),
-                    code.make(&OP_ADD, &vec![]),
-                    code.make(&OP_CONSTANT, &vec![2]),
-                    code.make(&OP_CONSTANT, &vec![3]),
-                    code.make(&OP_SUB, &vec![]),
-                    code.make(&OP_CONSTANT, &vec![4]),
-                    code.make(&OP_CONSTANT, &vec![5]),
-                    code.make(&OP_MUL, &vec![]),
-                    code.make(&OP_ARRAY, &vec![3]),
+                    code.make(&OP_CLOSURE, &vec![1, 0]),
                     code.make(&OP_POP, &vec![]),
                 ],
             },
@@ -811,92 +2112,51 @@ mod tests {
     }
 
     #[test]
-    fn test_hash_expressions() -> Result<()> {
+    fn test_ranges_and_slices() -> Result<()> {
         let code = MCode::new();
         let tests = vec![
             TestCase {
-                input: r#"
-                    {}
-                "#.to_string(),
-                expected_constants: vec![],
-                expected_instructions: vec![
-                    code.make(&OP_HASH, &vec![0]),
-                    code.make(&OP_POP, &vec![]),
-                ],
-            },
-            TestCase {
-                input: r#"
-                    {1: 2, 3: 4, 5: 6}
-                "#.to_string(),
-                expected_constants: vec![1, 2, 3, 4, 5, 6].iter().map(|x| i_to_o(*x) ).collect(),
+                input: "0..=2".to_string(),
+                expected_constants: [0, 2].iter().map(|x| i_to_o(*x) ).collect(),
                 expected_instructions: vec![
                     code.make(&OP_CONSTANT, &vec![0]),
                     code.make(&OP_CONSTANT, &vec![1]),
-                    code.make(&OP_CONSTANT, &vec![2]),
-                    code.make(&OP_CONSTANT, &vec![3]),
-                    code.make(&OP_CONSTANT, &vec![4]),
-                    code.make(&OP_CONSTANT, &vec![5]),
-                    code.make(&OP_HASH, &vec![3]),
+                    code.make(&OP_RANGE, &vec![1]),
                     code.make(&OP_POP, &vec![]),
                 ],
             },
             TestCase {
-                input: r#"
-                    {1: 2 + 3, 4: 5 * 6}
-                "#.to_string(),
-                expected_constants: vec![1, 2, 3, 4, 5, 6].iter().map(|x| i_to_o(*x) ).collect(),
+                input: "[1][1:-1]".to_string(),
+                expected_constants: [1, 1, 1].iter().map(|x| i_to_o(*x) ).collect(),
                 expected_instructions: vec![
                     code.make(&OP_CONSTANT, &vec![0]),
+                    code.make(&OP_ARRAY, &vec![1]),
                     code.make(&OP_CONSTANT, &vec![1]),
                     code.make(&OP_CONSTANT, &vec![2]),
-                    code.make(&OP_ADD, &vec![]),
-                    code.make(&OP_CONSTANT, &vec![3]),
-                    code.make(&OP_CONSTANT, &vec![4]),
-                    code.make(&OP_CONSTANT, &vec![5]),
-                    code.make(&OP_MUL, &vec![]),
-                    code.make(&OP_HASH, &vec![2]),
+                    code.make(&OP_MINUS, &vec![]),
+                    code.make(&OP_SLICE, &vec![]),
                     code.make(&OP_POP, &vec![]),
                 ],
             },
-        ];
-
-        run_compiler_tests(tests)
-    }
-
-    #[test]
-    fn test_index_operator() -> Result<()> {
-        let code = MCode::new();
-        let tests = vec![
             TestCase {
-                input: r#"
-                    [1, 2, 3][1 + 1]
-                "#.to_string(),
-                expected_constants: vec![1, 2, 3, 1, 1].iter().map(|x| i_to_o(*x) ).collect(),
+                input: "\"ab\"[:1]".to_string(),
+                expected_constants: vec![s_to_o("ab"), i_to_o(1)],
                 expected_instructions: vec![
                     code.make(&OP_CONSTANT, &vec![0]),
+                    code.make(&OP_NULL, &vec![]),
                     code.make(&OP_CONSTANT, &vec![1]),
-                    code.make(&OP_CONSTANT, &vec![2]),
-                    code.make(&OP_ARRAY, &vec![3]),
-                    code.make(&OP_CONSTANT, &vec![3]),
-                    code.make(&OP_CONSTANT, &vec![4]),
-                    code.make(&OP_ADD, &vec![]),
-                    code.make(&OP_INDEX, &vec![]),
+                    code.make(&OP_SLICE, &vec![]),
                     code.make(&OP_POP, &vec![]),
                 ],
             },
             TestCase {
-                input: r#"
-                    {1: 2}[2 - 1]
-                "#.to_string(),
-                expected_constants: vec![1, 2, 2, 1].iter().map(|x| i_to_o(*x) ).collect(),
+                input: "[][:]".to_string(),
+                expected_constants: vec![],
                 expected_instructions: vec![
-                    code.make(&OP_CONSTANT, &vec![0]),
-                    code.make(&OP_CONSTANT, &vec![1]),
-                    code.make(&OP_HASH, &vec![1]),
-                    code.make(&OP_CONSTANT, &vec![2]),
-                    code.make(&OP_CONSTANT, &vec![3]),
-                    code.make(&OP_SUB, &vec![]),
-                    code.make(&OP_INDEX, &vec![]),
+                    code.make(&OP_ARRAY, &vec![0]),
+                    code.make(&OP_NULL, &vec![]),
+                    code.make(&OP_NULL, &vec![]),
+                    code.make(&OP_SLICE, &vec![]),
                     code.make(&OP_POP, &vec![]),
                 ],
             },
@@ -922,6 +2182,7 @@ mod tests {
                     code.make(&OP_ADD, &vec![]),
                     code.make(&OP_RETURN_VAL, &vec![]),
                 ].into_iter().flatten().collect(),
+                ..CompiledFunction::default()
             }
         );
         let func2 = MObject::CompiledFn(
@@ -934,6 +2195,7 @@ mod tests {
                     code.make(&OP_CONSTANT, &vec![1]),
                     code.make(&OP_RETURN_VAL, &vec![]),
                 ].into_iter().flatten().collect(),
+                ..CompiledFunction::default()
             }
         );
         let mut constants1 = constants.clone();
@@ -988,6 +2250,7 @@ mod tests {
                 instructions: vec![
                     code.make(&OP_RETURN, &vec![]),
                 ].into_iter().flatten().collect(),
+                ..CompiledFunction::default()
             }
         );
 
@@ -1039,6 +2302,74 @@ mod tests {
         assert_eq!(OP_MUL, prev.opcode);
     }
 
+    #[test]
+    fn test_default_and_rest_parameters() -> Result<()> {
+        let code = MCode::new();
+        let tests = vec![
+            TestCase {
+                input: "let f = fn(a, b = 2) { b };".to_string(),
+                expected_constants: vec![
+                    i_to_o(2),
+                    MObject::CompiledFn(
+                        CompiledFunction {
+                            num_locals: 3,
+                            num_params: 2,
+                            instructions: vec![
+                                // 0000
+                                code.make(&OP_JUMP, &vec![12]),
+                                // 0003
+                                code.make(&OP_JUMP_PASSED, &vec![1, 12]),
+                                // 0007
+                                code.make(&OP_CONSTANT, &vec![0]),
+                                // 0010
+                                code.make(&OP_SET_LOCAL, &vec![1]),
+                                // 0012
+                                code.make(&OP_GET_LOCAL, &vec![1]),
+                                // 0014
+                                code.make(&OP_RETURN_VAL, &vec![]),
+                            ].into_iter().flatten().collect(),
+                            name: Some("f".to_string()),
+                            params: vec!["a".to_string(), "b".to_string()].into(),
+                            defaults: vec![3],
+                            rest: false,
+                            ..CompiledFunction::default()
+                        }
+                    ),
+                ],
+                expected_instructions: vec![
+                    code.make(&OP_CLOSURE, &vec![1, 0]),
+                    code.make(&OP_SET_GLOBAL, &vec![0]),
+                ],
+            },
+            TestCase {
+                input: "fn(a, ...rest) { rest }".to_string(),
+                expected_constants: vec![
+                    MObject::CompiledFn(
+                        CompiledFunction {
+                            num_locals: 2,
+                            num_params: 1,
+                            instructions: vec![
+                                code.make(&OP_GET_LOCAL, &vec![1]),
+                                code.make(&OP_RETURN_VAL, &vec![]),
+                            ].into_iter().flatten().collect(),
+                            name: None,
+                            params: vec!["a".to_string()].into(),
+                            defaults: vec![],
+                            rest: true,
+                            ..CompiledFunction::default()
+                        }
+                    ),
+                ],
+                expected_instructions: vec![
+                    code.make(&OP_CLOSURE, &vec![0, 0]),
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
+        ];
+
+        run_compiler_tests(tests)
+    }
+
     #[test]
     fn test_function_calls() -> Result<()> {
         let code = MCode::new();
@@ -1057,6 +2388,7 @@ mod tests {
                                 code.make(&OP_CONSTANT, &vec![0]),
                                 code.make(&OP_RETURN_VAL, &vec![]),
                             ].into_iter().flatten().collect(),
+                            ..CompiledFunction::default()
                         }
                     )
                 ],
@@ -1081,6 +2413,8 @@ mod tests {
                                 code.make(&OP_CONSTANT, &vec![0]),
                                 code.make(&OP_RETURN_VAL, &vec![]),
                             ].into_iter().flatten().collect(),
+                            name: Some("noArg".to_string()),
+                            ..CompiledFunction::default()
                         }
                     )
                 ],
@@ -1106,6 +2440,9 @@ mod tests {
                                 code.make(&OP_GET_LOCAL, &vec![0]),
                                 code.make(&OP_RETURN_VAL, &vec![]),
                             ].into_iter().flatten().collect(),
+                            name: Some("oneArg".to_string()),
+                            params: vec!["a".to_string()].into(),
+                            ..CompiledFunction::default()
                         }
                     ),
                     i_to_o(24),
@@ -1137,6 +2474,9 @@ mod tests {
                                 code.make(&OP_GET_LOCAL, &vec![2]),
                                 code.make(&OP_RETURN_VAL, &vec![]),
                             ].into_iter().flatten().collect(),
+                            name: Some("manyArg".to_string()),
+                            params: vec!["a".to_string(), "b".to_string(), "c".to_string()].into(),
+                            ..CompiledFunction::default()
                         }
                     ),
                     i_to_o(24),
@@ -1154,6 +2494,39 @@ mod tests {
                     code.make(&OP_POP, &vec![]),
                 ],
             },
+            TestCase {
+                input: r#"
+                    let pair = fn(a, b) { a };
+                    pair(1, b: 2);
+                "#.to_string(),
+                expected_constants: vec![
+                    MObject::CompiledFn(
+                        CompiledFunction {
+                            num_locals: 2,
+                            num_params: 2,
+                            instructions: vec![
+                                code.make(&OP_GET_LOCAL, &vec![0]),
+                                code.make(&OP_RETURN_VAL, &vec![]),
+                            ].into_iter().flatten().collect(),
+                            name: Some("pair".to_string()),
+                            params: vec!["a".to_string(), "b".to_string()].into(),
+                            ..CompiledFunction::default()
+                        }
+                    ),
+                    i_to_o(1),
+                    i_to_o(2),
+                    MObject::Array(MArray { elements: vec![s_to_o("b")] }),
+                ],
+                expected_instructions: vec![
+                    code.make(&OP_CLOSURE, &vec![0, 0]),
+                    code.make(&OP_SET_GLOBAL, &vec![0]),
+                    code.make(&OP_CONSTANT, &vec![1]),
+                    code.make(&OP_CONSTANT, &vec![2]),
+                    code.make(&OP_GET_GLOBAL, &vec![0]),
+                    code.make(&OP_CALL_NAMED, &vec![2, 3]),
+                    code.make(&OP_POP, &vec![]),
+                ],
+            },
         ];
 
         run_compiler_tests(tests)
@@ -1178,6 +2551,7 @@ mod tests {
                                 code.make(&OP_GET_GLOBAL, &vec![0]),
                                 code.make(&OP_RETURN_VAL, &vec![]),
                             ].into_iter().flatten().collect(),
+                            ..CompiledFunction::default()
                         }
                     )
                 ],
@@ -1207,6 +2581,7 @@ mod tests {
                                 code.make(&OP_GET_LOCAL, &vec![0]),
                                 code.make(&OP_RETURN_VAL, &vec![]),
                             ].into_iter().flatten().collect(),
+                            ..CompiledFunction::default()
                         }
                     )
                 ],
@@ -1240,6 +2615,7 @@ mod tests {
                                 code.make(&OP_ADD, &vec![]),
                                 code.make(&OP_RETURN_VAL, &vec![]),
                             ].into_iter().flatten().collect(),
+                            ..CompiledFunction::default()
                         }
                     )
                 ],
@@ -1290,6 +2666,7 @@ mod tests {
                                 code.make(&OP_CALL, &vec![1]),
                                 code.make(&OP_RETURN_VAL, &vec![]),
                             ].into_iter().flatten().collect(),
+                            ..CompiledFunction::default()
                         }
                     )
                 ],
@@ -1326,6 +2703,8 @@ mod tests {
                                 code.make(&OP_ADD, &vec![]),
                                 code.make(&OP_RETURN_VAL, &vec![]),
                             ].into_iter().flatten().collect(),
+                            params: vec!["b".to_string()].into(),
+                            ..CompiledFunction::default()
                         }
                     ),
                     MObject::CompiledFn(
@@ -1333,10 +2712,12 @@ mod tests {
                             num_locals: 1,
                             num_params: 1,
                             instructions: vec![
-                                code.make(&OP_GET_LOCAL, &vec![0]),
+                                code.make(&OP_CAPTURE_LOCAL, &vec![0]),
                                 code.make(&OP_CLOSURE, &vec![0, 1]),
                                 code.make(&OP_RETURN_VAL, &vec![]),
                             ].into_iter().flatten().collect(),
+                            params: vec!["a".to_string()].into(),
+                            ..CompiledFunction::default()
                         }
                     )
                 ],
@@ -1368,6 +2749,8 @@ mod tests {
                                 code.make(&OP_ADD, &vec![]),
                                 code.make(&OP_RETURN_VAL, &vec![]),
                             ].into_iter().flatten().collect(),
+                            params: vec!["c".to_string()].into(),
+                            ..CompiledFunction::default()
                         }
                     ),
                     MObject::CompiledFn(
@@ -1375,11 +2758,13 @@ mod tests {
                             num_locals: 1,
                             num_params: 1,
                             instructions: vec![
-                                code.make(&OP_GET_FREE, &vec![0]),
-                                code.make(&OP_GET_LOCAL, &vec![0]),
+                                code.make(&OP_CAPTURE_FREE, &vec![0]),
+                                code.make(&OP_CAPTURE_LOCAL, &vec![0]),
                                 code.make(&OP_CLOSURE, &vec![0, 2]),
                                 code.make(&OP_RETURN_VAL, &vec![]),
                             ].into_iter().flatten().collect(),
+                            params: vec!["b".to_string()].into(),
+                            ..CompiledFunction::default()
                         }
                     ),
                     MObject::CompiledFn(
@@ -1387,10 +2772,12 @@ mod tests {
                             num_locals: 1,
                             num_params: 1,
                             instructions: vec![
-                                code.make(&OP_GET_LOCAL, &vec![0]),
+                                code.make(&OP_CAPTURE_LOCAL, &vec![0]),
                                 code.make(&OP_CLOSURE, &vec![1, 1]),
                                 code.make(&OP_RETURN_VAL, &vec![]),
                             ].into_iter().flatten().collect(),
+                            params: vec!["a".to_string()].into(),
+                            ..CompiledFunction::default()
                         }
                     )
                 ],
@@ -1438,6 +2825,7 @@ mod tests {
                                 code.make(&OP_ADD, &vec![]),
                                 code.make(&OP_RETURN_VAL, &vec![]),
                             ].into_iter().flatten().collect(),
+                            ..CompiledFunction::default()
                         }
                     ),
                     MObject::CompiledFn(
@@ -1447,11 +2835,12 @@ mod tests {
                             instructions: vec![
                                 code.make(&OP_CONSTANT, &vec![2]),
                                 code.make(&OP_SET_LOCAL, &vec![0]),
-                                code.make(&OP_GET_FREE, &vec![0]),
-                                code.make(&OP_GET_LOCAL, &vec![0]),
+                                code.make(&OP_CAPTURE_FREE, &vec![0]),
+                                code.make(&OP_CAPTURE_LOCAL, &vec![0]),
                                 code.make(&OP_CLOSURE, &vec![4, 2]),
                                 code.make(&OP_RETURN_VAL, &vec![]),
                             ].into_iter().flatten().collect(),
+                            ..CompiledFunction::default()
                         }
                     ),
                     MObject::CompiledFn(
@@ -1461,10 +2850,11 @@ mod tests {
                             instructions: vec![
                                 code.make(&OP_CONSTANT, &vec![1]),
                                 code.make(&OP_SET_LOCAL, &vec![0]),
-                                code.make(&OP_GET_LOCAL, &vec![0]),
+                                code.make(&OP_CAPTURE_LOCAL, &vec![0]),
                                 code.make(&OP_CLOSURE, &vec![5, 1]),
                                 code.make(&OP_RETURN_VAL, &vec![]),
                             ].into_iter().flatten().collect(),
+                            ..CompiledFunction::default()
                         }
                     )
                 ],
@@ -1503,6 +2893,9 @@ mod tests {
                                 code.make(&OP_CALL, &vec![1]),
                                 code.make(&OP_RETURN_VAL, &vec![]),
                             ].into_iter().flatten().collect(),
+                            name: Some("countDown".to_string()),
+                            params: vec!["x".to_string()].into(),
+                            ..CompiledFunction::default()
                         }
                     ),
                     i_to_o(1),
@@ -1538,6 +2931,9 @@ mod tests {
                                 code.make(&OP_CALL, &vec![1]),
                                 code.make(&OP_RETURN_VAL, &vec![]),
                             ].into_iter().flatten().collect(),
+                            name: Some("countDown".to_string()),
+                            params: vec!["x".to_string()].into(),
+                            ..CompiledFunction::default()
                         }
                     ),
                     i_to_o(1),
@@ -1553,6 +2949,8 @@ mod tests {
                                 code.make(&OP_CALL, &vec![1]),
                                 code.make(&OP_RETURN_VAL, &vec![]),
                             ].into_iter().flatten().collect(),
+                            name: Some("wrapper".to_string()),
+                            ..CompiledFunction::default()
                         }
                     ),
                 ],
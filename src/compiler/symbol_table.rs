@@ -31,6 +31,7 @@ pub struct SymbolTable {
     builtins: HashMap<String, Rc<Symbol>>,
     functions: HashMap<String, Rc<Symbol>>,
     free: RefCell<Vec<Rc<Symbol>>>,
+    num_definitions: usize,
 }
 
 impl SymbolTable {
@@ -41,6 +42,7 @@ impl SymbolTable {
             builtins: HashMap::new(),
             functions: HashMap::new(),
             free: RefCell::new(Vec::new()),
+            num_definitions: 0,
         }
     }
 
@@ -51,9 +53,12 @@ impl SymbolTable {
             builtins: HashMap::new(),
             functions: HashMap::new(),
             free: RefCell::new(Vec::new()),
+            num_definitions: 0,
         }
     }
 
+    // Defining a name again in the same scope reuses its slot, like `let` replacing the binding in
+    // the evaluator's environment.
     pub fn define(&mut self, name: String) -> Rc<Symbol> {
         let scope = if self.outer.is_none() {
             Scope::Global
@@ -62,12 +67,38 @@ impl SymbolTable {
         };
 
         let mut store = self.store.borrow_mut();
-        let symbol = Rc::new(Symbol::new(name.clone(), scope, store.len()));
+        if let Some(existing) = store.get(&name).filter(|s| s.scope == scope) {
+            return existing.clone();
+        };
+
+        let symbol = Rc::new(Symbol::new(name.clone(), scope, self.num_definitions));
+        self.num_definitions += 1;
         store.insert(name.clone(), symbol.clone());
 
         symbol
     }
 
+    // Defines a variable in a slot of its own for the duration of a block, like the variable of a
+    // `for` loop. Returns the symbol it shadows, which `end_shadow` puts back.
+    pub fn define_shadow(&mut self, name: String) -> (Rc<Symbol>, Option<Rc<Symbol>>) {
+        let shadowed = self.store.borrow_mut().remove(&name);
+        (self.define(name), shadowed)
+    }
+
+    pub fn end_shadow(&mut self, name: String, shadowed: Option<Rc<Symbol>>) {
+        let mut store = self.store.borrow_mut();
+        match shadowed {
+            Some(symbol) => store.insert(name, symbol),
+            None => store.remove(&name),
+        };
+    }
+
+    // Reserves a local that no name refers to.
+    pub fn reserve(&mut self) -> usize {
+        self.num_definitions += 1;
+        self.num_definitions - 1
+    }
+
     pub fn define_builtin(&mut self, name: String) -> Rc<Symbol> {
         let symbol = Rc::new(Symbol::new(name.clone(), Scope::Builtin, self.builtins.len()));
         self.builtins.insert(name.clone(), symbol.clone());
@@ -81,16 +112,26 @@ impl SymbolTable {
     }
 
     pub fn resolve(&self, name: &String) -> Option<Rc<Symbol>> {
+        self.lookup(name, true)
+    }
+
+    // Resolves the variable an assignment updates. The name a function uses to refer to itself
+    // isn't a variable, so it's skipped in favour of the binding the function was defined with.
+    pub fn resolve_variable(&self, name: &String) -> Option<Rc<Symbol>> {
+        self.lookup(name, false)
+    }
+
+    fn lookup(&self, name: &String, functions: bool) -> Option<Rc<Symbol>> {
         {
             // Make sure the borrow to self.store is released before trying to borrow_mut in define_free
             let store = self.store.borrow();
             if let Some(x) = store.get(name) { return Some(x.clone()); };
         }
 
-        if let Some(x) = self.functions.get(name) {
+        if let Some(x) = self.functions.get(name).filter(|_| functions) {
             Some(x.clone())
         } else if let Some(outer) = &self.outer {
-            let result = outer.resolve(name)?;
+            let result = outer.lookup(name, functions)?;
             if result.scope == Scope::Global || result.scope == Scope::Builtin { return Some(result); };
 
             Some(self.define_free(name, result))
@@ -121,8 +162,23 @@ impl SymbolTable {
         }
     }
 
+    pub fn names(&self) -> Vec<String> {
+        self.store.borrow().keys().cloned().collect()
+    }
+
+    // Every name a lookup from this scope can resolve, including outer scopes and builtins.
+    pub fn visible_names(&self) -> Vec<String> {
+        let mut names = self.names();
+        names.extend(self.functions.keys().cloned());
+        if let Some(outer) = &self.outer {
+            names.extend(outer.visible_names());
+        };
+        names.extend(self.builtins.keys().cloned());
+        names
+    }
+
     pub fn len(&self) -> u8 {
-        (self.store.borrow().len() - self.free.borrow().len()) as u8
+        self.num_definitions as u8
     }
 
     pub fn free_symbols(&self) -> Vec<Rc<Symbol>> {
@@ -333,4 +389,24 @@ mod tests {
 
         assert_eq!(expected, *global.resolve(&a).unwrap());
     }
+
+    #[test]
+    fn test_redefine_reuses_index() {
+        let a = "a".to_string();
+        let b = "b".to_string();
+        let mut global = SymbolTable::new();
+
+        assert_eq!(0, global.define(a.clone()).index);
+        assert_eq!(1, global.define(b.clone()).index);
+        assert_eq!(0, global.define(a.clone()).index);
+        assert_eq!(2, global.len());
+
+        let mut first_local = SymbolTable::enclose(global);
+        first_local.define(b.clone());
+
+        let mut second_local = SymbolTable::enclose(first_local);
+        assert_eq!(Scope::Free, second_local.resolve(&b).unwrap().scope);
+        assert_eq!(Symbol::new(b.clone(), Scope::Local, 0), *second_local.define(b.clone()));
+        assert_eq!(1, second_local.len());
+    }
 }
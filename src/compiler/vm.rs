@@ -1,9 +1,10 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, cell::RefCell, rc::Rc};
 
 use crate::{
-    error::{Result, Error},
+    error::{Result, Error, RuntimeErrorKind},
     compiler::code::*,
     object::*,
+    arithmetic::*,
     compiler::compiler::Bytecode, builtin,
 };
 
@@ -43,6 +44,13 @@ pub struct Vm {
     frames: Vec<Frame>,
     stack: Vec<MObject>,
     last_op_pop_element: Option<MObject>,
+
+    // The number of frames below and the height of the stack at the start of each loop being run.
+    // `break` and `continue` unwind the stack back to it.
+    loops: Vec<(usize, usize)>,
+
+    // Indexing an array or string out of range is an error instead of null.
+    strict: bool,
 }
 
 impl Vm {
@@ -50,11 +58,11 @@ impl Vm {
         let mut frames = Vec::with_capacity(MAX_FRAMES);
         frames.push(Frame::new(
             Closure {
-                f: CompiledFunction {
+                f: Rc::new(CompiledFunction {
                     instructions: bytecode.instructions,
-                    num_locals: 0,
-                    num_params: 0,
-                },
+                    spans: bytecode.spans,
+                    ..CompiledFunction::default()
+                }),
                 free: Vec::new(),
             },
             0,
@@ -66,6 +74,8 @@ impl Vm {
             frames,
             stack: Vec::with_capacity(STACK_SIZE),
             last_op_pop_element: None,
+            loops: Vec::new(),
+            strict: false,
         }
     }
 
@@ -73,11 +83,11 @@ impl Vm {
         let mut frames = Vec::with_capacity(MAX_FRAMES);
         frames.push(Frame::new(
             Closure {
-                f: CompiledFunction {
+                f: Rc::new(CompiledFunction {
                     instructions: bytecode.instructions,
-                    num_locals: 0,
-                    num_params: 0,
-                },
+                    spans: bytecode.spans,
+                    ..CompiledFunction::default()
+                }),
                 free: Vec::new(),
             },
             0,
@@ -89,240 +99,414 @@ impl Vm {
             frames,
             stack: Vec::with_capacity(STACK_SIZE),
             last_op_pop_element: None,
+            loops: Vec::new(),
+            strict: false,
         }
     }
 
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
     pub fn globals(&self) -> Vec<MObject> {
         self.globals.clone()
     }
 
     pub fn run(&mut self) -> Result<()> {
         while self.current_frame().ip < self.current_frame().instructions().len() {
-            let frame = self.pop_frame();
-            let mut ip = frame.ip;
-            let mut bp = frame.bp;
-            let mut cl = frame.cl;
-            let instructions: &[u8] = &cl.f.instructions;
-            let op = instructions[ip];
-            ip += 1;
-
-            match op {
-                OP_CONSTANT => {
-                    let const_idx: usize = BigEndian::read_u16(&instructions[ip..]).into();
-                    ip += 2;
-
-                    self.push(self.constants[const_idx].clone())?;
-                },
-                OP_CLOSURE => {
-                    let const_idx: usize = BigEndian::read_u16(&instructions[ip..]).into();
-                    let num_free: usize = instructions[ip + 2].into();
-                    ip += 3;
+            let Frame { mut cl, mut ip, mut bp } = self.pop_frame();
+            let op_ip = ip;
 
-                    let mut free = Vec::with_capacity(num_free);
+            if let Err(e) = self.step(&mut cl, &mut ip, &mut bp) {
+                return Err(locate_error(e, &cl.f.spans, op_ip));
+            };
 
-                    for i in 0..num_free {
-                        free.push(self.stack[self.stack.len() - num_free + i].clone());
-                    };
+            self.push_frame(Frame { cl, ip, bp });
+        }
 
-                    let closure = match &self.constants[const_idx] {
-                        MObject::CompiledFn(f) => {
-                            MObject::Closure(
-                                Closure {
-                                    f: f.clone(),
-                                    free,
-                                },
-                            )
-                        },
-                        x => return Err(Error::new(format!("Cannot turn {} into a closure.", x))),
-                    };
-                    self.push(closure)?;
-                },
-                OP_CURRENT_CLOSURE => {
-                    self.push(MObject::Closure(cl.clone()))?;
-                },
-                OP_ADD..=OP_DIV => self.add_op(op)?,
-                OP_TRUE => self.push(TRUE)?,
-                OP_FALSE => self.push(FALSE)?,
-                OP_EQUAL..=OP_GREATER_THAN => self.comparison_op(op)?,
-                OP_MINUS => {
-                    let object = self.pop()?;
-                    match object {
-                        MObject::Int(x) => self.push(MObject::Int(Integer { value: -x.value }))?,
-                        _ => return Err(Error::new(format!("object not an integer: {}", object))),
-                    };
-                },
-                OP_BANG => {
-                    match self.pop()? {
-                        MObject::Bool(x) => self.push(native_bool_to_boolean(!x.value))?,
-                        NULL => self.push(TRUE)?,
-                        _ => self.push(FALSE)?,
-                    };
-                },
-                OP_INDEX => self.index_op()?,
-                OP_JUMP_NOT_TRUE => {
-                    if is_truthy(self.pop()?) {
-                        ip += 2;
-                    } else {
-                        ip = BigEndian::read_u16(&instructions[ip..]).into();
-                    };
-                },
-                OP_SET_GLOBAL => {
-                    let globals_idx: usize = BigEndian::read_u16(&instructions[ip..]).into();
-                    ip += 2;
+        Ok(())
+    }
 
-                    let obj = self.pop()?;
-                    self.globals.insert(globals_idx, obj);
-                },
-                OP_GET_GLOBAL => {
-                    let globals_idx: usize = BigEndian::read_u16(&instructions[ip..]).into();
-                    ip += 2;
+    // Executes the instruction at `ip`, leaving `cl`, `ip` and `bp` at the next one to execute.
+    #[inline(always)]
+    fn step(&mut self, cl: &mut Closure, ip: &mut usize, bp: &mut usize) -> Result<()> {
+        let op_ip = *ip;
+        let instructions: &[u8] = &cl.f.instructions;
+        let op = instructions[op_ip];
+        *ip += 1;
 
-                    let obj = match self.globals.get(globals_idx) {
-                        Some(x) => (*x).clone(),
-                        None => return Err(Error::new(format!("No global found for index: {}, len: {}", globals_idx, self.globals.len()))),
-                    };
-                    self.push(obj)?;
-                },
-                OP_SET_LOCAL => {
-                    let locals_idx: u8 = instructions[ip];
-                    ip += 1;
-
-                    let mut obj = self.pop()?;
-                    let idx = self.current_frame().bp + (locals_idx as usize);
-                    let stack_null = match self.stack.get_mut(idx) {
-                        Some(x) => x,
-                        None => return Err(
-                            Error::new(
-                                format!(
-                                    "No local on stack. index: {}, bp: {}, len: {}, stack:\n{:?}",
-                                    locals_idx,
-                                    self.current_frame().bp,
-                                    self.stack.len(),
-                                    self.stack,
-                                )
-                            )
-                        ),
-                    };
-                    std::mem::swap(
-                        stack_null,
-                        &mut obj,
-                    );
-                },
-                OP_GET_LOCAL => {
-                    let locals_idx: u8 = instructions[ip];
-                    ip += 1;
-
-                    let idx = self.current_frame().bp + (locals_idx as usize);
-                    let obj = match self.stack.get(idx) {
-                        Some(x) => x.clone(),
-                        None => return Err(
-                            Error::new(
-                                format!(
-                                    "No local on stack. index: {}, bp: {}, len: {}, stack:\n{:?}",
-                                    locals_idx,
-                                    self.current_frame().bp,
-                                    self.stack.len(),
-                                    self.stack,
-                                )
-                            )
-                        ),
-                    };
-                    self.stack.push(obj);
-                },
-                OP_GET_FREE => {
-                    let free_idx: usize = instructions[ip].into();
-                    ip += 1;
+        match op {
+            OP_CONSTANT => {
+                let const_idx: usize = BigEndian::read_u16(&instructions[*ip..]).into();
+                *ip += 2;
 
-                    self.stack.push(cl.free[free_idx].clone());
-                },
-                OP_GET_BUILTIN => {
-                    let builtin_idx = instructions[ip];
-                    ip += 1;
+                self.push(self.constants[const_idx].clone())?;
+            },
+            OP_CLOSURE => {
+                let const_idx: usize = BigEndian::read_u16(&instructions[*ip..]).into();
+                let num_free: usize = instructions[*ip + 2].into();
+                *ip += 3;
+
+                let free = self.stack.split_off(self.stack.len() - num_free);
+
+                let closure = match &self.constants[const_idx] {
+                    MObject::CompiledFn(f) => {
+                        MObject::Closure(
+                            Closure {
+                                f: Rc::new(f.clone()),
+                                free,
+                            },
+                        )
+                    },
+                    x => return Err(Error::from(RuntimeErrorKind::NotAFunction(x.type_name()))),
+                };
+                self.push(closure)?;
+            },
+            OP_CURRENT_CLOSURE => {
+                self.push(MObject::Closure(cl.clone()))?;
+            },
+            OP_ADD..=OP_DIV | OP_MOD..=OP_SHR => self.binary_op(op)?,
+            OP_LESS_THAN..=OP_GREATER_EQUAL => self.binary_op(op)?,
+            OP_TRUE => self.push(TRUE)?,
+            OP_FALSE => self.push(FALSE)?,
+            OP_EQUAL..=OP_GREATER_THAN => self.binary_op(op)?,
+            OP_MINUS | OP_BIT_NOT => {
+                let object = self.pop()?;
+                let operator = if op == OP_MINUS { "-" } else { "~" };
+                match integer_prefix_op(operator, &object) {
+                    Some(x) => self.push(x)?,
+                    None => return Err(Error::from(RuntimeErrorKind::UnknownOperator { operator: operator.to_string(), operand: object.type_name() })),
+                };
+            },
+            OP_BANG => {
+                match self.pop()? {
+                    MObject::Bool(x) => self.push(native_bool_to_boolean(!x.value))?,
+                    NULL => self.push(TRUE)?,
+                    _ => self.push(FALSE)?,
+                };
+            },
+            OP_INDEX => self.index_op()?,
+            OP_SET_INDEX => {
+                let depth: usize = instructions[*ip].into();
+                *ip += 1;
 
-                    let f = match builtin::get_builtin_by_index(builtin_idx) {
-                        Some(x) => x,
-                        None => return Err(Error::new(format!("No builtin defined with index={}", builtin_idx))),
-                    };
+                self.set_index_op(depth)?;
+            },
+            OP_DESTRUCTURE_ARRAY => {
+                let count: usize = BigEndian::read_u16(&instructions[*ip..]).into();
+                let rest = instructions[*ip + 2] == 1;
+                *ip += 3;
+
+                let value = self.pop()?;
+                for x in value.destructure_array(count, rest)?.into_iter().rev() {
+                    self.push(x)?;
+                };
+            },
+            OP_DESTRUCTURE_HASH => {
+                let count: usize = BigEndian::read_u16(&instructions[*ip..]).into();
+                *ip += 2;
+
+                let keys = self.stack.split_off(self.stack.len() - count);
+                let value = self.pop()?;
+                for x in value.destructure_hash(&keys)?.into_iter().rev() {
+                    self.push(x)?;
+                };
+            },
+            OP_DUP => {
+                let top = self.stack.last().cloned().ok_or(Error::from(RuntimeErrorKind::StackUnderflow))?;
+                self.push(top)?;
+            },
+            OP_MATCH_VALUE => {
+                let literal = self.pop()?;
+                let value = self.pop()?;
+                self.push(native_bool_to_boolean(values_equal(&value, &literal)))?;
+            },
+            OP_MATCH_ARRAY => {
+                let count: usize = BigEndian::read_u16(&instructions[*ip..]).into();
+                let rest = instructions[*ip + 2] == 1;
+                *ip += 3;
 
-                    self.push(f)?;
-                },
-                OP_ARRAY => {
-                    let array_len: usize = BigEndian::read_u16(&instructions[ip..]).into();
-                    ip += 2;
+                let value = self.pop()?;
+                self.push(native_bool_to_boolean(value.matches_array(count, rest)))?;
+            },
+            OP_MATCH_HASH => {
+                let count: usize = BigEndian::read_u16(&instructions[*ip..]).into();
+                *ip += 2;
 
-                    let mut elements = vec![];
-                    for _ in 0..array_len {
-                        elements.push(self.pop()?);
-                    };
-                    elements.reverse();
+                let keys = self.stack.split_off(self.stack.len() - count);
+                let value = self.pop()?;
+                self.push(native_bool_to_boolean(value.matches_hash(&keys)))?;
+            },
+            OP_PEEK_INDEX => {
+                let depth: usize = instructions[*ip].into();
+                *ip += 1;
+
+                let start = self.stack.len() - depth - 1;
+                let mut obj = self.stack[start].clone();
+                for i in start + 1..self.stack.len() {
+                    obj = index_value(obj, self.stack[i].clone(), self.strict)?;
+                };
+                self.push(obj)?;
+            },
+            OP_JUMP_NOT_TRUE => {
+                if is_truthy(self.pop()?) {
+                    *ip += 2;
+                } else {
+                    *ip = BigEndian::read_u16(&instructions[*ip..]).into();
+                };
+            },
+            OP_JUMP_TRUE => {
+                if is_truthy(self.pop()?) {
+                    *ip = BigEndian::read_u16(&instructions[*ip..]).into();
+                } else {
+                    *ip += 2;
+                };
+            },
+            OP_SET_GLOBAL => {
+                let globals_idx: usize = BigEndian::read_u16(&instructions[*ip..]).into();
+                *ip += 2;
 
-                    self.push(MObject::Array(MArray { elements }))?;
-                },
-                OP_HASH => {
-                    let hash_len: usize = BigEndian::read_u16(&instructions[ip..]).into();
-                    ip += 2;
-
-                    let mut pairs = HashMap::new();
-                    for _ in 0..hash_len {
-                        let value = self.pop()?;
-                        let key = self.pop()?;
-                        let hash_key = match key.clone() {
-                            MObject::Str(x) => HashKey::Str(x),
-                            MObject::Int(x) => HashKey::Int(x),
-                            MObject::Bool(x) => HashKey::Bool(x),
-                            _ => panic!("Expected key to be Int, Str, or Bool. Got: {:?}", key),
-                        };
+                let obj = self.pop()?;
+                if self.globals.len() <= globals_idx {
+                    self.globals.resize(globals_idx + 1, NULL);
+                };
+                self.globals[globals_idx] = obj;
+            },
+            OP_GET_GLOBAL => {
+                let globals_idx: usize = BigEndian::read_u16(&instructions[*ip..]).into();
+                *ip += 2;
 
-                        let pair = HashPair { key, value };
+                let obj = match self.globals.get(globals_idx) {
+                    Some(x) => (*x).clone(),
+                    None => return Err(Error::from(RuntimeErrorKind::UndefinedGlobal(globals_idx))),
+                };
+                self.push(obj)?;
+            },
+            OP_SET_LOCAL => {
+                let locals_idx: u8 = instructions[*ip];
+                *ip += 1;
+
+                let obj = self.pop()?;
+                let idx = self.current_frame().bp + (locals_idx as usize);
+                match self.stack.get_mut(idx) {
+                    Some(MObject::Cell(x)) => *x.value.borrow_mut() = obj,
+                    Some(x) => *x = obj,
+                    None => return Err(Error::from(RuntimeErrorKind::UndefinedLocal(idx))),
+                };
+            },
+            OP_GET_LOCAL => {
+                let locals_idx: u8 = instructions[*ip];
+                *ip += 1;
+
+                let idx = self.current_frame().bp + (locals_idx as usize);
+                let obj = match self.stack.get(idx) {
+                    Some(MObject::Cell(x)) => x.value.borrow().clone(),
+                    Some(x) => x.clone(),
+                    None => return Err(Error::from(RuntimeErrorKind::UndefinedLocal(idx))),
+                };
+                self.stack.push(obj);
+            },
+            OP_GET_FREE => {
+                let free_idx: usize = instructions[*ip].into();
+                *ip += 1;
 
-                        pairs.insert(hash_key, pair);
-                    };
+                let obj = match &cl.free[free_idx] {
+                    MObject::Cell(x) => x.value.borrow().clone(),
+                    x => x.clone(),
+                };
+                self.stack.push(obj);
+            },
+            OP_SET_FREE => {
+                let free_idx: usize = instructions[*ip].into();
+                *ip += 1;
+
+                let obj = self.pop()?;
+                match &mut cl.free[free_idx] {
+                    MObject::Cell(x) => *x.value.borrow_mut() = obj,
+                    x => *x = obj,
+                };
+            },
+            // Moves a local into a cell the first time a closure captures it, and pushes the
+            // cell for OpClosure.
+            OP_CAPTURE_LOCAL => {
+                let locals_idx: u8 = instructions[*ip];
+                *ip += 1;
+
+                let idx = self.current_frame().bp + (locals_idx as usize);
+                let cell = match self.stack.get_mut(idx) {
+                    Some(MObject::Cell(x)) => x.clone(),
+                    Some(x) => {
+                        let cell = MCell { value: Rc::new(RefCell::new(std::mem::replace(x, NULL))) };
+                        *x = MObject::Cell(cell.clone());
+                        cell
+                    },
+                    None => return Err(Error::from(RuntimeErrorKind::UndefinedLocal(idx))),
+                };
+                self.push(MObject::Cell(cell))?;
+            },
+            OP_CAPTURE_FREE => {
+                let free_idx: usize = instructions[*ip].into();
+                *ip += 1;
 
-                    self.push(MObject::Hash(MHash { pairs }))?;
-                },
-                OP_CALL => {
-                    let num_args = instructions[ip]; 
-                    ip += 1;
-
-                    if let Some((closure, bp)) = self.execute_call(num_args)? {
-                        self.push_frame(Frame { cl, ip, bp });
-                        ip = 0;
-                        cl = closure;
+                self.push(cl.free[free_idx].clone())?;
+            },
+            OP_GET_BUILTIN => {
+                let builtin_idx = instructions[*ip];
+                *ip += 1;
+
+                let f = match builtin::get_builtin_by_index(builtin_idx) {
+                    Some(x) => x,
+                    None => return Err(Error::from(RuntimeErrorKind::UndefinedBuiltin(builtin_idx))),
+                };
+
+                self.push(f)?;
+            },
+            OP_ARRAY => {
+                let array_len: usize = BigEndian::read_u16(&instructions[*ip..]).into();
+                *ip += 2;
+
+                let mut elements = vec![];
+                for _ in 0..array_len {
+                    elements.push(self.pop()?);
+                };
+                elements.reverse();
+
+                self.push(MObject::Array(MArray { elements }))?;
+            },
+            OP_HASH => {
+                let hash_len: usize = BigEndian::read_u16(&instructions[*ip..]).into();
+                *ip += 2;
+
+                let mut pairs = HashMap::new();
+                for _ in 0..hash_len {
+                    let value = self.pop()?;
+                    let key = self.pop()?;
+                    let hash_key = match key.clone() {
+                        MObject::Str(x) => HashKey::Str(x),
+                        MObject::Int(x) => HashKey::Int(x),
+                        MObject::BigInt(x) => HashKey::BigInt(x),
+                        MObject::Bool(x) => HashKey::Bool(x),
+                        _ => return Err(Error::from(RuntimeErrorKind::UnusableHashKey(key.type_name()))),
                     };
-                },
-                OP_RETURN_VAL => {
-                    let retval = self.pop()?;
-                    let frame = self.pop_frame();
-                    ip = frame.ip;
-                    bp = frame.bp;
-                    cl = frame.cl;
-
-                    // Pop off the local variables
-                    for _ in bp..self.stack.len() { self.pop()?; };
-                    self.push(retval)?;
-                },
-                OP_RETURN => {
-                    let frame = self.pop_frame();
-                    ip = frame.ip;
-                    bp = frame.bp;
-                    cl = frame.cl;
-
-                    // Pop off the local variables
-                    for _ in bp..self.stack.len() { self.pop()?; };
-                    self.push(NULL)?;
-                },
-                OP_JUMP => ip = BigEndian::read_u16(&instructions[ip..]).into(),
-                OP_NULL => self.push(NULL)?,
-                OP_POP => self.last_op_pop_element = Some(self.pop()?),
-                _ => {
-                    let code = MCode::new();
-                    let def = code.lookup(&op)?;
-                    return Err(Error::new(format!("Opcode not implemented: {}", def.name)))
-                },
-            };
 
-            self.push_frame(Frame { cl, ip, bp });
-        }
+                    let pair = HashPair { key, value };
+
+                    pairs.insert(hash_key, pair);
+                };
+
+                self.push(MObject::Hash(MHash { pairs }))?;
+            },
+            OP_CALL => {
+                let num_args = instructions[*ip]; 
+                *ip += 1;
+
+                if let Some((closure, bp, entry)) = self.execute_call(num_args)? {
+                    let caller = std::mem::replace(cl, closure);
+                    self.push_frame(Frame { cl: caller, ip: *ip, bp });
+                    *ip = entry;
+                };
+            },
+            OP_CALL_NAMED => {
+                let num_args = instructions[*ip];
+                let names_idx: usize = BigEndian::read_u16(&instructions[*ip + 1..]).into();
+                *ip += 3;
+
+                if let Some((closure, bp, entry)) = self.execute_named_call(num_args, names_idx)? {
+                    let caller = std::mem::replace(cl, closure);
+                    self.push_frame(Frame { cl: caller, ip: *ip, bp });
+                    *ip = entry;
+                };
+            },
+            OP_RETURN_VAL => {
+                let retval = self.pop()?;
+                let frame = self.pop_frame();
+                *ip = frame.ip;
+                *bp = frame.bp;
+                *cl = frame.cl;
+                self.end_frame_loops();
+
+                // Pop off the local variables
+                for _ in *bp..self.stack.len() { self.pop()?; };
+                self.push(retval)?;
+            },
+            OP_RETURN => {
+                let frame = self.pop_frame();
+                *ip = frame.ip;
+                *bp = frame.bp;
+                *cl = frame.cl;
+                self.end_frame_loops();
+
+                // Pop off the local variables
+                for _ in *bp..self.stack.len() { self.pop()?; };
+                self.push(NULL)?;
+            },
+            OP_JUMP => *ip = BigEndian::read_u16(&instructions[*ip..]).into(),
+            OP_JUMP_PASSED => {
+                let param: usize = instructions[*ip].into();
+                let passed = match &self.stack[*bp + cl.f.num_params as usize + cl.f.rest as usize] {
+                    MObject::Array(mask) => mask.elements[param] == TRUE,
+                    _ => false,
+                };
+
+                if passed {
+                    *ip = BigEndian::read_u16(&instructions[*ip + 1..]).into();
+                } else {
+                    *ip += 3;
+                };
+            },
+            OP_RANGE => {
+                let inclusive = instructions[*ip] == 1;
+                *ip += 1;
+
+                let end = self.pop()?;
+                let start = self.pop()?;
+                self.push(MObject::range(&start, &end, inclusive)?)?;
+            },
+            OP_SLICE => {
+                let end = self.pop()?;
+                let start = self.pop()?;
+                let left = self.pop()?;
+                self.push(left.slice(&start, &end)?)?;
+            },
+            OP_ITER => {
+                let iterable = self.pop()?;
+                let iter = match iterable.iter() {
+                    Some(x) => x,
+                    None => return Err(Error::from(RuntimeErrorKind::NotIterable(iterable.type_name()))),
+                };
+
+                self.push(MObject::Iter(iter))?;
+            },
+            OP_ITER_NEXT => {
+                let next = match self.stack.last_mut() {
+                    Some(MObject::Iter(iter)) => iter.next(),
+                    Some(x) => return Err(Error::from(RuntimeErrorKind::NotIterable(x.type_name()))),
+                    None => return Err(Error::from(RuntimeErrorKind::StackUnderflow)),
+                };
+
+                match next {
+                    Some(element) => {
+                        *ip += 2;
+                        self.push(element)?;
+                    },
+                    None => *ip = BigEndian::read_u16(&instructions[*ip..]).into(),
+                };
+            },
+            OP_LOOP => self.loops.push((self.frames.len(), self.stack.len())),
+            OP_LOOP_END => { self.loops.pop(); },
+            OP_UNWIND => {
+                if let Some(&(_, height)) = self.loops.last() {
+                    self.stack.truncate(height);
+                };
+            },
+            OP_NULL => self.push(NULL)?,
+            OP_POP => self.last_op_pop_element = Some(self.pop()?),
+            _ => {
+                let code = MCode::new();
+                let def = code.lookup(&op)?;
+                return Err(Error::from(RuntimeErrorKind::UnimplementedOpcode(def.name.to_string())))
+            },
+        };
 
         Ok(())
     }
@@ -336,17 +520,24 @@ impl Vm {
             self.stack.push(o);
             Ok(())
         } else {
-            Err(Error::new("Stack overflow".to_string()))
+            Err(Error::from(RuntimeErrorKind::StackOverflow))
         }
     }
 
     fn pop(&mut self) -> Result<MObject> {
         match self.stack.pop() {
             Some(x) => Ok(x),
-            None => Err(Error::new("Stack is empty".to_string())),
+            None => Err(Error::from(RuntimeErrorKind::StackUnderflow)),
         }
     }
 
+    // Forgets the loops of a function that returned from inside them.
+    fn end_frame_loops(&mut self) {
+        while self.loops.last().is_some_and(|&(depth, _)| depth > self.frames.len()) {
+            self.loops.pop();
+        };
+    }
+
     fn current_frame(&self) -> &Frame {
         self.frames.last().unwrap()
     }
@@ -359,127 +550,168 @@ impl Vm {
         self.frames.pop().unwrap()
     }
 
-    fn add_op(&mut self, op: u8) -> Result<()> {
+    fn binary_op(&mut self, op: u8) -> Result<()> {
         let right = self.pop()?;
         let left = self.pop()?;
 
-        if let MObject::Int(left_val) = left {
-            if let MObject::Int(right_val) = right {
-                return self.arithmetic_op(left_val.value, op, right_val.value);
-            }
-        } else if let MObject::Str(ref left_val) = left {
-            if let MObject::Str(ref right_val) = right {
-                match op {
-                    OP_ADD => {
-                        let value = format!("{}{}", left_val.value, right_val.value);
-                        return self.push(MObject::Str(MString { value }));
-                    },
-                    _ => {},
-                }
-            }
+        // The most common integer operators skip looking the operator up by its symbol. Results
+        // that overflow take the general path.
+        if let (MObject::Int(left_val), MObject::Int(right_val)) = (&left, &right) {
+            let (l, r) = (left_val.value, right_val.value);
+            let result = match op {
+                OP_ADD => l.checked_add(r).map(|value| MObject::Int(Integer { value })),
+                OP_SUB => l.checked_sub(r).map(|value| MObject::Int(Integer { value })),
+                OP_MUL => l.checked_mul(r).map(|value| MObject::Int(Integer { value })),
+                OP_EQUAL => Some(native_bool_to_boolean(l == r)),
+                OP_NOT_EQUAL => Some(native_bool_to_boolean(l != r)),
+                OP_GREATER_THAN => Some(native_bool_to_boolean(l > r)),
+                OP_LESS_THAN => Some(native_bool_to_boolean(l < r)),
+                _ => None,
+            };
+            if let Some(x) = result { return self.push(x); };
         };
 
-        Err(Error::new(format!("type mismatch: {} {} {}", left, op, right)))
-    }
+        let operator = operator_symbol(op);
 
-    fn arithmetic_op(&mut self, left: i128, op: u8, right: i128) -> Result<()> {
-        let value = match op {
-            OP_ADD => left + right,
-            OP_SUB => left - right,
-            OP_MUL => left * right,
-            OP_DIV => left / right,
-            _ => unreachable!(),
+        let numeric = if let Some((left_val, right_val)) = float_operands(&left, &right) {
+            float_infix_op(left_val, operator, right_val).map(Ok)
+        } else if let Some((left_val, right_val)) = bigint_operands(&left, &right) {
+            bigint_infix_op(left_val, operator, right_val)
+        } else if let (MObject::Int(left_val), MObject::Int(right_val)) = (&left, &right) {
+            integer_infix_op(left_val.value, operator, right_val.value)
+        } else {
+            None
         };
-        self.push(MObject::Int(Integer { value }))
-    }
-
-    fn comparison_op(&mut self, op: u8) -> Result<()> {
-        let right = self.pop()?;
-        let left = self.pop()?;
 
-        if let MObject::Int(left_val) = left {
-            if let MObject::Int(right_val) = right {
-                let value = match op {
-                    OP_EQUAL => left_val == right_val,
-                    OP_NOT_EQUAL => left_val != right_val,
-                    OP_GREATER_THAN => left_val > right_val,
-                    _ => unreachable!(),
-                };
-                return self.push(native_bool_to_boolean(value));
-            }
-        } else if let MObject::Bool(left_val) = left {
-            if let MObject::Bool(right_val) = right {
-                let value = match op {
-                    OP_EQUAL => left_val == right_val,
-                    OP_NOT_EQUAL => left_val != right_val,
-                    _ => unreachable!(),
-                };
-                return self.push(native_bool_to_boolean(value));
-            }
+        if let Some(result) = numeric {
+            return self.push(result?);
         };
 
-        Err(Error::new(format!("type mismatch: {} {} {}", left, op, right)))
+        match (&left, &right, op) {
+            (MObject::Str(left_val), MObject::Str(right_val), OP_ADD) => {
+                let value = format!("{}{}", left_val.value, right_val.value);
+                self.push(MObject::Str(MString { value }))
+            },
+            (MObject::Bool(left_val), MObject::Bool(right_val), OP_EQUAL) => {
+                self.push(native_bool_to_boolean(left_val == right_val))
+            },
+            (MObject::Bool(left_val), MObject::Bool(right_val), OP_NOT_EQUAL) => {
+                self.push(native_bool_to_boolean(left_val != right_val))
+            },
+            _ => Err(type_mismatch(&left, op, &right)),
+        }
     }
 
     fn index_op(&mut self) -> Result<()> {
         let index = self.pop()?;
         let obj = self.pop()?;
 
-        let value = match obj {
-            MObject::Array(ref x) => {
-                if let MObject::Int(ref i) = index {
-                    match x.elements.get(i.value as usize) {
-                        Some(v) => v.clone(),
-                        None => NULL,
-                    }
-                } else {
-                    return Err(Error::new(format!("OpIndex not implmented for: {}[{}]", obj, index)));
-                }
-            },
-            MObject::Hash(ref h) => {
-                let hash_key = match index {
-                    MObject::Int(x) => HashKey::Int(x),
-                    MObject::Str(x) => HashKey::Str(x),
-                    MObject::Bool(x) => HashKey::Bool(x),
-                    _ => return Err(Error::new(format!("Expected hash key to be Int, Str, or Bool. Got: {:?}", index))),
-                };
+        self.push(index_value(obj, index, self.strict)?)
+    }
 
-                match h.pairs.get(&hash_key) {
-                    Some(pair) => pair.value.clone(),
-                    None => NULL,
-                }
-            }
-            _ => return Err(Error::new(format!("OpIndex not implmented for: {}[{}]", obj, index))),
-        };
+    // Pops the value, `depth` indexes and the variable's array or hash, and pushes the value back
+    // followed by a copy of the container with the element replaced, ready to be stored.
+    fn set_index_op(&mut self, depth: usize) -> Result<()> {
+        let value = self.pop()?;
+        let keys = self.stack.split_off(self.stack.len() - depth);
+        let container = self.pop()?;
 
-        self.push(value)
+        let updated = set_index_path(container, &keys, value.clone(), self.strict)?;
+        self.push(value)?;
+        self.push(updated)
     }
 
-    fn execute_call(&mut self, num_args: u8) -> Result<Option<(Closure, usize)>> {
+    fn execute_call(&mut self, num_args: u8) -> Result<Option<(Closure, usize, usize)>> {
         let callee = self.pop()?;
         match callee {
             MObject::Closure(x) => self.call_function(x, num_args),
             MObject::Builtin(x) => self.call_builtin(x, num_args),
-            _ => Err(Error::new(format!("Expected compiled funtion or builtin, got: {}", callee))),
+            _ => Err(Error::from(RuntimeErrorKind::NotCallable(callee.type_name()))),
         }
     }
 
-    fn call_function(&mut self, callee: Closure, num_args: u8) -> Result<Option<(Closure, usize)>> {
-        if callee.f.num_params != num_args {
-            return Err(Error::new(format!("wrong number of arguments: want={}, got={}", callee.f.num_params, num_args)));
+    // The arguments become the first locals, followed by an array of the extra arguments for a rest
+    // parameter. A call that leaves out parameters starts where their default values are set.
+    fn call_function(&mut self, callee: Closure, num_args: u8) -> Result<Option<(Closure, usize, usize)>> {
+        let f = &callee.f;
+        let num_args = num_args as usize;
+        let num_params = f.num_params as usize;
+        let bp = self.stack.len() - num_args;
+
+        // A call with exactly the parameters has nothing to fill in.
+        if num_args == num_params && !f.rest {
+            for _ in 0..f.num_locals { self.stack.push(NULL); };
+            return Ok(Some((callee, bp, 0)));
+        };
+
+        f.check_arity(num_args)?;
+        let extra = self.stack.split_off(bp + num_args.min(num_params));
+        if f.rest {
+            for _ in num_args..num_params { self.stack.push(NULL); };
+            self.stack.push(MObject::Array(MArray { elements: extra }));
         };
 
-        let num_locals = callee.f.num_locals;
+        let ip = match num_args.checked_sub(f.required_params()) {
+            Some(i) if i < f.defaults.len() => f.defaults[i],
+            _ => 0,
+        };
+
+        // Make room for the locals
+        for _ in 0..f.num_locals { self.stack.push(NULL); };
+
+        Ok(Some((callee, bp, ip)))
+    }
+
+    #[cold]
+    fn execute_named_call(&mut self, num_args: u8, names_idx: usize) -> Result<Option<(Closure, usize, usize)>> {
+        let names = match &self.constants[names_idx] {
+            MObject::Array(names) => names.elements.iter().filter_map(|name| match name {
+                MObject::Str(name) => Some(name.value.clone()),
+                _ => None,
+            }).collect::<Vec<String>>(),
+            _ => Vec::new(),
+        };
+
+        let callee = self.pop()?;
+        match callee {
+            MObject::Closure(x) => self.call_function_named(x, num_args, names),
+            MObject::Builtin(x) => {
+                let function = Some(x.name().to_string());
+                Err(Error::from(RuntimeErrorKind::UnknownArgument { function, name: names[0].clone() }))
+            },
+            _ => Err(Error::from(RuntimeErrorKind::NotCallable(callee.type_name()))),
+        }
+    }
 
-        let bp = self.stack.len() - (num_args as usize);
+    // Named arguments can leave out a parameter before one that is passed, the local following the
+    // parameters holds which ones were passed so that their default values are skipped.
+    fn call_function_named(&mut self, callee: Closure, num_args: u8, names: Vec<String>) -> Result<Option<(Closure, usize, usize)>> {
+        let f = &callee.f;
+        let bp = self.stack.len() - num_args as usize;
+        let named_values = self.stack.split_off(self.stack.len() - names.len());
+        let args = self.stack.split_off(bp);
+
+        let (values, extra) = f.bind_arguments(args, names.into_iter().zip(named_values).collect())?;
+        let missing = values.iter().position(Option::is_none);
+        let passed = values.iter().map(|value| native_bool_to_boolean(value.is_some())).collect();
+
+        self.stack.extend(values.into_iter().map(|value| value.unwrap_or(NULL)));
+        if f.rest { self.stack.push(MObject::Array(MArray { elements: extra })); };
+        if !f.defaults.is_empty() { self.stack.push(MObject::Array(MArray { elements: passed })); };
+
+        let ip = match missing {
+            Some(i) => f.defaults[i - f.required_params()],
+            None => 0,
+        };
 
         // Make room for the locals
-        for _ in 0..num_locals { self.stack.push(NULL); };
+        for _ in 0..f.num_locals { self.stack.push(NULL); };
 
-        Ok(Some((callee, bp)))
+        Ok(Some((callee, bp, ip)))
     }
 
-    fn call_builtin(&mut self, callee: builtin::Builtin, num_args: u8) -> Result<Option<(Closure, usize)>> {
+    // An error returned by a builtin stops the program, as it does in the evaluator.
+    fn call_builtin(&mut self, callee: builtin::Builtin, num_args: u8) -> Result<Option<(Closure, usize, usize)>> {
         let mut args = Vec::new();
         for _ in 0..num_args { args.push(self.pop()?); };
         args.reverse();
@@ -493,12 +725,96 @@ impl Vm {
             builtin::Builtin::Puts(puts) => puts(&mut args),
         };
 
-        self.push(result?)?;
+        match result? {
+            MObject::Err(e) => return Err(Error::from(e)),
+            x => self.push(x)?,
+        };
 
         Ok(None)
     }
 }
 
+// Errors raised by the object helpers don't know where they come from, they are located at the
+// expression the failing instruction was compiled from. The span is only looked up here, so that
+// running instructions doesn't keep track of it.
+fn locate_error(err: Error, spans: &SpanTable, op_ip: usize) -> Error {
+    match err {
+        Error::Runtime(mut e) if e.span.start.line == 0 => {
+            e.span = spans.lookup(op_ip);
+            Error::Runtime(e)
+        },
+        x => x,
+    }
+}
+
+fn index_value(obj: MObject, index: MObject, strict: bool) -> Result<MObject> {
+    let value = match (&obj, &index) {
+        (MObject::Array(_) | MObject::Str(_), MObject::Int(i)) => obj.element(i.value, strict)?,
+        (MObject::Array(_) | MObject::Str(_), MObject::Range(range)) => obj.slice_range(range)?,
+        (MObject::Hash(h), _) => {
+            let hash_key = match index.hash_key() {
+                Some(x) => x,
+                None => return Err(Error::from(RuntimeErrorKind::UnusableHashKey(index.type_name()))),
+            };
+
+            match h.pairs.get(&hash_key) {
+                Some(pair) => pair.value.clone(),
+                None => NULL,
+            }
+        },
+        _ => return Err(Error::from(RuntimeErrorKind::NotIndexable { left: obj.type_name(), index: index.type_name() })),
+    };
+
+    Ok(value)
+}
+
+fn set_index_path(container: MObject, keys: &[MObject], value: MObject, strict: bool) -> Result<MObject> {
+    let (key, rest) = match keys.split_first() {
+        Some(x) => x,
+        None => return Ok(value),
+    };
+
+    let value = if rest.is_empty() {
+        value
+    } else {
+        let element = index_value(container.clone(), key.clone(), strict)?;
+        set_index_path(element, rest, value, strict)?
+    };
+
+    Ok(container.set_index(key.clone(), value)?)
+}
+
+fn operator_symbol(op: u8) -> &'static str {
+    match op {
+        OP_ADD => "+",
+        OP_SUB => "-",
+        OP_MUL => "*",
+        OP_DIV => "/",
+        OP_MOD => "%",
+        OP_POW => "**",
+        OP_BIT_AND => "&",
+        OP_BIT_OR => "|",
+        OP_BIT_XOR => "^",
+        OP_SHL => "<<",
+        OP_SHR => ">>",
+        OP_EQUAL => "==",
+        OP_NOT_EQUAL => "!=",
+        OP_GREATER_THAN => ">",
+        OP_LESS_THAN => "<",
+        OP_LESS_EQUAL => "<=",
+        OP_GREATER_EQUAL => ">=",
+        _ => unreachable!(),
+    }
+}
+
+fn type_mismatch(left: &MObject, op: u8, right: &MObject) -> Error {
+    Error::from(RuntimeErrorKind::TypeMismatch {
+        left: left.type_name(),
+        operator: operator_symbol(op).to_string(),
+        right: right.type_name(),
+    })
+}
+
 #[inline]
 fn native_bool_to_boolean(b: bool) -> MObject {
     if b { TRUE } else { FALSE }
@@ -536,14 +852,7 @@ mod tests {
             return Ok(());
         }
 
-        let mut msg = format!("The Parser had {} errors:\n", errors.len());
-
-        for e in errors {
-            msg.push_str(&e);
-            msg.push('\n');
-        }
-
-        Err(Error::new(msg))
+        Err(Error::Parse(errors))
     }
 
     fn parse(input: &[u8]) -> Result<Program> {
@@ -601,6 +910,80 @@ mod tests {
             TestCase { input: "5 * 2 + 10".to_string(), expected: i_to_o(20) },
             TestCase { input: "5 + 2 * 10".to_string(), expected: i_to_o(25) },
             TestCase { input: "5 * (2 + 10)".to_string(), expected: i_to_o(60) },
+            TestCase { input: "7 % 3".to_string(), expected: i_to_o(1) },
+            TestCase { input: "-7 % 3".to_string(), expected: i_to_o(-1) },
+            TestCase { input: "2 ** 10".to_string(), expected: i_to_o(1024) },
+            TestCase { input: "2 ** 3 ** 2".to_string(), expected: i_to_o(512) },
+            TestCase { input: "-2 ** 2".to_string(), expected: i_to_o(-4) },
+            TestCase { input: "2 ** -1".to_string(), expected: f_to_o(0.5) },
+            TestCase { input: "12 & 10".to_string(), expected: i_to_o(8) },
+            TestCase { input: "12 | 10".to_string(), expected: i_to_o(14) },
+            TestCase { input: "12 ^ 10".to_string(), expected: i_to_o(6) },
+            TestCase { input: "~5".to_string(), expected: i_to_o(-6) },
+            TestCase { input: "1 << 4".to_string(), expected: i_to_o(16) },
+            TestCase { input: "-32 >> 2".to_string(), expected: i_to_o(-8) },
+            TestCase { input: "1 >> 200".to_string(), expected: i_to_o(0) },
+            TestCase { input: "1 | 2 ^ 3 & 4 << 1".to_string(), expected: i_to_o(3) },
+            TestCase { input: "7.5 % 2".to_string(), expected: f_to_o(1.5) },
+        ];
+
+        run_vm_tests(&tests)
+    }
+
+    #[test]
+    fn test_bignum_arithmetic() -> Result<()> {
+        let max = "170141183460469231731687303715884105727";
+        let min = "-170141183460469231731687303715884105728";
+        let tests = vec![
+            TestCase { input: format!("{} + 1", max), expected: big_to_o("170141183460469231731687303715884105728") },
+            TestCase { input: format!("{} * 2", max), expected: big_to_o("340282366920938463463374607431768211454") },
+            TestCase { input: format!("-{} - 2", max), expected: big_to_o("-170141183460469231731687303715884105729") },
+            TestCase { input: format!("let min = {}; -min", min), expected: big_to_o("170141183460469231731687303715884105728") },
+            TestCase { input: format!("let min = {}; min / -1", min), expected: big_to_o("170141183460469231731687303715884105728") },
+            TestCase { input: min.to_string(), expected: i_to_o(i128::MIN) },
+            TestCase { input: format!("({} + 1) - 1", max), expected: i_to_o(i128::MAX) },
+            TestCase { input: "99999999999999999999999999999999999999999 % 7".to_string(), expected: i_to_o(4) },
+            TestCase { input: "2 ** 130 >> 129".to_string(), expected: i_to_o(2) },
+            TestCase { input: "2 ** 128 == 0x1_0000_0000_0000_0000_0000_0000_0000_0000".to_string(), expected: TRUE },
+            TestCase { input: format!("{} + 1 > {}", max, max), expected: TRUE },
+            TestCase { input: "(2 ** 128) * 0.5".to_string(), expected: f_to_o(1.7014118346046923e38) },
+            TestCase { input: "{2 ** 128: 1}[2 ** 129 / 2]".to_string(), expected: i_to_o(1) },
+            TestCase {
+                input: "let h = {2 ** 128: 1}; h[2 ** 128] = 2; h".to_string(),
+                expected: mhash![(big_to_o("340282366920938463463374607431768211456"), i_to_o(2))],
+            },
+            TestCase {
+                input: (1..=40).map(|i| i.to_string()).collect::<Vec<String>>().join(" * "),
+                expected: big_to_o("815915283247897734345611269596115894272000000000"),
+            },
+            TestCase { input: format!("({} * 4) / 4 == {}", max, max), expected: TRUE },
+            TestCase { input: format!("{} * 2 - 1 < 1", max), expected: FALSE },
+            TestCase { input: "1 << 130 == 2 ** 130".to_string(), expected: TRUE },
+            TestCase { input: "(1 << 200) >> 199".to_string(), expected: i_to_o(2) },
+            TestCase { input: "-(1 << 200) >> 300".to_string(), expected: i_to_o(-1) },
+            TestCase { input: "(2 ** 130 + 5) % 8".to_string(), expected: i_to_o(5) },
+            TestCase { input: "~(1 << 130) + (1 << 130)".to_string(), expected: i_to_o(-1) },
+            TestCase { input: "((1 << 130) | 6) & 3".to_string(), expected: i_to_o(2) },
+            TestCase { input: format!("{{{} + 1: \"big\"}}[{} * 2 - {} + 1]", max, max, max), expected: s_to_o("big") },
+            TestCase { input: format!("{{{}: \"small\"}}[({} + 1) - 1]", max, max), expected: s_to_o("small") },
+        ];
+
+        run_vm_tests(&tests)
+    }
+
+    #[test]
+    fn test_float_arithmetic() -> Result<()> {
+        let tests = vec![
+            TestCase { input: "2.75".to_string(), expected: f_to_o(2.75) },
+            TestCase { input: "-2.5".to_string(), expected: f_to_o(-2.5) },
+            TestCase { input: "1.5 + 1.5".to_string(), expected: f_to_o(3.0) },
+            TestCase { input: "1 + 0.5".to_string(), expected: f_to_o(1.5) },
+            TestCase { input: "3 / 2.0".to_string(), expected: f_to_o(1.5) },
+            TestCase { input: "2.0 * 3 - 1".to_string(), expected: f_to_o(5.0) },
+            TestCase { input: "1 < 1.5".to_string(), expected: TRUE },
+            TestCase { input: "2.5 > 3".to_string(), expected: FALSE },
+            TestCase { input: "1 == 1.0".to_string(), expected: TRUE },
+            TestCase { input: "0.5 != 0.5".to_string(), expected: FALSE },
         ];
 
         run_vm_tests(&tests)
@@ -633,6 +1016,20 @@ mod tests {
             TestCase { input: "(1 > 2) == true".to_string(), expected: FALSE },
             TestCase { input: "(1 > 2) == false".to_string(), expected: TRUE },
             TestCase { input: "!(if (false) { 5; })".to_string(), expected: TRUE },
+            TestCase { input: "1 <= 1".to_string(), expected: TRUE },
+            TestCase { input: "2 <= 1".to_string(), expected: FALSE },
+            TestCase { input: "1 >= 2".to_string(), expected: FALSE },
+            TestCase { input: "2 >= 2".to_string(), expected: TRUE },
+            TestCase { input: "1.5 >= 1".to_string(), expected: TRUE },
+            TestCase { input: "true && true".to_string(), expected: TRUE },
+            TestCase { input: "true && false".to_string(), expected: FALSE },
+            TestCase { input: "false || true".to_string(), expected: TRUE },
+            TestCase { input: "false || false".to_string(), expected: FALSE },
+            TestCase { input: "1 && \"\"".to_string(), expected: TRUE },
+            TestCase { input: "1 < 2 && 2 < 3".to_string(), expected: TRUE },
+            TestCase { input: "false && (1 / 0 == 1)".to_string(), expected: FALSE },
+            TestCase { input: "true || (1 / 0 == 1)".to_string(), expected: TRUE },
+            TestCase { input: "let x = 0; (false && x) || x".to_string(), expected: TRUE },
         ];
 
         run_vm_tests(&tests)
@@ -673,6 +1070,7 @@ mod tests {
             TestCase { input: r#""monkey""#.to_string(), expected: s_to_o("monkey") },
             TestCase { input: r#""mon" + "key""#.to_string(), expected: s_to_o("monkey") },
             TestCase { input: r#""mon" + "key" + "banana""#.to_string(), expected: s_to_o("monkeybanana") },
+            TestCase { input: r#""tab\tand \"quotes\"\n""#.to_string(), expected: s_to_o("tab\tand \"quotes\"\n") },
         ];
 
         run_vm_tests(&tests)
@@ -724,16 +1122,56 @@ mod tests {
             TestCase { input: "[[1, 1, 1]][0][0]".to_string(), expected: i_to_o(1) },
             TestCase { input: "[][0]".to_string(), expected: NULL },
             TestCase { input: "[1, 2, 3][99]".to_string(), expected: NULL },
-            TestCase { input: "[1][-1]".to_string(), expected: NULL },
+            TestCase { input: "[1][-1]".to_string(), expected: i_to_o(1) },
+            TestCase { input: "[1, 2, 3][-3]".to_string(), expected: i_to_o(1) },
+            TestCase { input: "[1, 2, 3][-4]".to_string(), expected: NULL },
             TestCase { input: "{1: 1, 2: 2}[1]".to_string(), expected: i_to_o(1) },
             TestCase { input: "{1: 1, 2: 2}[2]".to_string(), expected: i_to_o(2) },
             TestCase { input: "{1: 1}[0]".to_string(), expected: NULL },
-            TestCase { input: "{}[0]".to_string(), expected: NULL }
+            TestCase { input: "{}[0]".to_string(), expected: NULL },
+            TestCase { input: r#""héllo"[1]"#.to_string(), expected: s_to_o("é") },
+            TestCase { input: r#""héllo"[4]"#.to_string(), expected: s_to_o("o") },
+            TestCase { input: r#""héllo"[5]"#.to_string(), expected: NULL },
+            TestCase { input: r#""héllo"[-4]"#.to_string(), expected: s_to_o("é") },
+            TestCase { input: r#""héllo"[-6]"#.to_string(), expected: NULL },
+            TestCase { input: "let a = [1, [2, 3]]; a[-1][-2] += 10; a".to_string(), expected: mvec![i_to_o(1), mvec![i_to_o(12), i_to_o(3)]] },
         ];
 
         run_vm_tests(&tests)
     }
 
+    #[test]
+    fn test_strict_indexing() -> Result<()> {
+        let tests = vec![
+            ("[1, 2, 3][-1]", Ok(i_to_o(3))),
+            (r#""héllo"[-5]"#, Ok(s_to_o("h"))),
+            ("{}[1]", Ok(NULL)),
+            ("[1, 2, 3][3]", Err(RuntimeErrorKind::IndexOutOfRange { index: 3, length: 3 })),
+            ("[1, 2, 3][-4]", Err(RuntimeErrorKind::IndexOutOfRange { index: -4, length: 3 })),
+            (r#""héllo"[-6]"#, Err(RuntimeErrorKind::IndexOutOfRange { index: -6, length: 5 })),
+            ("let f = fn(a) { a[1] }; f([])", Err(RuntimeErrorKind::IndexOutOfRange { index: 1, length: 0 })),
+            ("let a = [[1]]; a[1][0] = 2;", Err(RuntimeErrorKind::IndexOutOfRange { index: 1, length: 1 })),
+            ("let [a, b] = [1, 2]; match ([1]) { [x] => a + b + x }", Ok(i_to_o(4))),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input.as_bytes())?;
+            let mut compiler = Compiler::new();
+            compiler.compile(MNode::Prog(program))?;
+
+            let mut vm = Vm::new(compiler.bytecode());
+            vm.set_strict(true);
+
+            match (vm.run(), expected) {
+                (Ok(_), Ok(expected)) => assert_eq!(Some(&expected), vm.stack_top(), "{}", input),
+                (Err(Error::Runtime(e)), Err(expected)) => assert_eq!(expected, e.kind, "{}", input),
+                (x, _) => panic!("{}: unexpected result: {:?}", input, x),
+            };
+        };
+
+        Ok(())
+    }
+
     #[test]
     fn test_calling_funtions_without_arguments() -> Result<()> {
         let tests = vec![
@@ -939,20 +1377,88 @@ mod tests {
         run_vm_tests(&tests)
     }
 
+    #[test]
+    fn test_default_and_rest_parameters() -> Result<()> {
+        let tests = vec![
+            TestCase { input: "let f = fn(a, b = 10) { a + b }; [f(1), f(1, 2)]".to_string(), expected: mvec![i_to_o(11), i_to_o(3)] },
+            TestCase { input: "let f = fn(a = 1, b = a * 2) { [a, b] }; [f(), f(5), f(5, 6)]".to_string(), expected: mvec![mvec![i_to_o(1), i_to_o(2)], mvec![i_to_o(5), i_to_o(10)], mvec![i_to_o(5), i_to_o(6)]] },
+            TestCase { input: "let f = fn(a, ...rest) { [a, rest] }; [f(1), f(1, 2, 3)]".to_string(), expected: mvec![mvec![i_to_o(1), mvec![]], mvec![i_to_o(1), mvec![i_to_o(2), i_to_o(3)]]] },
+            TestCase { input: "let f = fn(a, b = 2, ...rest) { let c = len(rest); [a, b, c] }; [f(1), f(1, 5, 6, 7)]".to_string(), expected: mvec![mvec![i_to_o(1), i_to_o(2), i_to_o(0)], mvec![i_to_o(1), i_to_o(5), i_to_o(2)]] },
+            TestCase { input: "let f = fn(a = []) { push(a, 1) }; [f(), f()]".to_string(), expected: mvec![mvec![i_to_o(1)], mvec![i_to_o(1)]] },
+            TestCase { input: "let n = 3; let f = fn(a = n) { a }; n = 4; f()".to_string(), expected: i_to_o(4) },
+            TestCase { input: "let g = fn(x) { fn(y = x) { y } }; g(7)()".to_string(), expected: i_to_o(7) },
+            TestCase { input: "let sum = fn(...xs) { let s = 0; for (x in xs) { s += x; }; s }; sum(1, 2, 3, 4)".to_string(), expected: i_to_o(10) },
+            TestCase {
+                input: r#"let greet = fn(name, greeting = "Hello", ...others) {
+                    let line = greeting + ", " + name;
+                    for (other in others) { line += " and " + other; };
+                    line
+                };
+                [greet("Ann"), greet("Bob", "Hi"), greet("Cy", "Hey", "Di", "Ed")]"#.to_string(),
+                expected: mvec![s_to_o("Hello, Ann"), s_to_o("Hi, Bob"), s_to_o("Hey, Cy and Di and Ed")],
+            },
+        ];
+
+        run_vm_tests(&tests)
+    }
+
+    #[test]
+    fn test_named_arguments() -> Result<()> {
+        let tests = vec![
+            TestCase { input: "let f = fn(x) { x }; f(x: 1)".to_string(), expected: i_to_o(1) },
+            TestCase { input: "let f = fn(a, b) { a - b }; [f(b: 1, a: 3), f(3, b: 1)]".to_string(), expected: mvec![i_to_o(2), i_to_o(2)] },
+            TestCase { input: "let f = fn(a, b = 2, c = 3) { [a, b, c] }; [f(1, c: 5), f(a: 1), f(c: 5, a: 1, b: 4)]".to_string(), expected: mvec![mvec![i_to_o(1), i_to_o(2), i_to_o(5)], mvec![i_to_o(1), i_to_o(2), i_to_o(3)], mvec![i_to_o(1), i_to_o(4), i_to_o(5)]] },
+            TestCase { input: "let f = fn(a = 1, b = a * 2) { [a, b] }; [f(b: 5), f(a: 3)]".to_string(), expected: mvec![mvec![i_to_o(1), i_to_o(5)], mvec![i_to_o(3), i_to_o(6)]] },
+            TestCase { input: "let n = 0; let bump = fn() { n += 1; n }; let f = fn(a = 1, b = bump()) { b }; f(b: 7); f(a: 2, b: 7); n".to_string(), expected: i_to_o(0) },
+            TestCase { input: "let f = fn(a, ...rest) { [a, rest] }; f(a: 1)".to_string(), expected: mvec![i_to_o(1), mvec![]] },
+            TestCase { input: "let f = fn(a, b = 2) { [a, b] }; let g = fn() { f(b: 3, a: 1) }; g()".to_string(), expected: mvec![i_to_o(1), i_to_o(3)] },
+        ];
+
+        run_vm_tests(&tests)
+    }
+
     #[test]
     fn test_calling_functions_with_wrong_arguments() -> Result<()> {
         let tests = vec![
             (
                 "fn() { 1; }(1);".to_string(),
-                "wrong number of arguments: want=0, got=1".to_string(),
+                "wrong number of arguments to fn(): want 0, got 1".to_string(),
             ),
             (
                 "fn(a) { a; }();".to_string(),
-                "wrong number of arguments: want=1, got=0".to_string(),
+                "wrong number of arguments to fn(): want 1, got 0".to_string(),
+            ),
+            (
+                "let add = fn(a, b) { a + b; }; add(1);".to_string(),
+                "wrong number of arguments to add(): want 2, got 1".to_string(),
+            ),
+            (
+                "let f = fn(a, b = 1) { a + b; }; f(1, 2, 3);".to_string(),
+                "wrong number of arguments to f(): want 1 to 2, got 3".to_string(),
+            ),
+            (
+                "let f = fn(a, ...rest) { a; }; f();".to_string(),
+                "wrong number of arguments to f(): want at least 1, got 0".to_string(),
+            ),
+            (
+                "let f = fn(a) { a; }; f(b: 1);".to_string(),
+                "unknown argument b to f()".to_string(),
             ),
             (
-                "fn(a, b) { a + b; }(1);".to_string(),
-                "wrong number of arguments: want=2, got=1".to_string(),
+                "let f = fn(a) { a; }; f(1, a: 2);".to_string(),
+                "duplicate argument a to f()".to_string(),
+            ),
+            (
+                "fn(a) { a; }(a: 1, a: 2);".to_string(),
+                "duplicate argument a to fn()".to_string(),
+            ),
+            (
+                "let f = fn(a, b = 1) { a; }; f(b: 2);".to_string(),
+                "missing argument a to f()".to_string(),
+            ),
+            (
+                "len(x: [1]);".to_string(),
+                "unknown argument x to len()".to_string(),
             ),
         ];
 
@@ -972,12 +1478,393 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_runtime_error_kinds() -> Result<()> {
+        let tests = vec![
+            (
+                "1 + true;",
+                RuntimeErrorKind::TypeMismatch { left: "INTEGER", operator: "+".to_string(), right: "BOOLEAN" },
+            ),
+            (
+                "-true;",
+                RuntimeErrorKind::UnknownOperator { operator: "-".to_string(), operand: "BOOLEAN" },
+            ),
+            (
+                "1[0];",
+                RuntimeErrorKind::NotIndexable { left: "INTEGER", index: "INTEGER" },
+            ),
+            (
+                "{}[fn(x) { x }];",
+                RuntimeErrorKind::UnusableHashKey("CLOSURE"),
+            ),
+            (
+                "1();",
+                RuntimeErrorKind::NotCallable("INTEGER"),
+            ),
+            (
+                "1 / 0;",
+                RuntimeErrorKind::DivisionByZero,
+            ),
+            (
+                "170141183460469231731687303715884105727 * 2 / 0;",
+                RuntimeErrorKind::DivisionByZero,
+            ),
+            (
+                "5 % 0;",
+                RuntimeErrorKind::DivisionByZero,
+            ),
+            (
+                "let f = fn(x) { x / (x - x) }; f(5);",
+                RuntimeErrorKind::DivisionByZero,
+            ),
+            (
+                "1 >> -1;",
+                RuntimeErrorKind::InvalidOperand { operator: ">>".to_string(), reason: "negative shift amount" },
+            ),
+            (
+                "2 ** 5000000000;",
+                RuntimeErrorKind::InvalidOperand { operator: "**".to_string(), reason: "exponent too large" },
+            ),
+            (
+                "1.5 & 1;",
+                RuntimeErrorKind::TypeMismatch { left: "FLOAT", operator: "&".to_string(), right: "INTEGER" },
+            ),
+            (
+                "~1.5;",
+                RuntimeErrorKind::UnknownOperator { operator: "~".to_string(), operand: "FLOAT" },
+            ),
+            (
+                "true > false;",
+                RuntimeErrorKind::TypeMismatch { left: "BOOLEAN", operator: ">".to_string(), right: "BOOLEAN" },
+            ),
+            (
+                "let a = [1]; a[1] = 2;",
+                RuntimeErrorKind::IndexOutOfRange { index: 1, length: 1 },
+            ),
+            (
+                "let a = [[1]]; a[0][-2] = 2;",
+                RuntimeErrorKind::IndexOutOfRange { index: -2, length: 1 },
+            ),
+            (
+                "let s = \"ab\"; s[0] = \"c\";",
+                RuntimeErrorKind::NotAssignable("STRING"),
+            ),
+            (
+                "let h = {}; h[[1]] = 2;",
+                RuntimeErrorKind::UnusableHashKey("ARRAY"),
+            ),
+            (
+                "for (x in 5) { x }",
+                RuntimeErrorKind::NotIterable("INTEGER"),
+            ),
+            (
+                "1.5..2",
+                RuntimeErrorKind::TypeMismatch { left: "FLOAT", operator: "..".to_string(), right: "INTEGER" },
+            ),
+            (
+                "0..=170141183460469231731687303715884105727 * 2",
+                RuntimeErrorKind::InvalidOperand { operator: "..=".to_string(), reason: "range bound too large" },
+            ),
+            (
+                "5[1:2]",
+                RuntimeErrorKind::NotSliceable("INTEGER"),
+            ),
+            (
+                "let [a, b] = [1];",
+                RuntimeErrorKind::DestructureLength { expected: 2, actual: 1, rest: false },
+            ),
+            (
+                "let [a, ...b] = [];",
+                RuntimeErrorKind::DestructureLength { expected: 1, actual: 0, rest: true },
+            ),
+            (
+                "let [a] = 5;",
+                RuntimeErrorKind::NotDestructurable { pattern: "ARRAY", value: "INTEGER" },
+            ),
+            (
+                "let {\"k\": v} = {};",
+                RuntimeErrorKind::MissingKey("\"k\"".to_string()),
+            ),
+            (
+                "[1, 2][\"a\":]",
+                RuntimeErrorKind::InvalidSliceIndex("STRING"),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input.as_bytes())?;
+            let mut compiler = Compiler::new();
+            compiler.compile(MNode::Prog(program))?;
+
+            let mut vm = Vm::new(compiler.bytecode());
+
+            match vm.run() {
+                Err(Error::Runtime(e)) => assert_eq!(expected, e.kind),
+                x => panic!("Expected runtime error {:?}, got: {:?}", expected, x),
+            };
+        };
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_runtime_error_spans() -> Result<()> {
+        let tests = vec![
+            ("let a = 1;\na + true", (2, 1), (2, 9)),
+            ("let f = fn(x) {\n  x[0]\n};\nf(1)", (2, 3), (2, 7)),
+            ("let [a, b] = [1];", (1, 1), (1, 17)),
+            ("[1, 2][1:2](3)", (1, 1), (1, 15)),
+            ("1;\nlen(1, 2)", (2, 1), (2, 10)),
+        ];
+
+        for (input, start, end) in tests {
+            let program = parse(input.as_bytes())?;
+            let mut compiler = Compiler::new();
+            compiler.compile(MNode::Prog(program))?;
+
+            let mut vm = Vm::new(compiler.bytecode());
+
+            match vm.run() {
+                Err(Error::Runtime(e)) => {
+                    assert_eq!(start, (e.span.start.line, e.span.start.column), "{}", input);
+                    assert_eq!(end, (e.span.end.line, e.span.end.column), "{}", input);
+                },
+                x => panic!("Expected runtime error for {}, got: {:?}", input, x),
+            };
+        };
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_assignments() -> Result<()> {
+        let tests = vec![
+            TestCase { input: "let a = 5; a = 7; a;".to_string(), expected: i_to_o(7) },
+            TestCase { input: "let a = 5; a = a * 2;".to_string(), expected: i_to_o(10) },
+            TestCase { input: "let a = 1; let b = 2; a = b = 3; a + b;".to_string(), expected: i_to_o(6) },
+            TestCase { input: "let a = 5; a += 2; a -= 1; a *= 3; a;".to_string(), expected: i_to_o(18) },
+            TestCase { input: "let a = 17; a %= 5; a **= 3; a;".to_string(), expected: i_to_o(8) },
+            TestCase { input: "let a = 1; a <<= 4; a |= 1; a ^= 3; a;".to_string(), expected: i_to_o(18) },
+            TestCase { input: "let a = 1; let a = 2; let b = 3; a + b;".to_string(), expected: i_to_o(5) },
+            TestCase { input: "let a = [1, 2]; a[1] = 5;".to_string(), expected: i_to_o(5) },
+            TestCase { input: "let a = [1, 2]; a[1] = 5; a;".to_string(), expected: mvec![i_to_o(1), i_to_o(5)] },
+            TestCase { input: "let a = [1, [2, 3]]; a[1][0] += 10; a;".to_string(), expected: mvec![i_to_o(1), mvec![i_to_o(12), i_to_o(3)]] },
+            TestCase { input: r#"let h = {"k": 1}; h["k"] += 1; h["n"] = 5; h["k"] + h["n"];"#.to_string(), expected: i_to_o(7) },
+            TestCase { input: "let a = [1]; let b = a; b[0] = 2; a[0];".to_string(), expected: i_to_o(1) },
+            TestCase { input: "fn() { let a = [1, 2]; a[0] = 3; a[0] + a[1] }();".to_string(), expected: i_to_o(5) },
+            TestCase { input: "let a = 1; let f = fn() { a = 2; }; f(); a;".to_string(), expected: i_to_o(2) },
+            TestCase {
+                input: "let counter = fn() { let c = 0; fn() { c += 1 } }; let next = counter(); next(); next(); next();".to_string(),
+                expected: i_to_o(3),
+            },
+            TestCase {
+                input: "let f = fn() { let x = 1; let g = fn() { x = 10; }; g(); x }; f();".to_string(),
+                expected: i_to_o(10),
+            },
+            TestCase {
+                input: "
+                    let f = fn() {
+                        let x = 1;
+                        let g = fn() { fn() { x *= 20; } };
+                        g()();
+                        x
+                    };
+                    f();
+                ".to_string(),
+                expected: i_to_o(20),
+            },
+            TestCase {
+                input: "fn(n) { let inc = fn() { n += 1 }; inc(); inc(); n }(5);".to_string(),
+                expected: i_to_o(7),
+            },
+            TestCase { input: "let h = {}; h[\"k\"] = 1; h[\"k\"] <<= 3; h;".to_string(), expected: mhash![(s_to_o("k"), i_to_o(8))] },
+            TestCase { input: "let x = 1; let f = fn() { x = x + 1; x }; f() + x;".to_string(), expected: i_to_o(4) },
+            TestCase { input: "let f = fn() { f = 1; f }; f();".to_string(), expected: i_to_o(1) },
+            TestCase { input: "let f = fn(n) { if (n > 0) { f(n - 1) } else { f = n; 7 } }; f(3) + f;".to_string(), expected: i_to_o(7) },
+        ];
+
+        run_vm_tests(&tests)
+    }
+
+    #[test]
+    fn test_loops() -> Result<()> {
+        let tests = vec![
+            TestCase { input: "let i = 0; while (i < 5) { i += 1; }; i".to_string(), expected: i_to_o(5) },
+            TestCase {
+                input: "let i = 0; let s = 0; while (true) { i += 1; if (i > 4) { break; }; if (i == 2) { continue; }; s += i; }; s".to_string(),
+                expected: i_to_o(8),
+            },
+            TestCase { input: "let s = 0; for (x in [1, 2, 3]) { s += x * x; }; s".to_string(), expected: i_to_o(14) },
+            TestCase {
+                input: r#"let h = {"a": 1, "b": 2}; let s = 0; for (k in h) { s += h[k]; }; s"#.to_string(),
+                expected: i_to_o(3),
+            },
+            TestCase { input: r#"let s = ""; for (c in "héllo") { s = c + s; }; s"#.to_string(), expected: s_to_o("olléh") },
+            TestCase {
+                input: "let s = 0; for (a in [1, 2]) { for (b in [10, 20, 30]) { if (b == 30) { break; }; s += a * b; } }; s".to_string(),
+                expected: i_to_o(90),
+            },
+            TestCase {
+                input: "let f = fn(xs) { for (x in xs) { if (x > 1) { return x; } }; 0 }; f([1, 5, 7]) + f([])".to_string(),
+                expected: i_to_o(5),
+            },
+            TestCase {
+                input: "fn() { let s = 0; for (x in [1, 2, 3]) { let add = fn() { s += x }; add(); }; s }()".to_string(),
+                expected: i_to_o(6),
+            },
+            TestCase { input: "let i = 0; while (i < 100000) { i += 1; }; i".to_string(), expected: i_to_o(100000) },
+            TestCase { input: "let x = 7; for (x in [1, 2]) { x = 3; }; x".to_string(), expected: i_to_o(7) },
+            TestCase { input: "fn() { let x = 7; for (x in [1, 2]) { }; x }()".to_string(), expected: i_to_o(7) },
+            TestCase { input: "let fs = []; for (x in [1, 2]) { fs = push(fs, fn() { x }) }; fs[0]()".to_string(), expected: i_to_o(2) },
+            TestCase { input: "while (false) { 1 }".to_string(), expected: NULL },
+            TestCase { input: "for (x in [1]) { break; }".to_string(), expected: NULL },
+            TestCase {
+                input: r#"let ks = []; for (k in {"b": 1, "a": 2, 3: 0, true: 1, -5: 0, 2 ** 128: 0}) { ks = push(ks, k) }; ks"#.to_string(),
+                expected: mvec![s_to_o("a"), s_to_o("b"), TRUE, i_to_o(-5), i_to_o(3), big_to_o("340282366920938463463374607431768211456")],
+            },
+            TestCase {
+                input: r#"let primes = [];
+                let n = 2;
+                while (len(primes) < 10) {
+                    let prime = true;
+                    for (p in primes) {
+                        if (p * p > n) { break; };
+                        if (n % p == 0) { prime = false; break; };
+                    };
+                    if (prime) { primes = push(primes, n); };
+                    n += 1;
+                };
+                primes"#.to_string(),
+                expected: mvec![i_to_o(2), i_to_o(3), i_to_o(5), i_to_o(7), i_to_o(11), i_to_o(13), i_to_o(17), i_to_o(19), i_to_o(23), i_to_o(29)],
+            },
+        ];
+
+        run_vm_tests(&tests)
+    }
+
+    #[test]
+    fn test_loop_control_in_expressions() -> Result<()> {
+        let tests = vec![
+            TestCase { input: "let s = 0; for (i in 0..5) { s += match (i) { 2 => { continue; }, x => x } }; s".to_string(), expected: i_to_o(8) },
+            TestCase { input: "let n = 0; for (i in 0..3) { n += 1 + (if (true) { continue; } else { 1 }) }; n".to_string(), expected: i_to_o(0) },
+            TestCase {
+                input: "let s = []; for (i in 0..4) { s = push(s, if (i == 2) { break; } else { i }) }; s".to_string(),
+                expected: mvec![i_to_o(0), i_to_o(1)],
+            },
+            TestCase {
+                input: "let i = 0; let s = 0; while (i < 5) { i += 1; s += [i, if (i % 2 == 0) { continue; } else { i }][1] }; s".to_string(),
+                expected: i_to_o(9),
+            },
+            TestCase { input: "let s = 0; for (a in 0..3) { s += len([a, for (b in 0..3) { if (b == a) { break; } }]) }; s".to_string(), expected: i_to_o(6) },
+            TestCase { input: "let f = fn() { for (k in 0..10) { 1 + (if (k == 3) { return k; } else { k }) } }; [f(), f()]".to_string(), expected: mvec![i_to_o(3), i_to_o(3)] },
+            TestCase { input: "let f = fn(x) { for (i in 0..3) { return x } }; let s = 0; for (i in 0..3) { s += f(i) + (if (i == 1) { continue; } else { 10 }) }; s".to_string(), expected: i_to_o(22) },
+        ];
+
+        run_vm_tests(&tests)
+    }
+
+    #[test]
+    fn test_destructuring_let() -> Result<()> {
+        let tests = vec![
+            TestCase { input: "let [a, b, ...rest] = [1, 2, 3, 4]; [a, b, rest]".to_string(), expected: mvec![i_to_o(1), i_to_o(2), mvec![i_to_o(3), i_to_o(4)]] },
+            TestCase { input: "let [a, ...rest] = [1]; rest".to_string(), expected: mvec![] },
+            TestCase { input: "let [x, y] = [1, 2]; let [x, y] = [y, x]; x * 10 + y".to_string(), expected: i_to_o(21) },
+            TestCase { input: r#"let {"name": n, "age": a} = {"name": "Ann", "age": 30}; n"#.to_string(), expected: s_to_o("Ann") },
+            TestCase { input: "let {1: one, true: t} = {1: 10, true: 20}; one + t".to_string(), expected: i_to_o(30) },
+            TestCase { input: "let f = fn(pair) { let [p, q] = pair; p * q }; f([6, 7])".to_string(), expected: i_to_o(42) },
+            TestCase { input: "let f = fn() { let [a] = [5]; fn() { a } }; f()()".to_string(), expected: i_to_o(5) },
+            TestCase {
+                input: r#"let people = [{"name": "Ann", "age": 30}, {"name": "Bob", "age": 25}];
+                let [oldest, ...others] = people;
+                let {"name": name, "age": age} = oldest;
+                let names = [];
+                for (person in others) {
+                    let {"name": other} = person;
+                    names = push(names, other);
+                };
+                [name, age, names]"#.to_string(),
+                expected: mvec![s_to_o("Ann"), i_to_o(30), mvec![s_to_o("Bob")]],
+            },
+        ];
+
+        run_vm_tests(&tests)
+    }
+
+    #[test]
+    fn test_match_expressions() -> Result<()> {
+        let tests = vec![
+            TestCase { input: r#"match (2) { 1 => "one", 2 => "two", _ => "many" }"#.to_string(), expected: s_to_o("two") },
+            TestCase { input: r#"match (5) { 1 => "one", _ => "many" }"#.to_string(), expected: s_to_o("many") },
+            TestCase { input: "match (-1) { 1 => 1, -1 => -10 }".to_string(), expected: i_to_o(-10) },
+            TestCase { input: "match (1.0) { 1 => 1, 1.0 => 2 }".to_string(), expected: i_to_o(1) },
+            TestCase { input: "match (3) { 1 => 1 }".to_string(), expected: NULL },
+            TestCase { input: "match (3) { n => n * 2 }".to_string(), expected: i_to_o(6) },
+            TestCase { input: "match ([1, [2, 3], 4, 5]) { [a, [b, c], ...rest] => [a + b + c, rest] }".to_string(), expected: mvec![i_to_o(6), mvec![i_to_o(4), i_to_o(5)]] },
+            TestCase { input: "match ([1, 2, 3]) { [a, b] => 2, [a, b, c] => 3 }".to_string(), expected: i_to_o(3) },
+            TestCase { input: "match ([1]) { [a, b, ..._] => 2, [..._] => 0 }".to_string(), expected: i_to_o(0) },
+            TestCase { input: "match ([1, 2]) { [2, x] => x, [1, x] => x * 10 }".to_string(), expected: i_to_o(20) },
+            TestCase { input: r#"match ({"k": [1, 2], "j": 3}) { {"k": [x, y]} => x + y }"#.to_string(), expected: i_to_o(3) },
+            TestCase { input: r#"match ({"k": 1}) { {"j": x} => x, {} => 0 }"#.to_string(), expected: i_to_o(0) },
+            TestCase { input: "match ([1]) { {} => 1, [1] => 2 }".to_string(), expected: i_to_o(2) },
+            TestCase { input: "match (7) { n if n > 10 => 1, n if n > 5 => 2, _ => 3 }".to_string(), expected: i_to_o(2) },
+            TestCase { input: "match (1) { 1 => { let x = 2; } }".to_string(), expected: NULL },
+            TestCase { input: "let b = 1; match ([4, 5]) { [a, b] => a }; b".to_string(), expected: i_to_o(1) },
+            TestCase { input: "let x = 1; let r = match (5) { x if x > 10 => 0, _ => 1 }; [r, x]".to_string(), expected: mvec![i_to_o(1), i_to_o(1)] },
+            TestCase { input: "let x = 1; match ([2, [3]]) { [x, [y]] => { x = y; let z = x; } }; [x, z]".to_string(), expected: mvec![i_to_o(1), i_to_o(3)] },
+            TestCase { input: "fn(x) { let f = match (x + 1) { x => fn() { x } }; [f(), x] }(1)".to_string(), expected: mvec![i_to_o(2), i_to_o(1)] },
+            TestCase { input: "match (1.0) { 1 => 10, _ => 0 }".to_string(), expected: i_to_o(10) },
+            TestCase { input: "match (2) { 2.0 => 10, _ => 0 }".to_string(), expected: i_to_o(10) },
+            TestCase { input: "match ([0.5, 3]) { [0.5, 3.0] => 10, _ => 0 }".to_string(), expected: i_to_o(10) },
+            TestCase { input: r#"match ("1") { 1 => 10, _ => 0 }"#.to_string(), expected: i_to_o(0) },
+            TestCase { input: "let f = fn(x) { match (x) { [h, ...t] => h + len(t), _ => 0 } }; f([1, 2, 3]) + f(1)".to_string(), expected: i_to_o(3) },
+            TestCase { input: "let s = 0; for (x in [1, 2, 3, 4]) { match (x % 2) { 0 => { continue; } }; s += x; }; s".to_string(), expected: i_to_o(4) },
+            TestCase {
+                input: r#"let describe = fn(shape) {
+                    match (shape) {
+                        {"kind": "circle", "r": r} => 3 * r * r,
+                        {"kind": "rect", "size": [w, h]} if w == h => "square",
+                        {"kind": "rect", "size": [w, h]} => w * h,
+                        [first, ...others] => describe(first) + len(others),
+                        _ => "unknown",
+                    }
+                };
+                [describe({"kind": "circle", "r": 2}), describe({"kind": "rect", "size": [2, 2]}),
+                 describe({"kind": "rect", "size": [2, 3]}), describe([{"kind": "circle", "r": 1}, 0]), describe(0)]"#.to_string(),
+                expected: mvec![i_to_o(12), s_to_o("square"), i_to_o(6), i_to_o(4), s_to_o("unknown")],
+            },
+        ];
+
+        run_vm_tests(&tests)
+    }
+
+    #[test]
+    fn test_ranges_and_slices() -> Result<()> {
+        let tests = vec![
+            TestCase { input: "let s = 0; for (i in 1..=100) { s += i; }; s".to_string(), expected: i_to_o(5050) },
+            TestCase { input: "let s = 0; for (i in 0..4) { s += i; }; s".to_string(), expected: i_to_o(6) },
+            TestCase { input: "let n = 0; for (i in 5..1) { n += 1; }; n".to_string(), expected: i_to_o(0) },
+            TestCase { input: "let a = [1, 2, 3, 4, 5]; a[1:3]".to_string(), expected: mvec![i_to_o(2), i_to_o(3)] },
+            TestCase { input: "let a = [1, 2, 3, 4, 5]; a[-2:]".to_string(), expected: mvec![i_to_o(4), i_to_o(5)] },
+            TestCase { input: "let a = [1, 2, 3]; a[:-1]".to_string(), expected: mvec![i_to_o(1), i_to_o(2)] },
+            TestCase { input: "[1, 2, 3][4:1]".to_string(), expected: mvec![] },
+            TestCase { input: "[1, 2, 3][-10:10]".to_string(), expected: mvec![i_to_o(1), i_to_o(2), i_to_o(3)] },
+            TestCase { input: "[1, 2, 3, 4, 5][-3..=-1]".to_string(), expected: mvec![i_to_o(3), i_to_o(4), i_to_o(5)] },
+            TestCase { input: "[1, 2, 3, 4, 5][1..3]".to_string(), expected: mvec![i_to_o(2), i_to_o(3)] },
+            TestCase { input: r#""héllo"[1:3]"#.to_string(), expected: s_to_o("él") },
+            TestCase { input: r#""héllo"[2:]"#.to_string(), expected: s_to_o("llo") },
+            TestCase { input: r#""héllo"[:]"#.to_string(), expected: s_to_o("héllo") },
+        ];
+
+        run_vm_tests(&tests)
+    }
+
     #[test]
     fn test_builtin_functions() -> Result<()> {
         let tests = vec![
             TestCase { input: r#"len("")"#.to_string(), expected: i_to_o(0) },
             TestCase { input: r#"len("four")"#.to_string(), expected: i_to_o(4) },
             TestCase { input: r#"len("hello world")"#.to_string(), expected: i_to_o(11) },
+            TestCase { input: r#"len("héllo \u{1F600}")"#.to_string(), expected: i_to_o(7) },
             TestCase { input: r#"len([1, 2, 3])"#.to_string(), expected: i_to_o(3) },
             TestCase { input: r#"len([])"#.to_string(), expected: i_to_o(0) },
             TestCase { input: r#"puts("hello", "world!")"#.to_string(), expected: NULL },
@@ -996,14 +1883,28 @@ mod tests {
     #[test]
     fn test_calling_builtins_with_wrong_arguments() -> Result<()> {
         let tests = vec![
-            TestCase { input: "len(1)".to_string(), expected: merr!("argument to 'len' not supported, got: 1") },
-            TestCase { input: r#"len("one", "two")"#.to_string(), expected: merr!("wrong number of arguments, got: 2, want: 1") },
-            TestCase { input: "first(1)".to_string(), expected: merr!("argument to 'first' not supported, got: 1") },
-            TestCase { input: "last(1)".to_string(), expected: merr!("argument to 'last' not supported, got: 1") },
-            TestCase { input: "push(1, 1)".to_string(), expected: merr!("first argument to 'push' not supported, got: 1") },
+            ("len(1)", "argument to 'len' not supported, got: 1"),
+            (r#"len("one", "two")"#, "wrong number of arguments, got: 2, want: 1"),
+            ("first(1)", "argument to 'first' not supported, got: 1"),
+            ("last(1)", "argument to 'last' not supported, got: 1"),
+            ("push(1, 1)", "first argument to 'push' not supported, got: 1"),
+            ("len(1); 2", "argument to 'len' not supported, got: 1"),
         ];
 
-        run_vm_tests(&tests)
+        for (input, expected) in tests {
+            let program = parse(input.as_bytes())?;
+            let mut compiler = Compiler::new();
+            compiler.compile(MNode::Prog(program))?;
+
+            let mut vm = Vm::new(compiler.bytecode());
+
+            match vm.run() {
+                Err(Error::Runtime(e)) => assert_eq!(RuntimeErrorKind::Uncaught(expected.to_string()), e.kind),
+                x => panic!("Expected runtime error for {}, got: {:?}", input, x),
+            };
+        };
+
+        Ok(())
     }
 
     #[test]
@@ -0,0 +1,273 @@
+use std::fmt::Write;
+
+use crate::{
+    builtin,
+    error::Error,
+    lexer::{span::Span, token_type::KEYWORDS},
+};
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Option<Span>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(message: String, span: Option<Span>) -> Self {
+        Self {
+            message,
+            span,
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn with_note(mut self, note: String) -> Self {
+        self.notes.push(note);
+        self
+    }
+
+    // A parse error expands into one diagnostic per syntax error, everything else is a single
+    // diagnostic.
+    pub fn from_error(err: &Error) -> Vec<Diagnostic> {
+        match err {
+            Error::Parse(errors) => {
+                errors
+                    .iter()
+                    .map(|e| Diagnostic::new(e.to_string(), Some(e.span)))
+                    .collect()
+            },
+            Error::Compile(e) => {
+                let diagnostic = Diagnostic::new(e.to_string(), Some(e.span));
+                match &e.note {
+                    Some(note) => vec![diagnostic.with_note(note.clone())],
+                    None => vec![diagnostic],
+                }
+            },
+            Error::Runtime(e) => {
+                let diagnostic = Diagnostic::new(e.to_string(), Some(e.span));
+                match &e.note {
+                    Some(note) => vec![diagnostic.with_note(note.clone())],
+                    None => vec![diagnostic],
+                }
+            },
+            _ => vec![Diagnostic::new(err.to_string(), err.span())],
+        }
+    }
+
+    // Renders the diagnostic in the style of rustc:
+    //
+    //   error: Expected next token to be RPAREN, got SEMICOLON instead.
+    //    --> script.monkey:1:14
+    //     |
+    //   1 | let x = foo(1;
+    //     |              ^
+    pub fn render(&self, file: &str, source: &str) -> String {
+        let mut out = String::new();
+        writeln!(out, "error: {}", self.message).unwrap();
+
+        // Errors raised outside of the source, like a bad opcode, carry a default span on line 0.
+        if let Some(span) = self.span.filter(|s| s.start.line > 0) {
+            let line_no = span.start.line.to_string();
+            let gutter = " ".repeat(line_no.len());
+            let line = source.lines().nth(span.start.line as usize - 1).unwrap_or("");
+
+            writeln!(out, "{}--> {}:{}", gutter, file, span.start).unwrap();
+            writeln!(out, "{} |", gutter).unwrap();
+            writeln!(out, "{} | {}", line_no, line).unwrap();
+            writeln!(out, "{} | {}", gutter, underline(line, span)).unwrap();
+        }
+
+        for note in &self.notes {
+            writeln!(out, "= note: {}", note).unwrap();
+        }
+
+        out
+    }
+}
+
+pub fn render(err: &Error, file: &str, source: &str) -> String {
+    Diagnostic::from_error(err)
+        .iter()
+        .map(|d| d.render(file, source))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// Tabs are kept so that the carets line up with the source line however the terminal renders them.
+// Spans covering multiple lines are only underlined to the end of the first line.
+fn underline(line: &str, span: Span) -> String {
+    let start = span.start.column as usize - 1;
+    let width = if span.end.line == span.start.line {
+        (span.end.column as usize).saturating_sub(span.start.column as usize)
+    } else {
+        line.chars().count().saturating_sub(start)
+    };
+
+    let mut out = line
+        .chars()
+        .take(start)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect::<String>();
+    out.push_str(&"^".repeat(width.max(1)));
+    out
+}
+
+// Finds the keyword, builtin or one of the given names closest to a misspelled identifier. A
+// candidate must keep at least one character of the name, so `b` doesn't suggest `a`.
+pub fn suggest(name: &str, names: &[String]) -> Option<String> {
+    let length = name.chars().count();
+    let max_distance = if length > 4 { 2 } else { 1 }.min(length.saturating_sub(1));
+
+    KEYWORDS
+        .iter()
+        .map(|(k, _)| *k)
+        .chain(builtin::NAMES)
+        .chain(names.iter().map(String::as_str))
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.to_string())
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut curr = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            curr.push((prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1));
+        }
+        prev = curr;
+    }
+
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Read;
+
+    use crate::{
+        ast::MNode,
+        compiler::compiler::Compiler,
+        error::Result,
+        lexer::lexer::Lexer,
+        parser::parser::Parser,
+        engine::Engine,
+        runner,
+    };
+
+    fn parse_error(input: &str) -> Result<Error> {
+        let lexer = Lexer::new(input.as_bytes().bytes().peekable())?;
+        let mut parser = Parser::new(lexer.peekable())?;
+        parser.parse()?;
+
+        Ok(Error::Parse(parser.errors()))
+    }
+
+    #[test]
+    fn test_render_parser_errors() -> Result<()> {
+        let input = "let x = 1;\nlet y = add(x, 2;\n";
+        let err = parse_error(input)?;
+
+        let expected = r#"error: Expected next token to be RPAREN, got SEMICOLON instead.
+ --> test.monkey:2:17
+  |
+2 | let y = add(x, 2;
+  |                 ^
+"#;
+
+        assert_eq!(expected, render(&err, "test.monkey", input));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_notes() -> Result<()> {
+        let input = "lenn([1, 2]);";
+        let lexer = Lexer::new(input.as_bytes().bytes().peekable())?;
+        let mut parser = Parser::new(lexer.peekable())?;
+        let program = parser.parse()?;
+
+        let err = match Compiler::new().compile(MNode::Prog(program)) {
+            Err(e) => e,
+            Ok(_) => panic!("Expected `lenn` to be undefined."),
+        };
+
+        let expected = r#"error: Identifier not found: lenn
+ --> test.monkey:1:1
+  |
+1 | lenn([1, 2]);
+  | ^^^^
+= note: did you mean `len`?
+"#;
+
+        assert_eq!(expected, render(&err, "test.monkey", input));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_runtime_errors() {
+        let input = "let xs = [1, 2];\nlet n = xs[0] + \"a\";\n";
+        let tests = vec![
+            (Engine::eval(), "type mismatch: 1 + \"a\""),
+            (Engine::vm(), "type mismatch: INTEGER + STRING"),
+        ];
+
+        for (mut engine, message) in tests {
+            let err = match runner::run(input, &[], &mut engine) {
+                Err(e) => e,
+                Ok(x) => panic!("Expected a runtime error, got: {}", x),
+            };
+
+            let expected = format!(r#"error: {}
+ --> test.monkey:2:9
+  |
+2 | let n = xs[0] + "a";
+  |         ^^^^^^^^^^^
+"#, message);
+
+            assert_eq!(expected, render(&err, "test.monkey", input), "{}", engine.name());
+        }
+    }
+
+    #[test]
+    fn test_render_suggestions() {
+        let tests = vec![
+            ("let total = 1;\ntotl + 1;\n", "totl + 1;", "total"),
+            ("let f = fn(count) {\n  cont * 2\n};\nf(1);\n", "  cont * 2", "count"),
+        ];
+        let engines = vec![
+            (Engine::eval(), "identifier not found"),
+            (Engine::vm(), "Identifier not found"),
+        ];
+
+        for (mut engine, message) in engines {
+            for (input, line, expected) in &tests {
+                let err = match runner::run(input, &[], &mut engine) {
+                    Err(e) => e,
+                    Ok(x) => panic!("Expected an undefined identifier, got: {}", x),
+                };
+
+                let rendered = render(&err, "test.monkey", input);
+                assert!(rendered.starts_with(&format!("error: {}", message)), "{}", rendered);
+                assert!(rendered.contains(&format!("2 | {}", line)), "{}", rendered);
+                assert!(rendered.ends_with(&format!("= note: did you mean `{}`?\n", expected)), "{}: {}", engine.name(), rendered);
+            }
+        }
+    }
+
+    #[test]
+    fn test_render_without_span() {
+        let err = Error::from(crate::error::RuntimeErrorKind::StackOverflow);
+
+        assert_eq!("error: Stack overflow\n", render(&err, "test.monkey", ""));
+    }
+}
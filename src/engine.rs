@@ -0,0 +1,243 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    object::{MObject, NULL},
+    interpreter::{
+        evaluator,
+        environment::Environment,
+    },
+    error::Result,
+    compiler::{compiler::Compiler, vm::Vm, symbol_table::SymbolTable},
+    ast::MNode,
+};
+
+#[derive(Debug)]
+struct State {
+    constants: Vec<MObject>,
+    globals: Vec<MObject>,
+    symbols: SymbolTable,
+    strict: bool,
+}
+
+impl State {
+    pub fn new() -> Self {
+        let mut symbols = SymbolTable::new();
+
+        symbols.define_builtin("len".to_string());
+        symbols.define_builtin("first".to_string());
+        symbols.define_builtin("last".to_string());
+        symbols.define_builtin("rest".to_string());
+        symbols.define_builtin("push".to_string());
+        symbols.define_builtin("puts".to_string());
+
+        Self {
+            constants: Vec::new(),
+            globals: Vec::new(),
+            symbols,
+            strict: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Env {
+    Eval(Rc<RefCell<Environment>>),
+    Vm(Box<State>),
+}
+
+pub struct Engine {
+    runner: fn(MNode, &mut Env) -> Result<MObject>,
+    env: Env,
+}
+
+impl Engine {
+    pub fn vm() -> Self {
+        Self {
+            runner: Self::vm_runner,
+            env: Env::Vm(Box::new(State::new())),
+        }
+    }
+
+    pub fn eval() -> Self {
+        Self {
+            runner: Self::eval_runner,
+            env: Env::Eval(Environment::new()),
+        }
+    }
+
+    pub fn run(&mut self, node: MNode) -> Result<MObject> {
+        (self.runner)(node, &mut self.env)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self.env {
+            Env::Eval(_) => "eval",
+            Env::Vm(_) => "vm",
+        }
+    }
+
+    // The globals defined so far with their current values, sorted by name.
+    pub fn bindings(&self) -> Vec<(String, MObject)> {
+        let mut bindings = match &self.env {
+            Env::Eval(environment) => {
+                let environment = environment.borrow();
+                environment
+                    .names()
+                    .into_iter()
+                    .filter_map(|name| environment.get(&name).map(|x| (name, (*x).clone())))
+                    .collect::<Vec<(String, MObject)>>()
+            },
+            Env::Vm(state) => {
+                state.symbols
+                    .names()
+                    .into_iter()
+                    .filter_map(|name| {
+                        let symbol = state.symbols.resolve(&name)?;
+                        state.globals.get(symbol.index).map(|x| (name, x.clone()))
+                    })
+                    .collect::<Vec<(String, MObject)>>()
+            },
+        };
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        bindings
+    }
+
+    // The names of the globals defined so far.
+    pub fn names(&self) -> Vec<String> {
+        match &self.env {
+            Env::Eval(environment) => environment.borrow().names(),
+            Env::Vm(state) => state.symbols.names(),
+        }
+    }
+
+    // A compiler that resolves the globals defined so far when running the VM.
+    pub fn compiler(&self) -> Compiler {
+        match &self.env {
+            Env::Vm(state) => Compiler::with_state(state.symbols.clone(), state.constants.clone()),
+            Env::Eval(_) => Compiler::new(),
+        }
+    }
+
+    // In strict mode indexing an array or string out of range is an error instead of null.
+    pub fn set_strict(&mut self, strict: bool) {
+        match &mut self.env {
+            Env::Eval(environment) => environment.borrow_mut().set_strict(strict),
+            Env::Vm(state) => state.strict = strict,
+        };
+    }
+
+    pub fn is_strict(&self) -> bool {
+        match &self.env {
+            Env::Eval(environment) => environment.borrow().is_strict(),
+            Env::Vm(state) => state.strict,
+        }
+    }
+
+    // Starts a new session on the same engine, still in strict mode if this one was.
+    pub fn reset(&mut self) {
+        let strict = self.is_strict();
+        *self = match self.env {
+            Env::Eval(_) => Engine::eval(),
+            Env::Vm(_) => Engine::vm(),
+        };
+        self.set_strict(strict);
+    }
+
+    // Carries on the session with `other`, keeping strict mode and the globals. Functions only run
+    // on the engine that made them, so the globals holding one are dropped and their names
+    // returned.
+    pub fn switch(&mut self, mut other: Engine) -> Vec<String> {
+        if other.name() == self.name() {
+            return Vec::new();
+        };
+
+        other.set_strict(self.is_strict());
+
+        let mut dropped = Vec::new();
+        for (name, value) in self.bindings() {
+            if is_portable(&value) {
+                other.define(&name, value);
+            } else {
+                dropped.push(name);
+            };
+        }
+
+        *self = other;
+        dropped
+    }
+
+    // Binds a global before any code runs, e.g. the script arguments.
+    pub fn define(&mut self, name: &str, value: MObject) {
+        match &mut self.env {
+            Env::Eval(environment) => {
+                environment.borrow_mut().insert(name.to_string(), value);
+            },
+            Env::Vm(state) => {
+                let symbol = state.symbols.define(name.to_string());
+                if state.globals.len() <= symbol.index {
+                    state.globals.resize(symbol.index + 1, NULL);
+                };
+                state.globals[symbol.index] = value;
+            },
+        };
+    }
+
+    fn vm_runner(node: MNode, env: &mut Env) -> Result<MObject> {
+        let state = match env {
+            Env::Vm(x) => x,
+            _ => unreachable!("wanted: Env::Vm, got: {:?}", env),
+        };
+
+        let mut compiler = Compiler::with_state(state.symbols.clone(), state.constants.clone());
+        compiler.compile(node)?;
+
+        let code = compiler.bytecode();
+
+        let mut vm = Vm::with_state(code.clone(), state.globals.clone());
+        vm.set_strict(state.strict);
+
+        vm.run()?;
+
+        let result = match vm.stack_top() {
+            Some(x) => x.clone(),
+            None => NULL,
+        };
+
+        // The state is only committed once the input has compiled and run without an error, so a
+        // failed input doesn't leave partially defined symbols, constants or globals behind.
+        if let MObject::Err(_) = result {
+            return Ok(result);
+        };
+
+        state.symbols = compiler.symbol_table();
+        state.constants = code.contstants;
+        state.globals = vm.globals();
+
+        Ok(result)
+    }
+
+    fn eval_runner(node: MNode, env: &mut Env) -> Result<MObject> {
+        if let Env::Eval(environment) = env {
+            // Mirror the vm and discard the bindings made by an input that failed.
+            let snapshot = environment.borrow().clone();
+            let result = evaluator::eval(node, environment.to_owned());
+
+            if let Err(_) | Ok(MObject::Err(_)) = result {
+                *environment.borrow_mut() = snapshot;
+            };
+
+            result
+        } else {
+            unreachable!("wanted: Env::Eval, got: {:?}", env)
+        }
+    }
+}
+
+fn is_portable(value: &MObject) -> bool {
+    match value {
+        MObject::Array(array) => array.elements.iter().all(is_portable),
+        MObject::Hash(hash) => hash.pairs.values().all(|pair| is_portable(&pair.key) && is_portable(&pair.value)),
+        MObject::Fn(_) | MObject::CompiledFn(_) | MObject::Closure(_) | MObject::Cell(_) | MObject::Iter(_) | MObject::Macro(_) => false,
+        _ => true,
+    }
+}
@@ -2,45 +2,373 @@ use std::{fmt, io};
 use std::str::Utf8Error;
 use std::string::FromUtf8Error;
 
+use crate::{lexer::{span::Span, token_type::TokenType}, object::MError};
+
 pub type Result<T> = std::result::Result<T, Error>;
 
-#[derive(PartialEq, Debug)]
-pub struct Error {
-    details: String
+#[derive(PartialEq, Clone, Debug)]
+pub enum Error {
+    Lex(LexError),
+    Parse(Vec<ParseError>),
+    Compile(CompileError),
+    // Boxed so that results stay small on the engines' hot paths.
+    Runtime(Box<RuntimeError>),
+    Io(io::ErrorKind, String),
+    Utf8(String),
 }
 
 impl Error {
-    pub fn new(msg: String) -> Error {
-        Self{details: msg}
+    // The location of the error in the source, when one is known.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Error::Lex(x) => Some(x.span),
+            Error::Parse(x) => x.first().map(|e| e.span),
+            Error::Compile(x) => Some(x.span),
+            Error::Runtime(x) => Some(x.span),
+            Error::Io(..) => None,
+            Error::Utf8(_) => None,
+        }
     }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.details)
+        match self {
+            Error::Lex(x) => write!(f, "{}", x),
+            Error::Parse(errors) => {
+                writeln!(f, "The Parser had {} errors:", errors.len())?;
+                for e in errors {
+                    writeln!(f, "{}", e)?;
+                }
+                Ok(())
+            },
+            Error::Compile(x) => write!(f, "{}", x),
+            Error::Runtime(x) => write!(f, "{}", x),
+            Error::Io(_, msg) => write!(f, "{}", msg),
+            Error::Utf8(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<LexError> for Error {
+    fn from(err: LexError) -> Self {
+        Error::Lex(err)
+    }
+}
+
+impl From<CompileError> for Error {
+    fn from(err: CompileError) -> Self {
+        Error::Compile(err)
+    }
+}
+
+impl From<RuntimeError> for Error {
+    fn from(err: RuntimeError) -> Self {
+        Error::Runtime(Box::new(err))
+    }
+}
+
+// The object helpers don't know where in the source they were called from, the engines fill in
+// the span afterwards.
+impl From<RuntimeErrorKind> for Error {
+    fn from(kind: RuntimeErrorKind) -> Self {
+        Error::from(RuntimeError::new(kind, Span::default()))
     }
 }
 
-impl std::error::Error for Error {
-    fn description(&self) -> &str {
-        &self.details
+// A Monkey error value that reached the top level of a script or the REPL.
+impl From<MError> for Error {
+    fn from(err: MError) -> Self {
+        let mut error = RuntimeError::new(RuntimeErrorKind::Uncaught(err.value), err.span);
+        error.note = err.note;
+        Error::from(error)
     }
 }
 
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Self {
-        Error::new(format!("{}", err))
+        Error::Io(err.kind(), format!("{}", err))
     }
 }
 
 impl From<Utf8Error> for Error {
     fn from(err: Utf8Error) -> Self {
-        Error::new(format!("{}", err))
+        Error::Utf8(format!("{}", err))
     }
 }
 
 impl From<FromUtf8Error> for Error {
     fn from(err: FromUtf8Error) -> Self {
-        Error::new(format!("{}", err))
+        Error::Utf8(format!("{}", err))
+    }
+}
+
+// ============================================================================
+// Lexer
+// ============================================================================
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum LexErrorKind {
+    InvalidUtf8,
+    UnterminatedComment,
+    UnterminatedString,
+    InvalidEscape,
+    MissingDigits(u32),
+    InvalidDigit(char, u32),
+    TrailingSeparator,
+    DuplicateSeparator,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub span: Span,
+}
+
+impl LexError {
+    pub fn new(kind: LexErrorKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            LexErrorKind::InvalidUtf8 => write!(f, "invalid UTF-8 in source"),
+            LexErrorKind::UnterminatedComment => write!(f, "unterminated block comment"),
+            LexErrorKind::UnterminatedString => write!(f, "unterminated string literal"),
+            LexErrorKind::InvalidEscape => write!(f, "invalid escape sequence"),
+            LexErrorKind::MissingDigits(radix) => {
+                write!(f, "expected {} digits after {}", radix_name(*radix), radix_prefix(*radix))
+            },
+            LexErrorKind::InvalidDigit(ch, radix) => write!(f, "invalid digit '{}' in {} literal", ch, radix_name(*radix)),
+            LexErrorKind::TrailingSeparator => write!(f, "trailing digit separator"),
+            LexErrorKind::DuplicateSeparator => write!(f, "duplicate digit separator"),
+        }
+    }
+}
+
+fn radix_name(radix: u32) -> &'static str {
+    match radix {
+        16 => "hexadecimal",
+        8 => "octal",
+        2 => "binary",
+        _ => "decimal",
+    }
+}
+
+fn radix_prefix(radix: u32) -> &'static str {
+    match radix {
+        16 => "0x",
+        8 => "0o",
+        _ => "0b",
+    }
+}
+
+// ============================================================================
+// Parser
+// ============================================================================
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum ParseErrorKind {
+    UnexpectedToken { expected: TokenType, actual: TokenType },
+    NoPrefixParseFn(TokenType),
+    InvalidInteger(String),
+    InvalidFloat(String),
+    InvalidAssignmentTarget(String),
+    OutsideLoop(TokenType),
+    InvalidPattern(TokenType),
+    RequiredAfterDefault(String),
+    PositionalAfterNamed,
+    UnexpectedEof,
+    Lex(LexErrorKind),
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub span: Span,
+}
+
+impl ParseError {
+    pub fn new(kind: ParseErrorKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            ParseErrorKind::UnexpectedToken { expected, actual } => {
+                write!(f, "Expected next token to be {:?}, got {:?} instead.", expected, actual)
+            },
+            ParseErrorKind::NoPrefixParseFn(t) => write!(f, "Prefix parse function for {:?} not found.", t),
+            ParseErrorKind::InvalidInteger(lit) => write!(f, "Could not parse {} as integer", lit),
+            ParseErrorKind::InvalidFloat(lit) => write!(f, "Could not parse {} as float", lit),
+            ParseErrorKind::InvalidAssignmentTarget(target) => write!(f, "Cannot assign to {}", target),
+            ParseErrorKind::OutsideLoop(t) => write!(f, "{:?} outside of a loop.", t),
+            ParseErrorKind::InvalidPattern(t) => write!(f, "Expected a pattern, got {:?} instead.", t),
+            ParseErrorKind::RequiredAfterDefault(name) => {
+                write!(f, "Parameter {} without a default value follows one with a default value.", name)
+            },
+            ParseErrorKind::PositionalAfterNamed => write!(f, "Positional argument follows a named argument."),
+            ParseErrorKind::UnexpectedEof => write!(f, "Unexpected EOF."),
+            ParseErrorKind::Lex(kind) => write!(f, "{}", LexError::new(kind.clone(), self.span)),
+        }
+    }
+}
+
+// ============================================================================
+// Compiler
+// ============================================================================
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum CompileErrorKind {
+    UnknownOperator(String),
+    UndefinedIdentifier(String),
+    AssignToBuiltin(String),
+    OutsideLoop(String),
+    Unsupported(String),
+    UndefinedOpcode(u8),
+    UnsupportedOperandWidth(u8),
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct CompileError {
+    pub kind: CompileErrorKind,
+    pub span: Span,
+    pub note: Option<String>,
+}
+
+impl CompileError {
+    pub fn new(kind: CompileErrorKind, span: Span) -> Self {
+        Self { kind, span, note: None }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            CompileErrorKind::UnknownOperator(op) => write!(f, "unknown operator: {}", op),
+            CompileErrorKind::UndefinedIdentifier(name) => write!(f, "Identifier not found: {}", name),
+            CompileErrorKind::AssignToBuiltin(name) => write!(f, "cannot assign to builtin function: {}", name),
+            CompileErrorKind::OutsideLoop(stmt) => write!(f, "{} outside of a loop", stmt),
+            CompileErrorKind::Unsupported(expr) => write!(f, "Compilation not implemented for expression: {}", expr),
+            CompileErrorKind::UndefinedOpcode(op) => write!(f, "opcode {} undefined", op),
+            CompileErrorKind::UnsupportedOperandWidth(w) => write!(f, "No support for operands of width={}", w),
+        }
+    }
+}
+
+// ============================================================================
+// Runtime
+// ============================================================================
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum RuntimeErrorKind {
+    TypeMismatch { left: &'static str, operator: String, right: &'static str },
+    UnknownOperator { operator: String, operand: &'static str },
+    // `max` is None for functions with a rest parameter.
+    WrongArgumentCount { function: Option<String>, min: usize, max: Option<usize>, actual: usize },
+    UnknownArgument { function: Option<String>, name: String },
+    DuplicateArgument { function: Option<String>, name: String },
+    MissingArgument { function: Option<String>, name: String },
+    DivisionByZero,
+    InvalidOperand { operator: String, reason: &'static str },
+    NotCallable(&'static str),
+    NotIndexable { left: &'static str, index: &'static str },
+    IndexOutOfRange { index: i128, length: usize },
+    NotAssignable(&'static str),
+    NotIterable(&'static str),
+    NotSliceable(&'static str),
+    InvalidSliceIndex(&'static str),
+    UnusableHashKey(&'static str),
+    NotDestructurable { pattern: &'static str, value: &'static str },
+    DestructureLength { expected: usize, actual: usize, rest: bool },
+    MissingKey(String),
+    NotAFunction(&'static str),
+    UndefinedGlobal(usize),
+    UndefinedLocal(usize),
+    UndefinedBuiltin(u8),
+    UnimplementedOpcode(String),
+    EmptyBlock,
+    // A Monkey error value that reached the top level of a script.
+    Uncaught(String),
+    StackOverflow,
+    StackUnderflow,
+}
+
+impl fmt::Display for RuntimeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuntimeErrorKind::TypeMismatch { left, operator, right } => write!(f, "type mismatch: {} {} {}", left, operator, right),
+            RuntimeErrorKind::UnknownOperator { operator, operand } => write!(f, "unknown operator: {}{}", operator, operand),
+            RuntimeErrorKind::WrongArgumentCount { function, min, max, actual } => {
+                let function = function.as_deref().unwrap_or("fn");
+                let want = match max {
+                    Some(max) if max == min => format!("{}", min),
+                    Some(max) => format!("{} to {}", min, max),
+                    None => format!("at least {}", min),
+                };
+                write!(f, "wrong number of arguments to {}(): want {}, got {}", function, want, actual)
+            },
+            RuntimeErrorKind::UnknownArgument { function, name } => {
+                write!(f, "unknown argument {} to {}()", name, function.as_deref().unwrap_or("fn"))
+            },
+            RuntimeErrorKind::DuplicateArgument { function, name } => {
+                write!(f, "duplicate argument {} to {}()", name, function.as_deref().unwrap_or("fn"))
+            },
+            RuntimeErrorKind::MissingArgument { function, name } => {
+                write!(f, "missing argument {} to {}()", name, function.as_deref().unwrap_or("fn"))
+            },
+            RuntimeErrorKind::DivisionByZero => write!(f, "division by zero"),
+            RuntimeErrorKind::InvalidOperand { operator, reason } => write!(f, "invalid operand for {}: {}", operator, reason),
+            RuntimeErrorKind::NotCallable(t) => write!(f, "calling non-function: {}", t),
+            RuntimeErrorKind::NotIndexable { left, index } => write!(f, "index operator not supported: {}[{}]", left, index),
+            RuntimeErrorKind::IndexOutOfRange { index, length } => {
+                write!(f, "index out of range: {} for length {}", index, length)
+            },
+            RuntimeErrorKind::NotAssignable(t) => write!(f, "index assignment not supported: {}", t),
+            RuntimeErrorKind::NotIterable(t) => write!(f, "cannot iterate over {}", t),
+            RuntimeErrorKind::NotSliceable(t) => write!(f, "slice operator not supported: {}", t),
+            RuntimeErrorKind::InvalidSliceIndex(t) => write!(f, "slice indices must be integers, got: {}", t),
+            RuntimeErrorKind::UnusableHashKey(t) => write!(f, "unusable as hash key: {}", t),
+            RuntimeErrorKind::NotDestructurable { pattern, value } => write!(f, "cannot destructure {} as {}", value, pattern),
+            RuntimeErrorKind::DestructureLength { expected, actual, rest } => {
+                let at_least = if *rest { "at least " } else { "" };
+                write!(f, "wrong number of elements to destructure: want {}{}, got {}", at_least, expected, actual)
+            },
+            RuntimeErrorKind::MissingKey(key) => write!(f, "key not found: {}", key),
+            RuntimeErrorKind::NotAFunction(t) => write!(f, "Cannot turn {} into a closure.", t),
+            RuntimeErrorKind::UndefinedGlobal(idx) => write!(f, "No global found for index: {}", idx),
+            RuntimeErrorKind::UndefinedLocal(idx) => write!(f, "No local found for index: {}", idx),
+            RuntimeErrorKind::UndefinedBuiltin(idx) => write!(f, "No builtin defined with index={}", idx),
+            RuntimeErrorKind::UnimplementedOpcode(name) => write!(f, "Opcode not implemented: {}", name),
+            RuntimeErrorKind::EmptyBlock => write!(f, "No statements in statement list."),
+            RuntimeErrorKind::Uncaught(msg) => write!(f, "{}", msg),
+            RuntimeErrorKind::StackOverflow => write!(f, "Stack overflow"),
+            RuntimeErrorKind::StackUnderflow => write!(f, "Stack is empty"),
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct RuntimeError {
+    pub kind: RuntimeErrorKind,
+    pub span: Span,
+    pub note: Option<String>,
+}
+
+impl RuntimeError {
+    pub fn new(kind: RuntimeErrorKind, span: Span) -> Self {
+        Self { kind, span, note: None }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)
     }
 }
@@ -2,7 +2,7 @@ use std::{collections::HashMap, cell::RefCell, rc::Rc};
 
 use crate::{
     builtin,
-    object::MObject,
+    object::{MObject, NULL},
 };
 
 #[derive(PartialEq, Eq, Clone, Debug)]
@@ -10,6 +10,12 @@ pub struct Environment {
     store: HashMap<String, Rc<MObject>>,
     outer: Option<Rc<RefCell<Environment>>>,
     builtins: Option<Box<HashMap<String, Rc<MObject>>>>,
+    // Indexing an array or string out of range is an error instead of null. Enclosed environments
+    // inherit it.
+    strict: bool,
+    // A scope that only holds the variable of a `for` loop or the names bound by a match arm, other
+    // names are defined in the scope around it.
+    binding_scope: bool,
 }
 
 impl Environment {
@@ -22,11 +28,39 @@ impl Environment {
         builtins.insert("push".to_string(), Rc::new(builtin::PUSH));
         builtins.insert("puts".to_string(), Rc::new(builtin::PUTS));
 
-        Rc::new(RefCell::new(Self { store: HashMap::new(), outer: None, builtins: Some(Box::new(builtins)) }))
+        Rc::new(RefCell::new(Self { store: HashMap::new(), outer: None, builtins: Some(Box::new(builtins)), strict: false, binding_scope: false }))
     }
 
     pub fn enclose(env: Rc<RefCell<Environment>>) -> Rc<RefCell<Self>> {
-        Rc::new(RefCell::new(Self { store: HashMap::new(), outer: Some(env), builtins: None }))
+        let strict = env.borrow().strict;
+        Rc::new(RefCell::new(Self { store: HashMap::new(), outer: Some(env), builtins: None, strict, binding_scope: false }))
+    }
+
+    // The scope of a `for` loop variable, so it neither leaks out of the loop nor replaces a variable
+    // of the same name around it.
+    pub fn enclose_loop(env: Rc<RefCell<Environment>>, name: String) -> Rc<RefCell<Self>> {
+        Self::enclose_bindings(env, vec![(name, NULL)])
+    }
+
+    // The scope of the names bound by a match arm, for its guard and body.
+    pub fn enclose_bindings(env: Rc<RefCell<Environment>>, bindings: Vec<(String, MObject)>) -> Rc<RefCell<Self>> {
+        let enclosed = Self::enclose(env);
+        {
+            let mut scope = enclosed.borrow_mut();
+            scope.binding_scope = true;
+            for (name, value) in bindings {
+                scope.store.insert(name, Rc::new(value));
+            };
+        }
+        enclosed
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
     }
 
     pub fn get(&self, key: &String) -> Option<Rc<MObject>> {
@@ -46,7 +80,39 @@ impl Environment {
     }
 
     pub fn insert(&mut self, key: String, value: MObject) -> Option<Rc<MObject>> {
-        self.store.insert(key, Rc::new(value))
+        match &self.outer {
+            Some(env) if self.binding_scope && !self.store.contains_key(&key) => env.borrow_mut().insert(key, value),
+            _ => self.store.insert(key, Rc::new(value)),
+        }
+    }
+
+    // Updates the variable in the innermost scope that defines it, returns false when none does.
+    // Closures share the environment they were created in, so they see the update too.
+    pub fn assign(&mut self, key: &String, value: MObject) -> bool {
+        if let Some(x) = self.store.get_mut(key) {
+            *x = Rc::new(value);
+            true
+        } else if let Some(env) = &self.outer {
+            env.borrow_mut().assign(key, value)
+        } else {
+            false
+        }
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.store.keys().cloned().collect()
+    }
+
+    // Every name a lookup from this scope can resolve, including outer scopes and builtins.
+    pub fn visible_names(&self) -> Vec<String> {
+        let mut names = self.names();
+        if let Some(env) = &self.outer {
+            names.extend(env.borrow().visible_names());
+        }
+        if let Some(builtins) = &self.builtins {
+            names.extend(builtins.keys().cloned());
+        }
+        names
     }
 }
 
@@ -1,11 +1,13 @@
 use std::{collections::HashMap, cell::RefCell, rc::Rc};
 
 use crate::{
-    error::{Result, Error},
+    error::{Result, Error, RuntimeErrorKind},
     object::*,
+    arithmetic::*,
     builtin::Builtin,
+    diagnostic,
     interpreter::environment::Environment,
-    ast::*, lexer::{token::Token, token_type::TokenType},
+    ast::*, lexer::{token::Token, token_type::TokenType, span::Span},
 };
 
 #[inline]
@@ -28,10 +30,30 @@ fn new_error(value: String) -> MObject {
     MObject::Err(
         MError {
             value,
+            span: Span::default(),
+            note: None,
         }
     )
 }
 
+// The error carries a note naming the closest visible variable, keyword or builtin, if any.
+fn undefined_identifier(name: &str, env: &Environment) -> MObject {
+    MObject::Err(
+        MError {
+            value: format!("identifier not found: {}", name),
+            span: Span::default(),
+            note: diagnostic::suggest(name, &env.visible_names()).map(|x| format!("did you mean `{}`?", x)),
+        }
+    )
+}
+
+// Errors, and the `return`, `break` or `continue` that ended a block, stop the evaluation of the
+// expression they are an operand of and are passed on to the enclosing statement.
+#[inline]
+fn is_abrupt(o: &MObject) -> bool {
+    matches!(o, MObject::Err(_) | MObject::Return(_) | MObject::Break | MObject::Continue)
+}
+
 fn is_macro_definition(statement: &Stmt) -> bool {
     match statement {
         Stmt::Let(stmt) => {
@@ -95,7 +117,7 @@ fn quote_args(call: FnCall) -> Vec<Quote> {
     let mut args = vec![];
 
     for arg in call.args {
-        args.push(Quote { node: MNode::Expr(arg) });
+        args.push(Quote { node: Box::new(MNode::Expr(arg)) });
     };
 
     args
@@ -132,7 +154,7 @@ pub fn expand_macros(program: Program, env: Rc<RefCell<Environment>>) -> MNode {
                 let evaluated = eval(MNode::Stmt(Stmt::Block(mac.body)), eval_env);
 
                 if let Ok(MObject::Quote(q)) = evaluated {
-                    return q.node;
+                    return *q.node;
                 } else {
                     panic!("we only support return AST-nodes from macros");
                 }
@@ -144,121 +166,293 @@ pub fn expand_macros(program: Program, env: Rc<RefCell<Environment>>) -> MNode {
 
 pub fn eval(node: MNode, env: Rc<RefCell<Environment>>) -> Result<MObject> {
     match node {
-        MNode::Prog(x) => {
-            eval_program(x.stmts, env)
+        MNode::Prog(x) => eval_program(x.stmts, env),
+        MNode::Stmt(stmt) => eval_statement(stmt, env),
+        MNode::Expr(expr) => eval_expression(expr, env),
+    }
+}
+
+// Each kind of node is evaluated in its own function to keep the stack frames small, the tree
+// walker recurses through several of them for every Monkey function call.
+fn eval_statement(stmt: Stmt, env: Rc<RefCell<Environment>>) -> Result<MObject> {
+    match stmt {
+        Stmt::Expression(expr) => eval_expression(expr.expr, env),
+        Stmt::Block(blk_stmt) => eval_block_statements(blk_stmt.stmts, env),
+        Stmt::Let(let_stmt) => {
+            let value = eval_expression(let_stmt.value, env.clone())?;
+            if is_abrupt(&value) { return Ok(value); };
+            let mut env = env.borrow_mut();
+            env.insert(let_stmt.name.value.clone(), value.clone());
+
+            Ok(value)
         },
-        MNode::Stmt(stmt) => {
-            match stmt {
-                Stmt::Expression(expr) => eval(MNode::Expr(expr.expr), env),
-                Stmt::Block(blk_stmt) => eval_block_statements(blk_stmt.stmts, env),
-                Stmt::Let(let_stmt) => {
-                    let value = eval(MNode::Expr(let_stmt.value), env.clone())?;
-                    if let MObject::Err(_) = value { return Ok(value); };
-                    let mut env = env.borrow_mut();
-                    env.insert(let_stmt.name.value.clone(), value.clone());
-
-                    Ok(value)
-                },
-                Stmt::Return(ret) => {
-                    let val = eval(MNode::Expr(ret.retval), env)?;
-                    if let MObject::Err(_) = val { return Ok(val); };
+        Stmt::Destructure(stmt) => {
+            let span = stmt.span();
+            locate_error(eval_destructure_statement(stmt, env), span)
+        },
+        Stmt::Return(ret) => {
+            let val = eval_expression(ret.retval, env)?;
+            if is_abrupt(&val) { return Ok(val); };
 
-                    Ok(MObject::Return(ReturnValue { value: Box::new(val) }))
-                },
-            }
+            Ok(MObject::Return(ReturnValue { value: Box::new(val) }))
         },
-        MNode::Expr(expr) => {
-            match expr {
-                Expr::Int(i) => Ok(MObject::Int(Integer { value: i.value })),
-                Expr::Bool(b) => Ok(native_bool_to_boolean(b.value)),
-                Expr::Pre(prefix) => {
-                    let right = eval(MNode::Expr(*prefix.right), env)?;
-                    if let MObject::Err(_) = right { return Ok(right); };
-
-                    eval_prefix_expression(prefix.operator, right)
-                },
-                Expr::In(infix) => {
-                    let left = eval(MNode::Expr(*infix.left), env.clone())?;
-                    if let MObject::Err(_) = left { return Ok(left); };
+        Stmt::Break(_) => Ok(MObject::Break),
+        Stmt::Continue(_) => Ok(MObject::Continue),
+    }
+}
 
-                    let right = eval(MNode::Expr(*infix.right), env)?;
-                    if let MObject::Err(_) = right { return Ok(right); };
+fn eval_expression(expr: Expr, env: Rc<RefCell<Environment>>) -> Result<MObject> {
+    let span = expr.span();
+    let result = match expr {
+        Expr::Int(i) => Ok(MObject::Int(Integer { value: i.value })),
+        Expr::BigInt(x) => Ok(MObject::BigInt(BigInteger { value: x.value.clone() })),
+        Expr::Float(x) => Ok(MObject::Float(Float { value: x.value })),
+        Expr::Bool(b) => Ok(native_bool_to_boolean(b.value)),
+        Expr::Pre(prefix) => {
+            let right = eval_expression(*prefix.right, env)?;
+            if is_abrupt(&right) { return Ok(right); };
+
+            eval_prefix_expression(prefix.operator, right)
+        },
+        Expr::In(infix) if infix.operator == "&&" || infix.operator == "||" => {
+            eval_logical_expression(infix, env)
+        },
+        Expr::In(infix) => {
+            let left = eval_expression(*infix.left, env.clone())?;
+            if is_abrupt(&left) { return Ok(left); };
 
-                    eval_infix_expression(left, infix.operator, right)
-                },
-                Expr::If(if_expr) => eval_if_expression(if_expr, env),
-                Expr::Ident(ident) => eval_identifier_expression(ident, env),
-                Expr::Fn(func) => {
-                    Ok(
-                        MObject::Fn(
-                            Function {
-                                params: func.params,
-                                body: func.body,
-                                env: env.clone(),
-                            }
-                        )
-                    )
-                },
-                Expr::Call(func_call) => {
-                    if func_call.function.token_literal() == "quote" {
-                        return quote(func_call.args.get(0), env);
-                    };
+            let right = eval_expression(*infix.right, env)?;
+            if is_abrupt(&right) { return Ok(right); };
 
-                    let function = eval(MNode::Expr(*func_call.function), env.clone())?;
-                    if let MObject::Err(_) = function { return Ok(function); };
+            eval_infix_expression(left, infix.operator, right)
+        },
+        Expr::If(if_expr) => eval_if_expression(if_expr, env),
+        Expr::While(while_loop) => eval_while_expression(while_loop, env),
+        Expr::For(for_loop) => eval_for_expression(for_loop, env),
+        Expr::Match(match_expr) => eval_match_expression(match_expr, env),
+        Expr::Ident(ident) => eval_identifier_expression(ident, env),
+        Expr::Fn(func) => {
+            Ok(
+                MObject::Fn(
+                    Function {
+                        name: func.name,
+                        params: func.params,
+                        rest: func.rest,
+                        body: func.body,
+                        env: env.clone(),
+                    }
+                )
+            )
+        },
+        Expr::Call(func_call) => eval_call_expression(func_call, env),
+        Expr::Str(s) => Ok(MObject::Str(MString { value: s.value })),
+        Expr::Array(a) => eval_array_literal(a, env),
+        Expr::Index(i) => {
+            let left = eval_expression(*i.left, env.clone())?;
+            if is_abrupt(&left) { return Ok(left); };
 
-                    let mut args = eval_expressions(func_call.args, env)?;
+            let index = eval_expression(*i.index, env.clone())?;
+            if is_abrupt(&index) { return Ok(index); };
 
-                    if args.len() == 1 {
-                        if let Some(value) = args.get(0) {
-                            if let MObject::Err(_) = value {
-                                return Ok(value.clone());
-                            };
-                        };
-                    };
+            eval_index_expression(left, index, env.borrow().is_strict())
+        },
+        Expr::Slice(slice) => eval_slice_expression(slice, env),
+        Expr::Range(range) => eval_range_expression(range, env),
+        Expr::Hash(h) => {
+            eval_hash_literal_expression(h, env)
+        },
+        Expr::Assign(assign) => eval_assignment_expression(assign, env),
+        Expr::Macro(m) => {
+            Ok(new_error(format!("Macro not expanded: {}", m)))
+        }
+    };
 
-                    apply_function(function, &mut args)
-                },
-                Expr::Str(s) => Ok(MObject::Str(MString { value: s.value })),
-                Expr::Array(a) => {
-                    let elements = eval_expressions(a.elements, env)?;
-
-                    if elements.len() == 1 {
-                        if let Some(value) = elements.get(0) {
-                            if let MObject::Err(_) = value {
-                                return Ok(value.clone());
-                            };
-                        };
-                    };
+    locate_error(result, span)
+}
 
-                    Ok(
-                        MObject::Array(
-                            MArray {
-                                elements,
-                            }
-                        )
-                    )
-                },
-                Expr::Index(i) => {
-                    let left = eval(MNode::Expr(*i.left), env.clone())?;
-                    if let MObject::Err(_) = left { return Ok(left); };
+// Errors are located at the innermost expression that raised them, the enclosing expressions
+// pass them on untouched.
+fn locate_error(result: Result<MObject>, span: Span) -> Result<MObject> {
+    match result {
+        Ok(MObject::Err(mut e)) if e.span.start.line == 0 => {
+            e.span = span;
+            Ok(MObject::Err(e))
+        },
+        x => x,
+    }
+}
 
-                    let index = eval(MNode::Expr(*i.index), env)?;
-                    if let MObject::Err(_) = index { return Ok(index); };
+// The right side is only evaluated when the left one doesn't decide the result.
+fn eval_logical_expression(infix: Infix, env: Rc<RefCell<Environment>>) -> Result<MObject> {
+    let left = eval_expression(*infix.left, env.clone())?;
+    if is_abrupt(&left) { return Ok(left); };
 
-                    eval_index_expression(left, index)
-                },
-                Expr::Hash(h) => {
-                    eval_hash_literal_expression(h, env)
-                },
-                Expr::Macro(m) => {
-                    Ok(new_error(format!("Macro not expanded: {}", m)))
-                }
-            }
+    match (infix.operator.as_str(), is_truthy(left)) {
+        ("&&", false) => return Ok(FALSE),
+        ("||", true) => return Ok(TRUE),
+        _ => {},
+    };
+
+    let right = eval_expression(*infix.right, env)?;
+    if is_abrupt(&right) { return Ok(right); };
+
+    Ok(native_bool_to_boolean(is_truthy(right)))
+}
+
+fn eval_destructure_statement(stmt: DestructureStatement, env: Rc<RefCell<Environment>>) -> Result<MObject> {
+    let value = eval_expression(stmt.value, env.clone())?;
+    if is_abrupt(&value) { return Ok(value); };
+
+    let values = match stmt.pattern.as_ref() {
+        Pattern::Array(pattern) => value.destructure_array(pattern.names.len(), pattern.rest.is_some()),
+        Pattern::Hash(pattern) => {
+            let mut keys = Vec::new();
+            for (key, _) in &pattern.pairs {
+                let key = eval_expression(key.clone(), env.clone())?;
+                if is_abrupt(&key) { return Ok(key); };
+                keys.push(key);
+            };
+            value.destructure_hash(&keys)
         },
+    };
+
+    let values = match values {
+        Ok(x) => x,
+        Err(e) => return Ok(new_error(e.to_string())),
+    };
+
+    let mut env = env.borrow_mut();
+    for (name, x) in stmt.pattern.names().into_iter().zip(values) {
+        env.insert(name.value.clone(), x);
+    };
+
+    Ok(value)
+}
+
+// Evaluates the variable, then the indexes of the target, then the value. Assigning to an element
+// stores an updated copy of the whole array or hash back in the variable.
+fn eval_assignment_expression(assign: Assignment, env: Rc<RefCell<Environment>>) -> Result<MObject> {
+    let (name, indexes) = match assign.target.assignment_path() {
+        Some((name, indexes)) => (name.value.clone(), indexes.into_iter().cloned().collect::<Vec<Expr>>()),
+        None => return Ok(new_error(format!("cannot assign to {}", assign.target))),
+    };
+    let operator = assign.infix_operator().map(ToString::to_string);
+
+    let current = match env.borrow().get(&name) {
+        Some(x) => x.as_ref().clone(),
+        None => return Ok(undefined_identifier(&name, &env.borrow())),
+    };
+
+    let mut keys = Vec::new();
+    for index in indexes {
+        let key = eval_expression(index, env.clone())?;
+        if is_abrupt(&key) { return Ok(key); };
+        keys.push(key);
+    };
+
+    let mut value = eval_expression(*assign.value, env.clone())?;
+    if is_abrupt(&value) { return Ok(value); };
+
+    let strict = env.borrow().is_strict();
+    if let Some(op) = operator {
+        let mut old = current.clone();
+        for key in &keys {
+            old = eval_index_expression(old, key.clone(), strict)?;
+            if let MObject::Err(_) = old { return Ok(old); };
+        };
+
+        value = eval_infix_expression(old, op, value)?;
+        if let MObject::Err(_) = value { return Ok(value); };
+    };
+
+    let updated = eval_index_assignment(current, &keys, value.clone(), strict)?;
+    if let MObject::Err(_) = updated { return Ok(updated); };
+
+    if !env.borrow_mut().assign(&name, updated) {
+        return Ok(new_error(format!("cannot assign to builtin function: {}", name)));
+    };
+
+    Ok(value)
+}
+
+fn eval_index_assignment(container: MObject, keys: &[MObject], value: MObject, strict: bool) -> Result<MObject> {
+    let (key, rest) = match keys.split_first() {
+        Some(x) => x,
+        None => return Ok(value),
+    };
+
+    let value = if rest.is_empty() {
+        value
+    } else {
+        let element = eval_index_expression(container.clone(), key.clone(), strict)?;
+        if let MObject::Err(_) = element { return Ok(element); };
+
+        let updated = eval_index_assignment(element, rest, value, strict)?;
+        if let MObject::Err(_) = updated { return Ok(updated); };
+        updated
+    };
+
+    // Like the other evaluator errors, these show the values involved rather than their types.
+    if !matches!(container, MObject::Array(_) | MObject::Hash(_)) {
+        return Ok(new_error(format!("index assignment not supported: {}", container)));
+    };
+
+    match container.set_index(key.clone(), value) {
+        Ok(x) => Ok(x),
+        Err(RuntimeErrorKind::UnusableHashKey(_)) => Ok(new_error(format!("unusable as hash key: {}", key))),
+        Err(RuntimeErrorKind::NotIndexable { .. }) => Ok(new_error(format!("index operator not supported: {}", key))),
+        Err(e) => Ok(new_error(e.to_string())),
     }
 }
 
+fn eval_call_expression(func_call: FnCall, env: Rc<RefCell<Environment>>) -> Result<MObject> {
+    if func_call.function.token_literal() == "quote" {
+        return quote(func_call.args.get(0), env);
+    };
+
+    let function = eval_expression(*func_call.function, env.clone())?;
+    if is_abrupt(&function) { return Ok(function); };
+
+    let mut args = eval_expressions(func_call.args, env.clone())?;
+
+    if args.len() == 1 {
+        if let Some(value) = args.get(0) {
+            if is_abrupt(value) {
+                return Ok(value.clone());
+            };
+        };
+    };
+
+    let mut named = Vec::new();
+    for arg in func_call.named {
+        let value = eval_expression(arg.value, env.clone())?;
+        if is_abrupt(&value) { return Ok(value); };
+        named.push((arg.name.value, value));
+    };
+
+    apply_function(function, &mut args, named)
+}
+
+fn eval_array_literal(a: ArrayLiteral, env: Rc<RefCell<Environment>>) -> Result<MObject> {
+    let elements = eval_expressions(a.elements, env)?;
+
+    if elements.len() == 1 {
+        if let Some(value) = elements.get(0) {
+            if is_abrupt(value) {
+                return Ok(value.clone());
+            };
+        };
+    };
+
+    Ok(
+        MObject::Array(
+            MArray {
+                elements,
+            }
+        )
+    )
+}
+
 fn eval_program(stmts: Vec<Stmt>, env: Rc<RefCell<Environment>>) -> Result<MObject> {
     let mut result = if let Some(stmt) = stmts.get(0) {
         eval(MNode::Stmt(stmt.clone()), env.clone())?
@@ -290,9 +484,9 @@ fn eval_block_statements(stmts: Vec<Stmt>, env: Rc<RefCell<Environment>>) -> Res
     let mut result = if let Some(stmt) = stmts.get(0) {
         eval(MNode::Stmt(stmt.clone()), env.clone())?
     } else {
-        return Err(Error::new("No statements in statement list.".to_string()))
+        return Err(Error::from(RuntimeErrorKind::EmptyBlock))
     };
-    if let MObject::Return(_) = result {
+    if let MObject::Return(_) | MObject::Break | MObject::Continue = result {
         return Ok(result);
     } else if let MObject::Err(_) = result {
         return Ok(result);
@@ -302,7 +496,7 @@ fn eval_block_statements(stmts: Vec<Stmt>, env: Rc<RefCell<Environment>>) -> Res
         // TODO: consider taking ownership and removing the stmts from the Vec
         result = eval(MNode::Stmt(stmt.clone()), env.clone())?;
 
-        if let MObject::Return(_) = result {
+        if let MObject::Return(_) | MObject::Break | MObject::Continue = result {
             return Ok(result);
         } else if let MObject::Err(_) = result {
             return Ok(result);
@@ -317,7 +511,7 @@ fn eval_expressions(exprs: Vec<Expr>, env: Rc<RefCell<Environment>>) -> Result<V
 
     for expr in exprs {
         let obj = eval(MNode::Expr(expr), env.clone())?;
-        if let MObject::Err(_) = obj { return Ok(vec![obj]); };
+        if is_abrupt(&obj) { return Ok(vec![obj]); };
 
         results.push(obj);
     }
@@ -325,15 +519,35 @@ fn eval_expressions(exprs: Vec<Expr>, env: Rc<RefCell<Environment>>) -> Result<V
     Ok(results)
 }
 
-fn apply_function(obj: MObject, args: &mut Vec<MObject>) -> Result<MObject> {
+fn apply_function(obj: MObject, args: &mut Vec<MObject>, named: Vec<(String, MObject)>) -> Result<MObject> {
     if let MObject::Fn(f) = obj {
-        let Function { params, body, env } = f;
-
-        let extended_env = match extend_function_env(params, args, env) {
+        let bound = if named.is_empty() {
+            f.check_arity(args.len()).map(|_| {
+                let extra = args.split_off(args.len().min(f.params.len()));
+                let mut values = args.drain(..).map(Some).collect::<Vec<Option<MObject>>>();
+                values.resize(f.params.len(), None);
+                (values, extra)
+            })
+        } else {
+            f.bind_arguments(std::mem::take(args), named)
+        };
+        let (values, extra) = match bound {
             Ok(x) => x,
             Err(e) => return Ok(new_error(format!("{}", e))),
         };
 
+        let Function { params, rest, body, env, .. } = f;
+        let (extended_env, missing) = extend_function_env(params, rest, values, extra, env);
+
+        // The default values of the parameters that were left out can use the other parameters.
+        for param in missing {
+            if let Some(default) = param.default {
+                let value = eval_expression(default, extended_env.clone())?;
+                if is_abrupt(&value) { return Ok(value); };
+                extended_env.borrow_mut().insert(param.name.value, value);
+            };
+        };
+
         let evaluated = eval(MNode::Stmt(Stmt::Block(body)), extended_env)?;
 
         if let MObject::Return(retval) = evaluated {
@@ -342,6 +556,10 @@ fn apply_function(obj: MObject, args: &mut Vec<MObject>) -> Result<MObject> {
             Ok(evaluated)
         }
     } else if let MObject::Builtin(b) = obj {
+        if let Some((name, _)) = named.into_iter().next() {
+            return Ok(new_error(format!("{}", RuntimeErrorKind::UnknownArgument { function: Some(b.name().to_string()), name })));
+        };
+
         match b {
             Builtin::Len(len) => len(args),
             Builtin::First(first) => first(args),
@@ -355,28 +573,37 @@ fn apply_function(obj: MObject, args: &mut Vec<MObject>) -> Result<MObject> {
     }
 }
 
-fn extend_function_env(params: Vec<Identifier>, args: &mut Vec<MObject>, env: Rc<RefCell<Environment>>) -> Result<Rc<RefCell<Environment>>> {
+// Returns the parameters that were left out, which are bound to their default values afterwards.
+fn extend_function_env(
+    params: Vec<Parameter>,
+    rest: Option<Box<Identifier>>,
+    values: Vec<Option<MObject>>,
+    extra: Vec<MObject>,
+    env: Rc<RefCell<Environment>>,
+) -> (Rc<RefCell<Environment>>, Vec<Parameter>) {
     let enclosed = Environment::enclose(env);
+    let mut missing = Vec::new();
     {
-        if params.len() != args.len() {
-            return Err(Error::new(format!("Expected {} parameters, got {}.", params.len(), args.len())));
-        };
-        args.reverse();
-
         let mut env = enclosed.borrow_mut();
-        for param in params.iter() {
-            let arg = args.pop().unwrap();
-            env.insert(param.value.clone(), arg);
+        for (param, value) in params.into_iter().zip(values) {
+            match value {
+                Some(x) => { env.insert(param.name.value.clone(), x); },
+                None => missing.push(param),
+            };
         }
+
+        if let Some(rest) = rest {
+            env.insert(rest.value, MObject::Array(MArray { elements: extra }));
+        };
     }
 
-    Ok(enclosed)
+    (enclosed, missing)
 }
 
 fn eval_prefix_expression(op: String, obj: MObject) -> Result<MObject> {
     match op.as_str() {
         "!" => Ok(eval_bang_operator_expression(obj)),
-        "-" => Ok(eval_minus_prefix_operator_expression(obj)),
+        "-" | "~" => Ok(eval_numeric_prefix_operator_expression(&op, obj)),
         _ => Ok(NULL),
     }
 }
@@ -390,20 +617,32 @@ fn eval_bang_operator_expression(obj: MObject) -> MObject {
     }
 }
 
-fn eval_minus_prefix_operator_expression(obj: MObject) -> MObject {
-    if let MObject::Int(m_int) = obj {
-        MObject::Int(Integer { value: -m_int.value })
-    } else {
-        new_error(format!("unknown operator: -{}", obj))
+fn eval_numeric_prefix_operator_expression(op: &str, obj: MObject) -> MObject {
+    match integer_prefix_op(op, &obj) {
+        Some(x) => x,
+        None => new_error(format!("unknown operator: {}{}", op, obj)),
     }
 }
 
 fn eval_infix_expression(left: MObject, op: String, right: MObject) -> Result<MObject> {
-    if let MObject::Int(left_int) = left {
-        if let MObject::Int(right_int) = right {
-            return eval_integer_infix_operator(left_int.value, op, right_int.value);
-        }
-    } else if let MObject::Bool(left_bool) = left {
+    let numeric = if let Some((l, r)) = float_operands(&left, &right) {
+        Some(float_infix_op(l, &op, r).map(Ok))
+    } else if let Some((l, r)) = bigint_operands(&left, &right) {
+        Some(bigint_infix_op(l, &op, r))
+    } else if let (MObject::Int(l), MObject::Int(r)) = (&left, &right) {
+        Some(integer_infix_op(l.value, &op, r.value))
+    } else {
+        None
+    };
+
+    match numeric {
+        Some(Some(Ok(x))) => return Ok(x),
+        Some(Some(Err(e))) => return Ok(new_error(e.to_string())),
+        Some(None) => return Ok(new_error(format!("unknown operator: {} {} {}", left, op, right))),
+        None => {},
+    };
+
+    if let MObject::Bool(left_bool) = left {
         if let MObject::Bool(right_bool) = right {
             return eval_boolean_infix_operator(left_bool.value, op, right_bool.value);
         }
@@ -415,21 +654,6 @@ fn eval_infix_expression(left: MObject, op: String, right: MObject) -> Result<MO
     Ok(new_error(format!("type mismatch: {} {} {}", left, op, right)))
 }
 
-fn eval_integer_infix_operator(left: i128, op: String, right: i128) -> Result<MObject> {
-    let result = match op.as_str() {
-        "+" => MObject::Int(Integer { value: left + right }),
-        "-" => MObject::Int(Integer { value: left - right }),
-        "*" => MObject::Int(Integer { value: left * right }),
-        "/" => MObject::Int(Integer { value: left / right }),
-        "<" => native_bool_to_boolean(left < right),
-        ">" => native_bool_to_boolean(left > right),
-        "==" => native_bool_to_boolean(left == right),
-        "!=" => native_bool_to_boolean(left != right),
-        _ => new_error(format!("unknown operator: {} {} {}", left, op, right)),
-    };
-    Ok(result)
-}
-
 fn eval_boolean_infix_operator(left: bool, op: String, right: bool) -> Result<MObject> {
     let result = match op.as_str() {
         "==" => native_bool_to_boolean(left == right),
@@ -450,7 +674,7 @@ fn eval_string_infix_operator(left: String, op: String, right: String) -> Result
 fn eval_if_expression(if_expr: IfExpression, env: Rc<RefCell<Environment>>) -> Result<MObject> {
     let condition = eval(MNode::Expr(*if_expr.condition), env.clone())?;
 
-    if let MObject::Err(_) = condition {
+    if is_abrupt(&condition) {
         Ok(condition)
     } else if is_truthy(condition) {
         eval(MNode::Stmt(Stmt::Block(if_expr.consequence)), env)
@@ -461,26 +685,189 @@ fn eval_if_expression(if_expr: IfExpression, env: Rc<RefCell<Environment>>) -> R
     }
 }
 
+fn eval_while_expression(while_loop: WhileLoop, env: Rc<RefCell<Environment>>) -> Result<MObject> {
+    loop {
+        let condition = eval_expression(*while_loop.condition.clone(), env.clone())?;
+        if is_abrupt(&condition) { return Ok(condition); };
+        if !is_truthy(condition) { return Ok(NULL); };
+
+        if let Some(result) = eval_loop_body(&while_loop.body, env.clone())? { return Ok(result); };
+    }
+}
+
+fn eval_for_expression(for_loop: ForLoop, env: Rc<RefCell<Environment>>) -> Result<MObject> {
+    let iterable = eval_expression(*for_loop.iterable, env.clone())?;
+    if is_abrupt(&iterable) { return Ok(iterable); };
+
+    let elements = match iterable.iter() {
+        Some(x) => x,
+        None => return Ok(new_error(format!("cannot iterate over {}", iterable.type_name()))),
+    };
+
+    let scope = Environment::enclose_loop(env, for_loop.variable.value.clone());
+    for element in elements {
+        scope.borrow_mut().insert(for_loop.variable.value.clone(), element);
+
+        if let Some(result) = eval_loop_body(&for_loop.body, scope.clone())? { return Ok(result); };
+    }
+
+    Ok(NULL)
+}
+
+// Runs one iteration of a loop, returning the result of the whole loop when it has to stop early: null
+// for a `break`, or the return value or error that ended the body.
+fn eval_loop_body(body: &BlockStatement, env: Rc<RefCell<Environment>>) -> Result<Option<MObject>> {
+    if body.stmts.is_empty() { return Ok(None); };
+
+    match eval_block_statements(body.stmts.clone(), env)? {
+        MObject::Break => Ok(Some(NULL)),
+        result @ (MObject::Return(_) | MObject::Err(_)) => Ok(Some(result)),
+        _ => Ok(None),
+    }
+}
+
+// The names bound by an arm are only set once its whole pattern matches, in a scope of their own
+// for the guard and the body.
+fn eval_match_expression(match_expr: MatchExpression, env: Rc<RefCell<Environment>>) -> Result<MObject> {
+    let subject = eval_expression(*match_expr.subject, env.clone())?;
+    if is_abrupt(&subject) { return Ok(subject); };
+
+    for arm in match_expr.arms {
+        let mut bindings = Vec::new();
+        let matched = match_pattern(&arm.pattern, &subject, env.clone(), &mut bindings)?;
+        if is_abrupt(&matched) { return Ok(matched); };
+        if !is_truthy(matched) { continue; };
+
+        let scope = Environment::enclose_bindings(env.clone(), bindings);
+        if let Some(guard) = arm.guard {
+            let condition = eval_expression(guard, scope.clone())?;
+            if is_abrupt(&condition) { return Ok(condition); };
+            if !is_truthy(condition) { continue; };
+        };
+
+        return eval(MNode::Stmt(Stmt::Block(arm.body)), scope);
+    };
+
+    Ok(NULL)
+}
+
+// Evaluates to true or false depending on whether the value matches the pattern, collecting the
+// names it binds, or to the error raised by a literal or a key of the pattern.
+fn match_pattern(pattern: &MatchPattern, value: &MObject, env: Rc<RefCell<Environment>>, bindings: &mut Vec<(String, MObject)>) -> Result<MObject> {
+    match pattern {
+        MatchPattern::Wildcard(_) => Ok(TRUE),
+        MatchPattern::Binding(name) => {
+            bindings.push((name.value.clone(), value.clone()));
+            Ok(TRUE)
+        },
+        MatchPattern::Literal(literal) => {
+            let literal = eval_expression(literal.clone(), env)?;
+            if is_abrupt(&literal) { return Ok(literal); };
+
+            Ok(native_bool_to_boolean(values_equal(value, &literal)))
+        },
+        MatchPattern::Array(array) => {
+            if !value.matches_array(array.elements.len(), array.rest.is_some()) { return Ok(FALSE); };
+
+            for (i, element) in array.elements.iter().enumerate() {
+                let x = value.element(i as i128, false).unwrap_or_else(|e| new_error(e.to_string()));
+                let matched = match_pattern(element, &x, env.clone(), bindings)?;
+                if matched != TRUE { return Ok(matched); };
+            };
+
+            if let Some(rest) = &array.rest {
+                let start = MObject::Int(Integer { value: array.elements.len() as i128 });
+                let x = value.slice(&start, &NULL).unwrap_or_else(|e| new_error(e.to_string()));
+                return match_pattern(rest, &x, env, bindings);
+            };
+
+            Ok(TRUE)
+        },
+        MatchPattern::Hash(hash) => {
+            let mut keys = Vec::new();
+            for (key, _) in &hash.pairs {
+                let key = eval_expression(key.clone(), env.clone())?;
+                if is_abrupt(&key) { return Ok(key); };
+                keys.push(key);
+            };
+            if !value.matches_hash(&keys) { return Ok(FALSE); };
+
+            for (key, (_, pattern)) in keys.into_iter().zip(&hash.pairs) {
+                let x = eval_index_expression(value.clone(), key, false)?;
+                let matched = match_pattern(pattern, &x, env.clone(), bindings)?;
+                if matched != TRUE { return Ok(matched); };
+            };
+
+            Ok(TRUE)
+        },
+    }
+}
+
 fn eval_identifier_expression(ident: Identifier, env: Rc<RefCell<Environment>>) -> Result<MObject> {
     let env = env.borrow();
     if let Some(v) = env.get(&ident.value) {
         Ok(v.as_ref().clone())
     } else {
-        Ok(new_error(format!("identifier not found: {}", ident.value)))
+        Ok(undefined_identifier(&ident.value, &env))
     }
 }
 
-fn eval_index_expression(left: MObject, index: MObject) -> Result<MObject> {
-    if let MObject::Array(arr) = left {
-        if let MObject::Int(i) = index {
-            Ok(eval_array_index_expression(arr, i.value))
-        } else {
-            Ok(new_error(format!("index operator not supported: {}", index)))
-        }
+fn eval_range_expression(range: RangeExpression, env: Rc<RefCell<Environment>>) -> Result<MObject> {
+    let start = eval_expression(*range.start, env.clone())?;
+    if is_abrupt(&start) { return Ok(start); };
+
+    let end = eval_expression(*range.end, env)?;
+    if is_abrupt(&end) { return Ok(end); };
+
+    // Like the other evaluator errors, a type mismatch shows the values rather than their types.
+    match MObject::range(&start, &end, range.inclusive) {
+        Ok(x) => Ok(x),
+        Err(RuntimeErrorKind::TypeMismatch { operator, .. }) => {
+            Ok(new_error(format!("type mismatch: {} {} {}", start, operator, end)))
+        },
+        Err(e) => Ok(new_error(e.to_string())),
+    }
+}
+
+fn eval_slice_expression(slice: SliceOperation, env: Rc<RefCell<Environment>>) -> Result<MObject> {
+    let left = eval_expression(*slice.left, env.clone())?;
+    if is_abrupt(&left) { return Ok(left); };
+
+    let mut bounds = Vec::new();
+    for bound in [slice.start, slice.end] {
+        let value = match bound {
+            Some(x) => eval_expression(*x, env.clone())?,
+            None => NULL,
+        };
+        if is_abrupt(&value) { return Ok(value); };
+        bounds.push(value);
+    };
+
+    match left.slice(&bounds[0], &bounds[1]) {
+        Ok(x) => Ok(x),
+        Err(RuntimeErrorKind::NotSliceable(_)) => Ok(new_error(format!("slice operator not supported: {}", left))),
+        Err(RuntimeErrorKind::InvalidSliceIndex(_)) => {
+            let bound = bounds.iter().find(|b| !matches!(b, MObject::Null | MObject::Int(_) | MObject::BigInt(_)));
+            Ok(new_error(format!("slice indices must be integers, got: {}", bound.unwrap_or(&NULL))))
+        },
+        Err(e) => Ok(new_error(e.to_string())),
+    }
+}
+
+fn eval_index_expression(left: MObject, index: MObject, strict: bool) -> Result<MObject> {
+    if let MObject::Array(_) | MObject::Str(_) = left {
+        let value = match index {
+            MObject::Int(i) => left.element(i.value, strict),
+            MObject::Range(range) => left.slice_range(&range),
+            _ => return Ok(new_error(format!("index operator not supported: {}", index))),
+        };
+
+        Ok(value.unwrap_or_else(|e| new_error(e.to_string())))
     } else if let MObject::Hash(h) = left {
         let hash_key = match index {
             MObject::Str(x) => HashKey::Str(x),
             MObject::Int(x) => HashKey::Int(x),
+            MObject::BigInt(x) => HashKey::BigInt(x),
             MObject::Bool(x) => HashKey::Bool(x),
             _ => return Ok(new_error(format!("unusable as hash key: {}", index)))
         };
@@ -494,36 +881,23 @@ fn eval_index_expression(left: MObject, index: MObject) -> Result<MObject> {
     }
 }
 
-fn eval_array_index_expression(arr: MArray, index: i128) -> MObject {
-    let idx = index as usize;
-
-    if index < 0 || idx > arr.elements.len() {
-        NULL
-    } else {
-        if let Some(o) = arr.elements.get(idx) {
-            o.clone()
-        } else {
-            NULL
-        }
-    }
-}
-
 fn eval_hash_literal_expression(h: HashLiteral, env: Rc<RefCell<Environment>>) -> Result<MObject> {
     let mut pairs = HashMap::new();
 
     for (k_node, v_node) in h.pairs {
         let key = eval(MNode::Expr(k_node), env.clone())?;
-        if let MObject::Err(_) = key { return Ok(key); };
+        if is_abrupt(&key) { return Ok(key); };
 
         let hash_key = match key.clone() {
             MObject::Str(x) => HashKey::Str(x),
             MObject::Int(x) => HashKey::Int(x),
+            MObject::BigInt(x) => HashKey::BigInt(x),
             MObject::Bool(x) => HashKey::Bool(x),
             _ => return Ok(new_error(format!("unusable as hash key: {}", key))),
         };
 
         let value = eval(MNode::Expr(v_node), env.clone())?;
-        if let MObject::Err(_) = value { return Ok(value); };
+        if is_abrupt(&value) { return Ok(value); };
 
         let hash_value = HashPair { key, value };
 
@@ -549,7 +923,7 @@ fn quote(arg: Option<&Expr>, env: Rc<RefCell<Environment>>) -> Result<MObject> {
     Ok(
         MObject::Quote(
             Quote {
-                node,
+                node: Box::new(node),
             }
         )
     )
@@ -583,16 +957,32 @@ fn convert_obj_to_ast_node(obj: MObject) -> MNode {
         MObject::Int(x) => MNode::Expr(
             Expr::Int(
                 IntegerLiteral {
-                    token: Token { token_type: TokenType::INT, literal: format!("{}", x.value) },
+                    token: Token::new(TokenType::INT, format!("{}", x.value)),
+                    value: x.value,
+                }
+            )
+        ),
+        MObject::BigInt(x) => MNode::Expr(
+            Expr::BigInt(
+                BigIntegerLiteral {
+                    token: Token::new(TokenType::INT, format!("{}", x.value)),
+                    value: x.value,
+                }
+            )
+        ),
+        MObject::Float(x) => MNode::Expr(
+            Expr::Float(
+                FloatLiteral {
+                    token: Token::new(TokenType::FLOAT, format!("{}", x)),
                     value: x.value,
                 }
             )
         ),
         MObject::Bool(x) => {
             let token = if x.value {
-                Token { token_type: TokenType::TRUE, literal: "true".to_string() }
+                Token::new(TokenType::TRUE, "true".to_string())
             } else {
-                Token { token_type: TokenType::FALSE, literal: "false".to_string() }
+                Token::new(TokenType::FALSE, "false".to_string())
             };
             MNode::Expr(
                 Expr::Bool(
@@ -603,11 +993,11 @@ fn convert_obj_to_ast_node(obj: MObject) -> MNode {
                 ),
             )
         },
-        MObject::Quote(q) => q.node,
+        MObject::Quote(q) => *q.node,
         _ => MNode::Expr(
             Expr::Int(
                 IntegerLiteral {
-                    token: Token { token_type: TokenType::INT, literal: format!("{}", 0) },
+                    token: Token::new(TokenType::INT, format!("{}", 0)),
                     value: 0,
                 }
             )
@@ -625,7 +1015,7 @@ fn is_unquote_call(node: &MNode) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{lexer::{lexer::Lexer, token::Token}, parser::parser::Parser};
+    use crate::{lexer::{lexer::Lexer, token::Token}, parser::parser::Parser, test_utils::{s_to_o, f_to_o}};
 
     use std::io::Read;
 
@@ -648,14 +1038,7 @@ mod tests {
             return Ok(());
         }
 
-        let mut msg = format!("The Parser had {} errors:\n", errors.len());
-
-        for e in errors {
-            msg.push_str(&e);
-            msg.push('\n');
-        }
-
-        Err(Error::new(msg))
+        Err(Error::Parse(errors))
     }
 
     fn test_integer_obj(expected: i128, actual: MObject) -> Result<()> {
@@ -663,7 +1046,7 @@ mod tests {
             assert_eq!(expected, m_int.value);
             Ok(())
         } else {
-            Err(Error::new(format!("MObject wasn't an integer, it was {:?}.", actual)))
+            panic!("MObject wasn't an integer, it was {:?}.", actual)
         }
     }
 
@@ -672,7 +1055,7 @@ mod tests {
             assert_eq!(expected, m_bool.value);
             Ok(())
         } else {
-            Err(Error::new(format!("MObject wasn't a boolean, it was {:?}.", actual)))
+            panic!("MObject wasn't a boolean, it was {:?}.", actual)
         }
     }
 
@@ -694,6 +1077,19 @@ mod tests {
             ("3 * 3 * 3 + 10".to_string(), 37),
             ("3 * (3 * 3) + 10".to_string(), 37),
             ("(5 + 10 * 2 + 15 / 3) * 2 + -10".to_string(), 50),
+            ("7 % 3".to_string(), 1),
+            ("-7 % 3".to_string(), -1),
+            ("2 ** 10".to_string(), 1024),
+            ("2 ** 3 ** 2".to_string(), 512),
+            ("-2 ** 2".to_string(), -4),
+            ("12 & 10".to_string(), 8),
+            ("12 | 10".to_string(), 14),
+            ("12 ^ 10".to_string(), 6),
+            ("~5".to_string(), -6),
+            ("1 << 4".to_string(), 16),
+            ("-32 >> 2".to_string(), -8),
+            ("1 >> 200".to_string(), 0),
+            ("1 | 2 ^ 3 & 4 << 1".to_string(), 3),
         ];
 
         for tt in tests {
@@ -704,6 +1100,79 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_eval_bignum_expressions() -> Result<()> {
+        let max = "170141183460469231731687303715884105727";
+        let min = "-170141183460469231731687303715884105728";
+        let tests = vec![
+            (format!("{} + 1", max), "170141183460469231731687303715884105728"),
+            (format!("{} * 2", max), "340282366920938463463374607431768211454"),
+            (format!("-{} - 2", max), "-170141183460469231731687303715884105729"),
+            (format!("let min = {}; -min", min), "170141183460469231731687303715884105728"),
+            (format!("let min = {}; min / -1", min), "170141183460469231731687303715884105728"),
+            (min.to_string(), min),
+            (format!("({} + 1) - 1", max), max),
+            ("99999999999999999999999999999999999999999 % 7".to_string(), "4"),
+            ("2 ** 130 >> 129".to_string(), "2"),
+            ("2 ** 128 == 0x1_0000_0000_0000_0000_0000_0000_0000_0000".to_string(), "true"),
+            (format!("{} + 1 > {}", max, max), "true"),
+            ("(2 ** 128) * 0.5".to_string(), "1.7014118346046923e38"),
+            ("{2 ** 128: 1}[2 ** 129 / 2]".to_string(), "1"),
+            ("let h = {2 ** 128: 1}; h[2 ** 128] = 2; h".to_string(), "{340282366920938463463374607431768211456: 2}"),
+            ("[2 ** 128, -(2 ** 128)]".to_string(), "[340282366920938463463374607431768211456, -340282366920938463463374607431768211456]"),
+            ((1..=40).map(|i| i.to_string()).collect::<Vec<String>>().join(" * "), "815915283247897734345611269596115894272000000000"),
+            (format!("({} * 4) / 4 == {}", max, max), "true"),
+            (format!("{} * 2 - 1 < 1", max), "false"),
+            ("1 << 130 == 2 ** 130".to_string(), "true"),
+            ("(1 << 200) >> 199".to_string(), "2"),
+            ("-(1 << 200) >> 300".to_string(), "-1"),
+            ("(2 ** 130 + 5) % 8".to_string(), "5"),
+            ("~(1 << 130) + (1 << 130)".to_string(), "-1"),
+            ("((1 << 130) | 6) & 3".to_string(), "2"),
+            (format!("{{{} + 1: \"big\"}}[{} * 2 - {} + 1]", max, max, max), r#""big""#),
+            (format!("{{{}: \"small\"}}[({} + 1) - 1]", max, max), r#""small""#),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(expected, test_eval(input.clone())?.to_string(), "{}", input);
+        }
+
+        // Results that fit in an i128 are demoted back to an `Integer`.
+        test_integer_obj(i128::MIN, test_eval(min.to_string())?)?;
+        test_integer_obj(i128::MAX, test_eval(format!("({} + 1) - 1", max))?)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_float_expressions() -> Result<()> {
+        let tests = vec![
+            ("2.75", f_to_o(2.75), "2.75"),
+            ("-2.5", f_to_o(-2.5), "-2.5"),
+            ("1.5 + 1.5", f_to_o(3.0), "3.0"),
+            ("1 + 0.5", f_to_o(1.5), "1.5"),
+            ("3 / 2.0", f_to_o(1.5), "1.5"),
+            ("2.0 * 3 - 1", f_to_o(5.0), "5.0"),
+            ("1e-9", f_to_o(1e-9), "1e-9"),
+            ("1.0 / 0", f_to_o(f64::INFINITY), "inf"),
+            ("7.5 % 2", f_to_o(1.5), "1.5"),
+            ("2 ** 0.5 * 2 ** 0.5", f_to_o(2.0000000000000004), "2.0000000000000004"),
+            ("2 ** -1", f_to_o(0.5), "0.5"),
+            ("1 < 1.5", TRUE, "true"),
+            ("2.5 > 3", FALSE, "false"),
+            ("1 == 1.0", TRUE, "true"),
+            ("0.5 != 0.5", FALSE, "false"),
+        ];
+
+        for (input, expected, display) in tests {
+            let evaluated = test_eval(input.to_string())?;
+            assert_eq!(display, evaluated.to_string());
+            assert_eq!(expected, evaluated);
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_eval_boolean_expressions() -> Result<()> {
         let tests = vec![
@@ -726,6 +1195,20 @@ mod tests {
             ("(1 < 2) == false".to_string(), false),
             ("(1 > 2) == true".to_string(), false),
             ("(1 > 2) == false".to_string(), true),
+            ("1 <= 1".to_string(), true),
+            ("2 <= 1".to_string(), false),
+            ("1 >= 2".to_string(), false),
+            ("2 >= 2".to_string(), true),
+            ("1.5 >= 1".to_string(), true),
+            ("true && true".to_string(), true),
+            ("true && false".to_string(), false),
+            ("false || true".to_string(), true),
+            ("false || false".to_string(), false),
+            ("1 && \"\"".to_string(), true),
+            ("1 < 2 && 2 < 3".to_string(), true),
+            ("false && (1 / 0 == 1)".to_string(), false),
+            ("true || (1 / 0 == 1)".to_string(), true),
+            ("false && undefined".to_string(), false),
         ];
 
         for tt in tests {
@@ -838,6 +1321,31 @@ mod tests {
             ("{ [2]: true }".to_string(), "unusable as hash key: [2]".to_string()),
             ("{ true: true }[[2]]".to_string(), "unusable as hash key: [2]".to_string()),
             ("quote()".to_string(), "argument required for quote, got: null".to_string()),
+            ("1 / 0".to_string(), "division by zero".to_string()),
+            ("170141183460469231731687303715884105727 * 2 / 0".to_string(), "division by zero".to_string()),
+            ("5 % 0".to_string(), "division by zero".to_string()),
+            ("let f = fn(x) { x / (x - x) }; f(5);".to_string(), "division by zero".to_string()),
+            ("1 << -1".to_string(), "invalid operand for <<: negative shift amount".to_string()),
+            ("1.5 & 1".to_string(), "unknown operator: 1.5 & 1".to_string()),
+            ("~1.5".to_string(), "unknown operator: ~1.5".to_string()),
+            ("true ** false".to_string(), "unknown operator: true ** false".to_string()),
+            ("x = 1".to_string(), "identifier not found: x".to_string()),
+            ("len = 1".to_string(), "cannot assign to builtin function: len".to_string()),
+            ("let a = [1]; a[1] = 2".to_string(), "index out of range: 1 for length 1".to_string()),
+            ("let s = \"ab\"; s[0] = \"c\"".to_string(), "index assignment not supported: \"ab\"".to_string()),
+            ("let h = {}; h[[1]] = 2".to_string(), "unusable as hash key: [1]".to_string()),
+            ("let a = [1]; a[\"x\"] = 2".to_string(), "index operator not supported: \"x\"".to_string()),
+            ("let x = true; x += 1".to_string(), "type mismatch: true + 1".to_string()),
+            ("for (x in 5) { x }".to_string(), "cannot iterate over INTEGER".to_string()),
+            ("1.5..2".to_string(), "type mismatch: 1.5 .. 2".to_string()),
+            ("1..=true".to_string(), "type mismatch: 1 ..= true".to_string()),
+            ("5[1:2]".to_string(), "slice operator not supported: 5".to_string()),
+            ("let [a, b] = [1];".to_string(), "wrong number of elements to destructure: want 2, got 1".to_string()),
+            ("let [a, ...b] = [];".to_string(), "wrong number of elements to destructure: want at least 1, got 0".to_string()),
+            ("let {\"k\": v} = [1];".to_string(), "cannot destructure ARRAY as HASH".to_string()),
+            ("let {\"k\": v} = {};".to_string(), "key not found: \"k\"".to_string()),
+            ("[1, 2][\"a\":]".to_string(), "slice indices must be integers, got: \"a\"".to_string()),
+            ("let i = 0; while (i < 3) { i += 1; if (i == 2) { foo; } }; i".to_string(), "identifier not found: foo".to_string()),
         ];
 
         for tt in tests {
@@ -853,6 +1361,49 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_error_spans() -> Result<()> {
+        let tests = vec![
+            ("let a = 1;\na + true", (2, 1), (2, 9)),
+            ("let f = fn(x) {\n  x[0]\n};\nf(1)", (2, 3), (2, 7)),
+            ("let [a, b] = [1];", (1, 1), (1, 17)),
+            ("[1, 2][1:2](3)", (1, 1), (1, 15)),
+            ("1;\nlen(1, 2)", (2, 1), (2, 10)),
+        ];
+
+        for (input, start, end) in tests {
+            match test_eval(input.to_string())? {
+                MObject::Err(e) => {
+                    assert_eq!(start, (e.span.start.line, e.span.start.column), "{}", input);
+                    assert_eq!(end, (e.span.end.line, e.span.end.column), "{}", input);
+                },
+                x => panic!("Expected error for {}, got: {}", input, x),
+            };
+        };
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_undefined_identifier_suggestions() -> Result<()> {
+        let tests = vec![
+            ("let total = 1; totl", Some("did you mean `total`?")),
+            ("let f = fn(count) { fn() { cout } }; f(1)()", Some("did you mean `count`?")),
+            ("lenn([1])", Some("did you mean `len`?")),
+            ("let total = 1; totl = 2", Some("did you mean `total`?")),
+            ("xyz", None),
+        ];
+
+        for (input, expected) in tests {
+            match test_eval(input.to_string())? {
+                MObject::Err(e) => assert_eq!(expected, e.note.as_deref(), "{}", input),
+                x => panic!("Expected error for {}, got: {}", input, x),
+            };
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_let_statements() -> Result<()> {
         let tests = vec![
@@ -860,6 +1411,63 @@ mod tests {
             ("let a = 5 * 5; a;".to_string(), 25),
             ("let a = 5; let b = a; b;".to_string(), 5),
             ("let a = 5; let b = a; let c = a + b + 5; c;".to_string(), 15),
+            ("let a1 = 5; let a2 = a1 * 2; a2;".to_string(), 10),
+            ("let π = 3; let 变量 = π + 1; 变量;".to_string(), 4),
+        ];
+
+        for tt in tests {
+            let evaluated = test_eval(tt.0)?;
+            test_integer_obj(tt.1, evaluated)?;
+        };
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_assignment_expressions() -> Result<()> {
+        let tests = vec![
+            ("let a = 5; a = 7; a;".to_string(), 7),
+            ("let a = 5; a = a * 2;".to_string(), 10),
+            ("let a = 1; let b = 2; a = b = 3; a + b;".to_string(), 6),
+            ("let a = 5; a += 2; a -= 1; a *= 3; a;".to_string(), 18),
+            ("let a = 17; a %= 5; a **= 3; a;".to_string(), 8),
+            ("let a = 1; a <<= 4; a |= 1; a ^= 3; a;".to_string(), 18),
+            ("let a = [1, 2]; a[1] = 5; a[0] + a[1];".to_string(), 6),
+            ("let a = [1, [2, 3]]; a[1][0] += 10; a[1][0];".to_string(), 12),
+            ("let h = {\"k\": 1}; h[\"k\"] += 1; h[\"n\"] = 5; h[\"k\"] + h[\"n\"];".to_string(), 7),
+            ("let a = [1]; let b = a; b[0] = 2; a[0];".to_string(), 1),
+            ("let a = 1; let f = fn() { a = 2; }; f(); a;".to_string(), 2),
+            ("let counter = fn() { let c = 0; fn() { c += 1 } }; let next = counter(); next(); next(); next();".to_string(), 3),
+            ("let f = fn() { let x = 1; let g = fn() { x = 10; }; g(); x }; f();".to_string(), 10),
+            ("let h = {}; h[\"k\"] = 1; h[\"k\"] <<= 3; h[\"k\"];".to_string(), 8),
+            ("let x = 1; let f = fn() { x = x + 1; x }; f() + x;".to_string(), 4),
+            ("let f = fn() { f = 1; f }; f();".to_string(), 1),
+            ("let f = fn(n) { if (n > 0) { f(n - 1) } else { f = n; 7 } }; f(3) + f;".to_string(), 7),
+        ];
+
+        for tt in tests {
+            let evaluated = test_eval(tt.0)?;
+            test_integer_obj(tt.1, evaluated)?;
+        };
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_loop_expressions() -> Result<()> {
+        let tests = vec![
+            ("let i = 0; while (i < 5) { i += 1; }; i".to_string(), 5),
+            ("let i = 0; let s = 0; while (true) { i += 1; if (i > 4) { break; }; if (i == 2) { continue; }; s += i; }; s".to_string(), 8),
+            ("let s = 0; for (x in [1, 2, 3]) { s += x * x; }; s".to_string(), 14),
+            ("let h = {\"a\": 1, \"b\": 2}; let s = 0; for (k in h) { s += h[k]; }; s".to_string(), 3),
+            ("let n = 0; for (c in \"héllo\") { n += 1; }; n".to_string(), 5),
+            ("let s = 0; for (a in [1, 2]) { for (b in [10, 20, 30]) { if (b == 30) { break; }; s += a * b; } }; s".to_string(), 90),
+            ("let f = fn(xs) { for (x in xs) { if (x > 1) { return x; } }; 0 }; f([1, 5, 7]) + f([])".to_string(), 5),
+            ("let i = 0; while (i < 10000) { i += 1; }; i".to_string(), 10000),
+            ("let x = 7; for (x in [1, 2]) { x = 3; }; x".to_string(), 7),
+            ("fn() { let x = 7; for (x in [1, 2]) { }; x }()".to_string(), 7),
+            ("let fs = []; for (x in [1, 2]) { fs = push(fs, fn() { x }) }; fs[0]()".to_string(), 2),
+            ("for (x in [1]) { let y = x + 1; }; y".to_string(), 2),
         ];
 
         for tt in tests {
@@ -867,6 +1475,195 @@ mod tests {
             test_integer_obj(tt.1, evaluated)?;
         };
 
+        assert_eq!(NULL, test_eval("while (false) { 1 }".to_string())?);
+        assert_eq!(NULL, test_eval("for (x in [1]) { break; }".to_string())?);
+        assert_eq!(
+            "[2, 3, 5, 7, 11, 13, 17, 19, 23, 29]",
+            test_eval(r#"let primes = [];
+            let n = 2;
+            while (len(primes) < 10) {
+                let prime = true;
+                for (p in primes) {
+                    if (p * p > n) { break; };
+                    if (n % p == 0) { prime = false; break; };
+                };
+                if (prime) { primes = push(primes, n); };
+                n += 1;
+            };
+            primes"#.to_string())?.to_string(),
+        );
+        assert_eq!(
+            r#"["a", "b", true, -5, 3, 340282366920938463463374607431768211456]"#,
+            test_eval(r#"let ks = []; for (k in {"b": 1, "a": 2, 3: 0, true: 1, -5: 0, 2 ** 128: 0}) { ks = push(ks, k) }; ks"#.to_string())?.to_string(),
+        );
+        assert_eq!(
+            "ERROR: identifier not found: x",
+            test_eval("for (x in [1]) { }; x".to_string())?.to_string(),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_loop_control_in_expressions() -> Result<()> {
+        let tests = vec![
+            ("let s = 0; for (i in 0..5) { s += match (i) { 2 => { continue; }, x => x } }; s", "8"),
+            ("let n = 0; for (i in 0..3) { n += 1 + (if (true) { continue; } else { 1 }) }; n", "0"),
+            ("let s = []; for (i in 0..4) { s = push(s, if (i == 2) { break; } else { i }) }; s", "[0, 1]"),
+            ("let i = 0; let s = 0; while (i < 5) { i += 1; s += [i, if (i % 2 == 0) { continue; } else { i }][1] }; s", "9"),
+            ("let s = 0; for (a in 0..3) { s += len([a, for (b in 0..3) { if (b == a) { break; } }]) }; s", "6"),
+            ("let f = fn() { for (k in 0..10) { 1 + (if (k == 3) { return k; } else { k }) } }; [f(), f()]", "[3, 3]"),
+            ("let h = {}; for (i in 0..3) { h[i] = {i: if (i == 1) { continue; } else { i }} }; [h[0][0], h[1], h[2][2]]", "[0, null, 2]"),
+            ("let s = 0; for (i in 0..3) { let x = -(if (i == 1) { continue; } else { i }); s += x }; s", "-2"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(expected, test_eval(input.to_string())?.to_string(), "{}", input);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_destructuring_let() -> Result<()> {
+        let tests = vec![
+            ("let [a, b, ...rest] = [1, 2, 3, 4]; [a, b, rest]".to_string(), "[1, 2, [3, 4]]"),
+            ("let [a, ...rest] = [1]; rest".to_string(), "[]"),
+            ("let [x, y] = [1, 2]; let [x, y] = [y, x]; [x, y]".to_string(), "[2, 1]"),
+            (r#"let {"name": n, "age": a} = {"name": "Ann", "age": 30}; [n, a]"#.to_string(), r#"["Ann", 30]"#),
+            ("let f = fn(pair) { let [p, q] = pair; p * q }; f([6, 7])".to_string(), "42"),
+            ("let [a] = [1]".to_string(), "[1]"),
+            (r#"let people = [{"name": "Ann", "age": 30}, {"name": "Bob", "age": 25}];
+                let [oldest, ...others] = people;
+                let {"name": name, "age": age} = oldest;
+                let names = [];
+                for (person in others) {
+                    let {"name": other} = person;
+                    names = push(names, other);
+                };
+                [name, age, names]"#.to_string(), r#"["Ann", 30, ["Bob"]]"#),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(expected, test_eval(input)?.to_string());
+        };
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_match_expressions() -> Result<()> {
+        let tests = vec![
+            (r#"match (2) { 1 => "one", 2 => "two", _ => "many" }"#.to_string(), r#""two""#),
+            ("match (-1) { 1 => 1, -1 => -10 }".to_string(), "-10"),
+            ("match (1.0) { 1 => 1, 1.0 => 2 }".to_string(), "1"),
+            ("match (3) { 1 => 1 }".to_string(), "null"),
+            ("match ([1, [2, 3], 4, 5]) { [a, [b, c], ...rest] => [a + b + c, rest] }".to_string(), "[6, [4, 5]]"),
+            ("match ([1]) { [a, b, ..._] => 2, [..._] => 0 }".to_string(), "0"),
+            (r#"match ({"k": [1, 2], "j": 3}) { {"k": [x, y]} => x + y }"#.to_string(), "3"),
+            ("match ([1]) { {} => 1, [1] => 2 }".to_string(), "2"),
+            ("match (7) { n if n > 10 => 1, n if n > 5 => 2, _ => 3 }".to_string(), "2"),
+            ("match ([4, 5]) { [a, b] => a }; b".to_string(), "ERROR: identifier not found: b"),
+            ("let b = 1; match ([4, 5]) { [a, b] => a }; b".to_string(), "1"),
+            ("let x = 1; let r = match (5) { x if x > 10 => 0, _ => 1 }; [r, x]".to_string(), "[1, 1]"),
+            ("let x = 1; match ([2, [3]]) { [x, [y]] => { x = y; let z = x; } }; [x, z]".to_string(), "[1, 3]"),
+            ("fn(x) { let f = match (x + 1) { x => fn() { x } }; [f(), x] }(1)".to_string(), "[2, 1]"),
+            ("match (1.0) { 1 => 10, _ => 0 }".to_string(), "10"),
+            ("match (2) { 2.0 => 10, _ => 0 }".to_string(), "10"),
+            ("match ([0.5, 3]) { [0.5, 3.0] => 10, _ => 0 }".to_string(), "10"),
+            (r#"match ("1") { 1 => 10, _ => 0 }"#.to_string(), "0"),
+            ("match (1) { x if y => 1 }".to_string(), "ERROR: identifier not found: y"),
+            (r#"let describe = fn(shape) {
+                    match (shape) {
+                        {"kind": "circle", "r": r} => 3 * r * r,
+                        {"kind": "rect", "size": [w, h]} if w == h => "square",
+                        {"kind": "rect", "size": [w, h]} => w * h,
+                        [first, ...others] => describe(first) + len(others),
+                        _ => "unknown",
+                    }
+                };
+                [describe({"kind": "circle", "r": 2}), describe({"kind": "rect", "size": [2, 2]}),
+                 describe({"kind": "rect", "size": [2, 3]}), describe([{"kind": "circle", "r": 1}, 0]), describe(0)]"#.to_string(), r#"[12, "square", 6, 4, "unknown"]"#),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(expected, test_eval(input)?.to_string());
+        };
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ranges_and_slices() -> Result<()> {
+        let tests = vec![
+            ("1..4".to_string(), "1..4"),
+            ("1..=4".to_string(), "1..=4"),
+            ("let s = 0; for (i in 1..=100) { s += i; }; s".to_string(), "5050"),
+            ("let n = 0; for (i in 5..1) { n += 1; }; n".to_string(), "0"),
+            ("let a = [1, 2, 3, 4, 5]; a[1:3]".to_string(), "[2, 3]"),
+            ("let a = [1, 2, 3, 4, 5]; a[-2:]".to_string(), "[4, 5]"),
+            ("[1, 2, 3][:-1]".to_string(), "[1, 2]"),
+            ("[1, 2, 3][4:1]".to_string(), "[]"),
+            ("[1, 2, 3, 4, 5][-3..=-1]".to_string(), "[3, 4, 5]"),
+            (r#""héllo"[1:3]"#.to_string(), r#""él""#),
+            (r#""héllo"[2:]"#.to_string(), r#""llo""#),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(expected, test_eval(input)?.to_string());
+        };
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_and_rest_parameters() -> Result<()> {
+        let tests = vec![
+            ("let f = fn(a, b = 10) { a + b }; [f(1), f(1, 2)]".to_string(), "[11, 3]"),
+            ("let f = fn(a = 1, b = a * 2) { [a, b] }; [f(), f(5), f(5, 6)]".to_string(), "[[1, 2], [5, 10], [5, 6]]"),
+            ("let f = fn(a, ...rest) { [a, rest] }; [f(1), f(1, 2, 3)]".to_string(), "[[1, []], [1, [2, 3]]]"),
+            ("let f = fn(a, b = 2, ...rest) { [a, b, len(rest)] }; [f(1), f(1, 5, 6, 7)]".to_string(), "[[1, 2, 0], [1, 5, 2]]"),
+            ("let f = fn(a = []) { push(a, 1) }; [f(), f()]".to_string(), "[[1], [1]]"),
+            ("let g = fn(x) { fn(y = x) { y } }; g(7)()".to_string(), "7"),
+            ("let f = fn(a = b) { a }; f()".to_string(), "ERROR: identifier not found: b"),
+            ("let add = fn(a, b) { a + b }; add(1)".to_string(), "ERROR: wrong number of arguments to add(): want 2, got 1"),
+            ("let f = fn(a, b = 1) { a }; f(1, 2, 3)".to_string(), "ERROR: wrong number of arguments to f(): want 1 to 2, got 3"),
+            ("fn(a, ...rest) { a }()".to_string(), "ERROR: wrong number of arguments to fn(): want at least 1, got 0"),
+            (r#"let greet = fn(name, greeting = "Hello", ...others) {
+                    let line = greeting + ", " + name;
+                    for (other in others) { line += " and " + other; };
+                    line
+                };
+                [greet("Ann"), greet("Bob", "Hi"), greet("Cy", "Hey", "Di", "Ed")]"#.to_string(), r#"["Hello, Ann", "Hi, Bob", "Hey, Cy and Di and Ed"]"#),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(expected, test_eval(input)?.to_string());
+        };
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_named_arguments() -> Result<()> {
+        let tests = vec![
+            ("let f = fn(x) { x }; f(x: 1)".to_string(), "1"),
+            ("let f = fn(a, b) { a - b }; [f(b: 1, a: 3), f(3, b: 1)]".to_string(), "[2, 2]"),
+            ("let f = fn(a, b = 2, c = 3) { [a, b, c] }; [f(1, c: 5), f(a: 1), f(c: 5, a: 1, b: 4)]".to_string(), "[[1, 2, 5], [1, 2, 3], [1, 4, 5]]"),
+            ("let f = fn(a = 1, b = a * 2) { [a, b] }; [f(b: 5), f(a: 3)]".to_string(), "[[1, 5], [3, 6]]"),
+            ("let n = 0; let bump = fn() { n += 1; n }; let f = fn(a = 1, b = bump()) { b }; f(b: 7); f(a: 2, b: 7); n".to_string(), "0"),
+            ("let f = fn(a, ...rest) { [a, rest] }; f(a: 1)".to_string(), "[1, []]"),
+            ("let f = fn(a) { a }; f(b: 1)".to_string(), "ERROR: unknown argument b to f()"),
+            ("let f = fn(a) { a }; f(1, a: 2)".to_string(), "ERROR: duplicate argument a to f()"),
+            ("fn(a) { a }(a: 1, a: 2)".to_string(), "ERROR: duplicate argument a to fn()"),
+            ("let f = fn(a, b = 1) { a }; f(b: 2)".to_string(), "ERROR: missing argument a to f()"),
+            ("len(x: [1])".to_string(), "ERROR: unknown argument x to len()"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(expected, test_eval(input)?.to_string());
+        };
+
         Ok(())
     }
 
@@ -877,7 +1674,7 @@ mod tests {
 
         if let MObject::Fn(func) = evaluated {
             assert_eq!(1, func.params.len());
-            assert_eq!("x", func.params.get(0).unwrap().value);
+            assert_eq!("x", func.params.get(0).unwrap().name.value);
             assert_eq!("(x + 2)", format!("{}", func.body));
         } else {
             panic!("Expected function, got: {}", evaluated);
@@ -966,12 +1763,32 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_string_index_expressions() -> Result<()> {
+        let tests = vec![
+            (r#""héllo"[0]"#.to_string(), s_to_o("h")),
+            (r#""héllo"[1]"#.to_string(), s_to_o("é")),
+            (r#""a\u{1F600}b"[2]"#.to_string(), s_to_o("b")),
+            (r#""héllo"[5]"#.to_string(), NULL),
+            (r#""héllo"[-1]"#.to_string(), s_to_o("o")),
+            (r#""héllo"[-5]"#.to_string(), s_to_o("h")),
+            (r#""héllo"[-6]"#.to_string(), NULL),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(expected, test_eval(input)?);
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_builtin_functions() -> Result<()> {
         let tests = vec![
             ("len(\"\")".to_string(), 0),
             ("len(\"four\")".to_string(), 4),
             ("len(\"hello world\")".to_string(), 11),
+            ("len(\"héllo \\u{1F600}\")".to_string(), 7),
             ("len([1, 2])".to_string(), 2),
             ("len([1])".to_string(), 1),
             ("len([])".to_string(), 0),
@@ -1058,11 +1875,15 @@ mod tests {
             (r#"{5: 5}[5]"#.to_string(), 5),
             ("{true: 5}[true]".to_string(), 5),
             ("{false: 5}[false]".to_string(), 5),
+            ("[1, 2, 3][-1]".to_string(), 3),
+            ("[1, 2, 3][-3]".to_string(), 1),
+            ("let a = [1, 2, 3]; a[-1] = 5; a[2]".to_string(), 5),
+            ("let a = [1, [2, 3]]; a[-1][-2] += 10; a[1][0]".to_string(), 12),
         ];
 
         let nil_tests = vec![
             "[1, 2, 3][3]".to_string(),
-            "[1, 2, 3][-1]".to_string(),
+            "[1, 2, 3][-4]".to_string(),
             r#"{"foo": 5}["bar"]"#.to_string(),
             r#"{}["foo"]"#.to_string(),
         ];
@@ -1080,6 +1901,34 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_strict_indexing() -> Result<()> {
+        let tests = vec![
+            ("[1, 2, 3][-1]", "3"),
+            (r#""héllo"[-5]"#, r#""h""#),
+            ("{}[1]", "null"),
+            ("[1, 2, 3][3]", "ERROR: index out of range: 3 for length 3"),
+            ("[1, 2, 3][-4]", "ERROR: index out of range: -4 for length 3"),
+            (r#""héllo"[-6]"#, "ERROR: index out of range: -6 for length 5"),
+            ("let f = fn(a) { a[1] }; f([])", "ERROR: index out of range: 1 for length 0"),
+            ("let a = [[1]]; a[1][0] = 2;", "ERROR: index out of range: 1 for length 1"),
+            ("let [a, b] = [1, 2]; match ([1]) { [x] => a + b + x }", "4"),
+        ];
+
+        for (input, expected) in tests {
+            let lex = Lexer::new(input.as_bytes().bytes().peekable())?;
+            let mut parser = Parser::new(lex.peekable())?;
+            let program = parser.parse()?;
+            check_parser_errors(parser)?;
+
+            let env = Environment::new();
+            env.borrow_mut().set_strict(true);
+            assert_eq!(expected, eval(MNode::Prog(program), env)?.to_string(), "{}", input);
+        };
+
+        Ok(())
+    }
+
     #[test]
     fn test_hash_literal() -> Result<()> {
         let input = r###"
@@ -1,16 +1,22 @@
 use std::{io, str};
 use std::iter::Peekable;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
-use crate::error::{Result, Error};
+use unicode_xid::UnicodeXID;
+
+use crate::error::{Result, Error, LexError, LexErrorKind};
 use crate::lexer::token::Token;
 use crate::lexer::token_type::TokenType;
+use crate::lexer::span::{Position, Span};
 
 type FileByte = std::result::Result<u8, io::Error>;
 
 pub struct Lexer<I: Iterator<Item = FileByte>> {
     input: Peekable<I>,
+    // Bytes read ahead of `ch`, so a whole UTF-8 character can be decoded before it is consumed.
+    lookahead: VecDeque<u8>,
     ch: u8,
+    pos: Position,
     keyword_map: HashMap<&'static str, TokenType>,
 }
 
@@ -35,7 +41,9 @@ impl<I: Iterator<Item = FileByte>> Lexer<I> {
 
         let lex = Self {
             input,
+            lookahead: VecDeque::new(),
             ch,
+            pos: Position::start(),
             keyword_map: HashMap::new()
         };
 
@@ -45,13 +53,17 @@ impl<I: Iterator<Item = FileByte>> Lexer<I> {
     pub fn next_token(&mut self) -> Result<Token> {
         self.eat_whitespace()?;
         let ch = self.ch;
+        let start = self.pos;
 
-        let tok = match ch {
+        let mut tok = match ch {
             b'=' => {
                 let peeked = self.peek_char()?;
                 if peeked == b'=' {
                     self.next_char()?;
                     new_token(TokenType::EQ, &[ch, peeked])?
+                } else if peeked == b'>' {
+                    self.next_char()?;
+                    new_token(TokenType::ARROW, &[ch, peeked])?
                 } else {
                     new_token(TokenType::ASSIGN, &[ch])?
                 }
@@ -62,13 +74,76 @@ impl<I: Iterator<Item = FileByte>> Lexer<I> {
             b'[' => new_token(TokenType::LBRACKET, &[ch])?,
             b']' => new_token(TokenType::RBRACKET, &[ch])?,
             b',' => new_token(TokenType::COMMA, &[ch])?,
-            b'+' => new_token(TokenType::PLUS, &[ch])?,
-            b'-' => new_token(TokenType::MINUS, &[ch])?,
-            b'*' => new_token(TokenType::ASTERISK, &[ch])?,
-            b'/' => new_token(TokenType::SLASH, &[ch])?,
-            b'<' => new_token(TokenType::LT, &[ch])?,
-            b'>' => new_token(TokenType::GT, &[ch])?,
+            b'+' => self.operator_token(TokenType::PLUS, TokenType::PLUS_ASSIGN, &[ch])?,
+            b'-' => self.operator_token(TokenType::MINUS, TokenType::MINUS_ASSIGN, &[ch])?,
+            b'*' => {
+                let peeked = self.peek_char()?;
+                if peeked == b'*' {
+                    self.next_char()?;
+                    self.operator_token(TokenType::POWER, TokenType::POWER_ASSIGN, &[ch, peeked])?
+                } else {
+                    self.operator_token(TokenType::ASTERISK, TokenType::ASTERISK_ASSIGN, &[ch])?
+                }
+            },
+            b'/' => self.operator_token(TokenType::SLASH, TokenType::SLASH_ASSIGN, &[ch])?,
+            b'%' => self.operator_token(TokenType::PERCENT, TokenType::PERCENT_ASSIGN, &[ch])?,
+            b'&' => {
+                let peeked = self.peek_char()?;
+                if peeked == b'&' {
+                    self.next_char()?;
+                    new_token(TokenType::AND, &[ch, peeked])?
+                } else {
+                    self.operator_token(TokenType::AMPERSAND, TokenType::AMPERSAND_ASSIGN, &[ch])?
+                }
+            },
+            b'|' => {
+                let peeked = self.peek_char()?;
+                if peeked == b'|' {
+                    self.next_char()?;
+                    new_token(TokenType::OR, &[ch, peeked])?
+                } else {
+                    self.operator_token(TokenType::PIPE, TokenType::PIPE_ASSIGN, &[ch])?
+                }
+            },
+            b'^' => self.operator_token(TokenType::CARET, TokenType::CARET_ASSIGN, &[ch])?,
+            b'~' => new_token(TokenType::TILDE, &[ch])?,
+            b'<' => {
+                let peeked = self.peek_char()?;
+                if peeked == b'<' {
+                    self.next_char()?;
+                    self.operator_token(TokenType::LSHIFT, TokenType::LSHIFT_ASSIGN, &[ch, peeked])?
+                } else if peeked == b'=' {
+                    self.next_char()?;
+                    new_token(TokenType::LT_EQ, &[ch, peeked])?
+                } else {
+                    new_token(TokenType::LT, &[ch])?
+                }
+            },
+            b'>' => {
+                let peeked = self.peek_char()?;
+                if peeked == b'>' {
+                    self.next_char()?;
+                    self.operator_token(TokenType::RSHIFT, TokenType::RSHIFT_ASSIGN, &[ch, peeked])?
+                } else if peeked == b'=' {
+                    self.next_char()?;
+                    new_token(TokenType::GT_EQ, &[ch, peeked])?
+                } else {
+                    new_token(TokenType::GT, &[ch])?
+                }
+            },
             b':' => new_token(TokenType::COLON, &[ch])?,
+            b'.' if self.peek_char()? == b'.' => {
+                self.next_char()?;
+                if self.peek_char()? == b'=' {
+                    self.next_char()?;
+                    new_token(TokenType::DOT_DOT_EQ, b"..=")?
+                } else if self.peek_char()? == b'.' {
+                    self.next_char()?;
+                    new_token(TokenType::ELLIPSIS, b"...")?
+                } else {
+                    new_token(TokenType::DOT_DOT, b"..")?
+                }
+            },
             b'!' => {
                 let peeked = self.peek_char()?;
                 if peeked == b'=' {
@@ -86,94 +161,322 @@ impl<I: Iterator<Item = FileByte>> Lexer<I> {
             },
             0 => new_token(TokenType::EOF, &[])?,
             _ => {
-                if is_letter(ch) {
+                if is_identifier_start(self.current_char()?) {
                     let ident = self.read_identifier()?;
                     let tok_type = self.lookup_ident(&ident);
 
                     Token::new(tok_type, ident)
                 } else if is_digit(ch) {
-                    let num = self.read_number()?;
+                    let (tok_type, num) = self.read_number()?;
 
-                    Token::new(TokenType::INT, num)
+                    Token::new(tok_type, num)
                 } else {
-                    new_token(TokenType::ILLEGAL, &[])?
+                    let mut illegal = Vec::new();
+                    self.read_char(&mut illegal)?;
+
+                    Token::new(TokenType::ILLEGAL, String::from_utf8(illegal)?)
                 }
             },
         };
 
         self.next_char()?;
+        tok.span = Span::new(start, self.pos);
 
         Ok(tok)
     }
 
+    // Lexes an operator that also has a compound assignment form, e.g. `+` and `+=`. The lexer is on
+    // the last byte of `op`.
+    fn operator_token(&mut self, op: TokenType, assign: TokenType, op_bytes: &[u8]) -> Result<Token> {
+        if self.peek_char()? != b'=' {
+            return new_token(op, op_bytes);
+        };
+
+        self.next_char()?;
+        new_token(assign, &[op_bytes, b"="].concat())
+    }
+
+    // Comments are skipped along with the whitespace around them.
     fn eat_whitespace(&mut self) -> Result<()> {
-        let mut ch = self.ch;
+        loop {
+            match (self.ch, self.peek_char()?) {
+                (b' ' | b'\t' | b'\n' | b'\r', _) => { self.next_char()?; },
+                (b'/', b'/') => self.eat_line_comment()?,
+                (b'/', b'*') => self.eat_block_comment()?,
+                _ => return Ok(()),
+            };
+        }
+    }
 
-        while ch == b' ' || ch == b'\t' || ch == b'\n' || ch == b'\r' {
-            ch = self.next_char()?;
+    fn eat_line_comment(&mut self) -> Result<()> {
+        while self.ch != b'\n' && self.ch != 0 {
+            self.next_char()?;
         }
 
         Ok(())
     }
 
+    // Block comments nest, so `/* a /* b */ c */` is a single comment.
+    fn eat_block_comment(&mut self) -> Result<()> {
+        let start = self.pos;
+        let mut depth = 0;
+
+        loop {
+            match (self.ch, self.peek_char()?) {
+                (0, _) => return Err(self.error(LexErrorKind::UnterminatedComment, start)),
+                (b'/', b'*') => {
+                    self.next_char()?;
+                    depth += 1;
+                },
+                (b'*', b'/') => {
+                    self.next_char()?;
+                    depth -= 1;
+                    if depth == 0 {
+                        self.next_char()?;
+                        return Ok(());
+                    };
+                },
+                _ => {},
+            };
+            self.next_char()?;
+        }
+    }
+
     fn next_char(&mut self) -> Result<u8> {
-        self.ch = match self.input.next() {
-            Some(ch) => ch?,
-            None => 0,
+        let prev = self.ch;
+        self.ch = match self.lookahead.pop_front() {
+            Some(ch) => ch,
+            None => match self.input.next() {
+                Some(ch) => ch?,
+                None => 0,
+            },
         };
+        self.advance_position(prev);
         Ok(self.ch)
     }
 
+    // Columns count characters rather than bytes, so UTF-8 continuation bytes don't move the
+    // column forward.
+    fn advance_position(&mut self, prev: u8) {
+        if prev == 0 { return; };
+
+        self.pos.offset += 1;
+
+        if prev == b'\n' {
+            self.pos.line += 1;
+            self.pos.column = 1;
+        } else if !is_continuation_byte(self.ch) {
+            self.pos.column += 1;
+        }
+    }
+
     fn peek_char(&mut self) -> Result<u8> {
-        match self.input.peek() {
-            Some(peeked) => {
-                match peeked.as_ref() {
-                    Ok(c) => Ok(*c),
-                    Err(e) => Err(Error::new(format!("{}", e))),
-                }
-            },
-            None => Ok(0),
+        self.peek_nth(0)
+    }
+
+    fn peek_nth(&mut self, n: usize) -> Result<u8> {
+        while self.lookahead.len() <= n {
+            match self.input.next() {
+                Some(ch) => self.lookahead.push_back(ch?),
+                None => return Ok(0),
+            };
+        }
+
+        Ok(self.lookahead[n])
+    }
+
+    // Decodes the character starting at the current byte without consuming its continuation bytes.
+    fn current_char(&mut self) -> Result<char> {
+        self.decode_char(self.ch, 0)
+    }
+
+    // Decodes the character after the current one without consuming it.
+    fn peek_utf8(&mut self) -> Result<char> {
+        let first = self.peek_char()?;
+        self.decode_char(first, 1)
+    }
+
+    fn decode_char(&mut self, first: u8, offset: usize) -> Result<char> {
+        let mut bytes = vec![first];
+        for i in 1..utf8_width(first) {
+            bytes.push(self.peek_nth(offset + i - 1)?);
+        }
+
+        match str::from_utf8(&bytes) {
+            Ok(x) => Ok(x.chars().next().unwrap_or('\0')),
+            Err(_) => Err(self.error(LexErrorKind::InvalidUtf8, self.pos)),
         }
     }
 
+    // Pushes the bytes of the character starting at the current byte, leaving the lexer on its
+    // last byte.
+    fn read_char(&mut self, buf: &mut Vec<u8>) -> Result<()> {
+        buf.push(self.ch);
+        for _ in 1..utf8_width(self.ch) {
+            buf.push(self.next_char()?);
+        }
+
+        Ok(())
+    }
+
     fn read_identifier(&mut self) -> Result<String> {
         let mut ident = Vec::new();
-        let mut ch = self.peek_char()?;
-        ident.push(self.ch);
+        self.read_char(&mut ident)?;
 
-        while is_letter(ch) {
+        while is_identifier_continue(self.peek_utf8()?) {
             self.next_char()?;
-            ident.push(ch);
-            ch = self.peek_char()?;
+            self.read_char(&mut ident)?;
         }
 
         Ok(String::from_utf8(ident)?)
     }
 
-    fn read_number(&mut self) -> Result<String> {
-        let ident = &mut Vec::<u8>::new();
-        let mut ch = self.peek_char()?;
-        ident.push(self.ch);
+    // Reads an integer, or a float when the digits are followed by a fraction or an exponent. A `.`
+    // or `e` only belongs to the number when a digit follows it, so `1.foo` is left alone. Integers
+    // may also be written in hex, octal or binary with a `0x`, `0o` or `0b` prefix, which must be
+    // followed by digits of that base only.
+    fn read_number(&mut self) -> Result<(TokenType, String)> {
+        let start = self.pos;
+        let mut num = vec![self.ch];
+        let mut tok_type = TokenType::INT;
 
-        while is_digit(ch) {
-            self.next_char()?;
-            ident.push(ch);
-            ch = self.peek_char()?;
+        if self.ch == b'0' {
+            if let Some(radix) = radix_for_prefix(self.peek_char()?) {
+                num.push(self.next_char()?);
+                self.read_digits(&mut num, radix)?;
+
+                if num.len() == 2 {
+                    self.next_char()?;
+                    return Err(self.error(LexErrorKind::MissingDigits(radix), start));
+                };
+
+                let ch = self.peek_char()?;
+                if ch.is_ascii_alphanumeric() {
+                    self.next_char()?;
+                    let digit = self.pos;
+                    self.next_char()?;
+                    return Err(self.error(LexErrorKind::InvalidDigit(ch as char, radix), digit));
+                };
+
+                return Ok((tok_type, String::from_utf8(num)?));
+            }
+        }
+
+        self.read_digits(&mut num, 10)?;
+
+        if self.peek_char()? == b'.' && is_digit(self.peek_nth(1)?) {
+            tok_type = TokenType::FLOAT;
+            num.push(self.next_char()?);
+            self.read_digits(&mut num, 10)?;
+        }
+
+        if matches!(self.peek_char()?, b'e' | b'E') {
+            let signed = matches!(self.peek_nth(1)?, b'+' | b'-');
+            let digit = if signed { self.peek_nth(2)? } else { self.peek_nth(1)? };
+
+            if is_digit(digit) {
+                tok_type = TokenType::FLOAT;
+                num.push(self.next_char()?);
+                if signed {
+                    num.push(self.next_char()?);
+                }
+                self.read_digits(&mut num, 10)?;
+            }
         }
 
-        Ok(String::from_utf8(ident.to_vec())?)
+        Ok((tok_type, String::from_utf8(num)?))
+    }
+
+    // Underscores can separate digits, e.g. `1_000_000`, but a digit has to follow each one.
+    fn read_digits(&mut self, buf: &mut Vec<u8>, radix: u32) -> Result<()> {
+        loop {
+            let ch = self.peek_char()?;
+
+            if ch == b'_' {
+                buf.push(self.next_char()?);
+                let separator = self.pos;
+
+                match self.peek_char()? {
+                    b'_' => {
+                        self.next_char()?;
+                        let duplicate = self.pos;
+                        self.next_char()?;
+                        return Err(self.error(LexErrorKind::DuplicateSeparator, duplicate));
+                    },
+                    x if is_radix_digit(x, radix) => {},
+                    _ => {
+                        self.next_char()?;
+                        return Err(self.error(LexErrorKind::TrailingSeparator, separator));
+                    },
+                };
+            } else if is_radix_digit(ch, radix) {
+                buf.push(self.next_char()?);
+            } else {
+                return Ok(());
+            };
+        }
     }
 
     fn read_string(&mut self) -> Result<String> {
+        let start = self.pos;
         self.next_char()?;
         let mut string_lit = Vec::new();
 
         while self.ch != b'"' {
-            string_lit.push(self.ch);
+            match self.ch {
+                0 => return Err(self.error(LexErrorKind::UnterminatedString, start)),
+                b'\\' => {
+                    let mut buf = [0; 4];
+                    let escaped = self.read_escape()?;
+                    string_lit.extend_from_slice(escaped.encode_utf8(&mut buf).as_bytes());
+                },
+                ch => string_lit.push(ch),
+            };
             self.next_char()?;
         }
 
-        Ok(String::from_utf8(string_lit)?)
+        String::from_utf8(string_lit).map_err(|_| self.error(LexErrorKind::InvalidUtf8, start))
+    }
+
+    // Reads the escape sequence starting at the current `\\`, leaving the lexer on its last byte.
+    fn read_escape(&mut self) -> Result<char> {
+        let start = self.pos;
+
+        let escaped = match self.next_char()? {
+            b'n' => '\n',
+            b't' => '\t',
+            b'r' => '\r',
+            b'0' => '\0',
+            b'\\' => '\\',
+            b'"' => '"',
+            b'u' => {
+                if self.next_char()? != b'{' {
+                    return Err(self.error(LexErrorKind::InvalidEscape, start));
+                };
+
+                let mut digits = String::new();
+                while self.peek_char()?.is_ascii_hexdigit() && digits.len() < 6 {
+                    digits.push(self.next_char()? as char);
+                }
+
+                if self.next_char()? != b'}' {
+                    return Err(self.error(LexErrorKind::InvalidEscape, start));
+                };
+
+                match u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32) {
+                    Some(c) => c,
+                    None => return Err(self.error(LexErrorKind::InvalidEscape, start)),
+                }
+            },
+            0 => return Err(self.error(LexErrorKind::UnterminatedString, start)),
+            _ => return Err(self.error(LexErrorKind::InvalidEscape, start)),
+        };
+
+        Ok(escaped)
+    }
+
+    fn error(&self, kind: LexErrorKind, start: Position) -> Error {
+        Error::Lex(LexError::new(kind, Span::new(start, self.pos)))
     }
 
     fn lookup_ident(&mut self, ident: &str) -> TokenType {
@@ -193,11 +496,15 @@ fn new_token(t: TokenType, literal: &[u8]) -> Result<Token> {
     Ok(Token::new(t, str::from_utf8(literal)?.to_string()))
 }
 
+// Identifiers follow the Unicode XID rules, with `_` also allowed to start one.
+#[inline]
+fn is_identifier_start(c: char) -> bool {
+    c == '_' || c.is_xid_start()
+}
+
 #[inline]
-fn is_letter(ch: u8) -> bool {
-    b'a' <= ch && ch <= b'z' ||
-        b'A' <= ch && ch <= b'Z' ||
-        ch == b'_'
+fn is_identifier_continue(c: char) -> bool {
+    c.is_xid_continue()
 }
 
 #[inline]
@@ -205,6 +512,37 @@ fn is_digit(ch: u8) -> bool {
     b'0' <= ch && ch <= b'9'
 }
 
+#[inline]
+fn is_radix_digit(ch: u8, radix: u32) -> bool {
+    (ch as char).is_digit(radix)
+}
+
+#[inline]
+fn radix_for_prefix(ch: u8) -> Option<u32> {
+    match ch {
+        b'x' | b'X' => Some(16),
+        b'o' | b'O' => Some(8),
+        b'b' | b'B' => Some(2),
+        _ => None,
+    }
+}
+
+#[inline]
+fn is_continuation_byte(ch: u8) -> bool {
+    ch & 0b1100_0000 == 0b1000_0000
+}
+
+// The number of bytes in the UTF-8 character that starts with `ch`.
+#[inline]
+fn utf8_width(ch: u8) -> usize {
+    match ch.leading_ones() {
+        2 => 2,
+        3 => 3,
+        4 => 4,
+        _ => 1,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -307,7 +645,7 @@ mod tests {
     #[test]
     fn test_monkey_symbols() {
         let input = br###"
-            !-/*5;
+            !-/ *5;
             5 < 10 > 5;
 
             if (5 < 10) {
@@ -394,4 +732,391 @@ mod tests {
 
         assert_tokens(tests, l);
     }
+
+    #[test]
+    fn test_comments() {
+        let input = br###"
+            // A line comment
+            let x = 5; // trailing
+            /* a block
+               comment */
+            x / /* nested /* block */ comment */ 2;
+            // The end of the file
+        "###.to_vec();
+        let l = &mut lex(input.bytes());
+
+        let tests = vec![
+            Expected { expected_type: TokenType::LET, expected_literal: "let".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "x".to_string() },
+            Expected { expected_type: TokenType::ASSIGN, expected_literal: "=".to_string() },
+            Expected { expected_type: TokenType::INT, expected_literal: "5".to_string() },
+            Expected { expected_type: TokenType::SEMICOLON, expected_literal: ";".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "x".to_string() },
+            Expected { expected_type: TokenType::SLASH, expected_literal: "/".to_string() },
+            Expected { expected_type: TokenType::INT, expected_literal: "2".to_string() },
+            Expected { expected_type: TokenType::SEMICOLON, expected_literal: ";".to_string() },
+            Expected { expected_type: TokenType::EOF, expected_literal: "".to_string() },
+        ];
+
+        assert_tokens(tests, l);
+    }
+
+    #[test]
+    fn test_unterminated_comment() {
+        let input = b"let x = 5;\n/* outer /* inner */\nx;".to_vec();
+        let l = &mut lex(input.bytes());
+
+        for _ in 0..5 {
+            l.next_token().unwrap();
+        }
+
+        match l.next_token() {
+            Err(Error::Lex(e)) => {
+                assert_eq!(LexErrorKind::UnterminatedComment, e.kind);
+                assert_eq!(Position::new(11, 2, 1), e.span.start);
+            },
+            x => panic!("Expected an unterminated comment error, got: {:?}", x),
+        };
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        let input = br###""a\nb" "\t\"quoted\"\\" "\u{1F600} \u{e9}" "/* not a comment */""###.to_vec();
+        let l = &mut lex(input.bytes());
+
+        let tests = vec![
+            Expected { expected_type: TokenType::STRING, expected_literal: "a\nb".to_string() },
+            Expected { expected_type: TokenType::STRING, expected_literal: "\t\"quoted\"\\".to_string() },
+            Expected { expected_type: TokenType::STRING, expected_literal: "\u{1F600} \u{e9}".to_string() },
+            Expected { expected_type: TokenType::STRING, expected_literal: "/* not a comment */".to_string() },
+            Expected { expected_type: TokenType::EOF, expected_literal: "".to_string() },
+        ];
+
+        assert_tokens(tests, l);
+    }
+
+    #[test]
+    fn test_string_errors() {
+        let tests = vec![
+            ("let s = \"unterminated;\n", LexErrorKind::UnterminatedString, Position::new(8, 1, 9)),
+            ("\"trailing \\", LexErrorKind::UnterminatedString, Position::new(10, 1, 11)),
+            ("\"bad \\q\"", LexErrorKind::InvalidEscape, Position::new(5, 1, 6)),
+            ("\"bad \\u{110000}\"", LexErrorKind::InvalidEscape, Position::new(5, 1, 6)),
+            ("\"bad \\u1F600\"", LexErrorKind::InvalidEscape, Position::new(5, 1, 6)),
+        ];
+
+        for (input, kind, start) in tests {
+            let input = input.as_bytes().to_vec();
+            let mut l = lex(input.bytes());
+
+            match l.find(|t| t.is_err()) {
+                Some(Err(Error::Lex(e))) => {
+                    assert_eq!(kind, e.kind);
+                    assert_eq!(start, e.span.start);
+                },
+                x => panic!("Expected a {:?} error, got: {:?}", kind, x),
+            };
+        }
+    }
+
+    #[test]
+    fn test_arithmetic_operators() {
+        let input = "a % b ** c * d & e | f ^ ~g << h >> i < j > k".as_bytes().to_vec();
+        let l = &mut lex(input.bytes());
+
+        let tests = vec![
+            Expected { expected_type: TokenType::IDENT, expected_literal: "a".to_string() },
+            Expected { expected_type: TokenType::PERCENT, expected_literal: "%".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "b".to_string() },
+            Expected { expected_type: TokenType::POWER, expected_literal: "**".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "c".to_string() },
+            Expected { expected_type: TokenType::ASTERISK, expected_literal: "*".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "d".to_string() },
+            Expected { expected_type: TokenType::AMPERSAND, expected_literal: "&".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "e".to_string() },
+            Expected { expected_type: TokenType::PIPE, expected_literal: "|".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "f".to_string() },
+            Expected { expected_type: TokenType::CARET, expected_literal: "^".to_string() },
+            Expected { expected_type: TokenType::TILDE, expected_literal: "~".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "g".to_string() },
+            Expected { expected_type: TokenType::LSHIFT, expected_literal: "<<".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "h".to_string() },
+            Expected { expected_type: TokenType::RSHIFT, expected_literal: ">>".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "i".to_string() },
+            Expected { expected_type: TokenType::LT, expected_literal: "<".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "j".to_string() },
+            Expected { expected_type: TokenType::GT, expected_literal: ">".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "k".to_string() },
+            Expected { expected_type: TokenType::EOF, expected_literal: "".to_string() },
+        ];
+
+        assert_tokens(tests, l);
+    }
+
+    #[test]
+    fn test_comparison_and_logical_operators() {
+        let input = "a <= b >= c && d || e & f | g".as_bytes().to_vec();
+        let l = &mut lex(input.bytes());
+
+        let tests = vec![
+            Expected { expected_type: TokenType::IDENT, expected_literal: "a".to_string() },
+            Expected { expected_type: TokenType::LT_EQ, expected_literal: "<=".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "b".to_string() },
+            Expected { expected_type: TokenType::GT_EQ, expected_literal: ">=".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "c".to_string() },
+            Expected { expected_type: TokenType::AND, expected_literal: "&&".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "d".to_string() },
+            Expected { expected_type: TokenType::OR, expected_literal: "||".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "e".to_string() },
+            Expected { expected_type: TokenType::AMPERSAND, expected_literal: "&".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "f".to_string() },
+            Expected { expected_type: TokenType::PIPE, expected_literal: "|".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "g".to_string() },
+            Expected { expected_type: TokenType::EOF, expected_literal: "".to_string() },
+        ];
+
+        assert_tokens(tests, l);
+    }
+
+    #[test]
+    fn test_loop_keywords() {
+        let input = "while for x in xs break continue inner".as_bytes().to_vec();
+        let l = &mut lex(input.bytes());
+
+        let tests = vec![
+            Expected { expected_type: TokenType::WHILE, expected_literal: "while".to_string() },
+            Expected { expected_type: TokenType::FOR, expected_literal: "for".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "x".to_string() },
+            Expected { expected_type: TokenType::IN, expected_literal: "in".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "xs".to_string() },
+            Expected { expected_type: TokenType::BREAK, expected_literal: "break".to_string() },
+            Expected { expected_type: TokenType::CONTINUE, expected_literal: "continue".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "inner".to_string() },
+            Expected { expected_type: TokenType::EOF, expected_literal: "".to_string() },
+        ];
+
+        assert_tokens(tests, l);
+    }
+
+    #[test]
+    fn test_range_operators() {
+        let input = "1..3 0..=n 1.5..2 a.b ...rest".as_bytes().to_vec();
+        let l = &mut lex(input.bytes());
+
+        let tests = vec![
+            Expected { expected_type: TokenType::INT, expected_literal: "1".to_string() },
+            Expected { expected_type: TokenType::DOT_DOT, expected_literal: "..".to_string() },
+            Expected { expected_type: TokenType::INT, expected_literal: "3".to_string() },
+            Expected { expected_type: TokenType::INT, expected_literal: "0".to_string() },
+            Expected { expected_type: TokenType::DOT_DOT_EQ, expected_literal: "..=".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "n".to_string() },
+            Expected { expected_type: TokenType::FLOAT, expected_literal: "1.5".to_string() },
+            Expected { expected_type: TokenType::DOT_DOT, expected_literal: "..".to_string() },
+            Expected { expected_type: TokenType::INT, expected_literal: "2".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "a".to_string() },
+            Expected { expected_type: TokenType::ILLEGAL, expected_literal: ".".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "b".to_string() },
+            Expected { expected_type: TokenType::ELLIPSIS, expected_literal: "...".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "rest".to_string() },
+            Expected { expected_type: TokenType::EOF, expected_literal: "".to_string() },
+        ];
+
+        assert_tokens(tests, l);
+    }
+
+    #[test]
+    fn test_match_tokens() {
+        let input = "match (x) { _ => 1 }".as_bytes().to_vec();
+        let l = &mut lex(input.bytes());
+
+        let tests = vec![
+            Expected { expected_type: TokenType::MATCH, expected_literal: "match".to_string() },
+            Expected { expected_type: TokenType::LPAREN, expected_literal: "(".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "x".to_string() },
+            Expected { expected_type: TokenType::RPAREN, expected_literal: ")".to_string() },
+            Expected { expected_type: TokenType::LBRACE, expected_literal: "{".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "_".to_string() },
+            Expected { expected_type: TokenType::ARROW, expected_literal: "=>".to_string() },
+            Expected { expected_type: TokenType::INT, expected_literal: "1".to_string() },
+            Expected { expected_type: TokenType::RBRACE, expected_literal: "}".to_string() },
+            Expected { expected_type: TokenType::EOF, expected_literal: "".to_string() },
+        ];
+
+        assert_tokens(tests, l);
+    }
+
+    #[test]
+    fn test_assignment_operators() {
+        let input = "a += b -= c *= d **= e /= f %= g &= h |= i ^= j <<= k >>= l".as_bytes().to_vec();
+        let l = &mut lex(input.bytes());
+
+        let tests = vec![
+            Expected { expected_type: TokenType::IDENT, expected_literal: "a".to_string() },
+            Expected { expected_type: TokenType::PLUS_ASSIGN, expected_literal: "+=".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "b".to_string() },
+            Expected { expected_type: TokenType::MINUS_ASSIGN, expected_literal: "-=".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "c".to_string() },
+            Expected { expected_type: TokenType::ASTERISK_ASSIGN, expected_literal: "*=".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "d".to_string() },
+            Expected { expected_type: TokenType::POWER_ASSIGN, expected_literal: "**=".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "e".to_string() },
+            Expected { expected_type: TokenType::SLASH_ASSIGN, expected_literal: "/=".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "f".to_string() },
+            Expected { expected_type: TokenType::PERCENT_ASSIGN, expected_literal: "%=".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "g".to_string() },
+            Expected { expected_type: TokenType::AMPERSAND_ASSIGN, expected_literal: "&=".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "h".to_string() },
+            Expected { expected_type: TokenType::PIPE_ASSIGN, expected_literal: "|=".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "i".to_string() },
+            Expected { expected_type: TokenType::CARET_ASSIGN, expected_literal: "^=".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "j".to_string() },
+            Expected { expected_type: TokenType::LSHIFT_ASSIGN, expected_literal: "<<=".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "k".to_string() },
+            Expected { expected_type: TokenType::RSHIFT_ASSIGN, expected_literal: ">>=".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "l".to_string() },
+            Expected { expected_type: TokenType::EOF, expected_literal: "".to_string() },
+        ];
+
+        assert_tokens(tests, l);
+    }
+
+    #[test]
+    fn test_numbers() {
+        let input = "5 3.14 1e-9 2E10 1.5e+3 1.foo 2e".as_bytes().to_vec();
+        let l = &mut lex(input.bytes());
+
+        let tests = vec![
+            Expected { expected_type: TokenType::INT, expected_literal: "5".to_string() },
+            Expected { expected_type: TokenType::FLOAT, expected_literal: "3.14".to_string() },
+            Expected { expected_type: TokenType::FLOAT, expected_literal: "1e-9".to_string() },
+            Expected { expected_type: TokenType::FLOAT, expected_literal: "2E10".to_string() },
+            Expected { expected_type: TokenType::FLOAT, expected_literal: "1.5e+3".to_string() },
+            Expected { expected_type: TokenType::INT, expected_literal: "1".to_string() },
+            Expected { expected_type: TokenType::ILLEGAL, expected_literal: ".".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "foo".to_string() },
+            Expected { expected_type: TokenType::INT, expected_literal: "2".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "e".to_string() },
+            Expected { expected_type: TokenType::EOF, expected_literal: "".to_string() },
+        ];
+
+        assert_tokens(tests, l);
+    }
+
+    #[test]
+    fn test_integer_literals() {
+        let input = "0xFF 0o755 0b1010 1_000_000 1_000.5 0x_ff".as_bytes().to_vec();
+        let l = &mut lex(input.bytes());
+
+        let tests = vec![
+            Expected { expected_type: TokenType::INT, expected_literal: "0xFF".to_string() },
+            Expected { expected_type: TokenType::INT, expected_literal: "0o755".to_string() },
+            Expected { expected_type: TokenType::INT, expected_literal: "0b1010".to_string() },
+            Expected { expected_type: TokenType::INT, expected_literal: "1_000_000".to_string() },
+            Expected { expected_type: TokenType::FLOAT, expected_literal: "1_000.5".to_string() },
+            Expected { expected_type: TokenType::INT, expected_literal: "0x_ff".to_string() },
+            Expected { expected_type: TokenType::EOF, expected_literal: "".to_string() },
+        ];
+
+        assert_tokens(tests, l);
+    }
+
+    #[test]
+    fn test_number_errors() {
+        let tests = vec![
+            ("x = 0x;", LexErrorKind::MissingDigits(16), (4, 6)),
+            ("0xZZ", LexErrorKind::MissingDigits(16), (0, 2)),
+            ("0o", LexErrorKind::MissingDigits(8), (0, 2)),
+            ("0b102", LexErrorKind::InvalidDigit('2', 2), (4, 5)),
+            ("0o78", LexErrorKind::InvalidDigit('8', 8), (3, 4)),
+            ("0x1g", LexErrorKind::InvalidDigit('g', 16), (3, 4)),
+            ("1_;", LexErrorKind::TrailingSeparator, (1, 2)),
+            ("1.5_", LexErrorKind::TrailingSeparator, (3, 4)),
+            ("0b1_", LexErrorKind::TrailingSeparator, (3, 4)),
+            ("1__0", LexErrorKind::DuplicateSeparator, (2, 3)),
+        ];
+
+        for (input, kind, (start, end)) in tests {
+            let bytes = input.as_bytes().to_vec();
+            let mut l = lex(bytes.bytes());
+
+            match l.find(|t| t.is_err()) {
+                Some(Err(Error::Lex(e))) => {
+                    assert_eq!(kind, e.kind, "{}", input);
+                    assert_eq!((start, end), (e.span.start.offset, e.span.end.offset), "{}", input);
+                },
+                x => panic!("Expected a {:?} error for {}, got: {:?}", kind, input, x),
+            };
+        }
+    }
+
+    #[test]
+    fn test_identifiers() {
+        let input = "let foo2 = x_1 + _y;\ncafé π 变量 a→b".as_bytes().to_vec();
+        let l = &mut lex(input.bytes());
+
+        let tests = vec![
+            Expected { expected_type: TokenType::LET, expected_literal: "let".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "foo2".to_string() },
+            Expected { expected_type: TokenType::ASSIGN, expected_literal: "=".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "x_1".to_string() },
+            Expected { expected_type: TokenType::PLUS, expected_literal: "+".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "_y".to_string() },
+            Expected { expected_type: TokenType::SEMICOLON, expected_literal: ";".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "café".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "π".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "变量".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "a".to_string() },
+            Expected { expected_type: TokenType::ILLEGAL, expected_literal: "→".to_string() },
+            Expected { expected_type: TokenType::IDENT, expected_literal: "b".to_string() },
+            Expected { expected_type: TokenType::EOF, expected_literal: "".to_string() },
+        ];
+
+        assert_tokens(tests, l);
+    }
+
+    #[test]
+    fn test_identifier_spans() {
+        let input = "café = 变量;".as_bytes().to_vec();
+        let l = &mut lex(input.bytes());
+
+        let tests = vec![
+            (TokenType::IDENT, (0, 1, 1), (5, 1, 5)),
+            (TokenType::ASSIGN, (6, 1, 6), (7, 1, 7)),
+            (TokenType::IDENT, (8, 1, 8), (14, 1, 10)),
+            (TokenType::SEMICOLON, (14, 1, 10), (15, 1, 11)),
+        ];
+
+        for (token_type, start, end) in tests {
+            let tok = l.next_token().unwrap();
+            assert_eq!(token_type, tok.token_type);
+            assert_eq!(Position::new(start.0, start.1, start.2), tok.span.start, "start of {:?}", tok);
+            assert_eq!(Position::new(end.0, end.1, end.2), tok.span.end, "end of {:?}", tok);
+        }
+    }
+
+    #[test]
+    fn test_token_spans() {
+        let input = "let x = 5;\n  \"héllo\" + foo;\n".as_bytes().to_vec();
+        let l = &mut lex(input.bytes());
+
+        let tests = vec![
+            (TokenType::LET, (0, 1, 1), (3, 1, 4)),
+            (TokenType::IDENT, (4, 1, 5), (5, 1, 6)),
+            (TokenType::ASSIGN, (6, 1, 7), (7, 1, 8)),
+            (TokenType::INT, (8, 1, 9), (9, 1, 10)),
+            (TokenType::SEMICOLON, (9, 1, 10), (10, 1, 11)),
+            (TokenType::STRING, (13, 2, 3), (21, 2, 10)),
+            (TokenType::PLUS, (22, 2, 11), (23, 2, 12)),
+            (TokenType::IDENT, (24, 2, 13), (27, 2, 16)),
+            (TokenType::SEMICOLON, (27, 2, 16), (28, 2, 17)),
+            (TokenType::EOF, (29, 3, 1), (29, 3, 1)),
+        ];
+
+        for (token_type, start, end) in tests {
+            let tok = l.next_token().unwrap();
+            assert_eq!(token_type, tok.token_type);
+            assert_eq!(Position::new(start.0, start.1, start.2), tok.span.start, "start of {:?}", tok);
+            assert_eq!(Position::new(end.0, end.1, end.2), tok.span.end, "end of {:?}", tok);
+        }
+    }
 }
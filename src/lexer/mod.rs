@@ -1,3 +1,4 @@
 pub mod lexer;
+pub mod span;
 pub mod token;
 pub mod token_type;
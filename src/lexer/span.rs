@@ -0,0 +1,83 @@
+use std::{cmp::Ordering, fmt, hash::{Hash, Hasher}};
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default, Hash)]
+pub struct Position {
+    pub offset: u32,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl Position {
+    pub fn new(offset: u32, line: u32, column: u32) -> Self {
+        Self { offset, line, column }
+    }
+
+    pub fn start() -> Self {
+        Self::new(0, 1, 1)
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+// The end position is exclusive, it points just past the last character of the span.
+//
+// Spans never take part in equality, ordering or hashing. Two nodes parsed from different places
+// in the source are still the same node, and the HashLiteral keys depend on that.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Self {
+        Self { start, end }
+    }
+
+    pub fn to(&self, other: Span) -> Self {
+        Self::new(self.start, other.end)
+    }
+
+    pub fn len(&self) -> u32 {
+        self.end.offset.saturating_sub(self.start.offset)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl PartialEq for Span {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Span {}
+
+impl PartialOrd for Span {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Span {
+    fn cmp(&self, _other: &Self) -> Ordering {
+        Ordering::Equal
+    }
+}
+
+impl Hash for Span {
+    fn hash<H: Hasher>(&self, _state: &mut H) {
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.start)
+    }
+}
@@ -1,13 +1,18 @@
-use crate::lexer::token_type::TokenType;
+use crate::lexer::{token_type::TokenType, span::Span};
 
 #[derive(PartialEq, Eq, Clone, Debug, Hash)]
 pub struct Token {
     pub token_type: TokenType,
     pub literal: String,
+    pub span: Span,
 }
 
 impl Token {
     pub fn new(token_type: TokenType, literal: String) -> Self {
-        Self { token_type, literal }
+        Self { token_type, literal, span: Span::default() }
+    }
+
+    pub fn with_span(token_type: TokenType, literal: String, span: Span) -> Self {
+        Self { token_type, literal, span }
     }
 }
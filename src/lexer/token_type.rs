@@ -9,17 +9,63 @@ pub enum TokenType {
     STRING,
     IDENT,
     INT,
+    FLOAT,
 
     // Operators
     ASSIGN,
+    #[allow(non_camel_case_types)]
+    PLUS_ASSIGN,
+    #[allow(non_camel_case_types)]
+    MINUS_ASSIGN,
+    #[allow(non_camel_case_types)]
+    ASTERISK_ASSIGN,
+    #[allow(non_camel_case_types)]
+    SLASH_ASSIGN,
+    #[allow(non_camel_case_types)]
+    PERCENT_ASSIGN,
+    #[allow(non_camel_case_types)]
+    POWER_ASSIGN,
+    #[allow(non_camel_case_types)]
+    AMPERSAND_ASSIGN,
+    #[allow(non_camel_case_types)]
+    PIPE_ASSIGN,
+    #[allow(non_camel_case_types)]
+    CARET_ASSIGN,
+    #[allow(non_camel_case_types)]
+    LSHIFT_ASSIGN,
+    #[allow(non_camel_case_types)]
+    RSHIFT_ASSIGN,
+
     PLUS,
     MINUS,
     BANG,
     ASTERISK,
     SLASH,
+    PERCENT,
+    POWER,
+
+    AMPERSAND,
+    PIPE,
+    CARET,
+    TILDE,
+    LSHIFT,
+    RSHIFT,
 
     LT,
     GT,
+    #[allow(non_camel_case_types)]
+    LT_EQ,
+    #[allow(non_camel_case_types)]
+    GT_EQ,
+
+    AND,
+    OR,
+
+    #[allow(non_camel_case_types)]
+    DOT_DOT,
+    #[allow(non_camel_case_types)]
+    DOT_DOT_EQ,
+    ELLIPSIS,
 
     EQ,
     #[allow(non_camel_case_types)]
@@ -29,6 +75,7 @@ pub enum TokenType {
     COMMA,
     SEMICOLON,
     COLON,
+    ARROW,
 
     LPAREN,
     RPAREN,
@@ -46,21 +93,33 @@ pub enum TokenType {
     ELSE,
     RETURN,
     MACRO,
+    WHILE,
+    FOR,
+    IN,
+    BREAK,
+    CONTINUE,
+    MATCH,
 }
 
-pub fn compute_keyword_map(map: &mut HashMap<&'static str, TokenType>) {
-    let keywords = [
-        ("fn", TokenType::FUNCTION),
-        ("let", TokenType::LET),
-        ("true", TokenType::TRUE),
-        ("false", TokenType::FALSE),
-        ("if", TokenType::IF),
-        ("else", TokenType::ELSE),
-        ("return", TokenType::RETURN),
-        ("macro", TokenType::MACRO),
-    ];
+pub const KEYWORDS: [(&str, TokenType); 14] = [
+    ("fn", TokenType::FUNCTION),
+    ("let", TokenType::LET),
+    ("true", TokenType::TRUE),
+    ("false", TokenType::FALSE),
+    ("if", TokenType::IF),
+    ("else", TokenType::ELSE),
+    ("return", TokenType::RETURN),
+    ("macro", TokenType::MACRO),
+    ("while", TokenType::WHILE),
+    ("for", TokenType::FOR),
+    ("in", TokenType::IN),
+    ("break", TokenType::BREAK),
+    ("continue", TokenType::CONTINUE),
+    ("match", TokenType::MATCH),
+];
 
-    for t in keywords {
+pub fn compute_keyword_map(map: &mut HashMap<&'static str, TokenType>) {
+    for t in KEYWORDS {
         map.insert(t.0, t.1);
     };
 }
@@ -2,11 +2,16 @@
 pub mod interpreter;
 pub mod parser;
 pub mod error;
+pub mod diagnostic;
 mod object;
+mod arithmetic;
 mod builtin;
 pub mod lexer;
 pub mod compiler;
+pub mod engine;
 pub mod repl;
+pub mod runner;
+pub mod cli;
 pub mod ast;
 
 #[cfg(test)]
@@ -12,11 +12,9 @@ macro_rules! mhash {
         let mut pairs = std::collections::HashMap::new();
 
         $(
-            let key = match $k.clone() {
-                $crate::object::MObject::Str(x) => $crate::object::HashKey::Str(x),
-                $crate::object::MObject::Int(x) => $crate::object::HashKey::Int(x),
-                $crate::object::MObject::Bool(x) => $crate::object::HashKey::Bool(x),
-                _ => panic!("Expected key to be Int, Str, or Bool. Got: {:?}", $k),
+            let key = match $k.hash_key() {
+                Some(x) => x,
+                None => panic!("Expected key to be Int, BigInt, Str, or Bool. Got: {:?}", $k),
             };
 
             let pair = $crate::object::HashPair {
@@ -69,7 +67,9 @@ macro_rules! merr {
     ( $value:expr ) => ({
         $crate::object::MObject::Err(
             $crate::object::MError {
-                value: $value.to_string()
+                value: $value.to_string(),
+                span: $crate::lexer::span::Span::default(),
+                note: None,
             }
         )
     });
@@ -78,7 +78,7 @@ macro_rules! merr {
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
-    use crate::{test_utils::*, object::*};
+    use crate::{test_utils::*, object::*, lexer::span::Span};
 
     #[test]
     fn test_empty_mhash() {
@@ -147,6 +147,8 @@ mod tests {
         let expected = MObject::Err(
             MError {
                 value: "arguments to `first` must be ARRAY, got 1".to_string(),
+                span: Span::default(),
+                note: None,
             }
         );
 
@@ -2,9 +2,14 @@ use crate::{
     ast::{self, MNode},
     builtin::Builtin,
     interpreter::environment::Environment,
-    compiler::code::{Instructions, MCode},
+    compiler::code::{Instructions, MCode, SpanTable},
+    error::RuntimeErrorKind,
+    lexer::span::Span,
 };
-use std::{fmt, collections::HashMap, cell::RefCell, rc::Rc};
+use std::{fmt, cmp::Ordering, collections::HashMap, cell::RefCell, hash::{Hash, Hasher}, rc::Rc};
+
+use num_bigint::BigInt;
+use num_traits::{ToPrimitive, Signed};
 
 pub const TRUE: MObject = MObject::Bool(Boolean { value: true });
 pub const FALSE: MObject = MObject::Bool(Boolean { value: false });
@@ -21,6 +26,54 @@ impl fmt::Display for Integer {
     }
 }
 
+// An integer too big for an i128. Arithmetic always demotes results that fit back into an
+// `Integer`, so a value only ever has one representation.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug, Hash)]
+pub struct BigInteger {
+    pub value: BigInt,
+}
+
+impl fmt::Display for BigInteger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+#[derive(PartialOrd, Copy, Clone, Debug)]
+pub struct Float {
+    pub value: f64,
+}
+
+impl Float {
+    // The bit pattern objects are compared and hashed by, so NaN equals itself and `Eq` holds. The
+    // `==` operator still follows IEEE 754, see `arithmetic::float_infix_op`.
+    fn bits(&self) -> u64 {
+        if self.value == 0.0 { 0 } else { self.value.to_bits() }
+    }
+}
+
+impl PartialEq for Float {
+    fn eq(&self, other: &Self) -> bool {
+        self.bits() == other.bits()
+    }
+}
+
+impl Eq for Float {}
+
+impl Hash for Float {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.bits().hash(state);
+    }
+}
+
+// Floats always print with a fraction or an exponent so they can't be confused with integers, e.g.
+// `3.0`, `0.1`, `1e-9`, `inf`, `NaN`.
+impl fmt::Display for Float {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.value)
+    }
+}
+
 #[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug, Hash)]
 pub struct Boolean {
     pub value: bool,
@@ -61,11 +114,44 @@ impl fmt::Display for MArray {
     }
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug, Hash)]
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
 pub enum HashKey {
     Str(MString),
     Bool(Boolean),
     Int(Integer),
+    BigInt(BigInteger),
+}
+
+// Strings sort before booleans and booleans before numbers, which sort by value whether or not
+// they fit in an i128. A `for` loop visits the keys of a hash in this order.
+impl Ord for HashKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (HashKey::Str(a), HashKey::Str(b)) => a.cmp(b),
+            (HashKey::Bool(a), HashKey::Bool(b)) => a.cmp(b),
+            (HashKey::Int(a), HashKey::Int(b)) => a.cmp(b),
+            (HashKey::BigInt(a), HashKey::BigInt(b)) => a.cmp(b),
+            (HashKey::Int(a), HashKey::BigInt(b)) => BigInt::from(a.value).cmp(&b.value),
+            (HashKey::BigInt(a), HashKey::Int(b)) => a.value.cmp(&BigInt::from(b.value)),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for HashKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl HashKey {
+    fn rank(&self) -> u8 {
+        match self {
+            HashKey::Str(_) => 0,
+            HashKey::Bool(_) => 1,
+            HashKey::Int(_) | HashKey::BigInt(_) => 2,
+        }
+    }
 }
 
 impl fmt::Display for HashKey {
@@ -74,6 +160,7 @@ impl fmt::Display for HashKey {
             HashKey::Str(x) => write!(f, "{}", x),
             HashKey::Bool(x) => write!(f, "{}", x),
             HashKey::Int(x) => write!(f, "{}", x),
+            HashKey::BigInt(x) => write!(f, "{}", x),
         }
     }
 }
@@ -102,6 +189,27 @@ impl fmt::Display for MHash {
     }
 }
 
+// The integers from `start` up to `end`, including `end` when the range was written with `..=`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct MRange {
+    pub start: i128,
+    pub end: i128,
+    pub inclusive: bool,
+}
+
+impl MRange {
+    // The end of the range as an exclusive bound.
+    pub fn exclusive_end(&self) -> i128 {
+        if self.inclusive { self.end.saturating_add(1) } else { self.end }
+    }
+}
+
+impl fmt::Display for MRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}{}", self.start, if self.inclusive { "..=" } else { ".." }, self.end)
+    }
+}
+
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct ReturnValue {
     pub value: Box<MObject>,
@@ -116,6 +224,10 @@ impl fmt::Display for ReturnValue {
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug, Hash)]
 pub struct MError {
     pub value: String,
+    // Where the error was raised, the evaluator fills it in once the error reaches an AST node.
+    pub span: Span,
+    // Shown below the error, e.g. a suggestion for a misspelled name.
+    pub note: Option<String>,
 }
 
 impl fmt::Display for MError {
@@ -126,48 +238,159 @@ impl fmt::Display for MError {
 
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct Function {
-    pub params: Vec<ast::Identifier>,
+    pub name: Option<String>,
+    pub params: Vec<ast::Parameter>,
+    pub rest: Option<Box<ast::Identifier>>,
     pub body: ast::BlockStatement,
     pub env: Rc<RefCell<Environment>>,
 }
 
+impl Function {
+    pub fn check_arity(&self, actual: usize) -> std::result::Result<(), RuntimeErrorKind> {
+        let required = self.params.iter().take_while(|p| p.default.is_none()).count();
+        let max = if self.rest.is_some() { None } else { Some(self.params.len()) };
+
+        check_arity(&self.name, required, max, actual)
+    }
+
+    pub fn bind_arguments(&self, args: Vec<MObject>, named: Vec<(String, MObject)>) -> std::result::Result<Arguments, RuntimeErrorKind> {
+        let params = self.params.iter().map(|p| p.name.value.as_str()).collect::<Vec<&str>>();
+        let required = self.params.iter().take_while(|p| p.default.is_none()).count();
+
+        bind_arguments(&self.name, &params, required, self.rest.is_some(), args, named)
+    }
+}
+
 impl fmt::Display for Function {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "fn(")?;
-        write!(
-            f,
-            "{}",
-            self.params.iter()
-                .map(|p| format!("{}", p))
-                .collect::<Vec<String>>()
-                .join(", ")
-        )?;
-        write!(f, ") {}", self.body)
+        let mut params = self.params.iter().map(|p| format!("{}", p)).collect::<Vec<String>>();
+        if let Some(rest) = &self.rest {
+            params.push(format!("...{}", rest));
+        };
+
+        write!(f, "fn({}) {}", params.join(", "), self.body)
     }
 }
 
-#[derive(PartialEq, Eq, Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct CompiledFunction {
     pub instructions: Instructions,
+    pub spans: SpanTable,
     pub num_locals: u8,
+    // Includes the parameters with a default value but not the rest parameter, which is the local
+    // right after them.
     pub num_params: u8,
+    pub name: Option<String>,
+    // The names of the parameters, for binding named arguments.
+    pub params: Rc<[String]>,
+    // Where the instructions setting each default value start, a call that leaves out parameters
+    // starts at the first one missing and sets the ones after it too before the body.
+    pub defaults: Vec<usize>,
+    pub rest: bool,
+}
+
+// Like spans, the span table never takes part in equality.
+impl PartialEq for CompiledFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.instructions == other.instructions &&
+            self.num_locals == other.num_locals &&
+            self.num_params == other.num_params &&
+            self.name == other.name &&
+            self.params == other.params &&
+            self.defaults == other.defaults &&
+            self.rest == other.rest
+    }
+}
+
+impl Eq for CompiledFunction {}
+
+impl CompiledFunction {
+    pub fn required_params(&self) -> usize {
+        self.num_params as usize - self.defaults.len()
+    }
+
+    pub fn check_arity(&self, actual: usize) -> std::result::Result<(), RuntimeErrorKind> {
+        let max = if self.rest { None } else { Some(self.num_params as usize) };
+
+        check_arity(&self.name, self.required_params(), max, actual)
+    }
+
+    pub fn bind_arguments(&self, args: Vec<MObject>, named: Vec<(String, MObject)>) -> std::result::Result<Arguments, RuntimeErrorKind> {
+        let params = self.params.iter().map(String::as_str).collect::<Vec<&str>>();
+
+        bind_arguments(&self.name, &params, self.required_params(), self.rest, args, named)
+    }
 }
 
 impl fmt::Display for CompiledFunction {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "CompiledFunction {{\nnum_locals: {},\nnum_params: {}\ninstructions: [\n{}]",
+            "CompiledFunction {{\nname: {},\nnum_locals: {},\nnum_params: {},\ndefaults: {:?},\nrest: {}\ninstructions: [\n{}]",
+            self.name.as_deref().unwrap_or("fn"),
             self.num_locals,
             self.num_params,
+            self.defaults,
+            self.rest,
             MCode::new().format(&self.instructions)
         )
     }
 }
 
+// A function takes between `min` and `max` arguments, or any number from `min` with a rest parameter.
+fn check_arity(function: &Option<String>, min: usize, max: Option<usize>, actual: usize) -> std::result::Result<(), RuntimeErrorKind> {
+    if actual < min || max.is_some_and(|max| actual > max) {
+        return Err(RuntimeErrorKind::WrongArgumentCount { function: function.clone(), min, max, actual });
+    };
+
+    Ok(())
+}
+
+// The value of each parameter, None for the ones left out, and the extra positional arguments that
+// go to a rest parameter.
+pub type Arguments = (Vec<Option<MObject>>, Vec<MObject>);
+
+// Binds the positional arguments of a call in order and the named ones to the parameter with their
+// name. Only the parameters with a default value can be left out.
+fn bind_arguments(
+    function: &Option<String>,
+    params: &[&str],
+    required: usize,
+    rest: bool,
+    mut args: Vec<MObject>,
+    named: Vec<(String, MObject)>,
+) -> std::result::Result<Arguments, RuntimeErrorKind> {
+    let actual = args.len() + named.len();
+    let extra = args.split_off(args.len().min(params.len()));
+    if !extra.is_empty() && !rest {
+        return Err(RuntimeErrorKind::WrongArgumentCount { function: function.clone(), min: required, max: Some(params.len()), actual });
+    };
+
+    let mut values = args.into_iter().map(Some).collect::<Vec<Option<MObject>>>();
+    values.resize(params.len(), None);
+
+    for (name, value) in named {
+        let i = match params.iter().position(|p| *p == name) {
+            Some(i) => i,
+            None => return Err(RuntimeErrorKind::UnknownArgument { function: function.clone(), name }),
+        };
+        if values[i].is_some() {
+            return Err(RuntimeErrorKind::DuplicateArgument { function: function.clone(), name });
+        };
+        values[i] = Some(value);
+    }
+
+    if let Some(i) = values[..required].iter().position(Option::is_none) {
+        return Err(RuntimeErrorKind::MissingArgument { function: function.clone(), name: params[i].to_string() });
+    };
+
+    Ok((values, extra))
+}
+
+// The function is shared, calls clone the closure they run.
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct Closure {
-    pub f: CompiledFunction,
+    pub f: Rc<CompiledFunction>,
     pub free: Vec<MObject>,
 }
 
@@ -186,9 +409,61 @@ impl fmt::Display for Closure {
     }
 }
 
+// A local variable captured by a closure in the VM. The function that defines the variable and every
+// closure that captures it share the cell, so an assignment through any of them is seen by all.
+// Cells only live in local slots and in `Closure.free`, reads always see the value inside.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct MCell {
+    pub value: Rc<RefCell<MObject>>,
+}
+
+impl fmt::Display for MCell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value.borrow())
+    }
+}
+
+// The state of a `for` loop. In the VM it sits on the stack while the loop runs and hands out the
+// elements of the iterable one at a time. Ranges produce their integers as they go instead of
+// allocating them up front.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum MIterator {
+    Elements { elements: Vec<MObject>, position: usize },
+    Range { next: Option<i128>, last: i128 },
+}
+
+impl Iterator for MIterator {
+    type Item = MObject;
+
+    fn next(&mut self) -> Option<MObject> {
+        match self {
+            MIterator::Elements { elements, position } => {
+                let element = elements.get(*position)?.clone();
+                *position += 1;
+                Some(element)
+            },
+            MIterator::Range { next, last } => {
+                let value = (*next)?;
+                *next = if value < *last { Some(value + 1) } else { None };
+                Some(MObject::Int(Integer { value }))
+            },
+        }
+    }
+}
+
+impl fmt::Display for MIterator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MIterator::Elements { elements, position } => write!(f, "ITERATOR({}/{})", position, elements.len()),
+            MIterator::Range { next: Some(next), last } => write!(f, "ITERATOR({}..={})", next, last),
+            MIterator::Range { next: None, .. } => write!(f, "ITERATOR(done)"),
+        }
+    }
+}
+
 #[derive(PartialEq, Eq, Clone, Debug, Hash)]
 pub struct Quote {
-    pub node: MNode,
+    pub node: Box<MNode>,
 }
 
 impl fmt::Display for Quote {
@@ -222,38 +497,377 @@ impl fmt::Display for Macro {
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub enum MObject {
     Int(Integer),
+    BigInt(BigInteger),
+    Float(Float),
     Bool(Boolean),
     Str(MString),
     Array(MArray),
     Hash(MHash),
+    Range(MRange),
     Return(ReturnValue),
     Err(MError),
     Fn(Function),
     CompiledFn(CompiledFunction),
     Closure(Closure),
+    Cell(MCell),
+    Iter(MIterator),
     Builtin(Builtin),
     Quote(Quote),
     Macro(Macro),
+    // `break` and `continue` unwinding the evaluator to the enclosing loop.
+    Break,
+    Continue,
     Null,
 }
 
+impl MObject {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            MObject::Int(_) => "INTEGER",
+            MObject::BigInt(_) => "INTEGER",
+            MObject::Float(_) => "FLOAT",
+            MObject::Bool(_) => "BOOLEAN",
+            MObject::Str(_) => "STRING",
+            MObject::Array(_) => "ARRAY",
+            MObject::Hash(_) => "HASH",
+            MObject::Range(_) => "RANGE",
+            MObject::Return(_) => "RETURN_VALUE",
+            MObject::Err(_) => "ERROR",
+            MObject::Fn(_) => "FUNCTION",
+            MObject::CompiledFn(_) => "COMPILED_FUNCTION",
+            MObject::Closure(_) => "CLOSURE",
+            MObject::Cell(_) => "CELL",
+            MObject::Iter(_) => "ITERATOR",
+            MObject::Builtin(_) => "BUILTIN",
+            MObject::Quote(_) => "QUOTE",
+            MObject::Macro(_) => "MACRO",
+            MObject::Break => "BREAK",
+            MObject::Continue => "CONTINUE",
+            MObject::Null => "NULL",
+        }
+    }
+
+    // Wraps an arbitrary-precision result, using the small representation when it fits.
+    pub fn from_bigint(value: BigInt) -> MObject {
+        match value.to_i128() {
+            Some(value) => MObject::Int(Integer { value }),
+            None => MObject::BigInt(BigInteger { value }),
+        }
+    }
+
+    pub fn to_bigint(&self) -> Option<BigInt> {
+        match self {
+            MObject::Int(x) => Some(BigInt::from(x.value)),
+            MObject::BigInt(x) => Some(x.value.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn to_f64(&self) -> Option<f64> {
+        match self {
+            MObject::Int(x) => Some(x.value as f64),
+            MObject::BigInt(x) => x.value.to_f64(),
+            MObject::Float(x) => Some(x.value),
+            _ => None,
+        }
+    }
+
+    pub fn hash_key(&self) -> Option<HashKey> {
+        match self {
+            MObject::Str(x) => Some(HashKey::Str(x.clone())),
+            MObject::Int(x) => Some(HashKey::Int(*x)),
+            MObject::BigInt(x) => Some(HashKey::BigInt(x.clone())),
+            MObject::Bool(x) => Some(HashKey::Bool(*x)),
+            _ => None,
+        }
+    }
+
+    // What a `for` loop visits: the elements of an array, the keys of a hash in sorted order, the
+    // characters of a string or the integers in a range.
+    pub fn iter(&self) -> Option<MIterator> {
+        let elements = match self {
+            MObject::Array(x) => x.elements.clone(),
+            MObject::Hash(x) => {
+                let mut pairs = x.pairs.iter().collect::<Vec<_>>();
+                pairs.sort_by(|a, b| a.0.cmp(b.0));
+                pairs.into_iter().map(|(_, pair)| pair.key.clone()).collect()
+            },
+            MObject::Str(x) => x.value.chars().map(|c| MObject::Str(MString { value: c.to_string() })).collect(),
+            MObject::Range(x) => {
+                let last = if x.inclusive { Some(x.end) } else { x.end.checked_sub(1) };
+                return Some(match last {
+                    Some(last) if x.start <= last => MIterator::Range { next: Some(x.start), last },
+                    _ => MIterator::Range { next: None, last: x.start },
+                });
+            },
+            _ => return None,
+        };
+
+        Some(MIterator::Elements { elements, position: 0 })
+    }
+
+    pub fn range(start: &MObject, end: &MObject, inclusive: bool) -> std::result::Result<MObject, RuntimeErrorKind> {
+        let operator = if inclusive { "..=" } else { ".." };
+        match (start, end) {
+            (MObject::Int(start), MObject::Int(end)) => {
+                Ok(MObject::Range(MRange { start: start.value, end: end.value, inclusive }))
+            },
+            (MObject::Int(_) | MObject::BigInt(_), MObject::Int(_) | MObject::BigInt(_)) => {
+                Err(RuntimeErrorKind::InvalidOperand { operator: operator.to_string(), reason: "range bound too large" })
+            },
+            _ => Err(RuntimeErrorKind::TypeMismatch { left: start.type_name(), operator: operator.to_string(), right: end.type_name() }),
+        }
+    }
+
+    // Copies part of an array or string. Null bounds were left out, negative bounds count back from
+    // the end and bounds past either end are clamped, like Python's slices.
+    pub fn slice(&self, start: &MObject, end: &MObject) -> std::result::Result<MObject, RuntimeErrorKind> {
+        let length = match self {
+            MObject::Array(x) => x.elements.len(),
+            MObject::Str(x) => x.value.chars().count(),
+            x => return Err(RuntimeErrorKind::NotSliceable(x.type_name())),
+        };
+
+        let start = slice_bound(start, length, 0)?;
+        let end = slice_bound(end, length, length)?.max(start);
+
+        match self {
+            MObject::Array(x) => Ok(MObject::Array(MArray { elements: x.elements[start..end].to_vec() })),
+            MObject::Str(x) => Ok(MObject::Str(MString { value: x.value.chars().skip(start).take(end - start).collect() })),
+            x => Err(RuntimeErrorKind::NotSliceable(x.type_name())),
+        }
+    }
+
+    // Arrays and strings are indexed by element, strings by character rather than by byte. A negative
+    // index counts back from the end. An index out of range is null, or an error in strict mode.
+    pub fn element(&self, index: i128, strict: bool) -> std::result::Result<MObject, RuntimeErrorKind> {
+        let length = match self {
+            MObject::Array(x) => x.elements.len(),
+            MObject::Str(x) => x.value.chars().count(),
+            x => return Err(RuntimeErrorKind::NotIndexable { left: x.type_name(), index: "INTEGER" }),
+        };
+
+        let element = element_index(index, length).and_then(|i| {
+            match self {
+                MObject::Array(x) => x.elements.get(i).cloned(),
+                MObject::Str(x) => x.value.chars().nth(i).map(|c| MObject::Str(MString { value: c.to_string() })),
+                _ => None,
+            }
+        });
+
+        match element {
+            Some(x) => Ok(x),
+            None if strict => Err(RuntimeErrorKind::IndexOutOfRange { index, length }),
+            None => Ok(NULL),
+        }
+    }
+
+    // Whether the value has the shape of an array match pattern with `count` elements, or at least
+    // that many with a rest pattern.
+    pub fn matches_array(&self, count: usize, rest: bool) -> bool {
+        match self {
+            MObject::Array(x) if rest => x.elements.len() >= count,
+            MObject::Array(x) => x.elements.len() == count,
+            _ => false,
+        }
+    }
+
+    // Whether the value is a hash with all of the keys of a hash match pattern.
+    pub fn matches_hash(&self, keys: &[MObject]) -> bool {
+        match self {
+            MObject::Hash(x) => keys.iter().all(|key| key.hash_key().is_some_and(|k| x.pairs.contains_key(&k))),
+            _ => false,
+        }
+    }
+
+    // The values for `let [a, b, ...rest] = arr;`: the first `count` elements followed, with a rest
+    // name, by an array of the remaining ones. Without a rest name the lengths have to match.
+    pub fn destructure_array(&self, count: usize, rest: bool) -> std::result::Result<Vec<MObject>, RuntimeErrorKind> {
+        let elements = match self {
+            MObject::Array(x) => &x.elements,
+            x => return Err(RuntimeErrorKind::NotDestructurable { pattern: "ARRAY", value: x.type_name() }),
+        };
+
+        let actual = elements.len();
+        if actual < count || (!rest && actual > count) {
+            return Err(RuntimeErrorKind::DestructureLength { expected: count, actual, rest });
+        };
+
+        let mut values = elements[..count].to_vec();
+        if rest {
+            values.push(MObject::Array(MArray { elements: elements[count..].to_vec() }));
+        };
+
+        Ok(values)
+    }
+
+    // The values for `let {"name": n} = h;`, every key has to be in the hash.
+    pub fn destructure_hash(&self, keys: &[MObject]) -> std::result::Result<Vec<MObject>, RuntimeErrorKind> {
+        let hash = match self {
+            MObject::Hash(x) => x,
+            x => return Err(RuntimeErrorKind::NotDestructurable { pattern: "HASH", value: x.type_name() }),
+        };
+
+        keys
+            .iter()
+            .map(|key| {
+                let hash_key = key.hash_key().ok_or(RuntimeErrorKind::UnusableHashKey(key.type_name()))?;
+                match hash.pairs.get(&hash_key) {
+                    Some(pair) => Ok(pair.value.clone()),
+                    None => Err(RuntimeErrorKind::MissingKey(key.to_string())),
+                }
+            })
+            .collect()
+    }
+
+    // Arrays and hashes are values, so assigning to an element returns an updated copy. Arrays
+    // don't grow through assignment, the index has to be in bounds.
+    pub fn set_index(self, index: MObject, value: MObject) -> std::result::Result<MObject, RuntimeErrorKind> {
+        match (self, index) {
+            (MObject::Array(mut arr), MObject::Int(i)) => {
+                let length = arr.elements.len();
+                match element_index(i.value, length) {
+                    Some(i) => arr.elements[i] = value,
+                    None => return Err(RuntimeErrorKind::IndexOutOfRange { index: i.value, length }),
+                };
+                Ok(MObject::Array(arr))
+            },
+            (MObject::Hash(mut hash), key) => {
+                let hash_key = match key.hash_key() {
+                    Some(x) => x,
+                    None => return Err(RuntimeErrorKind::UnusableHashKey(key.type_name())),
+                };
+                hash.pairs.insert(hash_key, HashPair { key, value });
+                Ok(MObject::Hash(hash))
+            },
+            (MObject::Array(_), index) => Err(RuntimeErrorKind::NotIndexable { left: "ARRAY", index: index.type_name() }),
+            (obj, _) => Err(RuntimeErrorKind::NotAssignable(obj.type_name())),
+        }
+    }
+
+    // `arr[range]` is the same as the slice `arr[start:end]`, so `arr[-3..=-1]` is the last three elements.
+    pub fn slice_range(&self, range: &MRange) -> std::result::Result<MObject, RuntimeErrorKind> {
+        let end = match range.exclusive_end() {
+            0 if range.end < 0 => NULL,
+            end => MObject::Int(Integer { value: end }),
+        };
+
+        self.slice(&MObject::Int(Integer { value: range.start }), &end)
+    }
+}
+
+fn element_index(index: i128, length: usize) -> Option<usize> {
+    let index = if index < 0 { index + length as i128 } else { index };
+
+    usize::try_from(index).ok().filter(|&i| i < length)
+}
+
+fn slice_bound(bound: &MObject, length: usize, default: usize) -> std::result::Result<usize, RuntimeErrorKind> {
+    let index = match bound {
+        MObject::Null => return Ok(default),
+        MObject::Int(x) => x.value,
+        MObject::BigInt(x) if x.value.is_negative() => i128::MIN,
+        MObject::BigInt(_) => i128::MAX,
+        x => return Err(RuntimeErrorKind::InvalidSliceIndex(x.type_name())),
+    };
+
+    let length = length as i128;
+    let index = if index < 0 { index + length } else { index };
+
+    Ok(index.clamp(0, length) as usize)
+}
+
 impl fmt::Display for MObject {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             MObject::Int(x) => write!(f, "{}", x),
+            MObject::BigInt(x) => write!(f, "{}", x),
+            MObject::Float(x) => write!(f, "{}", x),
             MObject::Bool(x) => write!(f, "{}", x),
             MObject::Str(x) => write!(f, "{}", x),
             MObject::Array(x) => write!(f, "{}", x),
             MObject::Hash(x) => write!(f, "{}", x),
+            MObject::Range(x) => write!(f, "{}", x),
             MObject::Return(x) => write!(f, "{}", x),
             MObject::Err(x) => write!(f, "{}", x),
             MObject::Fn(x) => write!(f, "{}", x),
             MObject::CompiledFn(x) => write!(f, "{}", x),
             MObject::Closure(x) => write!(f, "{}", x),
+            MObject::Cell(x) => write!(f, "{}", x),
+            MObject::Iter(x) => write!(f, "{}", x),
             MObject::Builtin(x) => write!(f, "{}", x),
             MObject::Quote(x) => write!(f, "{}", x),
             MObject::Macro(x) => write!(f, "{}", x),
+            MObject::Break => write!(f, "break"),
+            MObject::Continue => write!(f, "continue"),
             MObject::Null => write!(f, "null"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::hash_map::DefaultHasher;
+
+    fn hash_of(x: &Float) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        x.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_float_equality() {
+        let nan = Float { value: f64::NAN };
+        let zero = Float { value: 0.0 };
+        let negative_zero = Float { value: -0.0 };
+
+        assert_eq!(nan, nan);
+        assert_eq!(hash_of(&nan), hash_of(&nan));
+        assert_eq!(zero, negative_zero);
+        assert_eq!(hash_of(&zero), hash_of(&negative_zero));
+        assert_ne!(Float { value: 1.0 }, Float { value: 1.5 });
+    }
+
+    #[test]
+    fn test_element() {
+        let int = |value| MObject::Int(Integer { value });
+        let string = |value: &str| MObject::Str(MString { value: value.to_string() });
+        let array = MObject::Array(MArray { elements: vec![int(1), int(2), int(3)] });
+        let word = string("héllo");
+        let empty = MObject::Array(MArray { elements: vec![] });
+
+        let tests = vec![
+            (&array, 0, Ok(int(1))),
+            (&array, 2, Ok(int(3))),
+            (&array, -1, Ok(int(3))),
+            (&array, -3, Ok(int(1))),
+            (&array, 3, Ok(NULL)),
+            (&array, -4, Ok(NULL)),
+            (&word, 1, Ok(string("é"))),
+            (&word, -1, Ok(string("o"))),
+            (&word, -5, Ok(string("h"))),
+            (&word, 5, Ok(NULL)),
+            (&word, -6, Ok(NULL)),
+            (&NULL, 0, Err(RuntimeErrorKind::NotIndexable { left: "NULL", index: "INTEGER" })),
+        ];
+
+        for (value, index, expected) in tests {
+            assert_eq!(expected, value.element(index, false), "{}[{}]", value, index);
+        };
+
+        let strict_tests = vec![
+            (&array, -1, Ok(int(3))),
+            (&word, 4, Ok(string("o"))),
+            (&array, 3, Err(RuntimeErrorKind::IndexOutOfRange { index: 3, length: 3 })),
+            (&array, -4, Err(RuntimeErrorKind::IndexOutOfRange { index: -4, length: 3 })),
+            (&word, -6, Err(RuntimeErrorKind::IndexOutOfRange { index: -6, length: 5 })),
+            (&empty, 0, Err(RuntimeErrorKind::IndexOutOfRange { index: 0, length: 0 })),
+        ];
+
+        for (value, index, expected) in strict_tests {
+            assert_eq!(expected, value.element(index, true), "{}[{}]", value, index);
+        };
+    }
+}
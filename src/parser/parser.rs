@@ -1,19 +1,30 @@
 use std::{iter::Peekable, collections::HashMap};
 
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+
 use crate::{
     lexer::{
         token::Token,
         token_type::TokenType,
+        span::Span,
     },
     parser::precedence::Precedence,
-    error::{Result, Error},
+    error::{Result, Error, ParseError, ParseErrorKind},
     ast::*,
 };
 
 pub struct Parser<I: Iterator<Item = Result<Token>>> {
     l: Peekable<I>,
     tok: Token,
-    errors: Vec<String>,
+    errors: Vec<ParseError>,
+    // An error reading the input, which ends parsing. Errors in the source itself are kept in
+    // `errors`.
+    read_error: Option<Error>,
+    // The number of unclosed `{` before the current token.
+    depth: usize,
+    // The number of loops around the current token, within the innermost function.
+    loops: usize,
     prefix_parse_fns: HashMap<TokenType, fn(&mut Self) -> Option<Expr>>,
     infix_parse_fns: HashMap<TokenType, fn(&mut Self, Expr) -> Option<Expr>>,
     precedences: HashMap<TokenType, Precedence>,
@@ -23,7 +34,7 @@ impl<I: Iterator<Item = Result<Token>>> Parser<I> {
     pub fn new(mut l: Peekable<I>) -> Result<Self> {
         let tok = match l.next() {
             Some(t) => t?,
-            None => return Err(Error::new(String::from("Unexpected EOF."))),
+            None => return Err(Error::Parse(vec![ParseError::new(ParseErrorKind::UnexpectedEof, Span::default())])),
         };
 
         let mut precedences = HashMap::new();
@@ -33,6 +44,9 @@ impl<I: Iterator<Item = Result<Token>>> Parser<I> {
             l,
             tok,
             errors: Vec::new(),
+            read_error: None,
+            depth: 0,
+            loops: 0,
             prefix_parse_fns: HashMap::new(),
             infix_parse_fns: HashMap::new(),
             precedences,
@@ -40,12 +54,17 @@ impl<I: Iterator<Item = Result<Token>>> Parser<I> {
 
         p.register_prefix(TokenType::IDENT, Self::parse_identifier);
         p.register_prefix(TokenType::INT, Self::parse_integer_literal);
+        p.register_prefix(TokenType::FLOAT, Self::parse_float_literal);
         p.register_prefix(TokenType::TRUE, Self::parse_boolean);
         p.register_prefix(TokenType::FALSE, Self::parse_boolean);
         p.register_prefix(TokenType::BANG, Self::parse_prefix_expression);
         p.register_prefix(TokenType::MINUS, Self::parse_prefix_expression);
+        p.register_prefix(TokenType::TILDE, Self::parse_prefix_expression);
         p.register_prefix(TokenType::LPAREN, Self::parse_grouped_expression);
         p.register_prefix(TokenType::IF, Self::parse_if_expression);
+        p.register_prefix(TokenType::WHILE, Self::parse_while_expression);
+        p.register_prefix(TokenType::FOR, Self::parse_for_expression);
+        p.register_prefix(TokenType::MATCH, Self::parse_match_expression);
         p.register_prefix(TokenType::FUNCTION, Self::parse_function_expression);
         p.register_prefix(TokenType::STRING, Self::parse_string_expression);
         p.register_prefix(TokenType::LBRACKET, Self::parse_array_expression);
@@ -56,10 +75,35 @@ impl<I: Iterator<Item = Result<Token>>> Parser<I> {
         p.register_infix(TokenType::MINUS, Self::parse_infix_expression);
         p.register_infix(TokenType::SLASH, Self::parse_infix_expression);
         p.register_infix(TokenType::ASTERISK, Self::parse_infix_expression);
+        p.register_infix(TokenType::PERCENT, Self::parse_infix_expression);
+        p.register_infix(TokenType::POWER, Self::parse_infix_expression);
+        p.register_infix(TokenType::AMPERSAND, Self::parse_infix_expression);
+        p.register_infix(TokenType::PIPE, Self::parse_infix_expression);
+        p.register_infix(TokenType::CARET, Self::parse_infix_expression);
+        p.register_infix(TokenType::LSHIFT, Self::parse_infix_expression);
+        p.register_infix(TokenType::RSHIFT, Self::parse_infix_expression);
         p.register_infix(TokenType::EQ, Self::parse_infix_expression);
         p.register_infix(TokenType::NOT_EQ, Self::parse_infix_expression);
         p.register_infix(TokenType::LT, Self::parse_infix_expression);
         p.register_infix(TokenType::GT, Self::parse_infix_expression);
+        p.register_infix(TokenType::LT_EQ, Self::parse_infix_expression);
+        p.register_infix(TokenType::GT_EQ, Self::parse_infix_expression);
+        p.register_infix(TokenType::AND, Self::parse_infix_expression);
+        p.register_infix(TokenType::OR, Self::parse_infix_expression);
+        p.register_infix(TokenType::DOT_DOT, Self::parse_range_expression);
+        p.register_infix(TokenType::DOT_DOT_EQ, Self::parse_range_expression);
+        p.register_infix(TokenType::ASSIGN, Self::parse_assignment_expression);
+        p.register_infix(TokenType::PLUS_ASSIGN, Self::parse_assignment_expression);
+        p.register_infix(TokenType::MINUS_ASSIGN, Self::parse_assignment_expression);
+        p.register_infix(TokenType::ASTERISK_ASSIGN, Self::parse_assignment_expression);
+        p.register_infix(TokenType::SLASH_ASSIGN, Self::parse_assignment_expression);
+        p.register_infix(TokenType::PERCENT_ASSIGN, Self::parse_assignment_expression);
+        p.register_infix(TokenType::POWER_ASSIGN, Self::parse_assignment_expression);
+        p.register_infix(TokenType::AMPERSAND_ASSIGN, Self::parse_assignment_expression);
+        p.register_infix(TokenType::PIPE_ASSIGN, Self::parse_assignment_expression);
+        p.register_infix(TokenType::CARET_ASSIGN, Self::parse_assignment_expression);
+        p.register_infix(TokenType::LSHIFT_ASSIGN, Self::parse_assignment_expression);
+        p.register_infix(TokenType::RSHIFT_ASSIGN, Self::parse_assignment_expression);
         p.register_infix(TokenType::LPAREN, Self::parse_call_expression);
         p.register_infix(TokenType::LBRACKET, Self::parse_index_expression);
 
@@ -70,16 +114,21 @@ impl<I: Iterator<Item = Result<Token>>> Parser<I> {
         let mut program = Program::new();
 
         while !self.curr_token_is(TokenType::EOF) {
-            if let Some(stmt) = self.parse_statement() {
-                program.stmts.push(stmt);
+            let depth = self.depth;
+            match self.parse_statement() {
+                Some(stmt) => program.stmts.push(stmt),
+                None => self.synchronize(depth),
             }
-            self.next_token()?;
+            self.next_token();
         }
 
-        Ok(program)
+        match self.read_error.take() {
+            Some(e) => Err(e),
+            None => Ok(program),
+        }
     }
 
-    pub fn errors(&self) -> Vec<String> {
+    pub fn errors(&self) -> Vec<ParseError> {
         self.errors.clone()
     }
 
@@ -87,6 +136,7 @@ impl<I: Iterator<Item = Result<Token>>> Parser<I> {
         match self.tok.token_type {
             TokenType::LET => self.parse_let_statement(),
             TokenType::RETURN => self.parse_return_statement(),
+            TokenType::BREAK | TokenType::CONTINUE => self.parse_loop_control_statement(),
             _ => self.parse_expression_statement(),
         }
     }
@@ -94,6 +144,10 @@ impl<I: Iterator<Item = Result<Token>>> Parser<I> {
     fn parse_let_statement(&mut self) -> Option<Stmt> {
         let token = self.tok.clone();
 
+        if self.peek_token_is(TokenType::LBRACKET) || self.peek_token_is(TokenType::LBRACE) {
+            return self.parse_destructure_statement(token);
+        };
+
         self.expect_peek(TokenType::IDENT)?;
 
         let name = Identifier {
@@ -129,6 +183,85 @@ impl<I: Iterator<Item = Result<Token>>> Parser<I> {
         )
     }
 
+    fn parse_destructure_statement(&mut self, token: Token) -> Option<Stmt> {
+        self.ignore_next()?;
+
+        let pattern = if self.curr_token_is(TokenType::LBRACKET) {
+            self.parse_array_pattern()?
+        } else {
+            self.parse_hash_pattern()?
+        };
+
+        self.expect_peek(TokenType::ASSIGN)?;
+        self.ignore_next()?;
+
+        let value = self.parse_expression(Precedence::LOWEST)?;
+
+        if self.peek_token_is(TokenType::SEMICOLON) {
+            self.ignore_next()?;
+        }
+
+        Some(
+            Stmt::Destructure(
+                DestructureStatement {
+                    token,
+                    pattern: Box::new(pattern),
+                    value,
+                }
+            )
+        )
+    }
+
+    // `[a, b, ...rest]`, the rest name has to come last.
+    fn parse_array_pattern(&mut self) -> Option<Pattern> {
+        let token = self.tok.clone();
+        let mut names = Vec::new();
+        let mut rest = None;
+
+        while !self.peek_token_is(TokenType::RBRACKET) {
+            if self.peek_token_is(TokenType::ELLIPSIS) {
+                self.ignore_next()?;
+                self.expect_peek(TokenType::IDENT)?;
+                rest = Some(Identifier { token: self.tok.clone(), value: self.tok.literal.clone() });
+                break;
+            };
+
+            self.expect_peek(TokenType::IDENT)?;
+            names.push(Identifier { token: self.tok.clone(), value: self.tok.literal.clone() });
+
+            if !self.peek_token_is(TokenType::RBRACKET) {
+                self.expect_peek(TokenType::COMMA)?;
+            };
+        }
+
+        self.expect_peek(TokenType::RBRACKET)?;
+
+        Some(Pattern::Array(ArrayPattern { token, names, rest }))
+    }
+
+    // `{"name": n, "age": a}`, each key is an expression and each value the name it is bound to.
+    fn parse_hash_pattern(&mut self) -> Option<Pattern> {
+        let token = self.tok.clone();
+        let mut pairs = Vec::new();
+
+        while !self.peek_token_is(TokenType::RBRACE) {
+            self.ignore_next()?;
+
+            let key = self.parse_expression(Precedence::LOWEST)?;
+            self.expect_peek(TokenType::COLON)?;
+            self.expect_peek(TokenType::IDENT)?;
+            pairs.push((key, Identifier { token: self.tok.clone(), value: self.tok.literal.clone() }));
+
+            if !self.peek_token_is(TokenType::RBRACE) {
+                self.expect_peek(TokenType::COMMA)?;
+            };
+        }
+
+        self.expect_peek(TokenType::RBRACE)?;
+
+        Some(Pattern::Hash(HashPattern { token, pairs }))
+    }
+
     fn parse_return_statement(&mut self) -> Option<Stmt> {
         let token = self.tok.clone();
 
@@ -150,6 +283,24 @@ impl<I: Iterator<Item = Result<Token>>> Parser<I> {
         )
     }
 
+    fn parse_loop_control_statement(&mut self) -> Option<Stmt> {
+        let token = self.tok.clone();
+
+        if self.loops == 0 {
+            self.error(ParseErrorKind::OutsideLoop(token.token_type), token.span);
+            return None;
+        };
+
+        if self.peek_token_is(TokenType::SEMICOLON) {
+            self.ignore_next()?;
+        }
+
+        match token.token_type {
+            TokenType::BREAK => Some(Stmt::Break(BreakStatement { token })),
+            _ => Some(Stmt::Continue(ContinueStatement { token })),
+        }
+    }
+
     fn parse_block_statement(&mut self) -> Option<BlockStatement> {
         let token = self.tok.clone();
         let mut stmts = Vec::new();
@@ -157,16 +308,28 @@ impl<I: Iterator<Item = Result<Token>>> Parser<I> {
         self.ignore_next()?;
 
         while !self.curr_token_is(TokenType::RBRACE) && !self.curr_token_is(TokenType::EOF) {
-            if let Some(stmt) = self.parse_statement() {
-                stmts.push(stmt);
+            let depth = self.depth;
+            match self.parse_statement() {
+                Some(stmt) => stmts.push(stmt),
+                None => {
+                    self.synchronize(depth);
+                    if self.curr_token_is(TokenType::RBRACE) { break };
+                },
             }
             self.ignore_next()?;
         }
 
+        if self.curr_token_is(TokenType::EOF) {
+            let kind = ParseErrorKind::UnexpectedToken { expected: TokenType::RBRACE, actual: TokenType::EOF };
+            self.error(kind, self.tok.span);
+            return None;
+        };
+
         Some(
             BlockStatement {
                 token,
                 stmts,
+                close: self.tok.span.end,
             }
         )
     }
@@ -193,19 +356,16 @@ impl<I: Iterator<Item = Result<Token>>> Parser<I> {
         let mut left = if let Some(prefix) = self.prefix_parse_fns.get(&self.tok.token_type) {
             prefix(self)?
         } else {
-            self.errors.push(format!("Prefix parse function for {:?} not found.", self.tok.token_type));
+            self.error(ParseErrorKind::NoPrefixParseFn(self.tok.token_type), self.tok.span);
             return None;
         };
 
         while !self.peek_token_is(TokenType::SEMICOLON) && precedence < self.peek_precedence() {
-            let peeked = match self.l.peek() {
-                Some(p) => match p {
-                    Ok(tok) => tok.clone(),
-                    Err(_) => return Some(left),
-                },
+            let peeked = match self.peek() {
+                Some(tok) => tok.clone(),
                 None => return Some(left),
             };
-            if let Err(_) = self.next_token() { return Some(left) };
+            self.next_token();
 
             left = if let Some(infix) = self.infix_parse_fns.get(&peeked.token_type) {
                 infix(self, left)?
@@ -229,17 +389,41 @@ impl<I: Iterator<Item = Result<Token>>> Parser<I> {
     }
 
     fn parse_integer_literal(&mut self) -> Option<Expr> {
-        let lit = match self.tok.literal.parse::<i128>() {
+        let digits = self.tok.literal.replace('_', "");
+        let (radix, digits) = match digits.get(..2) {
+            Some("0x" | "0X") => (16, &digits[2..]),
+            Some("0o" | "0O") => (8, &digits[2..]),
+            Some("0b" | "0B") => (2, &digits[2..]),
+            _ => (10, &digits[..]),
+        };
+
+        let lit = match BigInt::parse_bytes(digits.as_bytes(), radix) {
+            Some(x) => x,
+            None => {
+                self.error(ParseErrorKind::InvalidInteger(self.tok.literal.clone()), self.tok.span);
+                return None
+            },
+        };
+
+        // Literals that fit stay on the i128 fast path, like the results of arithmetic.
+        match lit.to_i128() {
+            Some(value) => Some(Expr::Int(IntegerLiteral { token: self.tok.clone(), value })),
+            None => Some(Expr::BigInt(BigIntegerLiteral { token: self.tok.clone(), value: lit })),
+        }
+    }
+
+    fn parse_float_literal(&mut self) -> Option<Expr> {
+        let lit = match self.tok.literal.replace('_', "").parse::<f64>() {
             Ok(x) => x,
             Err(_) => {
-                self.errors.push(format!("Could not parse {} as integer", self.tok.literal));
+                self.error(ParseErrorKind::InvalidFloat(self.tok.literal.clone()), self.tok.span);
                 return None
             },
         };
 
         Some(
-            Expr::Int(
-                IntegerLiteral {
+            Expr::Float(
+                FloatLiteral {
                     token: self.tok.clone(),
                     value: lit,
                 }
@@ -261,11 +445,11 @@ impl<I: Iterator<Item = Result<Token>>> Parser<I> {
     fn parse_grouped_expression(&mut self) -> Option<Expr> {
         self.ignore_next()?;
 
-        let exp = self.parse_expression(Precedence::LOWEST);
+        let exp = self.parse_expression(Precedence::LOWEST)?;
 
         self.expect_peek(TokenType::RPAREN)?;
 
-        exp
+        Some(exp)
     }
 
     fn parse_if_expression(&mut self) -> Option<Expr> {
@@ -301,27 +485,288 @@ impl<I: Iterator<Item = Result<Token>>> Parser<I> {
         )
     }
 
-    fn parse_function_parameters(&mut self) -> Vec<Identifier> {
+    fn parse_while_expression(&mut self) -> Option<Expr> {
+        let token = self.tok.clone();
+
+        self.expect_peek(TokenType::LPAREN)?;
+        self.ignore_next()?;
+
+        let condition = self.parse_expression(Precedence::LOWEST)?;
+
+        self.expect_peek(TokenType::RPAREN)?;
+        self.expect_peek(TokenType::LBRACE)?;
+
+        let body = self.parse_loop_body()?;
+
+        Some(
+            Expr::While(
+                WhileLoop {
+                    token,
+                    condition: Box::new(condition),
+                    body,
+                }
+            )
+        )
+    }
+
+    fn parse_for_expression(&mut self) -> Option<Expr> {
+        let token = self.tok.clone();
+
+        self.expect_peek(TokenType::LPAREN)?;
+        self.expect_peek(TokenType::IDENT)?;
+
+        let variable = Identifier {
+            token: self.tok.clone(),
+            value: self.tok.literal.clone(),
+        };
+
+        self.expect_peek(TokenType::IN)?;
+        self.ignore_next()?;
+
+        let iterable = self.parse_expression(Precedence::LOWEST)?;
+
+        self.expect_peek(TokenType::RPAREN)?;
+        self.expect_peek(TokenType::LBRACE)?;
+
+        let body = self.parse_loop_body()?;
+
+        Some(
+            Expr::For(
+                ForLoop {
+                    token,
+                    variable,
+                    iterable: Box::new(iterable),
+                    body,
+                }
+            )
+        )
+    }
+
+    fn parse_match_expression(&mut self) -> Option<Expr> {
+        let token = self.tok.clone();
+
+        self.expect_peek(TokenType::LPAREN)?;
+        self.ignore_next()?;
+
+        let subject = self.parse_expression(Precedence::LOWEST)?;
+
+        self.expect_peek(TokenType::RPAREN)?;
+        self.expect_peek(TokenType::LBRACE)?;
+
+        let mut arms = Vec::new();
+        while !self.peek_token_is(TokenType::RBRACE) {
+            self.ignore_next()?;
+            arms.push(self.parse_match_arm()?);
+
+            if !self.peek_token_is(TokenType::RBRACE) {
+                self.expect_peek(TokenType::COMMA)?;
+            };
+        }
+
+        self.expect_peek(TokenType::RBRACE)?;
+
+        Some(
+            Expr::Match(
+                MatchExpression {
+                    token,
+                    subject: Box::new(subject),
+                    arms,
+                }
+            )
+        )
+    }
+
+    // `pattern [if guard] => body`, the body is a block or a single expression.
+    fn parse_match_arm(&mut self) -> Option<MatchArm> {
+        let pattern = self.parse_match_pattern()?;
+
+        let mut guard = None;
+        if self.peek_token_is(TokenType::IF) {
+            self.ignore_next()?;
+            self.ignore_next()?;
+            guard = Some(self.parse_expression(Precedence::LOWEST)?);
+        };
+
+        self.expect_peek(TokenType::ARROW)?;
+        self.ignore_next()?;
+
+        let body = if self.curr_token_is(TokenType::LBRACE) {
+            self.parse_block_statement()?
+        } else {
+            let token = self.tok.clone();
+            let expr = self.parse_expression(Precedence::LOWEST)?;
+            BlockStatement {
+                token: token.clone(),
+                close: expr.span().end,
+                stmts: vec![Stmt::Expression(ExpressionStatement { token, expr })],
+            }
+        };
+
+        Some(MatchArm { pattern, guard, body })
+    }
+
+    fn parse_match_pattern(&mut self) -> Option<MatchPattern> {
+        let token_type = self.tok.token_type;
+        match token_type {
+            TokenType::IDENT if self.tok.literal == "_" => Some(MatchPattern::Wildcard(self.tok.clone())),
+            TokenType::IDENT => {
+                Some(MatchPattern::Binding(Identifier { token: self.tok.clone(), value: self.tok.literal.clone() }))
+            },
+            TokenType::INT | TokenType::FLOAT | TokenType::STRING | TokenType::TRUE | TokenType::FALSE => {
+                Some(MatchPattern::Literal(self.parse_literal_pattern()?))
+            },
+            TokenType::MINUS if self.peek_token_is(TokenType::INT) || self.peek_token_is(TokenType::FLOAT) => {
+                let token = self.tok.clone();
+                self.ignore_next()?;
+                let right = self.parse_literal_pattern()?;
+                Some(MatchPattern::Literal(Expr::Pre(Prefix { token, operator: "-".to_string(), right: Box::new(right) })))
+            },
+            TokenType::LBRACKET => self.parse_array_match_pattern(),
+            TokenType::LBRACE => self.parse_hash_match_pattern(),
+            t => {
+                self.error(ParseErrorKind::InvalidPattern(t), self.tok.span);
+                None
+            },
+        }
+    }
+
+    // Only the literal itself, `"a"[0]` or `1 + 1` are not patterns.
+    fn parse_literal_pattern(&mut self) -> Option<Expr> {
+        match self.tok.token_type {
+            TokenType::INT => self.parse_integer_literal(),
+            TokenType::FLOAT => self.parse_float_literal(),
+            TokenType::STRING => self.parse_string_expression(),
+            _ => self.parse_boolean(),
+        }
+    }
+
+    // `[first, [x, y], ...rest]`, the rest pattern has to come last.
+    fn parse_array_match_pattern(&mut self) -> Option<MatchPattern> {
+        let token = self.tok.clone();
+        let mut elements = Vec::new();
+        let mut rest = None;
+
+        while !self.peek_token_is(TokenType::RBRACKET) {
+            if self.peek_token_is(TokenType::ELLIPSIS) {
+                self.ignore_next()?;
+                self.expect_peek(TokenType::IDENT)?;
+                rest = Some(Box::new(self.parse_match_pattern()?));
+                break;
+            };
+
+            self.ignore_next()?;
+            elements.push(self.parse_match_pattern()?);
+
+            if !self.peek_token_is(TokenType::RBRACKET) {
+                self.expect_peek(TokenType::COMMA)?;
+            };
+        }
+
+        self.expect_peek(TokenType::RBRACKET)?;
+
+        Some(MatchPattern::Array(ArrayMatchPattern { token, elements, rest }))
+    }
+
+    // `{"name": name, "tags": [first, ..._]}`, each key is an expression.
+    fn parse_hash_match_pattern(&mut self) -> Option<MatchPattern> {
+        let token = self.tok.clone();
+        let mut pairs = Vec::new();
+
+        while !self.peek_token_is(TokenType::RBRACE) {
+            self.ignore_next()?;
+
+            let key = self.parse_expression(Precedence::LOWEST)?;
+            self.expect_peek(TokenType::COLON)?;
+            self.ignore_next()?;
+            pairs.push((key, self.parse_match_pattern()?));
+
+            if !self.peek_token_is(TokenType::RBRACE) {
+                self.expect_peek(TokenType::COMMA)?;
+            };
+        }
+
+        self.expect_peek(TokenType::RBRACE)?;
+
+        Some(MatchPattern::Hash(HashMatchPattern { token, pairs }))
+    }
+
+    fn parse_loop_body(&mut self) -> Option<BlockStatement> {
+        self.loops += 1;
+        let body = self.parse_block_statement();
+        self.loops -= 1;
+
+        body
+    }
+
+    // `break` and `continue` in a function body can't reach a loop the function is defined in.
+    fn parse_function_body(&mut self) -> Option<BlockStatement> {
+        let loops = std::mem::take(&mut self.loops);
+        let body = self.parse_block_statement();
+        self.loops = loops;
+
+        body
+    }
+
+    fn parse_function_parameters(&mut self) -> Option<Vec<Identifier>> {
         let mut params = Vec::new();
 
         if self.peek_token_is(TokenType::RPAREN) {
-            if let Err(_) = self.next_token() { return params; };
-            return params;
+            self.ignore_next()?;
+            return Some(params);
         };
-        if let Err(_) = self.next_token() { return params; };
+        self.expect_peek(TokenType::IDENT)?;
 
         params.push(Identifier { token: self.tok.clone(), value: self.tok.literal.clone() });
 
         while self.peek_token_is(TokenType::COMMA) {
-            if let Err(_) = self.next_token() { return params; };
-            if let Err(_) = self.next_token() { return params; };
+            self.ignore_next()?;
+            self.expect_peek(TokenType::IDENT)?;
             params.push(Identifier { token: self.tok.clone(), value: self.tok.literal.clone() });
         }
 
-        match self.expect_peek(TokenType::RPAREN) {
-            Some(_) => params,
-            None => params,
+        self.expect_peek(TokenType::RPAREN)?;
+
+        Some(params)
+    }
+
+    // `(a, b = 2, ...rest)`, parameters with a default value come after the others and the rest
+    // parameter comes last.
+    fn parse_fn_parameters(&mut self) -> Option<(Vec<Parameter>, Option<Box<Identifier>>)> {
+        let mut params: Vec<Parameter> = Vec::new();
+        let mut rest = None;
+
+        while !self.peek_token_is(TokenType::RPAREN) {
+            if self.peek_token_is(TokenType::ELLIPSIS) {
+                self.ignore_next()?;
+                self.expect_peek(TokenType::IDENT)?;
+                rest = Some(Box::new(Identifier { token: self.tok.clone(), value: self.tok.literal.clone() }));
+                break;
+            };
+
+            self.expect_peek(TokenType::IDENT)?;
+            let name = Identifier { token: self.tok.clone(), value: self.tok.literal.clone() };
+
+            let default = if self.peek_token_is(TokenType::ASSIGN) {
+                self.ignore_next()?;
+                self.ignore_next()?;
+                Some(self.parse_expression(Precedence::LOWEST)?)
+            } else {
+                if params.last().is_some_and(|p| p.default.is_some()) {
+                    self.error(ParseErrorKind::RequiredAfterDefault(name.value.clone()), name.token.span);
+                    return None;
+                };
+                None
+            };
+            params.push(Parameter { name, default });
+
+            if !self.peek_token_is(TokenType::RPAREN) {
+                self.expect_peek(TokenType::COMMA)?;
+            };
         }
+
+        self.expect_peek(TokenType::RPAREN)?;
+
+        Some((params, rest))
     }
 
     fn parse_function_expression(&mut self) -> Option<Expr> {
@@ -329,11 +774,11 @@ impl<I: Iterator<Item = Result<Token>>> Parser<I> {
 
         self.expect_peek(TokenType::LPAREN)?;
 
-        let params = self.parse_function_parameters();
+        let (params, rest) = self.parse_fn_parameters()?;
 
         self.expect_peek(TokenType::LBRACE)?;
 
-        let body = self.parse_block_statement()?;
+        let body = self.parse_function_body()?;
 
         Some(
             Expr::Fn(
@@ -341,6 +786,7 @@ impl<I: Iterator<Item = Result<Token>>> Parser<I> {
                     name: None,
                     token,
                     params,
+                    rest,
                     body,
                 }
             )
@@ -360,13 +806,14 @@ impl<I: Iterator<Item = Result<Token>>> Parser<I> {
 
     fn parse_array_expression(&mut self) -> Option<Expr> {
         let token = self.tok.clone();
-        let elements = self.parse_expression_list(TokenType::RBRACKET);
+        let elements = self.parse_expression_list(TokenType::RBRACKET)?;
 
         Some(
             Expr::Array(
                 ArrayLiteral {
                     token,
                     elements,
+                    close: self.tok.span.end,
                 }
             )
         )
@@ -387,10 +834,7 @@ impl<I: Iterator<Item = Result<Token>>> Parser<I> {
             pairs.insert(key, value);
 
             if !self.peek_token_is(TokenType::RBRACE) {
-                if !self.peek_token_is(TokenType::COMMA) {
-                    return None;
-                };
-                self.ignore_next()?;
+                self.expect_peek(TokenType::COMMA)?;
             };
         }
 
@@ -401,6 +845,7 @@ impl<I: Iterator<Item = Result<Token>>> Parser<I> {
                 HashLiteral {
                     token,
                     pairs,
+                    close: self.tok.span.end,
                 }
             )
         )
@@ -410,10 +855,10 @@ impl<I: Iterator<Item = Result<Token>>> Parser<I> {
         let token = self.tok.clone();
         self.expect_peek(TokenType::LPAREN)?;
 
-        let params = self.parse_function_parameters();
+        let params = self.parse_function_parameters()?;
         self.expect_peek(TokenType::LBRACE)?;
 
-        let body = self.parse_block_statement()?;
+        let body = self.parse_function_body()?;
 
         Some(
             Expr::Macro(
@@ -448,66 +893,146 @@ impl<I: Iterator<Item = Result<Token>>> Parser<I> {
     fn parse_infix_expression(&mut self, left: Expr) -> Option<Expr> {
         let token = self.tok.clone();
         let operator = token.literal.clone();
-        let precedence = self.curr_precedence();
+        // `**` is right-associative, so its right operand may itself be a `**` expression.
+        let precedence = match token.token_type {
+            TokenType::POWER => Precedence::PREFIX,
+            _ => self.curr_precedence(),
+        };
 
         self.ignore_next()?;
 
-        let right = self.parse_expression(precedence)?;
+        let right = self.parse_expression(precedence)?;
+
+        Some(
+            Expr::In(
+                Infix {
+                    token,
+                    left: Box::new(left),
+                    operator,
+                    right: Box::new(right),
+                }
+            )
+        )
+    }
+
+    // Assignment is right-associative, so `a = b = 1` assigns 1 to both.
+    fn parse_assignment_expression(&mut self, target: Expr) -> Option<Expr> {
+        let token = self.tok.clone();
+        let operator = token.literal.clone();
+
+        if target.assignment_path().is_none() {
+            self.error(ParseErrorKind::InvalidAssignmentTarget(target.to_string()), target.span());
+            return None;
+        };
+
+        self.ignore_next()?;
+
+        let value = self.parse_expression(Precedence::LOWEST)?;
+
+        Some(
+            Expr::Assign(
+                Assignment {
+                    token,
+                    target: Box::new(target),
+                    operator,
+                    value: Box::new(value),
+                }
+            )
+        )
+    }
+
+    fn parse_expression_list(&mut self, end: TokenType) -> Option<Vec<Expr>> {
+        let mut args = Vec::new();
+
+        if self.peek_token_is(end) {
+            self.ignore_next()?;
+            return Some(args);
+        };
+        self.ignore_next()?;
+
+        args.push(self.parse_expression(Precedence::LOWEST)?);
+
+        while self.peek_token_is(TokenType::COMMA) {
+            self.ignore_next()?;
+            self.ignore_next()?;
+
+            args.push(self.parse_expression(Precedence::LOWEST)?);
+        }
+
+        self.expect_peek(end)?;
+
+        Some(args)
+    }
+
+    fn parse_call_expression(&mut self, function: Expr) -> Option<Expr> {
+        let token = self.tok.clone();
+        let (args, named) = self.parse_call_arguments()?;
 
         Some(
-            Expr::In(
-                Infix {
+            Expr::Call(
+                FnCall {
                     token,
-                    left: Box::new(left),
-                    operator,
-                    right: Box::new(right),
+                    function: Box::new(function),
+                    args,
+                    named,
+                    close: self.tok.span.end,
                 }
             )
         )
     }
 
-    fn parse_expression_list(&mut self, end: TokenType) -> Vec<Expr> {
+    // An argument written `name: value` is named, the ones after it have to be named too.
+    fn parse_call_arguments(&mut self) -> Option<(Vec<Expr>, Vec<NamedArgument>)> {
         let mut args = Vec::new();
+        let mut named = Vec::new();
 
-        if self.peek_token_is(end) {
-            if let Err(_) = self.next_token() { return args; };
-            return args;
+        if self.peek_token_is(TokenType::RPAREN) {
+            self.ignore_next()?;
+            return Some((args, named));
         };
-        if let Err(_) = self.next_token() { return args; };
 
-        if let Some(expr) = self.parse_expression(Precedence::LOWEST) {
-            args.push(expr);
-        } else {
-            return args;
-        }
-
-        while self.peek_token_is(TokenType::COMMA) {
-            if let Err(_) = self.next_token() { return args; };
-            if let Err(_) = self.next_token() { return args; };
+        loop {
+            self.ignore_next()?;
 
-            if let Some(expr) = self.parse_expression(Precedence::LOWEST) {
-                args.push(expr);
+            if self.curr_token_is(TokenType::IDENT) && self.peek_token_is(TokenType::COLON) {
+                let name = Identifier { token: self.tok.clone(), value: self.tok.literal.clone() };
+                self.ignore_next()?;
+                self.ignore_next()?;
+                named.push(NamedArgument { name, value: self.parse_expression(Precedence::LOWEST)? });
             } else {
-                return args;
-            }
-        }
+                let arg = self.parse_expression(Precedence::LOWEST)?;
+                if !named.is_empty() {
+                    self.error(ParseErrorKind::PositionalAfterNamed, arg.span());
+                    return None;
+                };
+                args.push(arg);
+            };
 
-        match self.expect_peek(end) {
-            Some(_) => args,
-            None => args,
+            if !self.peek_token_is(TokenType::COMMA) { break; };
+            self.ignore_next()?;
         }
+
+        self.expect_peek(TokenType::RPAREN)?;
+
+        Some((args, named))
     }
 
-    fn parse_call_expression(&mut self, function: Expr) -> Option<Expr> {
+    fn parse_range_expression(&mut self, start: Expr) -> Option<Expr> {
         let token = self.tok.clone();
-        let args = self.parse_expression_list(TokenType::RPAREN);
+        let inclusive = token.token_type == TokenType::DOT_DOT_EQ;
+        let precedence = self.curr_precedence();
+
+        self.ignore_next()?;
+
+        let end = self.parse_expression(precedence)?;
 
         Some(
-            Expr::Call(
-                FnCall {
+            Expr::Range(
+                RangeExpression {
                     token,
-                    function: Box::new(function),
-                    args,
+                    start: Box::new(start),
+                    end: Box::new(end),
+                    inclusive,
                 }
             )
         )
@@ -515,10 +1040,19 @@ impl<I: Iterator<Item = Result<Token>>> Parser<I> {
 
     fn parse_index_expression(&mut self, left: Expr) -> Option<Expr> {
         let token = self.tok.clone();
+
+        if self.peek_token_is(TokenType::COLON) {
+            return self.parse_slice_expression(token, left, None);
+        };
+
         self.ignore_next()?;
 
         let index = self.parse_expression(Precedence::LOWEST)?;
 
+        if self.peek_token_is(TokenType::COLON) {
+            return self.parse_slice_expression(token, left, Some(index));
+        };
+
         self.expect_peek(TokenType::RBRACKET)?;
 
         Some(
@@ -527,26 +1061,119 @@ impl<I: Iterator<Item = Result<Token>>> Parser<I> {
                     token,
                     left: Box::new(left),
                     index: Box::new(index),
+                    close: self.tok.span.end,
+                }
+            )
+        )
+    }
+
+    // Finishes `left[start:end]` from the `:`, the end can be left out as well as the start.
+    fn parse_slice_expression(&mut self, token: Token, left: Expr, start: Option<Expr>) -> Option<Expr> {
+        self.expect_peek(TokenType::COLON)?;
+
+        let end = if self.peek_token_is(TokenType::RBRACKET) {
+            None
+        } else {
+            self.ignore_next()?;
+            Some(self.parse_expression(Precedence::LOWEST)?)
+        };
+
+        self.expect_peek(TokenType::RBRACKET)?;
+
+        Some(
+            Expr::Slice(
+                SliceOperation {
+                    token,
+                    left: Box::new(left),
+                    start: start.map(Box::new),
+                    end: end.map(Box::new),
+                    close: self.tok.span.end,
                 }
             )
         )
     }
 
-    fn next_token(&mut self) -> Result<()> {
+    fn next_token(&mut self) {
+        self.depth = self.peek_depth();
+        self.peek();
         self.tok = match self.l.next() {
-            Some(t) => t?,
-            None => Token { token_type: TokenType::EOF, literal: String::from("") },
+            Some(Ok(t)) => t,
+            // `peek` has already taken any error out of the lexer.
+            _ => {
+                let end = Span::new(self.tok.span.end, self.tok.span.end);
+                Token::with_span(TokenType::EOF, String::from(""), end)
+            },
         };
-        Ok(())
     }
 
-    fn ignore_next(&mut self) -> Option<()> {
-        match self.next_token() {
-            Ok(_) => Some(()),
-            Err(_) => None,
+    // The next token, or None at the end of the input. The lexer can't resume after an error, a
+    // bad escape leaves it inside the string, so the error is recorded and the rest of the input
+    // is dropped.
+    fn peek(&mut self) -> Option<&Token> {
+        if let Some(Err(_)) = self.l.peek() {
+            match self.l.next() {
+                Some(Err(Error::Lex(e))) => self.error(ParseErrorKind::Lex(e.kind), e.span),
+                Some(Err(e)) => self.read_error = Some(e),
+                _ => {},
+            };
+            self.l.by_ref().for_each(drop);
+        }
+
+        match self.l.peek() {
+            Some(Ok(tok)) => Some(tok),
+            _ => None,
+        }
+    }
+
+    // Panic-mode recovery: after a statement fails to parse, skip tokens until the parser reaches
+    // the end of that statement. It stops on a `;` or on the `}` that closes the enclosing block,
+    // or just before a keyword that starts a new statement, so the caller's next call to
+    // `next_token` lands on the start of the next statement. `depth` is the brace nesting the
+    // statement started at, so the braces of a broken `if`, `fn` or hash literal are skipped over
+    // rather than mistaken for the end of the enclosing block.
+    fn synchronize(&mut self, depth: usize) {
+        loop {
+            match self.tok.token_type {
+                TokenType::EOF => return,
+                TokenType::SEMICOLON | TokenType::RBRACE if self.depth == depth => return,
+                _ => {},
+            }
+
+            if self.peek_depth() == depth && self.peek_starts_statement() {
+                return;
+            }
+
+            self.next_token();
+        }
+    }
+
+    fn peek_depth(&self) -> usize {
+        match self.tok.token_type {
+            TokenType::LBRACE => self.depth + 1,
+            TokenType::RBRACE => self.depth.saturating_sub(1),
+            _ => self.depth,
         }
     }
 
+    // Keywords that begin a new statement, where parsing resumes after an error, or the end of the
+    // block.
+    fn peek_starts_statement(&mut self) -> bool {
+        self.peek_token_is(TokenType::LET) ||
+            self.peek_token_is(TokenType::RETURN) ||
+            self.peek_token_is(TokenType::BREAK) ||
+            self.peek_token_is(TokenType::CONTINUE) ||
+            self.peek_token_is(TokenType::WHILE) ||
+            self.peek_token_is(TokenType::FOR) ||
+            self.peek_token_is(TokenType::MATCH) ||
+            self.peek_token_is(TokenType::RBRACE) ||
+            self.peek_token_is(TokenType::EOF)
+    }
+
+    fn ignore_next(&mut self) -> Option<()> {
+        self.next_token();
+        Some(())
+    }
+
     fn expect_peek(&mut self, t: TokenType) -> Option<()> {
         if self.peek_token_is(t) {
             self.ignore_next()
@@ -561,35 +1188,33 @@ impl<I: Iterator<Item = Result<Token>>> Parser<I> {
     }
 
     fn peek_token_is(&mut self, t: TokenType) -> bool {
-        if let Some(peeked) = self.l.peek() {
-            match peeked {
-                Ok(tok) => tok.token_type == t,
-                Err(_) => false,
-            }
-        } else {
-            t == TokenType::EOF
+        match self.peek() {
+            Some(tok) => tok.token_type == t,
+            None => t == TokenType::EOF,
         }
     }
 
     fn peek_error(&mut self, t: TokenType) {
-        let actual = if let Some(peeked) = self.l.peek() {
-            match peeked {
-                Ok(tok) => tok.token_type,
-                Err(_) => TokenType::ILLEGAL,
-            }
-        } else {
-            TokenType::EOF
+        let eof = Span::new(self.tok.span.end, self.tok.span.end);
+        let (actual, span) = match self.peek() {
+            Some(tok) => (tok.token_type, tok.span),
+            None => (TokenType::EOF, eof),
         };
-        let msg = format!("Expected next token to be {:?}, got {:?} instead.", t, actual);
-        self.errors.push(msg);
+        self.error(ParseErrorKind::UnexpectedToken { expected: t, actual }, span);
+    }
+
+    fn error(&mut self, kind: ParseErrorKind, span: Span) {
+        // The input ends at a lexer error, anything reported after it is about the truncation.
+        if let Some(ParseError { kind: ParseErrorKind::Lex(_), .. }) = self.errors.last() {
+            return;
+        }
+        self.errors.push(ParseError::new(kind, span));
     }
 
     fn peek_precedence(&mut self) -> Precedence {
-        if let Some(peeked) = self.l.peek() {
-            if let Ok(tok) = peeked {
-                if let Some(&p) = self.precedences.get(&tok.token_type) {
-                    return p;
-                }
+        if let Some(t) = self.peek().map(|tok| tok.token_type) {
+            if let Some(&p) = self.precedences.get(&t) {
+                return p;
             }
         }
         Precedence::LOWEST
@@ -641,7 +1266,7 @@ mod tests {
             assert_eq!(format!("{}", expected), x.token_literal());
             Ok(())
         } else {
-            Err(Error::new(format!("Expression {:?} was not an Integer literal.", expr)))
+            panic!("Expression {:?} was not an Integer literal.", expr)
         }
     }
 
@@ -651,7 +1276,7 @@ mod tests {
             assert_eq!(*expected, x.token_literal());
             Ok(())
         } else {
-            Err(Error::new(format!("Expression {:?} was not an identifier.", expr)))
+            panic!("Expression {:?} was not an identifier.", expr)
         }
     }
 
@@ -661,7 +1286,7 @@ mod tests {
             assert_eq!(format!("{}", expected), x.token_literal());
             Ok(())
         } else {
-            Err(Error::new(format!("Expression {:?} was not a boolean.", expr)))
+            panic!("Expression {:?} was not a boolean.", expr)
         }
     }
 
@@ -670,7 +1295,7 @@ mod tests {
             Expr::Int(x) => test_integer_literal(x.value, expr),
             Expr::Ident(x) => test_identifier(&x.value, expr),
             Expr::Bool(x) => test_boolean(x.value, expr),
-            _ => Err(Error::new(format!("Expression {:?} is not a literal expression.", expected)))
+            _ => panic!("Expression {:?} is not a literal expression.", expected),
         }
     }
 
@@ -680,7 +1305,7 @@ mod tests {
             assert_eq!(operator, x.operator);
             test_literal_expression(right, &x.right)
         } else {
-            Err(Error::new(format!("Expression `{}` was not an infix expression.", actual)))
+            panic!("Expression `{}` was not an infix expression.", actual)
         }
     }
 
@@ -712,6 +1337,89 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_destructuring_let_statements() -> Result<()> {
+        let tests = vec![
+            ("let [a, b] = arr;", "let [a, b] = arr;"),
+            ("let [first, ...rest] = [1, 2, 3]", "let [first, ...rest] = [1, 2, 3];"),
+            ("let [...all] = f();", "let [...all] = f();"),
+            ("let [] = [];", "let [] = [];"),
+            (r#"let {"name": n, "age": a} = h;"#, r#"let {"name": n, "age": a} = h;"#),
+            ("let {1 + 1: two,} = h;", "let {(1 + 1): two} = h;"),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input.to_string())?;
+            assert_eq!(1, program.stmts.len());
+            assert_eq!(expected, program.to_string());
+        }
+
+        let program = parse(r#"let {"b": b, "a": a} = h;"#.to_string())?;
+        match program.stmts.first() {
+            Some(Stmt::Destructure(stmt)) => {
+                let names = stmt.pattern.names().iter().map(|x| x.value.clone()).collect::<Vec<String>>();
+                assert_eq!(vec!["b", "a"], names);
+            },
+            x => panic!("Expected a destructuring let, got: {:?}", x),
+        };
+
+        let errors = vec![
+            ("let [a, ...b, c] = x;", "Expected next token to be RBRACKET, got COMMA instead."),
+            ("let [a, 1] = x;", "Expected next token to be IDENT, got INT instead."),
+            ("let [a b] = x;", "Expected next token to be COMMA, got IDENT instead."),
+            (r#"let {"a"} = x;"#, "Expected next token to be COLON, got RBRACE instead."),
+            (r#"let {"a": "b"} = x;"#, "Expected next token to be IDENT, got STRING instead."),
+        ];
+
+        for (input, expected) in errors {
+            let lexer = Lexer::new(input.as_bytes().bytes().peekable())?;
+            let mut parser = Parser::new(lexer.peekable())?;
+            parser.parse()?;
+
+            let errors = parser.errors();
+            assert_eq!(expected, errors[0].to_string(), "{}", input);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_match_expressions() -> Result<()> {
+        let tests = vec![
+            ("match (x) { 1 => \"one\", _ => \"many\" }", "match x { 1 => { \"one\" }, _ => { \"many\" } }"),
+            ("match (x) { -1 => a, -2.5 => b, true => c, }", "match x { (-1) => { a }, (-2.5) => { b }, true => { c } }"),
+            ("match (p) { [a, [b, _], ...rest] => a }", "match p { [a, [b, _], ...rest] => { a } }"),
+            ("match (h) { {\"k\": v, 1 + 1: [..._]} if v > 1 => { v } }", "match h { {\"k\": v, (1 + 1): [..._]} if (v > 1) => { v } }"),
+            ("match (f(x)) { n => { let y = n; y } }", "match f(x) { n => { let y = n;y } }"),
+            ("match (x) {}", "match x {  }"),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input.to_string())?;
+            assert_eq!(1, program.stmts.len());
+            assert_eq!(expected, program.to_string());
+        }
+
+        let errors = vec![
+            ("match (x) { a + 1 => 1 }", "Expected next token to be ARROW, got PLUS instead."),
+            ("match (x) { (1) => 1 }", "Expected a pattern, got LPAREN instead."),
+            ("match (x) { [...a, b] => 1 }", "Expected next token to be RBRACKET, got COMMA instead."),
+            ("match (x) { 1 => 1 2 => 2 }", "Expected next token to be COMMA, got INT instead."),
+            ("match x { _ => 1 }", "Expected next token to be LPAREN, got IDENT instead."),
+        ];
+
+        for (input, expected) in errors {
+            let lexer = Lexer::new(input.as_bytes().bytes().peekable())?;
+            let mut parser = Parser::new(lexer.peekable())?;
+            parser.parse()?;
+
+            let errors = parser.errors();
+            assert_eq!(expected, errors[0].to_string(), "{}", input);
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_parser_errors() -> Result<()> {
         let input = r###"
@@ -733,7 +1441,7 @@ mod tests {
             let test = tests.get(i).unwrap();
 
             let msg = format!("Expected next token to be {:?}, got {:?} instead.", test.0, test.1);
-            assert_eq!(msg, *err);
+            assert_eq!(msg, err.to_string());
         }
 
         assert_eq!(2, p.errors.len());
@@ -741,6 +1449,164 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_parser_error_recovery() -> Result<()> {
+        let tests = vec![
+            (
+                r###"
+                    let x 5;
+                    let = 10;
+                    let y = add(1, 2;
+                    let z = 3;
+                    z;
+                "###,
+                vec![
+                    (2, "Expected next token to be ASSIGN, got INT instead."),
+                    (3, "Expected next token to be IDENT, got ASSIGN instead."),
+                    (4, "Expected next token to be RPAREN, got SEMICOLON instead."),
+                ],
+                2,
+            ),
+            (
+                r###"
+                    let f = fn(a) {
+                        let = a;
+                        a + 1;
+                    };
+                    if (x { y; z };
+                    let ok = 1;
+                "###,
+                vec![
+                    (3, "Expected next token to be IDENT, got ASSIGN instead."),
+                    (6, "Expected next token to be RPAREN, got LBRACE instead."),
+                ],
+                2,
+            ),
+            (
+                r###"
+                    let f = fn() { x + };
+                    let g = fn() { return; 1 };
+                    f(1, 2
+                    let y = 1;
+                "###,
+                vec![
+                    (2, "Prefix parse function for RBRACE not found."),
+                    (3, "Prefix parse function for SEMICOLON not found."),
+                    (5, "Expected next token to be RPAREN, got LET instead."),
+                ],
+                3,
+            ),
+            (
+                r###"
+                    let h = {"a": 1 "b": 2};
+                    let f = fn(1, x) { x };
+                    let ok = 1;
+                "###,
+                vec![
+                    (2, "Expected next token to be COMMA, got STRING instead."),
+                    (3, "Expected next token to be IDENT, got INT instead."),
+                ],
+                1,
+            ),
+            (
+                r###"
+                    let a = (1
+                    while (false) { let b = [1
+                        continue;
+                    }
+                    let c = add(1
+                    for (i in [1]) { let d = {"k": 1
+                        break;
+                    }
+                    let e = (2
+                    match (1) { _ => 1 }
+                "###,
+                vec![
+                    (3, "Expected next token to be RPAREN, got WHILE instead."),
+                    (4, "Expected next token to be RBRACKET, got CONTINUE instead."),
+                    (7, "Expected next token to be RPAREN, got FOR instead."),
+                    (8, "Expected next token to be COMMA, got BREAK instead."),
+                    (11, "Expected next token to be RPAREN, got MATCH instead."),
+                ],
+                3,
+            ),
+            (
+                r###"
+                    let f = g(a: 1, 2);
+                    let ok = 1;
+                "###,
+                vec![
+                    (2, "Positional argument follows a named argument."),
+                ],
+                1,
+            ),
+            (
+                r###"
+                    let ok = 1;
+                    let f = fn() {
+                        ok + 1;
+                "###,
+                vec![
+                    (4, "Expected next token to be RBRACE, got EOF instead."),
+                ],
+                1,
+            ),
+            (
+                r###"
+                    let ok = 1;
+                    let x = "abc
+                    let y = 2;
+                "###,
+                vec![
+                    (3, "unterminated string literal"),
+                ],
+                1,
+            ),
+            (
+                r###"
+                    puts("a\q");
+                    let y = 2;
+                "###,
+                vec![
+                    (2, "invalid escape sequence"),
+                ],
+                0,
+            ),
+            (
+                r###"
+                    let x = ;
+                    let y = 2;
+                    /* open
+                "###,
+                vec![
+                    (2, "Prefix parse function for SEMICOLON not found."),
+                    (4, "unterminated block comment"),
+                ],
+                1,
+            ),
+        ];
+
+        for (input, expected, num_stmts) in tests {
+            let l = Lexer::new(input.as_bytes().bytes().peekable())?;
+            let mut p = Parser::new(l.peekable())?;
+            let program = p.parse()?;
+
+            let actual = p.errors()
+                .iter()
+                .map(|e| (e.span.start.line, e.to_string()))
+                .collect::<Vec<(u32, String)>>();
+            let expected = expected
+                .into_iter()
+                .map(|(line, msg)| (line, msg.to_string()))
+                .collect::<Vec<(u32, String)>>();
+
+            assert_eq!(expected, actual);
+            assert_eq!(num_stmts, program.stmts.len());
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_return_statement() -> Result<()> {
         let input = r###"
@@ -804,6 +1670,147 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_integer_literal_radixes() -> Result<()> {
+        let tests = vec![
+            ("0xFF;", 255),
+            ("0Xff;", 255),
+            ("0o755;", 493),
+            ("0b1010;", 10),
+            ("1_000_000;", 1_000_000),
+            ("0xdead_beef;", 0xdead_beef),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input.to_string())?;
+
+            match &program.stmts[0] {
+                Stmt::Expression(ExpressionStatement { expr: Expr::Int(x), .. }) => assert_eq!(expected, x.value),
+                x => panic!("Program statement was not an integer literal, got: {:?}", x),
+            };
+        }
+
+        let program = parse(format!("{};", i128::MAX))?;
+        match &program.stmts[0] {
+            Stmt::Expression(ExpressionStatement { expr: Expr::Int(x), .. }) => assert_eq!(i128::MAX, x.value),
+            x => panic!("Program statement was not an integer literal, got: {:?}", x),
+        };
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_big_integer_literals() -> Result<()> {
+        let tests = vec![
+            ("170141183460469231731687303715884105728;", "170141183460469231731687303715884105728"),
+            ("0x1_0000_0000_0000_0000_0000_0000_0000_0000;", "340282366920938463463374607431768211456"),
+            ("99999999999999999999999999999999999999999;", "99999999999999999999999999999999999999999"),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input.to_string())?;
+
+            match &program.stmts[0] {
+                Stmt::Expression(ExpressionStatement { expr: Expr::BigInt(x), .. }) => {
+                    assert_eq!(expected, x.value.to_string());
+                },
+                x => panic!("Program statement was not a big integer literal, got: {:?}", x),
+            };
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_loop_expressions() -> Result<()> {
+        let tests = vec![
+            ("while (x < 10) { x += 1; }", "while (x < 10) { (x += 1) }"),
+            ("for (x in [1, 2]) { if (x == 1) { continue; }; break; }", "for x in [1, 2] { if (x == 1) { continue; }break; }"),
+            ("while (true) { fn() { for (c in s) { break } } }", "while true { fn() { for c in s { break; } } }"),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input.as_bytes().bytes().peekable())?;
+            let mut parser = Parser::new(lexer.peekable())?;
+            let program = parser.parse()?;
+            check_parser_errors(parser)?;
+
+            assert_eq!(1, program.stmts.len());
+            assert_eq!(expected, program.to_string());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_loop_control_outside_loop() -> Result<()> {
+        let tests = vec![
+            ("break;", TokenType::BREAK),
+            ("if (true) { continue; }", TokenType::CONTINUE),
+            ("while (true) { fn() { break; } }", TokenType::BREAK),
+            ("for (x in xs) { macro() { continue; } }", TokenType::CONTINUE),
+        ];
+
+        for (input, token_type) in tests {
+            let lexer = Lexer::new(input.as_bytes().bytes().peekable())?;
+            let mut parser = Parser::new(lexer.peekable())?;
+            parser.parse()?;
+
+            let errors = parser.errors();
+            assert_eq!(1, errors.len(), "{}: {:?}", input, errors);
+            assert_eq!(ParseErrorKind::OutsideLoop(token_type), errors[0].kind);
+            assert_eq!(format!("{:?} outside of a loop.", token_type), errors[0].to_string());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_assignment_target() -> Result<()> {
+        let tests = vec![
+            ("1 + 2 = 3;", "(1 + 2)"),
+            ("f() += 1;", "f()"),
+            ("[1][0] = 2;", "([1][0])"),
+        ];
+
+        for (input, target) in tests {
+            let lexer = Lexer::new(input.as_bytes().bytes().peekable())?;
+            let mut parser = Parser::new(lexer.peekable())?;
+            parser.parse()?;
+
+            let errors = parser.errors();
+            assert_eq!(1, errors.len(), "{:?}", errors);
+            assert_eq!(ParseErrorKind::InvalidAssignmentTarget(target.to_string()), errors[0].kind);
+            assert_eq!(format!("Cannot assign to {}", target), errors[0].to_string());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_float_literal_expressions() -> Result<()> {
+        let tests = vec![
+            ("2.75;", 2.75, "2.75"),
+            ("1e-9;", 1e-9, "1e-9"),
+            ("2.5E3;", 2500.0, "2500.0"),
+        ];
+
+        for (input, expected, display) in tests {
+            let program = parse(input.to_string())?;
+            assert_eq!(1, program.stmts.len());
+
+            match &program.stmts[0] {
+                Stmt::Expression(ExpressionStatement { expr: Expr::Float(x), .. }) => {
+                    assert_eq!(expected, x.value);
+                    assert_eq!(display, x.to_string());
+                },
+                x => panic!("Program statement was not a float literal, got: {:?}", x),
+            };
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_boolean_expressions() -> Result<()> {
         let input = r###"
@@ -920,6 +1927,31 @@ mod tests {
             ("add(a + b + c * d / f + g)".to_string(), "add((((a + b) + ((c * d) / f)) + g))".to_string()),
             ("a * [1, 2, 3, 4][b * c] * d".to_string(), "((a * ([1, 2, 3, 4][(b * c)])) * d)".to_string()),
             ("add(a * b[2], b[1], 2 * [1, 2][1])".to_string(), "add((a * (b[2])), (b[1]), (2 * ([1, 2][1])))".to_string()),
+            ("a + b % c".to_string(), "(a + (b % c))".to_string()),
+            ("a ** b ** c".to_string(), "(a ** (b ** c))".to_string()),
+            ("a <= b == c >= d".to_string(), "((a <= b) == (c >= d))".to_string()),
+            ("a || b && c == d".to_string(), "(a || (b && (c == d)))".to_string()),
+            ("a && b || c && d".to_string(), "((a && b) || (c && d))".to_string()),
+            ("a & b && c | d".to_string(), "((a & b) && (c | d))".to_string()),
+            ("-a ** b".to_string(), "(-(a ** b))".to_string()),
+            ("a = b = c".to_string(), "(a = (b = c))".to_string()),
+            ("x += 1 * 2".to_string(), "(x += (1 * 2))".to_string()),
+            ("x <<= a || b".to_string(), "(x <<= (a || b))".to_string()),
+            ("a[0][1] = 1".to_string(), "(((a[0])[1]) = 1)".to_string()),
+            ("a ** -b * c".to_string(), "((a ** (-b)) * c)".to_string()),
+            ("a * b ** c[1]".to_string(), "(a * (b ** (c[1])))".to_string()),
+            ("a | b ^ c & d".to_string(), "(a | (b ^ (c & d)))".to_string()),
+            ("a & b << c + d".to_string(), "(a & (b << (c + d)))".to_string()),
+            ("a >> b == c | d".to_string(), "((a >> b) == (c | d))".to_string()),
+            ("a < b | c".to_string(), "(a < (b | c))".to_string()),
+            ("~a & ~b".to_string(), "((~a) & (~b))".to_string()),
+            ("0..n + 1".to_string(), "(0..(n + 1))".to_string()),
+            ("a..=b == c".to_string(), "((a..=b) == c)".to_string()),
+            ("a..b | c".to_string(), "(a..(b | c))".to_string()),
+            ("a[1:2]".to_string(), "(a[1:2])".to_string()),
+            ("a[:]".to_string(), "(a[:])".to_string()),
+            ("a[-1:]".to_string(), "(a[(-1):])".to_string()),
+            ("a[:b + 1][0]".to_string(), "((a[:(b + 1)])[0])".to_string()),
         ];
 
         for tt in tests {
@@ -1031,8 +2063,8 @@ mod tests {
 
         assert_eq!(2, fn_expr.params.len());
 
-        test_literal_expression(&l_to_expr("x".to_string()), &Expr::Ident(fn_expr.params.get(0).unwrap().clone()))?;
-        test_literal_expression(&l_to_expr("y".to_string()), &Expr::Ident(fn_expr.params.get(1).unwrap().clone()))?;
+        test_literal_expression(&l_to_expr("x".to_string()), &Expr::Ident(fn_expr.params.get(0).unwrap().name.clone()))?;
+        test_literal_expression(&l_to_expr("y".to_string()), &Expr::Ident(fn_expr.params.get(1).unwrap().name.clone()))?;
 
         assert_eq!(1, fn_expr.body.stmts.len());
 
@@ -1068,13 +2100,55 @@ mod tests {
             assert_eq!(tt.1.len(), fn_expr.params.len());
 
             for (i, ident) in tt.1.iter().enumerate() {
-                test_identifier(ident, &Expr::Ident(fn_expr.params.get(i).unwrap().clone()))?;
+                test_identifier(ident, &Expr::Ident(fn_expr.params.get(i).unwrap().name.clone()))?;
             }
         }
 
         Ok(())
     }
 
+    #[test]
+    fn test_default_and_rest_parameters() -> Result<()> {
+        let tests = vec![
+            ("fn(a, b = 2) { a }", "fn(a, b = 2) { a }"),
+            ("fn(a = 1 + 2, b = a,) { a }", "fn(a = (1 + 2), b = a) { a }"),
+            ("fn(a, ...rest) { rest }", "fn(a, ...rest) { rest }"),
+            ("fn(...all) { all }", "fn(...all) { all }"),
+            ("let f = fn(a = 1, ...b) { a };", "let f = fn(<f>a = 1, ...b) { a };"),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input.to_string())?;
+            assert_eq!(1, program.stmts.len());
+            assert_eq!(expected, program.to_string());
+        }
+
+        let program = parse("fn(a, b, c = 1, d = 2) {}".to_string())?;
+        match program.stmts.first() {
+            Some(Stmt::Expression(ExpressionStatement { expr: Expr::Fn(f), .. })) => assert_eq!(2, f.required_params()),
+            x => panic!("Expected a function literal, got: {:?}", x),
+        };
+
+        let errors = vec![
+            ("fn(a = 1, b) {}", "Parameter b without a default value follows one with a default value."),
+            ("fn(...a, b) {}", "Expected next token to be RPAREN, got COMMA instead."),
+            ("fn(...a = 1) {}", "Expected next token to be RPAREN, got ASSIGN instead."),
+            ("fn(a b) {}", "Expected next token to be COMMA, got IDENT instead."),
+            ("fn(1) {}", "Expected next token to be IDENT, got INT instead."),
+        ];
+
+        for (input, expected) in errors {
+            let lexer = Lexer::new(input.as_bytes().bytes().peekable())?;
+            let mut parser = Parser::new(lexer.peekable())?;
+            parser.parse()?;
+
+            let errors = parser.errors();
+            assert_eq!(expected, errors[0].to_string(), "{}", input);
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_fn_call_parsing() -> Result<()> {
         let input = r###"
@@ -1105,6 +2179,39 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_named_argument_parsing() -> Result<()> {
+        let input = r###"
+            add(1, b: 2 * 3, c: x);
+        "###.to_string();
+
+        let program = parse(input)?;
+        assert_eq!(1, program.stmts.len());
+
+        let call_expr = if let Stmt::Expression(x) = program.stmts.first().unwrap() {
+            if let Expr::Call(f) = &x.expr {
+                f
+            } else {
+                panic!("Program statement was not a Function expression.");
+            }
+        } else {
+            panic!("Program statement was not a expression statement.");
+        };
+
+        assert_eq!(1, call_expr.args.len());
+        assert_eq!(2, call_expr.named.len());
+
+        test_literal_expression(&i_to_expr(1), &call_expr.args[0])?;
+        assert_eq!("b", call_expr.named[0].name.value);
+        test_infix_expression(&call_expr.named[0].value, &i_to_expr(2), "*".to_string(), &i_to_expr(3))?;
+        assert_eq!("c", call_expr.named[1].name.value);
+        test_identifier(&"x".to_string(), &call_expr.named[1].value)?;
+
+        assert_eq!("add(1, b: (2 * 3), c: x)", call_expr.to_string());
+
+        Ok(())
+    }
+
     #[test]
     fn test_string_expressions() -> Result<()> {
         let input = "\"hello world\";".to_string();
@@ -1381,4 +2488,34 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_node_spans() -> Result<()> {
+        let input = "let add = fn(x, y) {\n    x + y;\n};\nadd(1, 2 * 3);\nadd[0]".to_string();
+        let program = parse(input)?;
+        assert_eq!(3, program.stmts.len());
+
+        let let_span = program.stmts[0].span();
+        assert_eq!((1, 1), (let_span.start.line, let_span.start.column));
+        assert_eq!((3, 2), (let_span.end.line, let_span.end.column));
+
+        let body = match &program.stmts[0] {
+            Stmt::Let(LetStatement { value: Expr::Fn(f), .. }) => &f.body,
+            x => panic!("Expected let statement with a function, got: {}", x),
+        };
+        let infix_span = body.stmts[0].span();
+        assert_eq!((2, 5), (infix_span.start.line, infix_span.start.column));
+        assert_eq!((2, 10), (infix_span.end.line, infix_span.end.column));
+        assert_eq!(5, infix_span.len());
+
+        let call_span = program.stmts[1].span();
+        assert_eq!((4, 1), (call_span.start.line, call_span.start.column));
+        assert_eq!((4, 14), (call_span.end.line, call_span.end.column));
+
+        let index_span = program.stmts[2].span();
+        assert_eq!((5, 1), (index_span.start.line, index_span.start.column));
+        assert_eq!((5, 7), (index_span.end.line, index_span.end.column));
+
+        Ok(())
+    }
 }
@@ -3,27 +3,62 @@ use std::collections::HashMap;
 use crate::lexer::token_type::TokenType;
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug, Hash)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum Precedence {
     LOWEST,
+    ASSIGN,      // = or +=
+    OR,          // ||
+    AND,         // &&
     EQUALS,      // ==
-    LESSGREATER, // > or <
+    LESSGREATER, // >, <, >= or <=
+    RANGE,       // .. or ..=
+    BITOR,       // |
+    BITXOR,      // ^
+    BITAND,      // &
+    SHIFT,       // << or >>
     SUM,         // +
     PRODUCT,     // *
     PREFIX,      // -X or !X
+    POWER,       // **
     CALL,        // myFunction(X)
     INDEX,       // myArray[0]
 }
 
 pub fn compute_priority_map(map: &mut HashMap<TokenType, Precedence>) {
     let precedences = [
+        (TokenType::ASSIGN,              Precedence::ASSIGN),
+        (TokenType::PLUS_ASSIGN,         Precedence::ASSIGN),
+        (TokenType::MINUS_ASSIGN,        Precedence::ASSIGN),
+        (TokenType::ASTERISK_ASSIGN,     Precedence::ASSIGN),
+        (TokenType::SLASH_ASSIGN,        Precedence::ASSIGN),
+        (TokenType::PERCENT_ASSIGN,      Precedence::ASSIGN),
+        (TokenType::POWER_ASSIGN,        Precedence::ASSIGN),
+        (TokenType::AMPERSAND_ASSIGN,    Precedence::ASSIGN),
+        (TokenType::PIPE_ASSIGN,         Precedence::ASSIGN),
+        (TokenType::CARET_ASSIGN,        Precedence::ASSIGN),
+        (TokenType::LSHIFT_ASSIGN,       Precedence::ASSIGN),
+        (TokenType::RSHIFT_ASSIGN,       Precedence::ASSIGN),
         (TokenType::EQ,       Precedence::EQUALS),
         (TokenType::NOT_EQ,   Precedence::EQUALS),
         (TokenType::LT,       Precedence::LESSGREATER),
         (TokenType::GT,       Precedence::LESSGREATER),
+        (TokenType::LT_EQ,    Precedence::LESSGREATER),
+        (TokenType::GT_EQ,    Precedence::LESSGREATER),
+        (TokenType::DOT_DOT,  Precedence::RANGE),
+        (TokenType::DOT_DOT_EQ, Precedence::RANGE),
+        (TokenType::AND,      Precedence::AND),
+        (TokenType::OR,       Precedence::OR),
         (TokenType::PLUS,     Precedence::SUM),
         (TokenType::MINUS,    Precedence::SUM),
+        (TokenType::PIPE,     Precedence::BITOR),
+        (TokenType::CARET,    Precedence::BITXOR),
+        (TokenType::AMPERSAND, Precedence::BITAND),
+        (TokenType::LSHIFT,   Precedence::SHIFT),
+        (TokenType::RSHIFT,   Precedence::SHIFT),
         (TokenType::SLASH,    Precedence::PRODUCT),
         (TokenType::ASTERISK, Precedence::PRODUCT),
+        (TokenType::PERCENT,  Precedence::PRODUCT),
+        (TokenType::POWER,    Precedence::POWER),
         (TokenType::LPAREN,   Precedence::CALL),
         (TokenType::LBRACKET,  Precedence::INDEX),
     ];
@@ -1,149 +1,590 @@
-use std::{io::{self, Read, Write, BufRead, BufReader}, cell::RefCell, rc::Rc};
+use std::{io::{self, Read, Write, BufRead, BufReader}, cell::RefCell, rc::Rc, env, fs, path::PathBuf, time::Instant};
+
+use rustyline::{
+    Editor, Helper, Context,
+    completion::Completer,
+    error::ReadlineError,
+    highlight::Highlighter,
+    hint::Hinter,
+    history::DefaultHistory,
+    validate::Validator,
+};
 
 use crate::{
     lexer::lexer::Lexer,
     parser::parser::Parser,
-    object::{MObject, NULL},
+    object::MObject,
     interpreter::{
         evaluator,
         environment::Environment,
     },
     error::{Result, Error},
-    compiler::{compiler::Compiler, vm::Vm, symbol_table::SymbolTable},
-    ast::MNode,
+    diagnostic,
+    compiler::code::MCode,
+    ast::{MNode, Program},
+    builtin,
+    engine::Engine,
+    lexer::token_type::KEYWORDS,
 };
 
-#[derive(Debug)]
-struct State {
-    constants: Vec<MObject>,
-    globals: Vec<MObject>,
-    symbols: SymbolTable,
+const PROMPT: &str = ">>> ";
+const CONTINUATION_PROMPT: &str = "... ";
+const HISTORY_FILE: &str = ".monkey_history";
+
+pub fn start<I: Read, O: Write>(input: I, output: &mut O, engine: &mut Engine) -> Result<()> {
+    let mut bufio = BufReader::new(input);
+    let mut buf = String::new();
+    let mut macro_env = Environment::new();
+
+    loop {
+        output.write_all(if buf.is_empty() { PROMPT } else { CONTINUATION_PROMPT }.as_bytes())?;
+        output.flush()?;
+        if bufio.read_line(&mut buf)? == 0 {
+            return end_of_input(&buf, output, engine, &mut macro_env);
+        };
+
+        if is_incomplete(&buf) {
+            continue;
+        };
+
+        handle_input(&buf, output, engine, &mut macro_env)?;
+        buf.clear()
+    }
+}
+
+// Input still waiting for more lines when the input ends is run anyway, so that its errors are
+// reported, and the session fails.
+fn end_of_input<O: Write>(buf: &str, output: &mut O, engine: &mut Engine, macro_env: &mut Rc<RefCell<Environment>>) -> Result<()> {
+    if buf.trim().is_empty() {
+        return Ok(());
+    };
+
+    output.write_all(b"\n")?;
+    handle_input(buf, output, engine, macro_env)?;
+
+    Err(Error::Io(io::ErrorKind::UnexpectedEof, "unexpected end of input".to_string()))
 }
 
-impl State {
-    pub fn new() -> Self {
-        let mut symbols = SymbolTable::new();
-
-        symbols.define_builtin("len".to_string());
-        symbols.define_builtin("first".to_string());
-        symbols.define_builtin("last".to_string());
-        symbols.define_builtin("rest".to_string());
-        symbols.define_builtin("push".to_string());
-        symbols.define_builtin("puts".to_string());
-
-        Self {
-            constants: Vec::new(),
-            globals: Vec::new(),
-            symbols,
-        }
+// The interactive REPL, with line editing, history that persists in `~/.monkey_history`, Ctrl-R
+// search, and tab completion of keywords, builtins and the globals defined so far.
+pub fn start_interactive(engine: &mut Engine) -> Result<()> {
+    let mut editor: Editor<MonkeyHelper, DefaultHistory> = Editor::new().map_err(readline_error)?;
+    editor.set_helper(Some(MonkeyHelper { names: Vec::new() }));
+
+    let history = env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE));
+    if let Some(path) = &history {
+        // There is no history the first time the REPL is started.
+        let _ = editor.load_history(path);
+    };
+
+    let mut output = io::stdout();
+    let mut buf = String::new();
+    let mut macro_env = Environment::new();
+
+    loop {
+        if let Some(helper) = editor.helper_mut() {
+            helper.names = engine.names();
+        };
+
+        let line = match editor.readline(if buf.is_empty() { PROMPT } else { CONTINUATION_PROMPT }) {
+            Ok(x) => x,
+            Err(ReadlineError::Interrupted) => {
+                buf.clear();
+                continue;
+            },
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(readline_error(e)),
+        };
+
+        buf.push_str(&line);
+        buf.push('\n');
+
+        if is_incomplete(&buf) {
+            continue;
+        };
+
+        editor.add_history_entry(buf.trim_end()).map_err(readline_error)?;
+        handle_input(&buf, &mut output, engine, &mut macro_env)?;
+        buf.clear()
     }
+
+    if let Some(path) = &history {
+        editor.save_history(path).map_err(readline_error)?;
+    };
+
+    Ok(())
 }
 
-#[derive(Debug)]
-enum Env {
-    Eval(Rc<RefCell<Environment>>),
-    Vm(State),
+const HELP: &str = "\
+:help               Show this message
+:ast <code>         Print the parsed AST
+:bytecode <code>    Print the compiled bytecode
+:env                List the bindings in the session
+:engine [vm|eval]   Show the engine, or switch to another one keeping the bindings
+:reset              Clear all bindings and macros
+:load <file>        Evaluate a script into the session
+:time <code>        Evaluate code and report how long it took
+";
+
+fn handle_input<O: Write>(src: &str, output: &mut O, engine: &mut Engine, macro_env: &mut Rc<RefCell<Environment>>) -> Result<()> {
+    if src.trim().is_empty() {
+        return Ok(());
+    };
+
+    if src.trim_start().starts_with(':') {
+        run_command(src.trim(), output, engine, macro_env)
+    } else {
+        eval_input(src, "<repl>", output, engine, macro_env.clone())
+    }
 }
 
-pub struct Engine {
-    runner: fn(MNode, &mut Env) -> Result<MObject>,
-    env: Env,
+fn run_command<O: Write>(cmd: &str, output: &mut O, engine: &mut Engine, macro_env: &mut Rc<RefCell<Environment>>) -> Result<()> {
+    let (name, arg) = match cmd.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (cmd, ""),
+    };
+
+    match name {
+        ":help" => output.write_all(HELP.as_bytes())?,
+        ":ast" => {
+            match parse_input(arg) {
+                Ok(program) => writeln!(output, "{}", program)?,
+                Err(e) => print_error(output, &e, "<repl>", arg)?,
+            };
+        },
+        ":bytecode" => {
+            if let Err(e) = parse_input(arg).and_then(|program| print_bytecode(MNode::Prog(program), output, engine)) {
+                print_error(output, &e, "<repl>", arg)?;
+            };
+        },
+        ":env" => {
+            for (name, value) in engine.bindings() {
+                writeln!(output, "{} = {}", name, value)?;
+            }
+        },
+        ":engine" => {
+            match arg {
+                "" => writeln!(output, "{}", engine.name())?,
+                "vm" | "eval" => {
+                    let other = if arg == "vm" { Engine::vm() } else { Engine::eval() };
+                    let dropped = engine.switch(other);
+                    if !dropped.is_empty() {
+                        writeln!(output, "Dropped functions, they only run on the engine that made them: {}", dropped.join(", "))?;
+                    };
+                },
+                _ => writeln!(output, "Unknown engine: {}, expected vm or eval", arg)?,
+            };
+        },
+        ":reset" => {
+            engine.reset();
+            *macro_env = Environment::new();
+        },
+        ":load" => {
+            match fs::read_to_string(arg) {
+                Ok(src) => eval_input(&src, arg, output, engine, macro_env.clone())?,
+                Err(e) => writeln!(output, "error: could not read {}: {}", arg, e)?,
+            };
+        },
+        ":time" => {
+            let now = Instant::now();
+            eval_input(arg, "<repl>", output, engine, macro_env.clone())?;
+            writeln!(output, "time: {:?}", now.elapsed())?;
+        },
+        _ => writeln!(output, "Unknown command: {}, see :help", name)?,
+    };
+
+    output.flush()?;
+    Ok(())
 }
 
-impl Engine {
-    pub fn vm() -> Self {
-        Self {
-            runner: Self::vm_runner,
-            env: Env::Vm(State::new()),
-        }
+fn parse_input(src: &str) -> Result<Program> {
+    let lex = Lexer::new(src.bytes().map(Ok).peekable())?;
+    let mut parser = Parser::new(lex.peekable())?;
+    let program = parser.parse()?;
+
+    let errors = parser.errors();
+    if errors.is_empty() {
+        Ok(program)
+    } else {
+        Err(Error::Parse(errors))
     }
+}
 
-    pub fn eval() -> Self {
-        Self {
-            runner: Self::eval_runner,
-            env: Env::Eval(Environment::new()),
-        }
+// Errors from the input are printed rather than returned so the session survives them, only a
+// failure to write to `output` ends the REPL.
+fn eval_input<O: Write>(src: &str, file: &str, output: &mut O, engine: &mut Engine, macro_env: Rc<RefCell<Environment>>) -> Result<()> {
+    let result = parse_input(src).and_then(|mut program| {
+        evaluator::define_macros(&mut program, macro_env.clone());
+        let expanded = evaluator::expand_macros(program, macro_env);
+
+        engine.run(expanded)
+    });
+
+    // Error values are reported like the errors the VM raises, with the location in the input.
+    match result {
+        Ok(MObject::Err(e)) => print_error(output, &Error::from(e), file, src)?,
+        Ok(evaluated) => output.write_all(format!("{}\n", evaluated).as_bytes())?,
+        Err(e) => print_error(output, &e, file, src)?,
+    };
+
+    output.flush()?;
+    Ok(())
+}
+
+// Only the constants added by this code are listed.
+fn print_bytecode<O: Write>(node: MNode, output: &mut O, engine: &Engine) -> Result<()> {
+    let mut compiler = engine.compiler();
+    let offset = compiler.bytecode().contstants.len();
+
+    compiler.compile(node)?;
+    let bytecode = compiler.bytecode();
+
+    output.write_all(MCode::new().format(&bytecode.instructions).as_bytes())?;
+    for (i, constant) in bytecode.contstants.iter().enumerate().skip(offset) {
+        writeln!(output, "constant {}: {}", i, constant)?;
     }
 
-    pub fn run(&mut self, node: MNode) -> Result<MObject> {
-        (self.runner)(node, &mut self.env)
+    Ok(())
+}
+
+fn readline_error(e: ReadlineError) -> Error {
+    match e {
+        ReadlineError::Io(x) => Error::from(x),
+        x => Error::Io(io::ErrorKind::Other, x.to_string()),
     }
+}
 
-    fn vm_runner(node: MNode, env: &mut Env) -> Result<MObject> {
-        let state = match env {
-            Env::Vm(x) => x,
-            _ => return Err(Error::new(format!("wanted: Env::Vm, got: {:?}", env))),
+struct MonkeyHelper {
+    names: Vec<String>,
+}
+
+impl Completer for MonkeyHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos]
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| c.is_alphanumeric() || *c == '_')
+            .last()
+            .map_or(pos, |(i, _)| i);
+        let prefix = &line[start..pos];
+
+        if prefix.is_empty() {
+            return Ok((pos, Vec::new()));
         };
 
-        let mut compiler = Compiler::with_state(state.symbols.clone(), state.constants.clone());
-        compiler.compile(node)?;
+        let mut candidates = KEYWORDS
+            .iter()
+            .map(|(k, _)| k.to_string())
+            .chain(builtin::NAMES.iter().map(|b| b.to_string()))
+            .chain(self.names.iter().cloned())
+            .filter(|c| c.starts_with(prefix))
+            .collect::<Vec<String>>();
+        candidates.sort();
+        candidates.dedup();
+
+        Ok((start, candidates))
+    }
+}
 
-        let code = compiler.bytecode();
+impl Hinter for MonkeyHelper {
+    type Hint = String;
+}
 
-        let mut vm = Vm::with_state(code.clone(), state.globals.clone());
+impl Highlighter for MonkeyHelper {}
 
-        vm.run()?;
+impl Validator for MonkeyHelper {}
 
-        state.symbols = compiler.symbol_table();
-        state.constants = code.contstants;
-        state.globals = vm.globals();
+impl Helper for MonkeyHelper {}
 
-        match vm.stack_top() {
-            Some(x) => Ok(x.clone()),
-            None => Ok(NULL),
-        }
+// Input is incomplete when it has unclosed delimiters, an unterminated string or block comment,
+// or ends with a binary operator, in which case the REPL keeps reading lines until the statement
+// is complete.
+fn is_incomplete(src: &str) -> bool {
+    let mut depth = 0;
+    let mut comment_depth = 0;
+    let mut in_string = false;
+    let mut last = None;
+    let mut chars = src.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if comment_depth > 0 {
+            match (c, chars.peek()) {
+                ('/', Some('*')) => { chars.next(); comment_depth += 1; },
+                ('*', Some('/')) => { chars.next(); comment_depth -= 1; },
+                _ => {},
+            };
+            continue;
+        };
+
+        match c {
+            '"' => in_string = !in_string,
+            '\\' if in_string => { chars.next(); },
+            _ if in_string => {},
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.next_if(|&c| c != '\n').is_some() {};
+                continue;
+            },
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                comment_depth = 1;
+                continue;
+            },
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            _ => {},
+        };
+
+        if !c.is_whitespace() {
+            last = Some(c);
+        };
     }
 
-    fn eval_runner(node: MNode, env: &mut Env) -> Result<MObject> {
-        if let Env::Eval(environment) = env {
-            evaluator::eval(node, environment.to_owned())
-        } else {
-            Err(Error::new(format!("wanted: Env::Eval, got: {:?}", env)))
+    if in_string || depth > 0 || comment_depth > 0 {
+        return true;
+    };
+
+    matches!(last, Some('+' | '-' | '*' | '/' | '=' | '<' | '>' | '!' | ',' | '%' | '&' | '|' | '^' | '~'))
+}
+
+fn print_error<O: Write>(output: &mut O, err: &Error, file: &str, src: &str) -> io::Result<()> {
+    output.write_all(diagnostic::render(err, file, src).as_bytes())?;
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_incomplete() {
+        let tests = vec![
+            ("let x = 5;", false),
+            ("", false),
+            ("let add = fn(a, b) {", true),
+            ("let add = fn(a, b) {\n  a + b;\n};", false),
+            ("[1, 2,", true),
+            ("puts(\"hello", true),
+            ("puts(\"{\")", false),
+            ("puts(\"say \\\"hi\\\"\")", false),
+            ("puts(\"\\\\\")", false),
+            ("puts(\"\\\")", true),
+            ("1 +", true),
+            ("let x =\n", true),
+            ("}", false),
+            ("let x = 1; // a comment {", false),
+            ("let x = 1 + // a comment", true),
+            ("/* a /* nested */ comment", true),
+            ("/* a /* nested */ comment */ 1", false),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(expected, is_incomplete(input), "input: {:?}", input);
         }
     }
-}
 
-const PROMPT: &[u8; 4] = b">>> ";
+    #[test]
+    fn test_completion() -> rustyline::Result<()> {
+        let history = DefaultHistory::new();
+        let ctx = Context::new(&history);
+        let helper = MonkeyHelper { names: vec!["fib".to_string(), "first_name".to_string()] };
 
-pub fn start<I: Read, O: Write>(input: I, output: &mut O, engine: &mut Engine) -> Result<()> {
-    let mut bufio = BufReader::new(input);
-    let mut buf = String::new();
-    let macro_env = Environment::new();
+        let tests = vec![
+            ("le", 0, vec!["len", "let"]),
+            ("let x = fi", 8, vec!["fib", "first", "first_name"]),
+            ("puts(re", 5, vec!["rest", "return"]),
+            ("1 + ", 4, vec![]),
+        ];
 
-    loop {
-        output.write_all(PROMPT)?;
-        output.flush()?;
-        bufio.read_line(&mut buf)?;
+        for (line, start, expected) in tests {
+            let (actual_start, candidates) = helper.complete(line, line.len(), &ctx)?;
+            assert_eq!(start, actual_start);
+            assert_eq!(expected, candidates);
+        }
 
-        let src = buf.bytes().map(|x| Ok(x)).peekable();
-        let lex = &mut Lexer::new(src)?;
-        let mut parser = Parser::new(lex.peekable())?;
-        let mut program = parser.parse()?;
+        Ok(())
+    }
 
-        if let Err(_) = print_parser_errors(output, parser.errors()) {
-            continue;
-        };
+    #[test]
+    fn test_multi_line_input() -> Result<()> {
+        let input = "fn(a, b) {\n  a + b;\n}(1,\n 2);\n[1,\n 2];\n";
 
-        evaluator::define_macros(&mut program, macro_env.clone());
-        let expanded = evaluator::expand_macros(program, macro_env.clone());
+        for mut engine in [Engine::eval(), Engine::vm()] {
+            let mut output = Vec::new();
+            start(input.as_bytes(), &mut output, &mut engine)?;
 
-        let evaluated = engine.run(expanded)?;
+            let expected = ">>> ... ... ... 3\n>>> ... [1, 2]\n>>> ";
+            assert_eq!(expected, String::from_utf8(output)?);
+        }
 
-        output.write_all(format!("{}\n", evaluated).as_bytes())?;
-        output.flush()?;
-        buf.clear()
+        Ok(())
     }
-}
 
-fn print_parser_errors<O: Write>(output: &mut O, errors: Vec<String>) -> io::Result<()> {
-    if errors.is_empty() {
-        return Ok(())
+    #[test]
+    fn test_incomplete_input_at_eof() -> Result<()> {
+        for mut engine in [Engine::eval(), Engine::vm()] {
+            let mut output = Vec::new();
+            let result = start("1;\nlet a = fn() {\n".as_bytes(), &mut output, &mut engine);
+
+            let expected = ">>> 1
+>>> ... 
+error: Expected next token to be RBRACE, got EOF instead.
+ --> <repl>:1:15
+  |
+1 | let a = fn() {
+  |               ^
+";
+            assert_eq!(expected, String::from_utf8(output)?);
+            assert_eq!("unexpected end of input", result.unwrap_err().to_string());
+        }
+
+        Ok(())
     }
 
-    output.write_all(b"Parser errors:\n")?;
-    for e in errors {
-        output.write_all(format!("\t{}\n", e).as_bytes())?;
+    #[test]
+    fn test_meta_commands() -> Result<()> {
+        let input = [
+            "let x = 5;",
+            ":env",
+            ":ast 1 + 2 * 3",
+            ":engine",
+            ":engine vm",
+            ":engine",
+            "let y = 2;",
+            ":env",
+            ":bytecode y + 1",
+            ":reset",
+            ":env",
+            ":nope",
+        ].join("\n");
+
+        let mut output = Vec::new();
+        start(input.as_bytes(), &mut output, &mut Engine::eval())?;
+
+        let expected = ">>> 5
+>>> x = 5
+>>> (1 + (2 * 3))
+>>> eval
+>>> >>> vm
+>>> null
+>>> x = 5
+y = 2
+>>> 0000 OpGetGlobal 1
+0003 OpConstant 1
+0006 OpAdd
+0007 OpPop
+constant 1: 1
+>>> >>> >>> Unknown command: :nope, see :help
+>>> ";
+        assert_eq!(expected, String::from_utf8(output)?);
+
+        Ok(())
     }
 
-    output.flush()
+    #[test]
+    fn test_engine_switch_keeps_session() -> Result<()> {
+        let input = [
+            "let xs = [1, 2];",
+            "let square = fn(x) { x * x };",
+            ":engine vm",
+            "xs;",
+            "xs[5];",
+            "square;",
+            ":engine eval",
+            ":env",
+            ":reset",
+            ":env",
+            "[1][5];",
+        ].join("\n");
+
+        let mut engine = Engine::eval();
+        engine.set_strict(true);
+
+        let mut output = Vec::new();
+        start(input.as_bytes(), &mut output, &mut engine)?;
+
+        let expected = ">>> [1, 2]
+>>> fn(x) (x * x)
+>>> Dropped functions, they only run on the engine that made them: square
+>>> [1, 2]
+>>> error: index out of range: 5 for length 2
+ --> <repl>:1:1
+  |
+1 | xs[5];
+  | ^^^^^
+>>> error: Identifier not found: square
+ --> <repl>:1:1
+  |
+1 | square;
+  | ^^^^^^
+>>> >>> xs = [1, 2]
+>>> >>> >>> error: index out of range: 5 for length 1
+ --> <repl>:1:1
+  |
+1 | [1][5];
+  | ^^^^^^
+>>> ";
+        assert_eq!(expected, String::from_utf8(output)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors_keep_session_alive() -> Result<()> {
+        let input = [
+            "let a = 1;",
+            "let b = 2; let c = b + true;",
+            ":env",
+            "b;",
+            "",
+            "a;",
+        ].join("\n");
+
+        let tests = vec![
+            (
+                Engine::eval(),
+                ">>> 1
+>>> error: type mismatch: 2 + true
+ --> <repl>:1:20
+  |
+1 | let b = 2; let c = b + true;
+  |                    ^^^^^^^^
+>>> a = 1
+>>> error: identifier not found: b
+ --> <repl>:1:1
+  |
+1 | b;
+  | ^
+>>> >>> 1
+>>> ",
+            ),
+            (
+                Engine::vm(),
+                ">>> null
+>>> error: type mismatch: INTEGER + BOOLEAN
+ --> <repl>:1:20
+  |
+1 | let b = 2; let c = b + true;
+  |                    ^^^^^^^^
+>>> a = 1
+>>> error: Identifier not found: b
+ --> <repl>:1:1
+  |
+1 | b;
+  | ^
+>>> >>> 1
+>>> ",
+            ),
+        ];
+
+        for (mut engine, expected) in tests {
+            let mut output = Vec::new();
+            start(input.as_bytes(), &mut output, &mut engine)?;
+
+            assert_eq!(expected, String::from_utf8(output)?);
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,118 @@
+use crate::{
+    lexer::lexer::Lexer,
+    parser::parser::Parser,
+    object::{MObject, MArray, MString},
+    interpreter::{
+        evaluator,
+        environment::Environment,
+    },
+    error::{Result, Error},
+    engine::Engine,
+};
+
+// Runs a whole Monkey script. The script arguments are bound to the `args` global as an array of
+// strings.
+pub fn run(source: &str, args: &[String], engine: &mut Engine) -> Result<MObject> {
+    let elements = args
+        .iter()
+        .map(|a| MObject::Str(MString { value: a.clone() }))
+        .collect();
+    engine.define("args", MObject::Array(MArray { elements }));
+
+    let lexer = Lexer::new(source.bytes().map(Ok).peekable())?;
+    let mut parser = Parser::new(lexer.peekable())?;
+    let mut program = parser.parse()?;
+
+    let errors = parser.errors();
+    if !errors.is_empty() {
+        return Err(Error::Parse(errors));
+    };
+
+    let macro_env = Environment::new();
+    evaluator::define_macros(&mut program, macro_env.clone());
+    let expanded = evaluator::expand_macros(program, macro_env);
+
+    match engine.run(expanded)? {
+        MObject::Err(e) => Err(Error::from(e)),
+        x => Ok(x),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{diagnostic, test_utils::*};
+
+    fn engines() -> Vec<Engine> {
+        vec![Engine::eval(), Engine::vm()]
+    }
+
+    #[test]
+    fn test_run_script() -> Result<()> {
+        let input = r#"
+            let fib = fn(n) {
+                if (n < 2) { return n; };
+                fib(n - 1) + fib(n - 2);
+            };
+
+            fib(10);
+        "#;
+
+        for mut engine in engines() {
+            assert_eq!(i_to_o(55), run(input, &[], &mut engine)?);
+        };
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_script_args() -> Result<()> {
+        let input = r#"len(args) + len(first(args));"#;
+        let args = vec!["four".to_string(), "x".to_string()];
+
+        for mut engine in engines() {
+            assert_eq!(i_to_o(6), run(input, &args, &mut engine)?);
+        };
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_script_errors() {
+        for mut engine in engines() {
+            match run("let x = ;", &[], &mut engine) {
+                Err(Error::Parse(errors)) => assert_eq!(1, errors.len()),
+                x => panic!("Expected a parse error, got: {:?}", x),
+            };
+
+            match run("1 + true;", &[], &mut engine) {
+                Err(Error::Runtime(e)) => assert!(e.to_string().starts_with("type mismatch")),
+                x => panic!("Expected a runtime error, got: {:?}", x),
+            };
+        };
+    }
+
+    #[test]
+    fn test_run_script_lex_errors() {
+        let tests = vec![
+            ("let x = \"abc", "error: unterminated string literal\n --> test.monkey:1:9"),
+            ("puts(\"a\\q\")", "error: invalid escape sequence\n --> test.monkey:1:8"),
+            ("let x = ;\n/* open", "error: unterminated block comment\n --> test.monkey:2:1"),
+            ("let x = 0x;", "error: expected hexadecimal digits after 0x\n --> test.monkey:1:9"),
+            ("let x = 1__0;", "error: duplicate digit separator\n --> test.monkey:1:11"),
+        ];
+
+        for (input, expected) in tests {
+            for mut engine in engines() {
+                let err = match run(input, &[], &mut engine) {
+                    Err(e) => e,
+                    Ok(x) => panic!("Expected a lexer error for {}, got: {}", input, x),
+                };
+
+                let rendered = diagnostic::render(&err, "test.monkey", input);
+                assert!(rendered.contains(expected), "{}: {}", engine.name(), rendered);
+            }
+        }
+    }
+}
@@ -4,6 +4,7 @@ use crate::{
     ast::*,
     object::*,
     error::{Result, Error},
+    diagnostic,
     lexer::{
         lexer::Lexer,
         token::Token,
@@ -20,6 +21,19 @@ pub fn i_to_o(i: i128) -> MObject {
     )
 }
 
+// Builds an integer from its decimal digits, demoted to an `Integer` when it fits like any result.
+pub fn big_to_o(digits: &str) -> MObject {
+    MObject::from_bigint(digits.parse().unwrap())
+}
+
+pub fn f_to_o(f: f64) -> MObject {
+    MObject::Float(
+        Float {
+            value: f,
+        }
+    )
+}
+
 pub fn s_to_o(s: &str) -> MObject {
     MObject::Str(
         MString {
@@ -31,7 +45,7 @@ pub fn s_to_o(s: &str) -> MObject {
 pub fn i_to_expr(i: i128) -> Expr {
     Expr::Int(
         IntegerLiteral {
-            token: Token { literal: format!("{}", i), token_type: TokenType::INT },
+            token: Token::new(TokenType::INT, format!("{}", i)),
             value: i,
         }
         )
@@ -40,7 +54,7 @@ pub fn i_to_expr(i: i128) -> Expr {
 pub fn b_to_expr(i: bool) -> Expr {
     Expr::Bool(
         BooleanLiteral {
-            token: Token { literal: format!("{}", i), token_type: if i { TokenType::TRUE } else { TokenType::FALSE } },
+            token: Token::new(if i { TokenType::TRUE } else { TokenType::FALSE }, format!("{}", i)),
             value: i,
         }
         )
@@ -49,7 +63,7 @@ pub fn b_to_expr(i: bool) -> Expr {
 pub fn l_to_expr(i: String) -> Expr {
     Expr::Ident(
         Identifier {
-            token: Token { literal: i.clone(), token_type: TokenType::IDENT },
+            token: Token::new(TokenType::IDENT, i.clone()),
             value: i,
         }
         )
@@ -58,7 +72,7 @@ pub fn l_to_expr(i: String) -> Expr {
 pub fn s_to_expr(i: String) -> Expr {
     Expr::Str(
         StringLiteral {
-            token: Token { literal: i.clone(), token_type: TokenType::STRING },
+            token: Token::new(TokenType::STRING, i.clone()),
             value: i,
         }
         )
@@ -69,7 +83,9 @@ pub fn parse(input: String) -> Result<Program> {
     let mut parser = Parser::new(lexer.peekable())?;
     let program = parser.parse()?;
 
-    check_parser_errors(parser)?;
+    if let Err(e) = check_parser_errors(parser) {
+        panic!("{}", diagnostic::render(&e, "test", &input));
+    };
 
     Ok(program)
 }
@@ -80,12 +96,5 @@ pub fn check_parser_errors<I: Iterator<Item = Result<Token>>>(p: Parser<I>) -> R
 
     if errors.is_empty() { return Ok(()); };
 
-    let mut msg = format!("The Parser had {} errors:\n", errors.len());
-
-    for e in errors {
-        msg.push_str(&e);
-        msg.push('\n');
-    }
-
-    Err(Error::new(msg))
+    Err(Error::Parse(errors))
 }
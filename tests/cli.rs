@@ -0,0 +1,57 @@
+use std::{fs, process::Command};
+
+// Runs a script through the `monkey` binary on both engines, returning the exit code, stdout and
+// stderr.
+fn run_script(name: &str, source: &str) -> Vec<(Option<i32>, String, String)> {
+    let path = std::env::temp_dir().join(name);
+    fs::write(&path, source).unwrap();
+
+    let results = ["vm", "eval"]
+        .iter()
+        .map(|engine| {
+            let output = Command::new(env!("CARGO_BIN_EXE_monkey"))
+                .arg(format!("--engine={}", engine))
+                .arg("run")
+                .arg(&path)
+                .output()
+                .unwrap();
+            (
+                output.status.code(),
+                String::from_utf8(output.stdout).unwrap(),
+                String::from_utf8(output.stderr).unwrap(),
+            )
+        })
+        .collect();
+
+    fs::remove_file(&path).unwrap();
+    results
+}
+
+#[test]
+fn test_lex_errors_exit_with_diagnostic() {
+    let tests = vec![
+        ("unterminated_string.monkey", "let x = \"abc", "error: unterminated string literal", "1 | let x = \"abc"),
+        ("invalid_escape.monkey", "puts(\"a\\q\")", "error: invalid escape sequence", "1 | puts(\"a\\q\")"),
+        ("unterminated_comment.monkey", "let x = ;\n/* open", "error: unterminated block comment", "2 | /* open"),
+    ];
+
+    for (name, source, message, snippet) in tests {
+        for (code, _, stderr) in run_script(name, source) {
+            assert_eq!(Some(1), code, "{}", stderr);
+            assert!(stderr.contains(message), "{}", stderr);
+            assert!(stderr.contains(snippet), "{}", stderr);
+        }
+    }
+}
+
+#[test]
+fn test_builtin_errors_stop_the_script() {
+    let source = "len(1);\nputs(\"after\");";
+
+    for (code, stdout, stderr) in run_script("builtin_error.monkey", source) {
+        assert_eq!(Some(1), code, "{}", stderr);
+        assert!(stderr.contains("error: argument to 'len' not supported, got: 1"), "{}", stderr);
+        assert!(stderr.contains("1 | len(1);"), "{}", stderr);
+        assert!(!stdout.contains("after"), "{}", stdout);
+    }
+}